@@ -16,5 +16,19 @@ fn main() {
         .unwrap_or_else(|_| "lidcgfpdpjpeambpilgmllbefcikkglh".to_string());
     println!("cargo:rustc-env=EXTENSION_ID={}", extension_id);
     
+    // Optional remote model catalog: URL and hex ed25519 public key for versions.json.sig,
+    // more extension IDs allowed to connect, and the ID of the Firefox build of the extension
+    for key in [
+        "CATALOG_URL",
+        "CATALOG_PUBLIC_KEY",
+        "EXTENSION_IDS",
+        "FIREFOX_EXTENSION_ID",
+    ] {
+        println!("cargo:rerun-if-env-changed={}", key);
+        if let Ok(value) = std::env::var(key) {
+            println!("cargo:rustc-env={}={}", key, value);
+        }
+    }
+    
     tauri_build::build()
 }
@@ -7,27 +7,117 @@
 
 use anyhow::{Context, Result};
 use chrono::Local;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write};
 use std::path::PathBuf;
-use std::process::Child;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Mutex;
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{mpsc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 // Import shared modules from main crate
-use sigma_eclipse_lib::ipc_state::{is_tauri_app_running, read_ipc_state};
+use sigma_eclipse_lib::download::{check_model_selectable, list_available_models, DownloadError};
+use sigma_eclipse_lib::download_history::read_download_history;
+use sigma_eclipse_lib::ipc_state::{
+    cancel_downloads, clear_server_state, get_ipc_state_path, is_tauri_app_running, read_ipc_state,
+};
+use sigma_eclipse_lib::server_logs::{
+    capture_server_output, flush_server_logs, latest_app_log_file, read_log_file_tail,
+    read_server_log_tail, read_server_logs, server_logs_dir, server_output_tail,
+    CRASH_OUTPUT_TAIL_LINES,
+};
 use sigma_eclipse_lib::server_manager::{
-    check_server_running, get_status, start_server_process, stop_server_by_pid, ServerConfig,
+    check_server_health, embeddings_server_status, fetch_server_metrics, find_embeddings_server,
+    find_server, get_status, reserve_restart_attempt, restart_backoff, running_servers,
+    server_exit_was_requested, start_embeddings_server_process, start_server_process,
+    stop_server_and_wait, stop_server_and_wait_with, stop_server_by_pid, stop_server_by_pid_with,
+    validate_config, wait_until_ready, ServerConfig, ServerReadiness, StopMode, StopOutcome,
+    STOP_GRACE_PERIOD,
 };
-use sigma_eclipse_lib::settings::get_server_settings;
-
-/// Global state for server process
+use sigma_eclipse_lib::settings::{
+    get_active_model, get_api_key, get_auto_restart, get_server_settings,
+    get_server_start_timeout_secs, set_active_model, set_server_settings,
+};
+use sigma_eclipse_lib::types::ServerStatus;
+
+/// Interval between /health polls while the model loads
+const READINESS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often the background threads check status and the server process
+const MONITOR_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Status is also checked this often while ipc_state.json is watched: a stale app
+/// heartbeat changes nothing on disk
+const STATUS_FALLBACK_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Writes to ipc_state.json within this time of the first are pushed together
+const STATUS_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Largest message accepted from the extension. Chrome allows more, but commands are
+/// tiny, so a bigger length prefix means corrupt framing
+const MAX_INBOUND_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// Chrome closes the connection on a host message over 1 MB
+const MAX_OUTBOUND_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// Version of the message protocol this host speaks. Bump it when commands are added or
+/// their responses change, and record the version new commands need in `COMMANDS`.
+/// 2: optional `model` param on the server commands, one server per model.
+/// 3: start_embeddings_server / stop_embeddings_server, `embeddings_server` in the status.
+/// 4: get_server_log_tail; server_crashed carries the last 30 lines of output.
+/// 5: cancel_download.
+/// 6: get_recent_logs / open_logs_folder.
+/// 7: `force` / `timeout_ms` params on stop_server, which reports each outcome and the duration.
+/// 8: optional `pid` param on get_server_logs, output is kept per server.
+const PROTOCOL_VERSION: u32 = 8;
+
+/// Every command with the protocol version that introduced it
+const COMMANDS: &[(&str, u32)] = &[
+    ("hello", 1),
+    ("start_server", 1),
+    ("stop_server", 1),
+    ("restart_server", 1),
+    ("get_server_status", 1),
+    ("get_server_metrics", 1),
+    ("get_api_key", 1),
+    ("list_models", 1),
+    ("set_active_model", 1),
+    ("isDownloading", 1),
+    ("get_download_history", 1),
+    ("get_server_logs", 1),
+    ("get_app_status", 1),
+    ("launch_app", 1),
+    ("start_embeddings_server", 3),
+    ("stop_embeddings_server", 3),
+    ("get_server_log_tail", 4),
+    ("cancel_download", 5),
+    ("get_recent_logs", 6),
+    ("open_logs_folder", 6),
+];
+
+/// Protocol version agreed on in `hello` (0 = no handshake yet: every command allowed,
+/// for extensions that predate it)
+static NEGOTIATED_PROTOCOL: AtomicU32 = AtomicU32::new(0);
+
+/// Lines of server output appended to a failed start's error
+const STARTUP_ERROR_TAIL_LINES: usize = 20;
+
+/// Lines of each log returned by get_recent_logs when `lines` is omitted
+const RECENT_LOG_LINES: usize = 200;
+
+/// Global state for the server processes started by this host, as (model, process)
 /// Note: This is process-local, shared state is in ipc_state.json
-static SERVER_PROCESS: Mutex<Option<Child>> = Mutex::new(None);
+static SERVER_PROCESSES: Mutex<Vec<(String, Child)>> = Mutex::new(Vec::new());
+
+/// The embeddings server started by this host
+static EMBEDDINGS_PROCESS: Mutex<Option<Child>> = Mutex::new(None);
+
+/// PIDs of the servers that became ready; only their crashes are restarted, not failed starts
+static SUPERVISED_PIDS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
 
 /// Global log file handle
 static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
@@ -112,6 +202,9 @@ struct NativeResponse {
     data: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
+    /// `DownloadError` kind, for errors about models and downloads
+    #[serde(rename = "errorKind", skip_serializing_if = "Option::is_none")]
+    error_kind: Option<&'static str>,
 }
 
 #[derive(Debug, Serialize)]
@@ -130,6 +223,15 @@ struct CachedStatus {
     download_progress: Option<f64>,
 }
 
+/// Log to stderr and file (stdout is reserved for Native Messaging Protocol)
+macro_rules! log {
+    ($($arg:tt)*) => {
+        let msg = format!($($arg)*);
+        eprintln!("[Native Host] {}", msg);
+        write_to_log_file(&msg);
+    };
+}
+
 /// Read a message from stdin using Native Messaging Protocol
 /// Format: [4 bytes length][JSON message]
 fn read_message() -> Result<NativeMessage> {
@@ -139,6 +241,14 @@ fn read_message() -> Result<NativeMessage> {
         .context("Failed to read message length")?;
 
     let length = u32::from_ne_bytes(length_bytes) as usize;
+    // Checked before allocating: the stream cannot be trusted after a bad prefix
+    if length > MAX_INBOUND_MESSAGE_SIZE {
+        anyhow::bail!(
+            "Message length {} exceeds the {} byte limit",
+            length,
+            MAX_INBOUND_MESSAGE_SIZE
+        );
+    }
 
     let mut buffer = vec![0u8; length];
     io::stdin()
@@ -153,8 +263,25 @@ fn read_message() -> Result<NativeMessage> {
 
 /// Send a response to stdout using Native Messaging Protocol (with lock for thread safety)
 /// Format: [4 bytes length][JSON message]
+/// A response over the size limit is replaced with an error for the same request.
 fn send_response(response: &NativeResponse) -> Result<()> {
-    let json = serde_json::to_string(response).context("Failed to serialize response")?;
+    let mut json = serde_json::to_string(response).context("Failed to serialize response")?;
+    if json.len() > MAX_OUTBOUND_MESSAGE_SIZE {
+        log!(
+            "Response to {} is {} bytes, over the {} byte limit",
+            response.id,
+            json.len(),
+            MAX_OUTBOUND_MESSAGE_SIZE
+        );
+        json = serde_json::to_string(&NativeResponse {
+            id: response.id.clone(),
+            success: false,
+            data: None,
+            error: Some(format!("Response too large ({} bytes)", json.len())),
+            error_kind: None,
+        })
+        .context("Failed to serialize response")?;
+    }
     let length = json.len() as u32;
 
     let _lock = STDOUT_LOCK.lock().unwrap();
@@ -173,6 +300,14 @@ fn send_response(response: &NativeResponse) -> Result<()> {
 /// Send a push message to stdout (same protocol as response, with lock)
 fn send_push(message: &StatusPushMessage) -> Result<()> {
     let json = serde_json::to_string(message).context("Failed to serialize push")?;
+    if json.len() > MAX_OUTBOUND_MESSAGE_SIZE {
+        anyhow::bail!(
+            "{} push is {} bytes, over the {} byte limit",
+            message.msg_type,
+            json.len(),
+            MAX_OUTBOUND_MESSAGE_SIZE
+        );
+    }
     let length = json.len() as u32;
 
     let _lock = STDOUT_LOCK.lock().unwrap();
@@ -188,22 +323,14 @@ fn send_push(message: &StatusPushMessage) -> Result<()> {
     Ok(())
 }
 
-/// Log to stderr and file (stdout is reserved for Native Messaging Protocol)
-macro_rules! log {
-    ($($arg:tt)*) => {
-        let msg = format!($($arg)*);
-        eprintln!("[Native Host] {}", msg);
-        write_to_log_file(&msg);
-    };
-}
-
 /// Check current status and send push if changed
 fn check_and_push_status() {
+    let state = read_ipc_state().unwrap_or_default();
     let new_status = CachedStatus {
         app_running: is_tauri_app_running().unwrap_or(false),
         model_running: get_status().map(|(r, _)| r).unwrap_or(false),
-        is_downloading: read_ipc_state().map(|s| s.is_downloading).unwrap_or(false),
-        download_progress: read_ipc_state().ok().and_then(|s| s.download_progress),
+        is_downloading: state.is_downloading,
+        download_progress: state.download_progress,
     };
 
     let mut cached_guard = CACHED_STATUS.lock().unwrap();
@@ -231,95 +358,660 @@ fn check_and_push_status() {
     }
 }
 
-/// Start background thread for status monitoring
+/// Watch the directory of ipc_state.json (the file is replaced by a rename, so it can't
+/// be watched itself) and send on `changed` whenever the file changes
+fn watch_ipc_state(changed: mpsc::Sender<()>) -> Result<RecommendedWatcher> {
+    let path = get_ipc_state_path()?;
+    let dir = path
+        .parent()
+        .context("IPC state file has no parent directory")?
+        .to_path_buf();
+    let file_name = path.file_name().map(|name| name.to_os_string());
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        // Reads of the file, ours included, are no changes
+        if event.kind.is_access() {
+            return;
+        }
+        if event
+            .paths
+            .iter()
+            .any(|changed_path| changed_path.file_name() == file_name.as_deref())
+        {
+            let _ = changed.send(());
+        }
+    })
+    .context("Failed to create file watcher")?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {:?}", dir))?;
+    Ok(watcher)
+}
+
+/// Start background thread for status monitoring. It pushes status (download progress
+/// in particular) on its own, also while the extension sends nothing: as soon as
+/// ipc_state.json changes, or by polling when the file can't be watched.
 fn start_status_monitor() {
     thread::spawn(|| {
+        let (sender, changes) = mpsc::channel();
+        let _watcher = match watch_ipc_state(sender) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                log!("Failed to watch IPC state, polling it instead: {:#}", e);
+                while !SHOULD_EXIT.load(Ordering::Relaxed) {
+                    check_and_push_status();
+                    thread::sleep(MONITOR_INTERVAL);
+                }
+                return;
+            }
+        };
+
         while !SHOULD_EXIT.load(Ordering::Relaxed) {
             check_and_push_status();
-            thread::sleep(Duration::from_millis(500));
+            if changes.recv_timeout(STATUS_FALLBACK_INTERVAL).is_ok() {
+                // Downloads write progress often: one push for a burst of writes
+                thread::sleep(STATUS_DEBOUNCE);
+                while changes.try_recv().is_ok() {}
+            }
         }
     });
 }
 
-/// Handle start_server command
-fn handle_start_server() -> Result<Value> {
+/// Start background thread for server supervision, separate from the status monitor
+fn start_server_supervisor() {
+    thread::spawn(|| {
+        while !SHOULD_EXIT.load(Ordering::Relaxed) {
+            supervise_server();
+            flush_server_logs();
+            thread::sleep(MONITOR_INTERVAL);
+        }
+    });
+}
+
+/// Optional `model` param of the server commands
+fn model_param(params: &Value) -> Option<String> {
+    params
+        .get("model")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+/// Handle start_server command: param `model` (the active model when omitted).
+/// Servers of other models keep running.
+fn handle_start_server(params: &Value) -> Result<Value> {
+    start_model_server(model_param(params))
+}
+
+/// Start the server of `model` (the active model with None) and wait until it is ready
+fn start_model_server(model: Option<String>) -> Result<Value> {
+    let model = match model {
+        Some(model) => model,
+        None => get_active_model()?,
+    };
+
     // Get settings from settings.json
     let (port, ctx_size, gpu_layers) = get_server_settings()?;
 
     // Use shared server manager
     let config = ServerConfig {
+        model: Some(model.clone()),
         port,
         ctx_size,
         gpu_layers,
+        force: false,
     };
 
-    let child = start_server_process(config, false)?;
+    // The port actually used may differ from settings when automatic selection kicked in
+    let (mut child, port) = start_server_process(config, true)?;
     let pid = child.id();
+    capture_server_output(&mut child, |_| {});
 
-    log!("Server started: port={}, pid={}", port, pid);
+    log!(
+        "Server started: model={}, port={}, pid={}",
+        model,
+        port,
+        pid
+    );
 
     // Store process handle locally
-    let mut process_guard = SERVER_PROCESS.lock().unwrap();
-    *process_guard = Some(child);
+    SERVER_PROCESSES
+        .lock()
+        .unwrap()
+        .push((model.clone(), child));
+
+    // The extension chats right after this returns, so wait until the model is loaded
+    wait_for_server_ready(&model, port, pid)?;
+    SUPERVISED_PIDS.lock().unwrap().push(pid);
+    log!("Server ready: model={}, port={}, pid={}", model, port, pid);
 
     Ok(json!({
-        "message": format!("Server started on port {} (PID: {})", port, pid),
+        "message": format!("Server for {} started on port {} (PID: {})", model, port, pid),
         "pid": pid,
         "port": port,
+        "model": model,
+        "apiKey": get_api_key()?,
     }))
 }
 
-/// Handle stop_server command
-fn handle_stop_server() -> Result<Value> {
-    let mut process_guard = SERVER_PROCESS.lock().unwrap();
+/// Hand exits of the servers started by this host to `handle_server_exit`.
+/// A server that is still starting is left to `wait_for_server_ready`, and one
+/// removed by handle_stop_server is never seen here.
+fn supervise_server() {
+    let mut processes = SERVER_PROCESSES.lock().unwrap();
+    let supervised = SUPERVISED_PIDS.lock().unwrap().clone();
+    let mut exited = Vec::new();
+    processes.retain_mut(|(model, child)| {
+        if !supervised.contains(&child.id()) {
+            return true;
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                exited.push((model.clone(), child.id(), status));
+                false
+            }
+            _ => true,
+        }
+    });
+    drop(processes);
 
-    if let Some(mut child) = process_guard.take() {
-        let pid = child.id();
+    for (model, pid, status) in exited {
+        handle_server_exit(model, pid, status);
+    }
+
+    // The embeddings server is not restarted, only cleared from the IPC state
+    let mut embeddings = EMBEDDINGS_PROCESS.lock().unwrap();
+    if let Some(Ok(Some(status))) = embeddings.as_mut().map(Child::try_wait) {
+        if let Some(child) = embeddings.take() {
+            log!(
+                "Embeddings server exited: pid={}, status={}",
+                child.id(),
+                status
+            );
+            let _ = clear_server_state(child.id());
+        }
+    }
+}
 
-        // Use shared server manager
-        stop_server_by_pid(pid)?;
+/// Clear the IPC state after the server of `model` exited, report a crash to the
+/// extension and restart the server when auto_restart is enabled. Runs on its own thread
+/// because of the grace period, the restart backoff and the wait for the restarted server.
+fn handle_server_exit(model: String, pid: u32, status: ExitStatus) {
+    thread::spawn(move || {
+        thread::sleep(STOP_GRACE_PERIOD);
+
+        // A stop from the Tauri app has already cleared the IPC state
+        if server_exit_was_requested(pid) {
+            log!("Server stopped: pid={}, status={}", pid, status);
+            return;
+        }
+        let _ = clear_server_state(pid);
+        SUPERVISED_PIDS
+            .lock()
+            .unwrap()
+            .retain(|&supervised_pid| supervised_pid != pid);
+
+        if status.success() {
+            log!("Server exited: pid={}, status={}", pid, status);
+            return;
+        }
+        log!(
+            "Server crashed: model={}, pid={}, status={}",
+            model,
+            pid,
+            status
+        );
+
+        let attempt = if get_auto_restart().unwrap_or(false) {
+            reserve_restart_attempt()
+        } else {
+            None
+        };
+        let _ = send_push(&StatusPushMessage {
+            msg_type: "server_crashed",
+            data: json!({
+                "pid": pid,
+                "model": model,
+                "exitStatus": status.to_string(),
+                "outputTail": server_output_tail(pid, CRASH_OUTPUT_TAIL_LINES),
+                "willRestart": attempt.is_some(),
+            }),
+        });
 
-        // Also clean up local Child handle
+        let Some(attempt) = attempt else {
+            return;
+        };
+        let delay = restart_backoff(attempt);
+        log!("Restarting server in {:?} (attempt {})", delay, attempt);
+        thread::sleep(delay);
+
+        match start_model_server(Some(model)) {
+            Ok(_) => {
+                log!("Server restarted after crash (attempt {})", attempt);
+            }
+            Err(e) => {
+                log!("Failed to restart server: {}", e);
+            }
+        }
+    });
+}
+
+/// Take the process of `model` started by this host out of `SERVER_PROCESSES`
+fn take_server_process(processes: &mut Vec<(String, Child)>, model: &str) -> Option<Child> {
+    let index = processes.iter().position(|(name, _)| name == model)?;
+    Some(processes.remove(index).1)
+}
+
+/// Poll /health until the server of `model` (process `pid`) started by this host is ready.
+/// When it exits during startup or is not ready within the configured timeout, it is
+/// stopped and the error ends with the last lines of its output.
+fn wait_for_server_ready(model: &str, port: u16, pid: u32) -> Result<()> {
+    let timeout = Duration::from_secs(get_server_start_timeout_secs()?);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to create runtime for health check")?;
+    let started = Instant::now();
+
+    let failure = loop {
+        {
+            let mut processes = SERVER_PROCESSES.lock().unwrap();
+            let exit = match processes
+                .iter_mut()
+                .find(|(name, _)| name == model)
+                .map(|(_, child)| child.try_wait())
+            {
+                Some(Ok(None)) => None,
+                Some(Ok(Some(status))) => Some(format!(
+                    "Server exited during startup with status: {}",
+                    status
+                )),
+                Some(Err(e)) => Some(format!("Failed to check server status: {}", e)),
+                None => Some("Server was stopped during startup".to_string()),
+            };
+            if let Some(message) = exit {
+                if let Some(child) = take_server_process(&mut processes, model) {
+                    let _ = clear_server_state(child.id());
+                }
+                break message;
+            }
+        }
+
+        if runtime.block_on(check_server_health(port)) == ServerReadiness::Ready {
+            return Ok(());
+        }
+
+        if started.elapsed() >= timeout {
+            break format!(
+                "Server did not become ready within {} seconds",
+                timeout.as_secs()
+            );
+        }
+
+        thread::sleep(READINESS_POLL_INTERVAL);
+    };
+
+    // A server that never became ready is killed (this also clears the IPC state)
+    let child = take_server_process(&mut SERVER_PROCESSES.lock().unwrap(), model);
+    if let Some(mut child) = child {
+        let _ = stop_server_by_pid(child.id());
         let _ = child.kill();
         let _ = child.wait();
+    }
 
-        log!("Server stopped: pid={}", pid);
+    match server_output_tail(pid, STARTUP_ERROR_TAIL_LINES) {
+        Some(tail) => anyhow::bail!("{}\nLast server output:\n{}", failure, tail),
+        None => anyhow::bail!("{}", failure),
+    }
+}
 
-        Ok(json!({
-            "message": "Server stopped",
-        }))
-    } else {
-        // Check if server is running elsewhere (e.g., via Tauri)
-        if let Some(pid) = check_server_running()? {
-            stop_server_by_pid(pid)?;
-            log!("Server stopped: pid={}", pid);
-            return Ok(json!({
-                "message": format!("Server stopped (PID: {})", pid),
-            }));
+/// Handle get_api_key command: the key to send as `Authorization: Bearer <key>`
+/// (null when the server does not require one)
+fn handle_get_api_key() -> Result<Value> {
+    Ok(json!({ "apiKey": get_api_key()? }))
+}
+
+/// Handle restart_server command: stop the server of `model` (the active model when
+/// omitted) wherever it was started, save the settings given in params (`port`,
+/// `ctx_size`, `gpu_layers`; omitted ones stay as they are) and start it again
+fn handle_restart_server(params: &Value) -> Result<Value> {
+    let model = match model_param(params) {
+        Some(model) => model,
+        None => get_active_model()?,
+    };
+    let (port, ctx_size, gpu_layers) = get_server_settings()?;
+    let config = ServerConfig {
+        model: Some(model.clone()),
+        port: match params.get("port").and_then(Value::as_u64) {
+            Some(port) => u16::try_from(port).context("Invalid port")?,
+            None => port,
+        },
+        ctx_size: match params.get("ctx_size").and_then(Value::as_u64) {
+            Some(ctx_size) => u32::try_from(ctx_size).context("Invalid context size")?,
+            None => ctx_size,
+        },
+        gpu_layers: match params.get("gpu_layers").and_then(Value::as_u64) {
+            Some(gpu_layers) => u32::try_from(gpu_layers).context("Invalid GPU layers")?,
+            None => gpu_layers,
+        },
+        force: false,
+    };
+    // Refuse bad settings before the running server is stopped
+    validate_config(&config)?;
+
+    stop_running_servers(Some(&model), StopMode::Graceful)?;
+
+    set_server_settings(config.port, config.ctx_size, config.gpu_layers)?;
+    start_model_server(Some(model))
+}
+
+/// Stop the servers of `model` (every server with None) wherever they were started and
+/// wait until they are gone; returns the PID and outcome of each
+fn stop_running_servers(model: Option<&str>, mode: StopMode) -> Result<Vec<(u32, StopOutcome)>> {
+    // Read before the local servers are taken out of the list, so an error leaves them tracked
+    let recorded = running_servers()?;
+    let local: Vec<Child> = {
+        let mut processes = SERVER_PROCESSES.lock().unwrap();
+        let (stopping, kept) = processes
+            .drain(..)
+            .partition(|(name, _)| model.is_none() || model == Some(name.as_str()));
+        *processes = kept;
+        stopping.into_iter().map(|(_, child)| child).collect()
+    };
+    let local_pids: Vec<u32> = local.iter().map(Child::id).collect();
+
+    // A failed stop does not keep the others running: the failures are reported together
+    let mut stopped = Vec::new();
+    let mut errors = Vec::new();
+    for mut child in local {
+        let pid = child.id();
+        let outcome = stop_server_by_pid_with(pid, mode);
+        // Also clean up local Child handle
+        let _ = child.kill();
+        let _ = child.wait();
+        match outcome {
+            Ok(outcome) => stopped.push((pid, outcome)),
+            Err(e) => errors.push(format!("PID {}: {}", pid, e)),
         }
+    }
+    for server in recorded {
+        if local_pids.contains(&server.pid)
+            || !(model.is_none() || model == Some(server.model.as_str()))
+        {
+            continue;
+        }
+        // Started by the Tauri app, which reaps it
+        match stop_server_and_wait_with(server.pid, mode) {
+            Ok(outcome) => stopped.push((server.pid, outcome)),
+            Err(e) => errors.push(format!("PID {}: {}", server.pid, e)),
+        }
+    }
 
-        Err(anyhow::anyhow!("Server is not running"))
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "Failed to stop {} of {} servers: {}",
+            errors.len(),
+            errors.len() + stopped.len(),
+            errors.join("; ")
+        );
     }
+    Ok(stopped)
 }
 
-/// Handle get_server_status command
-fn handle_get_server_status() -> Result<Value> {
-    // Use shared server manager
-    let (is_running, pid) = get_status()?;
-    
-    // Get additional info from IPC state
-    let state = read_ipc_state()?;
+/// Handle start_embeddings_server command: params `model_name` and `port` (the next free
+/// port is taken when it is in use); returns once the server answers /health
+fn handle_start_embeddings_server(params: &Value) -> Result<Value> {
+    let model_name = params
+        .get("model_name")
+        .and_then(Value::as_str)
+        .context("Missing model_name")?;
+    let port = params
+        .get("port")
+        .and_then(Value::as_u64)
+        .and_then(|port| u16::try_from(port).ok())
+        .context("Missing or invalid port")?;
+
+    if EMBEDDINGS_PROCESS.lock().unwrap().is_some() {
+        anyhow::bail!("Embeddings server is already running");
+    }
+    let (mut child, port) = start_embeddings_server_process(model_name, port, true)?;
+    let pid = child.id();
+    capture_server_output(&mut child, |_| {});
+    log!(
+        "Embeddings server started: model={}, port={}, pid={}",
+        model_name,
+        port,
+        pid
+    );
+
+    let timeout = Duration::from_secs(get_server_start_timeout_secs()?);
+    let ready = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to create runtime for health check")?
+        .block_on(wait_until_ready(&mut child, port, timeout));
+    if let Err(e) = ready {
+        let _ = stop_server_by_pid(pid);
+        let _ = child.kill();
+        let _ = child.wait();
+        match server_output_tail(pid, STARTUP_ERROR_TAIL_LINES) {
+            Some(tail) => anyhow::bail!("{}\nLast server output:\n{}", e, tail),
+            None => return Err(e),
+        }
+    }
+    *EMBEDDINGS_PROCESS.lock().unwrap() = Some(child);
+    log!(
+        "Embeddings server ready: model={}, port={}, pid={}",
+        model_name,
+        port,
+        pid
+    );
 
     Ok(json!({
-        "is_running": is_running,
+        "message": format!(
+            "Embeddings server for {} started on port {} (PID: {})",
+            model_name, port, pid
+        ),
         "pid": pid,
-        "port": state.server_port,
-        "ctx_size": state.server_ctx_size,
-        "gpu_layers": state.server_gpu_layers,
-        "message": if is_running { "Server is running" } else { "Server is not running" },
+        "port": port,
+        "model": model_name,
+        "apiKey": get_api_key()?,
+    }))
+}
+
+/// Handle stop_embeddings_server command, wherever the server was started
+fn handle_stop_embeddings_server() -> Result<Value> {
+    let local = EMBEDDINGS_PROCESS.lock().unwrap().take();
+    let (pid, outcome) = match (local, find_embeddings_server()?) {
+        (Some(mut child), _) => {
+            let pid = child.id();
+            let outcome = stop_server_by_pid(pid);
+            let _ = child.kill();
+            let _ = child.wait();
+            (pid, outcome?)
+        }
+        // Started by the Tauri app, which reaps it
+        (None, Some(server)) => (server.pid, stop_server_and_wait(server.pid)?),
+        (None, None) => anyhow::bail!("Embeddings server is not running"),
+    };
+    log!("Embeddings server {}: pid={}", outcome.description(), pid);
+    Ok(json!({
+        "message": format!("Embeddings server {} (PID: {})", outcome.description(), pid),
+    }))
+}
+
+/// Handle list_models command: the models from the configuration (same entries as the
+/// Tauri list_available_models command) and the active one
+fn handle_list_models() -> Result<Value> {
+    let models = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to create runtime for model list")?
+        .block_on(list_available_models())?;
+    Ok(json!({
+        "models": models,
+        "activeModel": get_active_model()?,
     }))
 }
 
+/// Handle set_active_model command: params `model_name`, and `restart` to replace a
+/// running server of the previous active model with one of the new model. When that
+/// model fails to load, the previous one is restored.
+fn handle_set_active_model(params: &Value) -> Result<Value> {
+    let model_name = params
+        .get("model_name")
+        .and_then(Value::as_str)
+        .context("Missing model_name")?
+        .to_string();
+    let restart = params
+        .get("restart")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+
+    check_model_selectable(&model_name)?;
+    let previous_model = get_active_model()?;
+    set_active_model(model_name.clone())?;
+    log!("Active model set: {}", model_name);
+
+    if !restart
+        || previous_model == model_name
+        || stop_running_servers(Some(&previous_model), StopMode::Graceful)?.is_empty()
+    {
+        return Ok(json!({
+            "message": format!("Active model set to: {}", model_name),
+            "restarted": false,
+        }));
+    }
+
+    // The new model may already have a server of its own
+    if let Some(server) = find_server(Some(&model_name))? {
+        return Ok(json!({
+            "message": format!("Switched to {}", model_name),
+            "restarted": true,
+            "pid": server.pid,
+            "port": server.port,
+        }));
+    }
+
+    let error = match start_model_server(None) {
+        Ok(started) => {
+            return Ok(json!({
+                "message": format!("Switched to {}", model_name),
+                "restarted": true,
+                "pid": started["pid"],
+                "port": started["port"],
+            }))
+        }
+        Err(e) => e,
+    };
+
+    log!(
+        "Failed to load {}, restoring {}: {}",
+        model_name,
+        previous_model,
+        error
+    );
+    set_active_model(previous_model.clone())?;
+    start_model_server(None).with_context(|| {
+        format!(
+            "Failed to load {} and to restore {}",
+            model_name, previous_model
+        )
+    })?;
+    Err(error.context(format!(
+        "Failed to load {}, restored {}",
+        model_name, previous_model
+    )))
+}
+
+/// Handle stop_server command: param `model`; without it every server is stopped.
+/// Like the Tauri command, `timeout_ms` replaces the stop grace period and `force` kills
+/// the servers right away.
+fn handle_stop_server(params: &Value) -> Result<Value> {
+    let model = model_param(params);
+    let force = params
+        .get("force")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let timeout_ms = params.get("timeout_ms").and_then(Value::as_u64);
+    let mode = match timeout_ms {
+        _ if force => StopMode::Force,
+        Some(timeout_ms) => StopMode::Timeout(Duration::from_millis(timeout_ms)),
+        None => StopMode::Graceful,
+    };
+
+    let started = Instant::now();
+    let stopped = stop_running_servers(model.as_deref(), mode)?;
+    for (pid, outcome) in &stopped {
+        log!("Server {}: pid={}", outcome.description(), pid);
+    }
+
+    let message = match stopped.as_slice() {
+        [] => {
+            return match model {
+                Some(model) => Err(anyhow::anyhow!("Server for {} is not running", model)),
+                None => Err(anyhow::anyhow!("Server is not running")),
+            }
+        }
+        [(pid, outcome)] => format!("Server {} (PID: {})", outcome.description(), pid),
+        _ => format!("Stopped {} servers", stopped.len()),
+    };
+
+    Ok(json!({
+        "message": message,
+        "stopped": stopped
+            .iter()
+            .map(|(pid, outcome)| json!({ "pid": pid, "outcome": outcome }))
+            .collect::<Vec<_>>(),
+        "duration_ms": started.elapsed().as_millis() as u64,
+    }))
+}
+
+/// Handle get_server_metrics command: param `model` (the primary server when omitted),
+/// same structure as the Tauri command
+fn handle_get_server_metrics(params: &Value) -> Result<Value> {
+    let model = model_param(params);
+    let metrics = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to create runtime for server metrics")?
+        .block_on(fetch_server_metrics(model.as_deref()))?;
+    Ok(serde_json::to_value(metrics)?)
+}
+
+/// Handle get_server_status command: param `model` (the primary server when omitted)
+fn handle_get_server_status(params: &Value) -> Result<Value> {
+    // Use shared server manager
+    let server = find_server(model_param(params).as_deref())?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to create runtime for health check")?;
+
+    // A live process may still be loading the model: ask llama-server itself
+    let readiness = match &server {
+        Some(server) => runtime.block_on(check_server_health(server.port)),
+        None => ServerReadiness::NotRunning,
+    };
+
+    let message = match readiness {
+        ServerReadiness::Ready => "Server is running",
+        ServerReadiness::Starting => "Server is starting (loading model)",
+        ServerReadiness::NotRunning => "Server is not running",
+    }
+    .to_string();
+
+    // Same structure as the Tauri get_server_status command, with details from IPC state
+    let mut status = match &server {
+        Some(server) => ServerStatus::running(readiness, message, server),
+        None => ServerStatus::stopped(message),
+    };
+    status.embeddings_server = runtime.block_on(embeddings_server_status());
+    Ok(serde_json::to_value(status)?)
+}
+
 /// Handle isDownloading command
 fn handle_is_downloading() -> Result<Value> {
     let state = read_ipc_state()?;
@@ -330,6 +1022,114 @@ fn handle_is_downloading() -> Result<Value> {
     }))
 }
 
+/// Handle cancel_download command - param `download_id` ("model:<name>" or "llama.cpp");
+/// without it every active download is cancelled. The app aborts the transfer within
+/// about a second and keeps the partial file for a later resume.
+fn handle_cancel_download(params: &Value) -> Result<Value> {
+    let download_id = params.get("download_id").and_then(Value::as_str);
+    let cancelled = cancel_downloads(download_id)?;
+    log!("Cancelled downloads: {:?}", cancelled);
+
+    // Report is_downloading=false now rather than on the next status check
+    check_and_push_status();
+
+    Ok(json!({
+        "cancelled": !cancelled.is_empty(),
+        "downloads": cancelled,
+    }))
+}
+
+/// Handle get_download_history command - downloads recorded by the app, oldest first
+fn handle_get_download_history() -> Result<Value> {
+    let entries = read_download_history()?;
+
+    Ok(json!({
+        "entries": entries,
+    }))
+}
+
+/// Handle get_server_logs command - param `pid` (the server whose output was written last
+/// when omitted), its recent llama-server output, oldest first
+fn handle_get_server_logs(params: &Value) -> Result<Value> {
+    let pid = params
+        .get("pid")
+        .and_then(Value::as_u64)
+        .and_then(|pid| u32::try_from(pid).ok());
+    let lines = read_server_logs(pid)?;
+
+    Ok(json!({
+        "lines": lines,
+    }))
+}
+
+/// Handle get_server_log_tail command - param `lines` (30 when omitted), the last lines of
+/// the newest llama-server log file
+fn handle_get_server_log_tail(params: &Value) -> Result<Value> {
+    let max_lines = params
+        .get("lines")
+        .and_then(Value::as_u64)
+        .map_or(CRASH_OUTPUT_TAIL_LINES, |lines| lines as usize);
+    let lines = read_server_log_tail(max_lines)?;
+
+    Ok(json!({
+        "lines": lines,
+    }))
+}
+
+/// Tail of a log file as `{path, lines}`, or `{path, error}` when it cannot be read
+fn log_tail_json(path: Option<PathBuf>, max_lines: usize) -> Value {
+    let Some(path) = path else {
+        return json!({ "path": null, "lines": [] });
+    };
+    match read_log_file_tail(&path, max_lines) {
+        Ok(lines) => json!({ "path": path, "lines": lines }),
+        Err(e) => json!({ "path": path, "lines": [], "error": e.to_string() }),
+    }
+}
+
+/// Handle get_recent_logs command - param `lines` (200 when omitted), the last lines of
+/// this host's log, the Tauri app log and the newest llama-server log, for diagnostics
+fn handle_get_recent_logs(params: &Value) -> Result<Value> {
+    let max_lines = params
+        .get("lines")
+        .and_then(Value::as_u64)
+        .map_or(RECENT_LOG_LINES, |lines| lines as usize);
+    // An unreadable server log should not hide the other two
+    let server_lines = read_server_log_tail(max_lines).unwrap_or_else(|e| {
+        log!("Failed to read server log: {}", e);
+        Vec::new()
+    });
+
+    Ok(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "native_host": log_tail_json(get_log_file_path(), max_lines),
+        "app": log_tail_json(latest_app_log_file(), max_lines),
+        "server": server_lines,
+    }))
+}
+
+/// Handle open_logs_folder command - show the llama-server log directory in the file manager
+fn handle_open_logs_folder() -> Result<Value> {
+    let dir = server_logs_dir()?;
+
+    #[cfg(target_os = "macos")]
+    let program = "open";
+    #[cfg(target_os = "windows")]
+    let program = "explorer";
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let program = "xdg-open";
+
+    std::process::Command::new(program)
+        .arg(&dir)
+        .spawn()
+        .with_context(|| format!("Failed to open {:?} with {}", dir, program))?;
+    log!("Opened logs folder: {:?}", dir);
+
+    Ok(json!({
+        "path": dir,
+    }))
+}
+
 /// Handle get_app_status command - check if Tauri app is running
 fn handle_get_app_status() -> Result<Value> {
     let is_running = is_tauri_app_running()?;
@@ -461,17 +1261,97 @@ fn handle_launch_app() -> Result<Value> {
     }
 }
 
-/// Process a single command
-fn process_command(message: NativeMessage) -> NativeResponse {
-    let result = match message.command.as_str() {
-        "start_server" => handle_start_server(),
-        "stop_server" => handle_stop_server(),
-        "get_server_status" => handle_get_server_status(),
+/// Handle hello command (handshake): params `protocol_version`, the highest version the
+/// extension speaks (1 when omitted). Both sides then use the lower of the two.
+/// Response:
+/// `{ "appVersion": "0.3.0", "protocolVersion": 1, "hostProtocolVersion": 1, "commands": [...] }`
+/// where `commands` lists what is available at the agreed version. An extension that
+/// needs a newer host should ask the user to update the desktop app.
+fn handle_hello(params: &Value) -> Result<Value> {
+    let requested = params
+        .get("protocol_version")
+        .and_then(Value::as_u64)
+        .map_or(Ok(1), u32::try_from)
+        .context("Invalid protocol_version")?;
+    if requested == 0 {
+        anyhow::bail!("Invalid protocol_version 0");
+    }
+    let negotiated = requested.min(PROTOCOL_VERSION);
+    NEGOTIATED_PROTOCOL.store(negotiated, Ordering::SeqCst);
+    log!(
+        "Handshake: extension protocol {}, host protocol {}, using {}",
+        requested,
+        PROTOCOL_VERSION,
+        negotiated
+    );
+
+    let commands: Vec<&str> = COMMANDS
+        .iter()
+        .filter(|(_, since)| *since <= negotiated)
+        .map(|(name, _)| *name)
+        .collect();
+    Ok(json!({
+        "appVersion": env!("CARGO_PKG_VERSION"),
+        "protocolVersion": negotiated,
+        "hostProtocolVersion": PROTOCOL_VERSION,
+        "commands": commands,
+    }))
+}
+
+/// Refuse a command the negotiated protocol version does not include, and explain an
+/// unknown one (usually an extension newer than this host)
+fn check_command_available(command: &str) -> Result<()> {
+    let Some(&(_, since)) = COMMANDS.iter().find(|(name, _)| *name == command) else {
+        anyhow::bail!(
+            "Unknown command: {} (Sigma Eclipse {}, protocol {}); \
+             the desktop app may need an update",
+            command,
+            env!("CARGO_PKG_VERSION"),
+            PROTOCOL_VERSION
+        );
+    };
+    let negotiated = NEGOTIATED_PROTOCOL.load(Ordering::SeqCst);
+    if negotiated != 0 && since > negotiated {
+        anyhow::bail!(
+            "Command {} needs protocol version {}, but version {} was agreed on",
+            command,
+            since,
+            negotiated
+        );
+    }
+    Ok(())
+}
+
+/// Run the handler of a command
+fn run_command(message: &NativeMessage) -> Result<Value> {
+    match message.command.as_str() {
+        "hello" => handle_hello(&message.params),
+        "start_server" => handle_start_server(&message.params),
+        "stop_server" => handle_stop_server(&message.params),
+        "restart_server" => handle_restart_server(&message.params),
+        "get_server_status" => handle_get_server_status(&message.params),
+        "get_server_metrics" => handle_get_server_metrics(&message.params),
+        "get_api_key" => handle_get_api_key(),
+        "list_models" => handle_list_models(),
+        "set_active_model" => handle_set_active_model(&message.params),
         "isDownloading" => handle_is_downloading(),
+        "cancel_download" => handle_cancel_download(&message.params),
+        "get_download_history" => handle_get_download_history(),
+        "get_server_logs" => handle_get_server_logs(&message.params),
         "get_app_status" => handle_get_app_status(),
         "launch_app" => handle_launch_app(),
+        "start_embeddings_server" => handle_start_embeddings_server(&message.params),
+        "stop_embeddings_server" => handle_stop_embeddings_server(),
+        "get_server_log_tail" => handle_get_server_log_tail(&message.params),
+        "get_recent_logs" => handle_get_recent_logs(&message.params),
+        "open_logs_folder" => handle_open_logs_folder(),
         _ => Err(anyhow::anyhow!("Unknown command: {}", message.command)),
-    };
+    }
+}
+
+/// Process a single command
+fn process_command(message: NativeMessage) -> NativeResponse {
+    let result = check_command_available(&message.command).and_then(|_| run_command(&message));
 
     match result {
         Ok(data) => NativeResponse {
@@ -479,6 +1359,7 @@ fn process_command(message: NativeMessage) -> NativeResponse {
             success: true,
             data: Some(data),
             error: None,
+            error_kind: None,
         },
         Err(e) => {
             log!("Error: {} (cmd: {})", e, message.command);
@@ -487,6 +1368,7 @@ fn process_command(message: NativeMessage) -> NativeResponse {
                 success: false,
                 data: None,
                 error: Some(e.to_string()),
+                error_kind: e.downcast_ref::<DownloadError>().map(DownloadError::kind),
             }
         }
     }
@@ -500,8 +1382,9 @@ fn main() {
     init_log_file();
     log!("Host started");
 
-    // Start background status monitor thread
+    // Start background status monitor and server supervisor threads
     start_status_monitor();
+    start_server_supervisor();
 
     // Main message loop
     loop {
@@ -512,7 +1395,9 @@ fn main() {
                     break;
                 }
             }
-            Err(_) => {
+            Err(e) => {
+                // Also the normal way out: the browser closes stdin
+                log!("Stopped reading messages: {:#}", e);
                 break;
             }
         }
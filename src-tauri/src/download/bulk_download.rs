@@ -0,0 +1,103 @@
+// Bulk download of llama.cpp and every catalog model, for provisioning machines
+// Downloads run one after another; a failed artifact is recorded and the rest continue.
+
+use super::download_utils::load_config;
+use super::error::DownloadError;
+use super::llama_download::{check_llama_version, download_llama_cpp};
+use super::model_download::download_model_by_name;
+use crate::paths::{get_llama_binary_path, is_model_downloaded};
+use crate::types::{BulkDownloadItem, BulkDownloadReport};
+use tauri::{AppHandle, Emitter};
+
+impl BulkDownloadReport {
+    fn record(
+        &mut self,
+        artifact: &str,
+        artifact_type: &str,
+        result: Result<Option<String>, DownloadError>,
+    ) {
+        let (status, message, error_kind) = match result {
+            Ok(Some(message)) => {
+                self.downloaded += 1;
+                ("downloaded", message, None)
+            }
+            Ok(None) => {
+                self.skipped += 1;
+                ("skipped", "Already up to date".to_string(), None)
+            }
+            Err(e) => {
+                log::error!("Bulk download of '{}' failed: {}", artifact, e);
+                self.failed += 1;
+                ("failed", e.to_string(), Some(e.kind()))
+            }
+        };
+        self.items.push(BulkDownloadItem {
+            artifact: artifact.to_string(),
+            artifact_type: artifact_type.to_string(),
+            status,
+            message,
+            error_kind,
+        });
+    }
+}
+
+/// Download llama.cpp (optional), returning None when the installed build is current
+async fn download_llama_if_needed(app: &AppHandle) -> Result<Option<String>, DownloadError> {
+    let installed = get_llama_binary_path().is_ok_and(|p| p.exists());
+    if installed && !check_llama_version(None).await?.needs_update {
+        return Ok(None);
+    }
+    download_llama_cpp(app.clone(), None).await.map(Some)
+}
+
+/// Download a model, returning None when it is already downloaded
+async fn download_model_if_needed(
+    app: &AppHandle,
+    model_name: &str,
+) -> Result<Option<String>, DownloadError> {
+    if is_model_downloaded(model_name).unwrap_or(false) {
+        return Ok(None);
+    }
+    download_model_by_name(model_name.to_string(), app.clone())
+        .await
+        .map(Some)
+}
+
+/// Download llama.cpp (if requested) and every model in the catalog that is not present yet
+#[tauri::command]
+pub async fn download_all(
+    app: AppHandle,
+    include_llama: bool,
+) -> Result<BulkDownloadReport, DownloadError> {
+    let config = load_config()?;
+    let mut model_names: Vec<String> = config.models.keys().cloned().collect();
+    model_names.sort();
+
+    log::info!(
+        "Bulk download started: {} models{}",
+        model_names.len(),
+        if include_llama { " and llama.cpp" } else { "" }
+    );
+
+    let mut report = BulkDownloadReport::default();
+
+    if include_llama {
+        let result = download_llama_if_needed(&app).await;
+        report.record("llama.cpp", "llama", result);
+    }
+
+    for model_name in &model_names {
+        let result = download_model_if_needed(&app, model_name).await;
+        report.record(model_name, "model", result);
+    }
+
+    log::info!(
+        "Bulk download finished: {} downloaded, {} skipped, {} failed",
+        report.downloaded,
+        report.skipped,
+        report.failed
+    );
+
+    let _ = app.emit("bulk-download-complete", &report);
+    Ok(report)
+}
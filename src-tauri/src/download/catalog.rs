@@ -0,0 +1,278 @@
+// Remote model catalog with detached ed25519 signature verification
+// The catalog (versions.json) carries the expected checksums for every download,
+// so it is only trusted when `versions.json.sig` verifies against the embedded key.
+
+use super::download_utils::apply_proxy;
+use super::source_headers::validate_config_headers;
+use crate::paths::get_app_data_dir;
+use crate::types::VersionsConfig;
+use ed25519_dalek::{Signature, VerifyingKey};
+use std::fs;
+use std::path::PathBuf;
+
+/// Remote catalog URL (loaded from .env at build time, remote catalog is disabled when unset)
+const CATALOG_URL: Option<&str> = option_env!("CATALOG_URL");
+
+/// Hex-encoded ed25519 public key the catalog must be signed with (loaded from .env at build time)
+const CATALOG_PUBLIC_KEY: Option<&str> = option_env!("CATALOG_PUBLIC_KEY");
+
+/// Decode a hex string into bytes
+fn decode_hex(input: &str) -> Option<Vec<u8>> {
+    let input = input.trim();
+    if !input.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..input.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(input.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Verify a detached, hex-encoded ed25519 signature over the catalog payload
+pub fn verify_catalog_signature(
+    payload: &[u8],
+    signature_hex: Option<&str>,
+    public_key_hex: &str,
+) -> Result<(), String> {
+    let signature_hex = signature_hex.ok_or_else(|| "Catalog signature is missing".to_string())?;
+
+    let key_bytes: [u8; 32] = decode_hex(public_key_hex)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "Catalog public key is not a valid 32-byte hex string".to_string())?;
+    let public_key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|e| format!("Invalid catalog public key: {}", e))?;
+
+    let signature_bytes: [u8; 64] = decode_hex(signature_hex)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "Catalog signature is not a valid 64-byte hex string".to_string())?;
+    let signature = Signature::from_bytes(&signature_bytes);
+
+    public_key
+        .verify_strict(payload, &signature)
+        .map_err(|_| "Catalog signature verification failed".to_string())
+}
+
+/// Directory holding the last known-good signed catalog
+fn get_catalog_cache_dir() -> Result<PathBuf, String> {
+    let dir = get_app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("catalog");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create catalog directory: {}", e))?;
+    Ok(dir)
+}
+
+/// Load the cached remote catalog, re-verifying its signature before trusting it.
+/// Returns None when remote catalogs are disabled or no valid signed copy exists.
+pub fn load_cached_catalog() -> Option<VersionsConfig> {
+    let public_key = CATALOG_PUBLIC_KEY?;
+    let cache_dir = get_catalog_cache_dir().ok()?;
+
+    let payload = fs::read(cache_dir.join("versions.json")).ok()?;
+    let signature = fs::read_to_string(cache_dir.join("versions.json.sig")).ok();
+
+    if let Err(e) = verify_catalog_signature(&payload, signature.as_deref(), public_key) {
+        log::error!("Cached model catalog rejected: {}", e);
+        return None;
+    }
+
+    let config: VersionsConfig = match serde_json::from_slice(&payload) {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("Cached model catalog is not valid JSON: {}", e);
+            return None;
+        }
+    };
+    if let Err(e) = validate_config_headers(&config) {
+        log::error!("Cached model catalog rejected: {}", e);
+        return None;
+    }
+    Some(config)
+}
+
+/// Fetch a URL body, returning None on 404 so a missing signature can be reported as such
+async fn fetch_bytes(client: &reqwest::Client, url: &str) -> Result<Option<Vec<u8>>, String> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to fetch {}: HTTP {}",
+            url,
+            response.status().as_u16()
+        ));
+    }
+
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", url, e))?;
+    Ok(Some(bytes.to_vec()))
+}
+
+/// Download the remote catalog and its signature, and cache them only if the signature verifies
+async fn fetch_and_verify_catalog(url: &str, public_key: &str) -> Result<(), String> {
+    let builder = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .connect_timeout(std::time::Duration::from_secs(15));
+    let client = apply_proxy(builder)?
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let payload = fetch_bytes(&client, url)
+        .await?
+        .ok_or_else(|| format!("Catalog not found at {}", url))?;
+    let signature = fetch_bytes(&client, &format!("{}.sig", url))
+        .await?
+        .map(|bytes| String::from_utf8_lossy(&bytes).to_string());
+
+    verify_catalog_signature(&payload, signature.as_deref(), public_key)?;
+
+    // Make sure the verified payload is actually a usable catalog before replacing the cache
+    let config = serde_json::from_slice::<VersionsConfig>(&payload)
+        .map_err(|e| format!("Remote catalog is not valid: {}", e))?;
+    validate_config_headers(&config).map_err(|e| format!("Remote catalog is not valid: {}", e))?;
+
+    let cache_dir = get_catalog_cache_dir()?;
+    fs::write(cache_dir.join("versions.json"), &payload)
+        .map_err(|e| format!("Failed to cache catalog: {}", e))?;
+    fs::write(
+        cache_dir.join("versions.json.sig"),
+        signature.unwrap_or_default(),
+    )
+    .map_err(|e| format!("Failed to cache catalog signature: {}", e))?;
+
+    Ok(())
+}
+
+/// Refresh the remote model catalog, keeping the last known-good copy on failure
+#[tauri::command]
+pub async fn refresh_model_catalog() -> Result<String, String> {
+    let (Some(url), Some(public_key)) = (CATALOG_URL, CATALOG_PUBLIC_KEY) else {
+        return Ok("Remote model catalog is not configured, using built-in catalog".to_string());
+    };
+
+    log::info!("Refreshing model catalog from: {}", url);
+
+    match fetch_and_verify_catalog(url, public_key).await {
+        Ok(()) => {
+            log::info!("Model catalog updated and signature verified");
+            Ok("Model catalog updated".to_string())
+        }
+        Err(e) => {
+            log::error!(
+                "REJECTED remote model catalog from {}: {}. Falling back to last known-good catalog.",
+                url,
+                e
+            );
+            Err(format!(
+                "Failed to update model catalog: {}. Using last known-good catalog.",
+                e
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const PAYLOAD: &[u8] = br#"{"llama_cpp":{},"models":{}}"#;
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// A fixed keypair, so the tests need no randomness: (public key hex, signature hex)
+    fn sign(payload: &[u8]) -> (String, String) {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let signature = signing_key.sign(payload);
+        (
+            to_hex(signing_key.verifying_key().as_bytes()),
+            to_hex(&signature.to_bytes()),
+        )
+    }
+
+    #[test]
+    fn accepts_valid_signature() {
+        let (public_key, signature) = sign(PAYLOAD);
+        assert_eq!(
+            verify_catalog_signature(PAYLOAD, Some(&signature), &public_key),
+            Ok(())
+        );
+        // A trailing newline in versions.json.sig is common
+        let signature = format!("{}\n", signature);
+        assert!(verify_catalog_signature(PAYLOAD, Some(&signature), &public_key).is_ok());
+    }
+
+    #[test]
+    fn rejects_modified_payload() {
+        let (public_key, signature) = sign(PAYLOAD);
+        let tampered = br#"{"llama_cpp":{},"models":{"x":{}}}"#;
+        assert_eq!(
+            verify_catalog_signature(tampered, Some(&signature), &public_key),
+            Err("Catalog signature verification failed".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_corrupted_signature() {
+        let (public_key, signature) = sign(PAYLOAD);
+
+        // One flipped hex digit
+        let mut corrupted = signature.clone().into_bytes();
+        corrupted[10] = if corrupted[10] == b'0' { b'1' } else { b'0' };
+        let corrupted = String::from_utf8(corrupted).unwrap();
+        assert_eq!(
+            verify_catalog_signature(PAYLOAD, Some(&corrupted), &public_key),
+            Err("Catalog signature verification failed".to_string())
+        );
+
+        // Truncated, and not hex at all
+        for signature in [&signature[..100], "zz"] {
+            assert_eq!(
+                verify_catalog_signature(PAYLOAD, Some(signature), &public_key),
+                Err("Catalog signature is not a valid 64-byte hex string".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_signature_from_another_key() {
+        let (_, signature) = sign(PAYLOAD);
+        let other_key = SigningKey::from_bytes(&[8u8; 32]).verifying_key();
+        let other_key = to_hex(other_key.as_bytes());
+        assert_eq!(
+            verify_catalog_signature(PAYLOAD, Some(&signature), &other_key),
+            Err("Catalog signature verification failed".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_missing_signature() {
+        let (public_key, _) = sign(PAYLOAD);
+        assert_eq!(
+            verify_catalog_signature(PAYLOAD, None, &public_key),
+            Err("Catalog signature is missing".to_string())
+        );
+        assert_eq!(
+            verify_catalog_signature(PAYLOAD, Some(""), &public_key),
+            Err("Catalog signature is not a valid 64-byte hex string".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_public_key() {
+        let (_, signature) = sign(PAYLOAD);
+        assert_eq!(
+            verify_catalog_signature(PAYLOAD, Some(&signature), "abcd"),
+            Err("Catalog public key is not a valid 32-byte hex string".to_string())
+        );
+    }
+}
@@ -0,0 +1,190 @@
+// Per-host download credentials
+// Stored in download_credentials.json (separate from settings.json so secrets are never
+// returned by get_settings_command). Credentials are matched by the URL host name.
+
+use super::download_utils::{apply_proxy, describe_request_error};
+use super::error::DownloadError;
+use crate::paths::get_app_data_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Credential attached to requests for a given host
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum DownloadCredential {
+    Bearer { token: String },
+    Basic { username: String, password: String },
+}
+
+// Never print secrets, even in debug logs
+impl fmt::Debug for DownloadCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadCredential::Bearer { .. } => write!(f, "Bearer(<redacted>)"),
+            DownloadCredential::Basic { username, .. } => {
+                write!(f, "Basic({}, <redacted>)", username)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CredentialsFile {
+    #[serde(default)]
+    hosts: HashMap<String, DownloadCredential>,
+}
+
+/// Get path to the credentials file
+fn get_credentials_path() -> Result<PathBuf, DownloadError> {
+    let app_dir = get_app_data_dir().map_err(|e| DownloadError::Other(e.to_string()))?;
+    Ok(app_dir.join("download_credentials.json"))
+}
+
+fn load_credentials() -> Result<CredentialsFile, DownloadError> {
+    let path = get_credentials_path()?;
+    if !path.exists() {
+        return Ok(CredentialsFile::default());
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|e| DownloadError::io("Failed to read download credentials", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| DownloadError::Other(format!("Failed to parse download credentials: {}", e)))
+}
+
+fn save_credentials(credentials: &CredentialsFile) -> Result<(), DownloadError> {
+    let path = get_credentials_path()?;
+    let content = serde_json::to_string_pretty(credentials)
+        .map_err(|e| DownloadError::Other(format!("Failed to serialize credentials: {}", e)))?;
+    fs::write(&path, content)
+        .map_err(|e| DownloadError::io("Failed to write download credentials", e))?;
+
+    // Credentials are secrets: keep the file readable by the current user only
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(&path, fs::Permissions::from_mode(0o600));
+    }
+
+    Ok(())
+}
+
+/// Host name of a URL, lowercased for matching
+fn url_host(url: &str) -> Option<String> {
+    Some(reqwest::Url::parse(url).ok()?.host_str()?.to_lowercase())
+}
+
+/// Find the credential configured for `host`
+fn credential_for_host(host: &str) -> Option<DownloadCredential> {
+    let credentials = match load_credentials() {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            log::warn!("Ignoring download credentials: {}", e);
+            return None;
+        }
+    };
+    credentials
+        .hosts
+        .into_iter()
+        .find(|(configured, _)| configured.to_lowercase() == host)
+        .map(|(_, credential)| credential)
+}
+
+/// Attach the Authorization header for the URL's host, if credentials are configured.
+/// Only the host is logged, never the header value.
+pub fn apply_credentials(request: reqwest::RequestBuilder, url: &str) -> reqwest::RequestBuilder {
+    let Some(host) = url_host(url) else {
+        return request;
+    };
+    match credential_for_host(&host) {
+        Some(DownloadCredential::Bearer { token }) => {
+            log::info!("Using bearer token for host: {}", host);
+            request.bearer_auth(token)
+        }
+        Some(DownloadCredential::Basic { username, password }) => {
+            log::info!("Using basic auth for host: {}", host);
+            request.basic_auth(username, Some(password))
+        }
+        None => request,
+    }
+}
+
+/// Set or remove (when `credential` is None) the credential for a host
+#[tauri::command]
+pub async fn set_download_credential(
+    host: String,
+    credential: Option<DownloadCredential>,
+) -> Result<String, DownloadError> {
+    let host = host.trim().to_lowercase();
+    if host.is_empty() {
+        return Err(DownloadError::Other(
+            "Host name must not be empty".to_string(),
+        ));
+    }
+
+    let mut credentials = load_credentials()?;
+    let message = match credential {
+        Some(credential) => {
+            credentials.hosts.insert(host.clone(), credential);
+            format!("Download credentials saved for: {}", host)
+        }
+        None => {
+            credentials.hosts.remove(&host);
+            format!("Download credentials removed for: {}", host)
+        }
+    };
+    save_credentials(&credentials)?;
+
+    Ok(message)
+}
+
+/// HEAD the URL with the configured credentials and report whether authentication succeeds
+#[tauri::command]
+pub async fn test_download_credentials(url: String) -> Result<String, DownloadError> {
+    let has_credentials = url_host(&url)
+        .and_then(|host| credential_for_host(&host))
+        .is_some();
+
+    let builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .timeout(std::time::Duration::from_secs(30))
+        .connect_timeout(std::time::Duration::from_secs(15));
+    let client = apply_proxy(builder)?
+        .build()
+        .map_err(|e| DownloadError::Other(format!("Failed to create HTTP client: {}", e)))?;
+
+    let response = apply_credentials(client.head(&url), &url)
+        .send()
+        .await
+        .map_err(|e| DownloadError::Network(describe_request_error("Request failed", &e)))?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        return Err(DownloadError::Network(if has_credentials {
+            format!("Authentication failed: HTTP {}", status.as_u16())
+        } else {
+            format!(
+                "Server requires authentication (HTTP {}) but no credentials are configured for this host",
+                status.as_u16()
+            )
+        }));
+    }
+    if !status.is_success() {
+        return Err(DownloadError::Network(format!(
+            "HTTP error: {} - {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("Unknown")
+        )));
+    }
+
+    Ok(if has_credentials {
+        format!("Authentication succeeded (HTTP {})", status.as_u16())
+    } else {
+        format!(
+            "Request succeeded without credentials (HTTP {})",
+            status.as_u16()
+        )
+    })
+}
@@ -1,62 +1,230 @@
-use crate::types::VersionsConfig;
+use super::catalog::load_cached_catalog;
+use super::error::DownloadError;
+use super::source_headers::validate_config_headers;
+use crate::ipc_state::update_download_status;
+use crate::settings::{
+    get_dns_overrides, get_download_speed_limit_mbps, get_max_extract_size_gb, get_prefer_ipv4,
+    get_proxy_no_proxy, get_proxy_url, load_settings,
+};
+use crate::types::{
+    AddressProbe, AppSettings, ConnectivityDiagnosis, DownloadLifecycleEvent, ExtractProgress,
+    ProxyTestResult, VersionsConfig,
+};
+use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
 
-/// Calculate SHA-256 checksum of a file
-pub fn calculate_sha256(file_path: &std::path::Path) -> Result<String, String> {
+/// Feed up to `limit` bytes of a file into a SHA-256 hasher,
+/// reporting the number of bytes hashed so far after every chunk
+fn hash_file_into(
+    file_path: &std::path::Path,
+    limit: u64,
+    hasher: &mut Sha256,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<(), DownloadError> {
     let file = File::open(file_path)
-        .map_err(|e| format!("Failed to open file for checksum: {}", e))?;
+        .map_err(|e| DownloadError::io("Failed to open file for checksum", e))?;
     
-    let mut reader = BufReader::new(file);
-    let mut hasher = Sha256::new();
+    let mut reader = BufReader::new(file).take(limit);
     let mut buffer = [0u8; 8192];
+    let mut hashed = 0u64;
     
     loop {
         let bytes_read = reader
             .read(&mut buffer)
-            .map_err(|e| format!("Failed to read file for checksum: {}", e))?;
+            .map_err(|e| DownloadError::io("Failed to read file for checksum", e))?;
         
         if bytes_read == 0 {
             break;
         }
         
         hasher.update(&buffer[..bytes_read]);
+        hashed += bytes_read as u64;
+        on_progress(hashed);
     }
     
-    let result = hasher.finalize();
-    Ok(format!("{:x}", result))
+    Ok(())
+}
+
+/// Calculate SHA-256 checksum of a file
+pub fn calculate_sha256(file_path: &std::path::Path) -> Result<String, DownloadError> {
+    calculate_sha256_with_progress(file_path, &mut |_| {})
+}
+
+/// Calculate SHA-256 checksum of a file, reporting the bytes hashed so far
+pub fn calculate_sha256_with_progress(
+    file_path: &std::path::Path,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<String, DownloadError> {
+    let mut hasher = Sha256::new();
+    hash_file_into(file_path, u64::MAX, &mut hasher, on_progress)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Start an incremental SHA-256 for a download, seeded with the first `resume_from` bytes
+/// of the partial file (a pre-allocated file is longer than what has been written)
+pub fn start_incremental_sha256(
+    partial_path: &std::path::Path,
+    resume_from: u64,
+) -> Result<Sha256, DownloadError> {
+    let mut hasher = Sha256::new();
+    if resume_from > 0 {
+        log::info!("Re-hashing {} bytes of partial download", resume_from);
+        hash_file_into(partial_path, resume_from, &mut hasher, &mut |_| {})?;
+    }
+    Ok(hasher)
 }
 
-/// Verify SHA-256 checksum of a file
-pub fn verify_sha256(file_path: &std::path::Path, expected_hash: &str) -> Result<(), String> {
+/// Compare an already calculated SHA-256 against the expected one
+pub fn check_sha256(
+    file_path: &std::path::Path,
+    calculated_hash: &str,
+    expected_hash: &str,
+) -> Result<(), DownloadError> {
     if expected_hash.is_empty() {
         log::warn!("SHA-256 checksum not configured for this file, skipping verification");
         return Ok(());
     }
     
-    // Get file size for logging
-    let file_size = std::fs::metadata(file_path)
-        .map(|m| m.len())
-        .unwrap_or(0);
-    
-    log::info!("Verifying SHA-256 for file: {:?}, size: {} bytes", file_path, file_size);
-    
-    let calculated_hash = calculate_sha256(file_path)?;
-    
     if calculated_hash.to_lowercase() != expected_hash.to_lowercase() {
-        return Err(format!(
+        // Get file size for logging
+        let file_size = std::fs::metadata(file_path)
+            .map(|m| m.len())
+            .unwrap_or(0);
+        log::error!(
             "SHA-256 checksum verification failed!\nFile: {:?}\nSize: {} bytes\nExpected: {}\nGot: {}",
             file_path, file_size, expected_hash, calculated_hash
-        ));
+        );
+        return Err(DownloadError::Checksum {
+            expected: expected_hash.to_string(),
+            got: calculated_hash.to_string(),
+        });
     }
     
     log::info!("SHA-256 checksum verified successfully: {}", calculated_hash);
     Ok(())
 }
 
+/// Sidecar file recording which artifact a partial download belongs to
+#[derive(Serialize, Deserialize)]
+struct ResumeMetadata {
+    url: String,
+    sha256: String,
+    /// Bytes known to be written and synced. Set for pre-allocated files, whose length
+    /// is the final size; None means the file length is the valid size (append strategy).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    committed_bytes: Option<u64>,
+    /// ETag or Last-Modified of the response the partial file came from, sent as If-Range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    validator: Option<String>,
+}
+
+impl ResumeMetadata {
+    fn is_for(&self, url: &str, sha256: &str) -> bool {
+        self.url == url && self.sha256 == sha256
+    }
+}
+
+fn resume_metadata_path(archive_path: &Path) -> PathBuf {
+    let mut file_name = archive_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".meta");
+    archive_path.with_file_name(file_name)
+}
+
+fn read_resume_metadata(archive_path: &Path) -> Option<ResumeMetadata> {
+    std::fs::read_to_string(resume_metadata_path(archive_path))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
+
+fn save_resume_metadata(
+    archive_path: &Path,
+    metadata: &ResumeMetadata,
+) -> Result<(), DownloadError> {
+    let content = serde_json::to_string(metadata)
+        .map_err(|e| DownloadError::Other(format!("Failed to serialize resume metadata: {}", e)))?;
+    std::fs::write(resume_metadata_path(archive_path), content)
+        .map_err(|e| DownloadError::io("Failed to write resume metadata", e))
+}
+
+/// Record the source of a download so a leftover partial file can be matched later.
+/// The watermark and validator are kept when the metadata already describes this download.
+pub fn write_resume_metadata(
+    archive_path: &Path,
+    url: &str,
+    sha256: &str,
+) -> Result<(), DownloadError> {
+    let existing =
+        read_resume_metadata(archive_path).filter(|metadata| metadata.is_for(url, sha256));
+    save_resume_metadata(
+        archive_path,
+        &ResumeMetadata {
+            url: url.to_string(),
+            sha256: sha256.to_string(),
+            committed_bytes: existing
+                .as_ref()
+                .and_then(|metadata| metadata.committed_bytes),
+            validator: existing.and_then(|metadata| metadata.validator),
+        },
+    )
+}
+
+/// Whether a partial download was started for the given URL and checksum
+pub fn resume_metadata_matches(archive_path: &Path, url: &str, sha256: &str) -> bool {
+    read_resume_metadata(archive_path).is_some_and(|metadata| metadata.is_for(url, sha256))
+}
+
+/// Committed-bytes watermark of a pre-allocated partial download
+pub fn read_committed_bytes(archive_path: &Path) -> Option<u64> {
+    read_resume_metadata(archive_path)?.committed_bytes
+}
+
+/// Update the committed-bytes watermark (None switches back to "file length is valid").
+/// Returns false when the download has no resume metadata to record it in.
+pub fn write_committed_bytes(
+    archive_path: &Path,
+    committed_bytes: Option<u64>,
+) -> Result<bool, DownloadError> {
+    let Some(mut metadata) = read_resume_metadata(archive_path) else {
+        return Ok(false);
+    };
+    metadata.committed_bytes = committed_bytes;
+    save_resume_metadata(archive_path, &metadata)?;
+    Ok(true)
+}
+
+/// Validator of the response a partial download was started from
+pub fn read_resume_validator(archive_path: &Path) -> Option<String> {
+    read_resume_metadata(archive_path)?.validator
+}
+
+/// Record the validator of a download that starts from byte zero.
+/// Returns false when the download has no resume metadata to record it in.
+pub fn write_resume_validator(
+    archive_path: &Path,
+    validator: Option<String>,
+) -> Result<bool, DownloadError> {
+    let Some(mut metadata) = read_resume_metadata(archive_path) else {
+        return Ok(false);
+    };
+    metadata.validator = validator;
+    save_resume_metadata(archive_path, &metadata)?;
+    Ok(true)
+}
+
+/// Delete a downloaded archive together with its resume metadata
+pub fn remove_partial_download(archive_path: &Path) {
+    let _ = std::fs::remove_file(archive_path);
+    let _ = std::fs::remove_file(resume_metadata_path(archive_path));
+}
+
 /// Get current platform identifier for llama.cpp downloads
-pub fn get_platform_id() -> Result<String, String> {
+pub fn get_platform_id() -> Result<String, DownloadError> {
     #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
     return Ok("macos-arm64".to_string());
 
@@ -75,12 +243,775 @@ pub fn get_platform_id() -> Result<String, String> {
         all(target_os = "linux", target_arch = "x86_64"),
         all(target_os = "windows", target_arch = "x86_64")
     )))]
-    return Err("Unsupported platform".to_string());
+    return Err(DownloadError::Unsupported("Unsupported platform".to_string()));
 }
 
 /// Load configuration from versions.json (includes llama.cpp and models)
-pub fn load_config() -> Result<VersionsConfig, String> {
+/// A signed remote catalog, once fetched and verified, takes precedence over the built-in one
+pub fn load_config() -> Result<VersionsConfig, DownloadError> {
+    if let Some(config) = load_cached_catalog() {
+        return Ok(config);
+    }
+
     let config_str = include_str!("../../versions.json");
-    serde_json::from_str(config_str).map_err(|e| format!("Failed to parse versions.json: {}", e))
+    let config: VersionsConfig = serde_json::from_str(config_str)
+        .map_err(|e| DownloadError::Other(format!("Failed to parse versions.json: {}", e)))?;
+    validate_config_headers(&config)
+        .map_err(|e| DownloadError::Other(format!("Invalid versions.json: {}", e)))?;
+    Ok(config)
+}
+
+
+/// Proxy in effect for downloads: the one from settings, or HTTP(S)_PROXY from the environment
+pub fn effective_proxy_url() -> Option<String> {
+    get_proxy_url().ok().flatten().or_else(|| {
+        ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+            .iter()
+            .find_map(|var| std::env::var(var).ok().filter(|v| !v.trim().is_empty()))
+    })
+}
+
+/// Strip credentials from a proxy URL so it can be shown in logs and error messages
+pub(crate) fn redact_proxy_url(proxy_url: &str) -> String {
+    match reqwest::Url::parse(proxy_url) {
+        Ok(mut url) => {
+            if !url.username().is_empty() || url.password().is_some() {
+                let _ = url.set_username("***");
+                let _ = url.set_password(None);
+            }
+            url.to_string()
+        }
+        Err(_) => "<invalid proxy URL>".to_string(),
+    }
+}
+
+/// Everything `apply_proxy` and `apply_network_settings` depend on,
+/// to tell when a cached client must be rebuilt
+pub fn client_config_key() -> String {
+    let env_no_proxy = std::env::var("NO_PROXY")
+        .or_else(|_| std::env::var("no_proxy"))
+        .unwrap_or_default();
+    let mut dns_overrides: Vec<_> = get_dns_overrides()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    dns_overrides.sort();
+    format!(
+        "{:?}|{:?}|{}|{}|{:?}",
+        effective_proxy_url(),
+        get_proxy_no_proxy().unwrap_or_default(),
+        env_no_proxy,
+        get_prefer_ipv4().unwrap_or(false),
+        dns_overrides
+    )
+}
+
+/// Apply the IPv4-only preference and DNS overrides from settings to a client builder
+pub fn apply_network_settings(mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+    if get_prefer_ipv4().unwrap_or(false) {
+        // Binding to an IPv4 address means only IPv4 addresses are connected to
+        log::info!("Downloads use IPv4 only");
+        builder = builder.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    }
+    for (host, ip) in get_dns_overrides().unwrap_or_default() {
+        log::info!("Resolving {} to {}", host, ip);
+        // The port is taken from the URL
+        builder = builder.resolve(&host, SocketAddr::new(ip, 0));
+    }
+    builder
+}
+
+/// Hosts that bypass the proxy: the list from settings, or NO_PROXY from the environment
+fn effective_no_proxy() -> Option<reqwest::NoProxy> {
+    let no_proxy = get_proxy_no_proxy().unwrap_or_default();
+    if no_proxy.is_empty() {
+        reqwest::NoProxy::from_env()
+    } else {
+        reqwest::NoProxy::from_string(&no_proxy.join(","))
+    }
+}
+
+/// Apply the download proxy to an HTTP client builder.
+/// The environment fallback is applied explicitly because some packaged builds
+/// (e.g. apps launched from the macOS Dock) do not let reqwest pick it up on its own.
+pub fn apply_proxy(builder: reqwest::ClientBuilder) -> Result<reqwest::ClientBuilder, String> {
+    match effective_proxy_url() {
+        Some(url) => {
+            let proxy = reqwest::Proxy::all(url.as_str())
+                .map_err(|e| format!("Invalid proxy URL '{}': {}", redact_proxy_url(&url), e))?
+                .no_proxy(effective_no_proxy());
+            log::info!("Using download proxy: {}", redact_proxy_url(&url));
+            Ok(builder.proxy(proxy))
+        }
+        None => Ok(builder),
+    }
+}
+
+/// Format a request error, calling out proxy connection failures explicitly
+pub fn describe_request_error(context: &str, error: &reqwest::Error) -> String {
+    if error.is_connect() {
+        if let Some(proxy_url) = effective_proxy_url() {
+            return format!(
+                "{}: could not connect through proxy {} ({}). Check your proxy settings.",
+                context,
+                redact_proxy_url(&proxy_url),
+                error
+            );
+        }
+    }
+    format!("{}: {}", context, error)
+}
+
+/// Fetch the first byte of the llama.cpp archive through the configured proxy
+/// and report the round-trip latency, so the settings UI can validate proxy input
+#[tauri::command]
+pub async fn test_proxy_connection() -> Result<ProxyTestResult, DownloadError> {
+    let config = load_config()?;
+    let platform_id = get_platform_id()?;
+    let test_url = config
+        .llama_cpp
+        .platforms
+        .get(&platform_id)
+        .map(|p| p.url.clone())
+        .ok_or_else(|| {
+            DownloadError::Unsupported(format!(
+                "Platform '{}' not supported in configuration",
+                platform_id
+            ))
+        })?;
+
+    let builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .timeout(std::time::Duration::from_secs(30))
+        .connect_timeout(std::time::Duration::from_secs(15));
+    let client = apply_proxy(apply_network_settings(builder))?
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let proxy = effective_proxy_url().map(|proxy_url| redact_proxy_url(&proxy_url));
+    log::info!(
+        "Testing download connectivity via {}: GET {}",
+        proxy.as_deref().unwrap_or("direct connection"),
+        test_url
+    );
+
+    let started = Instant::now();
+    let response = client
+        .get(&test_url)
+        .header("Range", "bytes=0-0")
+        .send()
+        .await
+        .map_err(|e| DownloadError::Network(describe_request_error("Proxy test failed", &e)))?;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(DownloadError::Network(format!(
+            "Proxy test failed: HTTP {} - {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("Unknown")
+        )));
+    }
+
+    Ok(ProxyTestResult {
+        proxy,
+        status: status.as_u16(),
+        latency_ms,
+    })
+}
+
+/// Check that downloads can get out through the configured proxy, as a message
+/// (`test_proxy_connection` reports the details)
+#[tauri::command]
+pub async fn test_proxy() -> Result<String, DownloadError> {
+    let result = test_proxy_connection().await?;
+    let via = match &result.proxy {
+        Some(proxy) => format!("via proxy {}", proxy),
+        None => "without a proxy".to_string(),
+    };
+    Ok(format!("Connection OK {} (HTTP {})", via, result.status))
+}
+
+fn address_family(address: &SocketAddr) -> String {
+    if address.is_ipv4() { "ipv4" } else { "ipv6" }.to_string()
+}
+
+/// Connect to an address directly (bypassing any proxy) and time the TCP handshake
+async fn probe_address(address: SocketAddr) -> AddressProbe {
+    let started = Instant::now();
+    let result = tokio::time::timeout(
+        Duration::from_secs(10),
+        tokio::net::TcpStream::connect(address),
+    )
+    .await;
+    let (connect_ms, error) = match result {
+        Ok(Ok(_)) => (Some(started.elapsed().as_millis() as u64), None),
+        Ok(Err(e)) => (None, Some(e.to_string())),
+        Err(_) => (None, Some("Connection timed out".to_string())),
+    };
+    AddressProbe {
+        address: address.to_string(),
+        family: address_family(&address),
+        connect_ms,
+        error,
+    }
+}
+
+/// Report how a download URL is reached: DNS result, TCP connect time per address family
+/// and the address the HTTP request ended up using, so support can tell IPv6, DNS and
+/// firewall problems apart without a packet capture
+#[tauri::command]
+pub async fn diagnose_connectivity(url: String) -> Result<ConnectivityDiagnosis, DownloadError> {
+    let parsed = reqwest::Url::parse(&url)
+        .map_err(|e| DownloadError::Other(format!("Invalid URL '{}': {}", url, e)))?;
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| DownloadError::Other(format!("URL '{}' has no host", url)))?
+        .to_lowercase();
+    let port = parsed.port_or_known_default().unwrap_or(443);
+    let prefer_ipv4 = get_prefer_ipv4().unwrap_or(false);
+    let dns_override = get_dns_overrides().unwrap_or_default().get(&host).copied();
+
+    let mut diagnosis = ConnectivityDiagnosis {
+        url: url.clone(),
+        host: host.clone(),
+        proxy: effective_proxy_url().map(|proxy_url| redact_proxy_url(&proxy_url)),
+        prefer_ipv4,
+        dns_override: dns_override.map(|ip| ip.to_string()),
+        dns_ms: None,
+        probes: Vec::new(),
+        used_address: None,
+        used_family: None,
+        status: None,
+        latency_ms: None,
+        error: None,
+    };
+
+    let addresses: Vec<SocketAddr> = match dns_override {
+        Some(ip) => vec![SocketAddr::new(ip, port)],
+        None => {
+            let started = Instant::now();
+            match tokio::net::lookup_host((host.as_str(), port)).await {
+                Ok(addresses) => {
+                    diagnosis.dns_ms = Some(started.elapsed().as_millis() as u64);
+                    addresses.collect()
+                }
+                Err(e) => {
+                    diagnosis.error = Some(format!("DNS lookup failed: {}", e));
+                    Vec::new()
+                }
+            }
+        }
+    };
+
+    let first_ipv4 = addresses.iter().find(|a| a.is_ipv4()).copied();
+    let first_ipv6 = addresses
+        .iter()
+        .find(|a| a.is_ipv6())
+        .copied()
+        .filter(|_| !prefer_ipv4);
+    for address in [first_ipv4, first_ipv6].into_iter().flatten() {
+        diagnosis.probes.push(probe_address(address).await);
+    }
+
+    let builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .timeout(Duration::from_secs(30))
+        .connect_timeout(Duration::from_secs(15));
+    let client = apply_proxy(apply_network_settings(builder))?
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let started = Instant::now();
+    match client.get(&url).header("Range", "bytes=0-0").send().await {
+        Ok(response) => {
+            diagnosis.latency_ms = Some(started.elapsed().as_millis() as u64);
+            diagnosis.status = Some(response.status().as_u16());
+            diagnosis.used_address = response.remote_addr().map(|a| a.to_string());
+            diagnosis.used_family = response.remote_addr().map(|a| address_family(&a));
+        }
+        Err(e) => {
+            diagnosis.error = Some(describe_request_error("Request failed", &e));
+        }
+    }
+
+    log::info!("Connectivity diagnosis: {:?}", diagnosis);
+    Ok(diagnosis)
+}
+
+/// Retry count and exponential backoff bounds for interrupted downloads
+pub struct RetryPolicy {
+    /// Maximum number of consecutive retry attempts for chunk read errors
+    pub max_retries: u32,
+    /// Base delay for exponential backoff (in milliseconds)
+    pub base_delay_ms: u64,
+    /// Maximum delay between retries (in milliseconds)
+    pub max_delay_ms: u64,
+    /// How long a chunk read may go without data before the connection is dropped
+    pub stall_timeout: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a policy from the current settings (read once per download)
+    pub fn from_settings() -> Self {
+        let settings = load_settings().unwrap_or_else(|e| {
+            log::warn!("Failed to read retry settings, using defaults: {}", e);
+            AppSettings::default()
+        });
+        Self {
+            max_retries: settings.download_max_retries.max(1),
+            base_delay_ms: settings.download_base_retry_delay_ms,
+            max_delay_ms: settings.download_max_retry_delay_ms,
+            stall_timeout: Duration::from_secs(settings.download_stall_timeout_secs.max(1)),
+        }
+    }
+
+    /// Calculate exponential backoff delay
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        let delay_ms = self.base_delay_ms.saturating_mul(2u64.pow(attempt.min(10)));
+        Duration::from_millis(delay_ms.min(self.max_delay_ms))
+    }
+}
+
+/// How often a running download picks up a changed speed limit
+const SPEED_LIMIT_REFRESH_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Read the configured download speed limit, converted to bytes per second
+pub fn configured_bytes_per_sec() -> Option<u64> {
+    get_download_speed_limit_mbps()
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to read download speed limit, downloading unthrottled: {}", e);
+            None
+        })
+        .map(|mbps| mbps as u64 * 125_000)
+}
+
+/// Token-bucket limiter for download throughput.
+/// The bucket holds up to one second worth of bytes, so short bursts are allowed
+/// while the sustained rate stays at the configured cap. The limit is re-read from
+/// settings periodically, so changing it applies to downloads already in progress.
+pub struct BandwidthLimiter {
+    /// Reads the current limit in bytes per second
+    source: fn() -> Option<u64>,
+    bytes_per_sec: Option<u64>,
+    tokens: f64,
+    last_refill: Instant,
+    last_settings_check: Instant,
+}
+
+impl BandwidthLimiter {
+    /// Create a limiter that follows the speed limit returned by `source`
+    /// (`configured_bytes_per_sec` for the one in settings)
+    pub fn new(source: fn() -> Option<u64>) -> Self {
+        let bytes_per_sec = source();
+        if let Some(limit) = bytes_per_sec {
+            log::info!("Download speed limited to {:.2} MB/s", limit as f64 / 1_048_576.0);
+        }
+        let now = Instant::now();
+        Self {
+            source,
+            bytes_per_sec,
+            tokens: bytes_per_sec.unwrap_or(0) as f64,
+            last_refill: now,
+            last_settings_check: now,
+        }
+    }
+
+    /// Pick up a speed limit changed in settings since the last check
+    fn refresh_limit(&mut self, now: Instant) {
+        if now.duration_since(self.last_settings_check) < SPEED_LIMIT_REFRESH_INTERVAL {
+            return;
+        }
+        self.last_settings_check = now;
+
+        let bytes_per_sec = (self.source)();
+        if bytes_per_sec != self.bytes_per_sec {
+            match bytes_per_sec {
+                Some(limit) => log::info!(
+                    "Download speed limit changed to {:.2} MB/s",
+                    limit as f64 / 1_048_576.0
+                ),
+                None => log::info!("Download speed limit removed"),
+            }
+            self.bytes_per_sec = bytes_per_sec;
+            self.tokens = bytes_per_sec.unwrap_or(0) as f64;
+        }
+    }
+
+    /// Account for `bytes` just written and sleep if the bucket ran dry
+    pub async fn consume(&mut self, bytes: u64) {
+        let now = Instant::now();
+        self.refresh_limit(now);
+
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+
+        let Some(limit) = self.bytes_per_sec else {
+            return;
+        };
+        let rate = limit as f64;
+        self.tokens = (self.tokens + elapsed * rate).min(rate) - bytes as f64;
+
+        if self.tokens < 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(-self.tokens / rate)).await;
+        }
+    }
 }
 
+/// Extracted bytes between two progress reports
+const EXTRACT_PROGRESS_INTERVAL: u64 = 50 * 1024 * 1024;
+
+/// Tracks bytes written while unpacking an archive and reports them every 50 MB
+pub struct ExtractionProgress<'a> {
+    artifact: String,
+    total: Option<u64>,
+    extracted: u64,
+    last_report: u64,
+    current_file: String,
+    on_progress: &'a mut dyn FnMut(&ExtractProgress),
+}
+
+impl<'a> ExtractionProgress<'a> {
+    /// `total` is the uncompressed size of the members that will be extracted
+    pub fn new(
+        artifact: &str,
+        total: Option<u64>,
+        on_progress: &'a mut dyn FnMut(&ExtractProgress),
+    ) -> Self {
+        Self {
+            artifact: artifact.to_string(),
+            total,
+            extracted: 0,
+            last_report: 0,
+            current_file: String::new(),
+            on_progress,
+        }
+    }
+
+    /// Report the member about to be extracted
+    pub fn start_file(&mut self, name: &str) {
+        self.current_file = name.to_string();
+        self.report();
+    }
+
+    fn add(&mut self, bytes: u64) {
+        self.extracted += bytes;
+        if self.extracted - self.last_report >= EXTRACT_PROGRESS_INTERVAL {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        self.last_report = self.extracted;
+        (self.on_progress)(&ExtractProgress {
+            artifact: self.artifact.clone(),
+            extracted: self.extracted,
+            total: self.total,
+            percentage: self
+                .total
+                .filter(|&total| total > 0)
+                .map(|total| (self.extracted as f64 / total as f64 * 100.0).min(100.0)),
+            current_file: self.current_file.clone(),
+        });
+    }
+
+    /// Wrap an output file so bytes copied into it are counted
+    pub fn writer<'w, W: Write>(&'w mut self, inner: W) -> ProgressWriter<'w, 'a, W> {
+        ProgressWriter {
+            inner,
+            progress: self,
+        }
+    }
+}
+
+/// Writer that counts bytes into an `ExtractionProgress`
+pub struct ProgressWriter<'w, 'a, W> {
+    inner: W,
+    progress: &'w mut ExtractionProgress<'a>,
+}
+
+impl<W: Write> Write for ProgressWriter<'_, '_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.progress.add(written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Extracted data may be at most this many times the archive size.
+/// Model archives barely compress and llama.cpp bundles about 4x, so this only stops bombs.
+const MAX_EXTRACT_RATIO: u64 = 20;
+
+/// Unix file type bits marking a symlink in zip external attributes
+const UNIX_SYMLINK_MODE: u32 = 0o120000;
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+
+/// Guards against decompression bombs: caps the total extracted bytes to a multiple
+/// of the archive size and to the `max_extract_size_gb` setting, whichever is lower
+pub struct ExtractionLimits {
+    max_bytes: u64,
+    extracted: u64,
+}
+
+impl ExtractionLimits {
+    pub fn for_archive(archive_path: &Path) -> Result<Self, String> {
+        let cap_gb = get_max_extract_size_gb().unwrap_or_else(|e| {
+            log::warn!("Failed to read extraction size limit, using default: {}", e);
+            AppSettings::default().max_extract_size_gb
+        });
+        Self::with_cap(archive_path, cap_gb)
+    }
+
+    /// Limits for `archive_path` with an absolute cap of `cap_gb` instead of the setting
+    pub fn with_cap(archive_path: &Path, cap_gb: u64) -> Result<Self, String> {
+        let archive_size = std::fs::metadata(archive_path)
+            .map_err(|e| format!("Failed to read archive size: {}", e))?
+            .len();
+        Ok(Self {
+            max_bytes: archive_size
+                .saturating_mul(MAX_EXTRACT_RATIO)
+                .min(cap_gb.saturating_mul(1024 * 1024 * 1024)),
+            extracted: 0,
+        })
+    }
+
+    /// Reject an entry whose declared uncompressed size alone would exceed the limit
+    pub fn check_declared(&self, name: &str, declared_size: u64) -> Result<(), String> {
+        if declared_size > self.max_bytes.saturating_sub(self.extracted) {
+            return Err(format!(
+                "Archive entry '{}' declares {} bytes, which exceeds the extraction limit of {} bytes",
+                name, declared_size, self.max_bytes
+            ));
+        }
+        Ok(())
+    }
+
+    /// Copy an entry, failing as soon as the actual output exceeds the limit
+    /// (declared sizes can lie)
+    pub fn copy<R: Read, W: Write>(
+        &mut self,
+        name: &str,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Result<(), String> {
+        let remaining = self.max_bytes.saturating_sub(self.extracted);
+        let copied = std::io::copy(&mut reader.take(remaining.saturating_add(1)), writer)
+            .map_err(|e| format!("Failed to extract file: {}", e))?;
+        if copied > remaining {
+            return Err(format!(
+                "Archive entry '{}' exceeds the extraction limit of {} bytes",
+                name, self.max_bytes
+            ));
+        }
+        self.extracted += copied;
+        Ok(())
+    }
+}
+
+/// Whether a zip entry is a symlink (never extracted: it could point outside the destination)
+pub fn is_zip_symlink(file: &zip::read::ZipFile<'_>) -> bool {
+    file.unix_mode()
+        .is_some_and(|mode| mode & UNIX_FILE_TYPE_MASK == UNIX_SYMLINK_MODE)
+}
+
+/// Make sure an output path stays inside `dest_dir` once symlinks and `..` are resolved.
+/// The parent directory must already exist.
+pub fn ensure_within_dir(dest_dir: &Path, output_path: &Path) -> Result<(), String> {
+    let root = dest_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {:?}: {}", dest_dir, e))?;
+    let parent = output_path
+        .parent()
+        .ok_or_else(|| format!("Invalid output path: {:?}", output_path))?
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {:?}: {}", output_path, e))?;
+    if !parent.starts_with(&root) {
+        return Err(format!(
+            "Archive entry {:?} would be written outside {:?}",
+            output_path, dest_dir
+        ));
+    }
+    Ok(())
+}
+
+/// Forward extraction progress to the UI as `extract-progress` and keep the IPC status
+/// marked as downloading, so the native host reports extraction as part of the download
+pub fn extract_progress_reporter<'a>(
+    app: &'a AppHandle,
+    download_id: &'a str,
+) -> impl FnMut(&ExtractProgress) + 'a {
+    move |progress| {
+        let _ = update_download_status(download_id, true, progress.percentage);
+        let _ = app.emit("extract-progress", progress);
+    }
+}
+
+/// Emit `download-started` for an artifact
+pub fn emit_download_started(app: &AppHandle, artifact: &str, artifact_type: &str) {
+    let _ = app.emit(
+        "download-started",
+        DownloadLifecycleEvent {
+            artifact: artifact.to_string(),
+            artifact_type: artifact_type.to_string(),
+            message: None,
+            error: None,
+            error_kind: None,
+            reason: None,
+        },
+    );
+}
+
+/// Emit `download-completed` or `download-failed` depending on the download result
+pub fn emit_download_finished(
+    app: &AppHandle,
+    artifact: &str,
+    artifact_type: &str,
+    result: &Result<String, DownloadError>,
+) {
+    let (event, message, error, error_kind, reason) = match result {
+        Ok(message) => (
+            "download-completed",
+            Some(message.clone()),
+            None,
+            None,
+            None,
+        ),
+        Err(error) => (
+            "download-failed",
+            None,
+            Some(error.to_string()),
+            Some(error.kind()),
+            error.reason(),
+        ),
+    };
+    let _ = app.emit(
+        event,
+        DownloadLifecycleEvent {
+            artifact: artifact.to_string(),
+            artifact_type: artifact_type.to_string(),
+            message,
+            error,
+            error_kind,
+            reason,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// An "archive" of `size` bytes, the ratio limit then allows 20 times that
+    fn archive_of_size(dir: &Path, size: usize) -> PathBuf {
+        let path = dir.join("archive.zip");
+        std::fs::write(&path, vec![0u8; size]).unwrap();
+        path
+    }
+
+    #[test]
+    fn ensure_within_dir_accepts_nested_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        assert!(ensure_within_dir(dir.path(), &dir.path().join("file.bin")).is_ok());
+        assert!(ensure_within_dir(dir.path(), &dir.path().join("a/b/file.bin")).is_ok());
+        // `..` that stays inside is fine
+        assert!(ensure_within_dir(dir.path(), &dir.path().join("a/b/../file.bin")).is_ok());
+    }
+
+    #[test]
+    fn ensure_within_dir_rejects_parent_traversal() {
+        let root = tempfile::tempdir().unwrap();
+        let dest = root.path().join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        assert!(ensure_within_dir(&dest, &dest.join("../evil.bin")).is_err());
+        assert!(ensure_within_dir(&dest, &dest.join("../../evil.bin")).is_err());
+    }
+
+    #[test]
+    fn ensure_within_dir_rejects_absolute_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let dest = root.path().join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        // Joining an absolute path replaces the destination altogether
+        let outside = dest.join(root.path().join("evil.bin"));
+        assert!(ensure_within_dir(&dest, &outside).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_within_dir_rejects_symlinked_directories() {
+        let root = tempfile::tempdir().unwrap();
+        let dest = root.path().join("dest");
+        let outside = root.path().join("outside");
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, dest.join("link")).unwrap();
+        assert!(ensure_within_dir(&dest, &dest.join("link/evil.bin")).is_err());
+    }
+
+    #[test]
+    fn limits_reject_declared_sizes_over_the_ratio() {
+        let dir = tempfile::tempdir().unwrap();
+        let limits = ExtractionLimits::with_cap(&archive_of_size(dir.path(), 1000), 10).unwrap();
+        assert!(limits.check_declared("model.gguf", 20_000).is_ok());
+        assert!(limits.check_declared("model.gguf", 20_001).is_err());
+    }
+
+    #[test]
+    fn limits_apply_the_absolute_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let limits = ExtractionLimits::with_cap(&archive_of_size(dir.path(), 1000), 0).unwrap();
+        assert!(limits.check_declared("model.gguf", 1).is_err());
+    }
+
+    #[test]
+    fn limits_stop_entries_that_lie_about_their_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut limits =
+            ExtractionLimits::with_cap(&archive_of_size(dir.path(), 1000), 10).unwrap();
+        // Declared as tiny, but decompresses to far more than the archive allows
+        limits.check_declared("bomb.bin", 10).unwrap();
+        let mut bomb = std::io::repeat(0).take(10_000_000);
+        let mut output = Vec::new();
+        let error = limits.copy("bomb.bin", &mut bomb, &mut output).unwrap_err();
+        assert!(error.contains("exceeds the extraction limit"), "{}", error);
+        // Copying stopped right past the limit instead of writing everything
+        assert_eq!(output.len(), 20_001);
+    }
+
+    #[test]
+    fn limits_count_every_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut limits =
+            ExtractionLimits::with_cap(&archive_of_size(dir.path(), 1000), 10).unwrap();
+        let mut output = Vec::new();
+        limits
+            .copy("a.bin", &mut Cursor::new(vec![1u8; 15_000]), &mut output)
+            .unwrap();
+        assert!(limits.check_declared("b.bin", 6_000).is_err());
+        assert!(limits
+            .copy("b.bin", &mut Cursor::new(vec![1u8; 6_000]), &mut output)
+            .is_err());
+    }
+
+    #[test]
+    fn zip_symlinks_are_recognised() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        writer.start_file("llama-server", options).unwrap();
+        writer.write_all(b"binary").unwrap();
+        writer
+            .add_symlink("libllama.so", "/etc/passwd", options)
+            .unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        assert!(!is_zip_symlink(&archive.by_name("llama-server").unwrap()));
+        assert!(is_zip_symlink(&archive.by_name("libllama.so").unwrap()));
+    }
+}
@@ -0,0 +1,1227 @@
+// Shared download engine
+// Streams a URL to a file with Range resume, retry with backoff, bandwidth limiting and
+// an incremental SHA-256. llama.cpp and model downloads are thin wrappers around it.
+// When the size is known the file is pre-allocated to limit fragmentation (NTFS especially),
+// and the resume metadata tracks how many bytes of it are valid.
+
+use super::credentials::apply_credentials;
+use super::download_utils::{
+    apply_network_settings, apply_proxy, check_sha256, client_config_key, configured_bytes_per_sec,
+    describe_request_error, read_committed_bytes, read_resume_validator, start_incremental_sha256,
+    write_committed_bytes, write_resume_validator, BandwidthLimiter, RetryPolicy,
+};
+use super::error::DownloadError;
+use super::source_headers::resolve_source_headers;
+use crate::download_history::{append_download_history, DownloadHistoryEntry};
+use crate::ipc_state::is_download_cancelled;
+use crate::types::DownloadProgress;
+use futures_util::StreamExt;
+use reqwest::header::HeaderMap;
+use sha2::Digest;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+/// A download fails when no data arrives for this long. There is no limit on the whole
+/// transfer, so multi-GB downloads on slow links are not aborted while data keeps flowing.
+const READ_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How often a running download reads IPC state to see whether it was cancelled
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// HTTP client shared by all downloads, keyed by the network settings it was built with.
+/// Reusing it keeps pooled connections (and their TLS sessions) across retries and downloads.
+static SHARED_CLIENT: Mutex<Option<(String, reqwest::Client)>> = Mutex::new(None);
+
+/// The shared download client, rebuilt when the network settings changed since it was built
+fn shared_client() -> Result<reqwest::Client, DownloadError> {
+    let key = client_config_key();
+    let mut cached = SHARED_CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached_key, client)) = cached.as_ref() {
+        if *cached_key == key {
+            return Ok(client.clone());
+        }
+        log::info!("Network settings changed, rebuilding download client");
+    }
+
+    let builder = reqwest::Client::builder()
+        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .read_timeout(READ_IDLE_TIMEOUT)
+        .connect_timeout(Duration::from_secs(30))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60));
+
+    let client = apply_proxy(apply_network_settings(builder))?
+        .build()
+        .map_err(|e| DownloadError::Other(format!("Failed to create HTTP client: {}", e)))?;
+    *cached = Some((key, client.clone()));
+    Ok(client)
+}
+
+/// Result of a completed download
+pub struct DownloadOutcome {
+    /// Total size of the file on disk
+    pub size: u64,
+    /// SHA-256 of the whole file, calculated while streaming
+    pub sha256: String,
+    /// Server validators of the response, reported when verification keeps failing
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+pub struct Downloader {
+    client: reqwest::Client,
+    /// Artifact name reported in progress events
+    artifact: String,
+    /// Human-readable name used in progress messages and logs
+    label: String,
+    retry_policy: RetryPolicy,
+    /// Per-source headers from versions.json, sent with every request
+    headers: HeaderMap,
+    /// Tells whether the download was cancelled, checked while it runs
+    cancel_check: Option<Box<dyn Fn() -> bool + Send + Sync>>,
+    /// Reads the speed limit in bytes per second, see `BandwidthLimiter`
+    speed_limit: fn() -> Option<u64>,
+}
+
+impl Downloader {
+    pub fn new(artifact: &str, label: &str) -> Result<Self, DownloadError> {
+        Ok(Self::with_client(
+            shared_client()?,
+            artifact,
+            label,
+            RetryPolicy::from_settings(),
+        ))
+    }
+
+    fn with_client(
+        client: reqwest::Client,
+        artifact: &str,
+        label: &str,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            client,
+            artifact: artifact.to_string(),
+            label: label.to_string(),
+            retry_policy,
+            headers: HeaderMap::new(),
+            cancel_check: None,
+            speed_limit: configured_bytes_per_sec,
+        }
+    }
+
+    /// Abort with `DownloadError::Cancelled` once cancel_download is called for `download_id`
+    pub fn with_download_id(mut self, download_id: &str) -> Self {
+        let download_id = download_id.to_string();
+        self.cancel_check = Some(Box::new(move || is_download_cancelled(&download_id)));
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_check
+            .as_ref()
+            .is_some_and(|cancelled| cancelled())
+    }
+
+    /// Whether the download was cancelled; checked at most once per `CANCEL_CHECK_INTERVAL`
+    fn cancel_requested(&self, last_check: &mut Instant) -> bool {
+        if self.cancel_check.is_none() || last_check.elapsed() < CANCEL_CHECK_INTERVAL {
+            return false;
+        }
+        *last_check = Instant::now();
+        self.is_cancelled()
+    }
+
+    /// Send the custom headers of the download source with every request
+    pub fn with_headers(
+        mut self,
+        headers: &HashMap<String, String>,
+    ) -> Result<Self, DownloadError> {
+        self.headers = resolve_source_headers(headers)?;
+        Ok(self)
+    }
+
+    /// Request with credentials and per-source headers applied
+    fn request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        apply_credentials(self.client.request(method, url), url).headers(self.headers.clone())
+    }
+
+    /// Check if server supports Range requests.
+    /// Some servers omit `accept-ranges` on HEAD (or reject HEAD) but still honor ranges,
+    /// so a one-byte ranged GET is used as a fallback probe.
+    async fn check_range_support(&self, url: &str) -> bool {
+        match self.request(reqwest::Method::HEAD, url).send().await {
+            Ok(response) => {
+                let accepts_ranges = response
+                    .headers()
+                    .get("accept-ranges")
+                    .map(|v| v.to_str().unwrap_or("") != "none")
+                    .unwrap_or(false);
+                if accepts_ranges {
+                    log::info!("Server range support: true");
+                    return true;
+                }
+            }
+            Err(e) => {
+                log::warn!("HEAD request for range support failed: {}", e);
+            }
+        }
+
+        let probe = self
+            .request(reqwest::Method::GET, url)
+            .header("Range", "bytes=0-0")
+            .header("Accept-Encoding", "identity")
+            .send()
+            .await;
+        match probe {
+            Ok(response) => {
+                // The response is dropped without reading the body, so at most the
+                // first buffered chunk is transferred even if the range is ignored
+                let accepts_ranges = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+                log::info!("Server range support (GET probe): {}", accepts_ranges);
+                accepts_ranges
+            }
+            Err(e) => {
+                log::warn!("Failed to check range support: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Start or resume a download request from a given byte offset.
+    /// With `if_range`, a server whose file changed answers 200 with the new content
+    /// instead of 206, so stale bytes are never continued.
+    async fn start_request(
+        &self,
+        url: &str,
+        start_byte: u64,
+        if_range: Option<&str>,
+    ) -> Result<(reqwest::Response, Option<u64>), DownloadError> {
+        let mut request = self
+            .request(reqwest::Method::GET, url)
+            .header("Accept", "*/*")
+            .header("Accept-Encoding", "identity");
+
+        if start_byte > 0 {
+            log::info!("Resuming download from byte {}", start_byte);
+            request = request.header("Range", format!("bytes={}-", start_byte));
+            if let Some(validator) = if_range {
+                request = request.header("If-Range", validator);
+            }
+        }
+
+        let response = request.send().await.map_err(|e| {
+            let context = format!("Failed to download {}", self.label);
+            DownloadError::Network(describe_request_error(&context, &e))
+        })?;
+
+        let status = response.status();
+        log::info!("HTTP response status: {}", status);
+
+        // A kept archive that is already complete: nothing left to fetch
+        if start_byte > 0 && status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok((response, Some(start_byte)));
+        }
+
+        // 200 OK for new download, 206 Partial Content for resume
+        if !status.is_success() && status != reqwest::StatusCode::PARTIAL_CONTENT {
+            return Err(DownloadError::Network(format!(
+                "HTTP error: {} - {}",
+                status.as_u16(),
+                status.canonical_reason().unwrap_or("Unknown")
+            )));
+        }
+
+        let total_size = if start_byte > 0 && status == reqwest::StatusCode::PARTIAL_CONTENT {
+            // For resumed downloads, parse Content-Range header to get total size
+            response
+                .headers()
+                .get("content-range")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|s| s.split('/').next_back())
+                .and_then(|s| s.parse::<u64>().ok())
+        } else {
+            response.content_length()
+        };
+
+        Ok((response, total_size))
+    }
+
+    fn progress(&self, downloaded: u64, total: Option<u64>, message: String) -> DownloadProgress {
+        DownloadProgress {
+            artifact: self.artifact.clone(),
+            downloaded,
+            total,
+            percentage: total.map(|total| (downloaded as f64 / total as f64) * 100.0),
+            message,
+        }
+    }
+
+    /// Download `url` into `dest`, resuming a partial file when the server supports it.
+    /// `on_progress` is called at start, every 10 MB and before each retry.
+    pub async fn download_to_file<F>(
+        &self,
+        url: &str,
+        dest: &Path,
+        mut on_progress: F,
+    ) -> Result<DownloadOutcome, DownloadError>
+    where
+        F: FnMut(&DownloadProgress),
+    {
+        log::info!("Downloading {} from: {}", self.label, url);
+
+        // Check if server supports range requests for resume capability
+        let supports_resume = self.check_range_support(url).await;
+
+        // Check if partial download exists
+        let mut downloaded: u64 = if supports_resume && dest.exists() {
+            let file_size = tokio::fs::metadata(dest)
+                .await
+                .map(|m| m.len())
+                .unwrap_or(0);
+            // A pre-allocated file is only valid up to its committed watermark
+            let existing_size = match read_committed_bytes(dest) {
+                Some(committed) => committed.min(file_size),
+                None => file_size,
+            };
+            if existing_size > 0 {
+                log::info!(
+                    "Found partial download: {:.2} MB, will attempt to resume",
+                    existing_size as f64 / 1_048_576.0
+                );
+            }
+            existing_size
+        } else {
+            0
+        };
+
+        let mut validator = if downloaded > 0 {
+            read_resume_validator(dest)
+        } else {
+            None
+        };
+        let (response, total_size) = self
+            .start_request(url, downloaded, validator.as_deref())
+            .await?;
+
+        if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            log::info!("{} already fully downloaded, skipping transfer", self.label);
+            let hasher = start_incremental_sha256(dest, downloaded)?;
+            return Ok(DownloadOutcome {
+                size: downloaded,
+                sha256: format!("{:x}", hasher.finalize()),
+                etag: None,
+                last_modified: None,
+            });
+        }
+
+        // Appending a full response to the partial file would corrupt it
+        let mut start_message = format!("Starting {} download...", self.label);
+        if downloaded > 0 && response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            if validator.is_some() {
+                log::warn!(
+                    "Remote file changed since the partial download, restarting from the beginning"
+                );
+                start_message = format!(
+                    "Remote {} file changed since the download was paused, restarting from the beginning...",
+                    self.label
+                );
+            } else {
+                log::warn!(
+                    "Server ignored the Range request, restarting download from the beginning"
+                );
+            }
+            downloaded = 0;
+        }
+
+        // A fresh download records the validator of the content it is going to receive
+        if downloaded == 0 {
+            validator = resume_validator(response.headers());
+            write_resume_validator(dest, validator.clone())?;
+        }
+
+        if let Some(size) = total_size {
+            log::info!("{} size: {:.2} MB", self.label, size as f64 / 1_048_576.0);
+        } else {
+            log::warn!("{} size: unknown (no Content-Length header)", self.label);
+        }
+
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header("etag");
+        let last_modified = header("last-modified");
+
+        // Log some response headers for debugging
+        log::info!("Content-Type: {:?}", response.headers().get("content-type"));
+        log::info!(
+            "Content-Encoding: {:?}",
+            response.headers().get("content-encoding")
+        );
+
+        let mut initial = self.progress(downloaded, total_size, start_message);
+        initial.percentage = initial.percentage.or(Some(0.0));
+        on_progress(&initial);
+
+        // Hash bytes as they arrive so the file is not re-read just for verification;
+        // a resumed download re-hashes the partial file first
+        let mut hasher = start_incremental_sha256(dest, downloaded)?;
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dest)
+            .await
+            .map_err(|e| DownloadError::io("Failed to open download file", e))?;
+
+        // Pre-allocate to the final size when known; the watermark is recorded first so a
+        // crash never leaves a full-length file that looks complete. Otherwise append,
+        // dropping anything past the valid bytes.
+        let preallocate_to = total_size.filter(|&total| total > downloaded);
+        // The watermark lives in the resume metadata; a download without it appends
+        let watermark_recorded = match preallocate_to {
+            Some(_) => write_committed_bytes(dest, Some(downloaded))?,
+            None => false,
+        };
+        let preallocated = match preallocate_to {
+            Some(total) if watermark_recorded => {
+                log::info!("Pre-allocating {:.2} MB", total as f64 / 1_048_576.0);
+                file.set_len(total)
+                    .await
+                    .map_err(|e| DownloadError::io("Failed to pre-allocate file", e))?;
+                true
+            }
+            _ => {
+                write_committed_bytes(dest, None)?;
+                file.set_len(downloaded)
+                    .await
+                    .map_err(|e| DownloadError::io("Failed to truncate partial file", e))?;
+                false
+            }
+        };
+
+        // Write at the absolute offset of the first missing byte
+        file.seek(std::io::SeekFrom::Start(downloaded))
+            .await
+            .map_err(|e| DownloadError::io("Failed to seek in download file", e))?;
+
+        let mut stream = response.bytes_stream();
+        let mut last_emit_mb = downloaded / (10 * 1024 * 1024);
+        let mut last_log_mb = downloaded / (50 * 1024 * 1024);
+        let mut consecutive_errors = 0u32;
+        let mut stalled = false;
+        let mut limiter = BandwidthLimiter::new(self.speed_limit);
+        let mut last_cancel_check = Instant::now();
+
+        log::info!("Starting download stream...");
+
+        loop {
+            if self.cancel_requested(&mut last_cancel_check) {
+                log::info!("{} download cancelled at byte {}", self.label, downloaded);
+                // Keep what was downloaded so a later download resumes from here
+                if preallocated {
+                    commit_watermark(&mut file, dest, downloaded).await?;
+                } else {
+                    file.flush()
+                        .await
+                        .map_err(|e| DownloadError::io("Failed to flush file", e))?;
+                }
+                return Err(DownloadError::Cancelled);
+            }
+
+            let next_chunk = tokio::time::timeout(self.retry_policy.stall_timeout, stream.next());
+            let interruption = match next_chunk.await {
+                // A blackholed connection yields no error, only silence
+                Err(_) => {
+                    stalled = true;
+                    format!(
+                        "No data received for {} seconds (connection stalled)",
+                        self.retry_policy.stall_timeout.as_secs()
+                    )
+                }
+                Ok(Some(Ok(chunk))) => {
+                    // Reset error counter on successful chunk
+                    consecutive_errors = 0;
+
+                    file.write_all(&chunk)
+                        .await
+                        .map_err(|e| DownloadError::io("Failed to write chunk", e))?;
+                    hasher.update(&chunk);
+
+                    downloaded += chunk.len() as u64;
+                    limiter.consume(chunk.len() as u64).await;
+
+                    // Log progress every 50 MB to console
+                    let current_log_mb = downloaded / (50 * 1024 * 1024);
+                    if current_log_mb > last_log_mb {
+                        last_log_mb = current_log_mb;
+                        if preallocated {
+                            commit_watermark(&mut file, dest, downloaded).await?;
+                        }
+                        let percentage =
+                            total_size.map(|total| (downloaded as f64 / total as f64) * 100.0);
+                        if let Some(pct) = percentage {
+                            log::info!(
+                                "Downloaded: {:.2} MB ({:.1}%)",
+                                downloaded as f64 / 1_048_576.0,
+                                pct
+                            );
+                        } else {
+                            log::info!("Downloaded: {:.2} MB", downloaded as f64 / 1_048_576.0);
+                        }
+                    }
+
+                    // Emit progress every 10 MB to reduce event spam
+                    let current_mb = downloaded / (10 * 1024 * 1024);
+                    if current_mb > last_emit_mb
+                        || total_size.is_some_and(|total| downloaded >= total)
+                    {
+                        last_emit_mb = current_mb;
+                        let message = if let Some(total) = total_size {
+                            format!(
+                                "Downloading {}: {:.2} MB / {:.2} MB",
+                                self.label,
+                                downloaded as f64 / 1_048_576.0,
+                                total as f64 / 1_048_576.0,
+                            )
+                        } else {
+                            format!(
+                                "Downloading {}: {:.2} MB",
+                                self.label,
+                                downloaded as f64 / 1_048_576.0
+                            )
+                        };
+                        on_progress(&self.progress(downloaded, total_size, message));
+                    }
+                    continue;
+                }
+                Ok(Some(Err(e))) => format!("Failed to read chunk: {}", e),
+                // A CDN may close a truncated response without any error
+                Ok(None) => match total_size {
+                    Some(total) if downloaded < total => format!(
+                        "Connection closed early: received {} of {} bytes ({} bytes missing)",
+                        downloaded,
+                        total,
+                        total - downloaded
+                    ),
+                    _ => break,
+                },
+            };
+
+            if !supports_resume {
+                return Err(DownloadError::Network(format!(
+                    "{} and server does not support resume",
+                    interruption
+                )));
+            }
+
+            // Flush current data before reconnecting
+            file.flush()
+                .await
+                .map_err(|e| DownloadError::io("Failed to flush file before retry", e))?;
+            file.sync_all()
+                .await
+                .map_err(|e| DownloadError::io("Failed to sync file before retry", e))?;
+            if preallocated {
+                write_committed_bytes(dest, Some(downloaded))?;
+            }
+
+            // Reconnect failures count as retry attempts too
+            let mut last_error = interruption;
+            loop {
+                consecutive_errors += 1;
+                log::warn!(
+                    "Download interrupted (attempt {}/{}): {}",
+                    consecutive_errors,
+                    self.retry_policy.max_retries,
+                    last_error
+                );
+
+                if consecutive_errors >= self.retry_policy.max_retries {
+                    return Err(DownloadError::Network(format!(
+                        "Download failed after {} retries: {}",
+                        self.retry_policy.max_retries, last_error
+                    )));
+                }
+
+                // A stalled connection delivers no chunks, so check here too
+                if self.is_cancelled() {
+                    log::info!("{} download cancelled while reconnecting", self.label);
+                    return Err(DownloadError::Cancelled);
+                }
+
+                let delay = self.retry_policy.backoff_delay(consecutive_errors - 1);
+                log::info!("Waiting {:?} before retry...", delay);
+                let message = if stalled {
+                    "Connection stalled, reconnecting...".to_string()
+                } else {
+                    format!("Connection lost, retrying in {} seconds...", delay.as_secs())
+                };
+                on_progress(&self.progress(downloaded, total_size, message));
+                tokio::time::sleep(delay).await;
+
+                // Reconnect and resume from current position
+                log::info!("Attempting to resume download from byte {}", downloaded);
+                match self
+                    .start_request(url, downloaded, validator.as_deref())
+                    .await
+                {
+                    Ok((new_response, _))
+                        if new_response.status() == reqwest::StatusCode::PARTIAL_CONTENT =>
+                    {
+                        stream = new_response.bytes_stream();
+                        stalled = false;
+                        log::info!("Successfully resumed download");
+                        break;
+                    }
+                    Ok(_) => {
+                        return Err(DownloadError::Network(
+                            "Remote file changed or server stopped honoring Range requests, \
+                             cannot resume"
+                                .to_string(),
+                        ));
+                    }
+                    Err(e) => last_error = e.to_string(),
+                }
+            }
+        }
+
+        log::info!(
+            "Download completed! Total: {:.2} MB",
+            downloaded as f64 / 1_048_576.0
+        );
+
+        // Flush and sync file to ensure all data is written to disk
+        file.flush()
+            .await
+            .map_err(|e| DownloadError::io("Failed to flush file", e))?;
+
+        file.sync_all()
+            .await
+            .map_err(|e| DownloadError::io("Failed to sync file", e))?;
+
+        // Explicitly close file before verification to ensure all data is persisted
+        drop(file);
+
+        if preallocated {
+            write_committed_bytes(dest, Some(downloaded))?;
+        }
+
+        log::info!("File synced successfully: {} bytes", downloaded);
+
+        Ok(DownloadOutcome {
+            size: downloaded,
+            sha256: format!("{:x}", hasher.finalize()),
+            etag,
+            last_modified,
+        })
+    }
+
+    /// Download `url` and verify it against `expected_sha256`.
+    /// Some CDNs advertise range support but serve corrupted partial responses, so after
+    /// a mismatch the file is deleted and downloaded once more from byte zero.
+    /// The attempt is recorded in the download history either way.
+    pub async fn download_verified<F>(
+        &self,
+        url: &str,
+        dest: &Path,
+        expected_sha256: &str,
+        on_progress: F,
+    ) -> Result<DownloadOutcome, DownloadError>
+    where
+        F: FnMut(&DownloadProgress),
+    {
+        let started_at = chrono::Local::now().to_rfc3339();
+        let result = self
+            .download_and_verify(url, dest, expected_sha256, on_progress)
+            .await;
+        self.record_history(url, dest, started_at, &result);
+        result
+    }
+
+    async fn download_and_verify<F>(
+        &self,
+        url: &str,
+        dest: &Path,
+        expected_sha256: &str,
+        mut on_progress: F,
+    ) -> Result<DownloadOutcome, DownloadError>
+    where
+        F: FnMut(&DownloadProgress),
+    {
+        let outcome = self.download_to_file(url, dest, &mut on_progress).await?;
+        let Err(e) = check_sha256(dest, &outcome.sha256, expected_sha256) else {
+            return Ok(outcome);
+        };
+
+        log::warn!(
+            "{} failed verification, restarting download from byte zero: {}",
+            self.label,
+            e
+        );
+        tokio::fs::remove_file(dest)
+            .await
+            .map_err(|e| DownloadError::io("Failed to delete corrupted download", e))?;
+        write_committed_bytes(dest, None)?;
+
+        let outcome = self.download_to_file(url, dest, &mut on_progress).await?;
+        if check_sha256(dest, &outcome.sha256, expected_sha256).is_err() {
+            return Err(DownloadError::ChecksumAfterRestart {
+                expected: expected_sha256.to_string(),
+                got: outcome.sha256,
+                etag: outcome.etag,
+                last_modified: outcome.last_modified,
+            });
+        }
+        Ok(outcome)
+    }
+
+    /// Append the outcome of a download to downloads_history.json
+    fn record_history(
+        &self,
+        url: &str,
+        dest: &Path,
+        started_at: String,
+        result: &Result<DownloadOutcome, DownloadError>,
+    ) {
+        let (bytes, result, error) = match result {
+            Ok(outcome) => (outcome.size, "completed", None),
+            Err(e) => {
+                // A pre-allocated file is only valid up to its watermark
+                let file_size = std::fs::metadata(dest).map(|m| m.len()).unwrap_or(0);
+                let bytes = read_committed_bytes(dest).map_or(file_size, |c| c.min(file_size));
+                let result = if e.kind() == "cancelled" {
+                    "cancelled"
+                } else {
+                    "failed"
+                };
+                (bytes, result, Some(e.to_string()))
+            }
+        };
+
+        let entry = DownloadHistoryEntry {
+            item: self.artifact.clone(),
+            url: url.to_string(),
+            started_at,
+            finished_at: chrono::Local::now().to_rfc3339(),
+            bytes,
+            result: result.to_string(),
+            error,
+        };
+        if let Err(e) = append_download_history(entry) {
+            log::warn!("Failed to record download history: {}", e);
+        }
+    }
+}
+
+/// Validator for If-Range: a strong ETag, else Last-Modified.
+/// Weak ETags are not allowed in If-Range, so they are skipped.
+fn resume_validator(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    header("etag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header("last-modified"))
+}
+
+/// Sync a pre-allocated file and advance its committed watermark to `downloaded`
+async fn commit_watermark(
+    file: &mut tokio::fs::File,
+    dest: &Path,
+    downloaded: u64,
+) -> Result<(), DownloadError> {
+    file.flush()
+        .await
+        .map_err(|e| DownloadError::io("Failed to flush file", e))?;
+    file.sync_data()
+        .await
+        .map_err(|e| DownloadError::io("Failed to sync file", e))?;
+    write_committed_bytes(dest, Some(downloaded))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::download_utils::write_resume_metadata;
+    use super::*;
+    use sha2::Sha256;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    /// A request received by the test server
+    #[derive(Debug, Clone)]
+    struct Request {
+        method: String,
+        range_start: Option<u64>,
+        if_range: Option<String>,
+    }
+
+    /// What the test server answers with. The connection is closed after every reply.
+    struct Reply {
+        status: &'static str,
+        headers: Vec<(&'static str, String)>,
+        body: Vec<u8>,
+        /// Content-Length to announce, when it is not the length of `body`
+        content_length: Option<usize>,
+        /// Wait this long before each 1 KB of the body
+        chunk_delay: Option<Duration>,
+    }
+
+    impl Reply {
+        fn new(status: &'static str, body: &[u8]) -> Self {
+            Self {
+                status,
+                headers: Vec::new(),
+                body: body.to_vec(),
+                content_length: None,
+                chunk_delay: None,
+            }
+        }
+
+        fn header(mut self, name: &'static str, value: impl ToString) -> Self {
+            self.headers.push((name, value.to_string()));
+            self
+        }
+    }
+
+    type Handler = dyn Fn(&Request) -> Reply + Send + Sync;
+
+    /// Serve HTTP/1.1 on a local port; returns the URL and the requests received
+    async fn serve(handler: Arc<Handler>) -> (String, Arc<Mutex<Vec<Request>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file.bin", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let handler = handler.clone();
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let head = String::from_utf8_lossy(&head).to_string();
+                    let header = |name: &str| {
+                        head.lines().find_map(|line| {
+                            let (key, value) = line.split_once(':')?;
+                            key.eq_ignore_ascii_case(name)
+                                .then(|| value.trim().to_string())
+                        })
+                    };
+                    let request = Request {
+                        method: head.split(' ').next().unwrap_or_default().to_string(),
+                        range_start: header("range").and_then(|range| {
+                            range
+                                .strip_prefix("bytes=")?
+                                .split('-')
+                                .next()?
+                                .parse()
+                                .ok()
+                        }),
+                        if_range: header("if-range"),
+                    };
+                    log.lock().unwrap().push(request.clone());
+
+                    let reply = handler(&request);
+                    let mut response = format!(
+                        "HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+                        reply.status,
+                        reply.content_length.unwrap_or(reply.body.len())
+                    );
+                    for (name, value) in &reply.headers {
+                        response.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    response.push_str("\r\n");
+                    if socket.write_all(response.as_bytes()).await.is_err() {
+                        return;
+                    }
+                    if request.method != "HEAD" {
+                        for chunk in reply.body.chunks(1024) {
+                            if let Some(delay) = reply.chunk_delay {
+                                tokio::time::sleep(delay).await;
+                            }
+                            if socket.write_all(chunk).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        (url, requests)
+    }
+
+    /// Answer like a server that supports Range requests for `body`, tagged "v1"
+    fn ranged_reply(body: &[u8], request: &Request) -> Reply {
+        let len = body.len() as u64;
+        // A changed file (other ETag) is sent in full
+        let range_start = request.range_start.filter(|_| {
+            request
+                .if_range
+                .as_deref()
+                .is_none_or(|tag| tag == "\"v1\"")
+        });
+        match range_start {
+            Some(start) if start >= len => Reply::new("416 Range Not Satisfiable", &[])
+                .header("Content-Range", format!("bytes */{}", len)),
+            Some(start) => Reply::new("206 Partial Content", &body[start as usize..])
+                .header("Accept-Ranges", "bytes")
+                .header("ETag", "\"v1\"")
+                .header(
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", start, len - 1, len),
+                ),
+            None => Reply::new("200 OK", body)
+                .header("Accept-Ranges", "bytes")
+                .header("ETag", "\"v1\""),
+        }
+    }
+
+    fn test_body(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    /// A downloader that reads nothing from settings or IPC state
+    fn test_downloader() -> Downloader {
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let retry_policy = RetryPolicy {
+            max_retries: 3,
+            base_delay_ms: 10,
+            max_delay_ms: 50,
+            stall_timeout: Duration::from_secs(5),
+        };
+        let mut downloader = Downloader::with_client(client, "test", "test file", retry_policy);
+        downloader.speed_limit = || None;
+        downloader
+    }
+
+    fn range_starts(requests: &Mutex<Vec<Request>>) -> Vec<Option<u64>> {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.method == "GET")
+            .map(|request| request.range_start)
+            .collect()
+    }
+
+    #[test]
+    fn incremental_hash_is_seeded_with_the_partial_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("partial.bin");
+        let body = test_body(10_000);
+        // A pre-allocated file: only the first 4000 bytes are valid
+        let mut partial = body[..4000].to_vec();
+        partial.resize(body.len(), 0);
+        std::fs::write(&path, &partial).unwrap();
+
+        let mut hasher = start_incremental_sha256(&path, 4000).unwrap();
+        hasher.update(&body[4000..]);
+        assert_eq!(format!("{:x}", hasher.finalize()), sha256_hex(&body));
+
+        let hasher = start_incremental_sha256(&path, 0).unwrap();
+        assert_eq!(format!("{:x}", hasher.finalize()), sha256_hex(&[]));
+    }
+
+    #[tokio::test]
+    async fn reconnect_mid_stream_hashes_the_whole_file() {
+        let body = test_body(200_000);
+        let (url, requests) = serve_dropping_first_response(&body).await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file.bin");
+
+        let outcome = test_downloader()
+            .download_to_file(&url, &dest, |_| {})
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.size, body.len() as u64);
+        assert_eq!(outcome.sha256, sha256_hex(&body));
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert_eq!(range_starts(&requests), vec![None, Some(70_000)]);
+    }
+
+    #[tokio::test]
+    async fn resumed_download_rehashes_the_partial_file() {
+        let body = test_body(150_000);
+        let served = body.clone();
+        let (url, requests) = serve(Arc::new(move |request: &Request| {
+            ranged_reply(&served, request)
+        }))
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file.bin");
+        std::fs::write(&dest, &body[..60_000]).unwrap();
+
+        let outcome = test_downloader()
+            .download_to_file(&url, &dest, |_| {})
+            .await
+            .unwrap();
+
+        // Only the rest was transferred, yet the hash covers the whole file
+        assert_eq!(range_starts(&requests), vec![Some(60_000)]);
+        assert_eq!(outcome.sha256, sha256_hex(&body));
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn downloads_a_whole_file() {
+        let body = test_body(300_000);
+        let served = body.clone();
+        let (url, requests) = serve(Arc::new(move |request: &Request| {
+            ranged_reply(&served, request)
+        }))
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file.bin");
+
+        let mut reports = Vec::new();
+        let outcome = test_downloader()
+            .download_to_file(&url, &dest, |progress| reports.push(progress.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.size, body.len() as u64);
+        assert_eq!(outcome.sha256, sha256_hex(&body));
+        assert_eq!(outcome.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert_eq!(range_starts(&requests), vec![None]);
+        // The start and the completion are always reported
+        assert_eq!(reports.first().unwrap().percentage, Some(0.0));
+        assert_eq!(reports.last().unwrap().downloaded, body.len() as u64);
+        assert_eq!(reports.last().unwrap().percentage, Some(100.0));
+    }
+
+    #[tokio::test]
+    async fn range_resume_sends_the_recorded_validator() {
+        let body = test_body(120_000);
+        let served = body.clone();
+        let (url, requests) = serve(Arc::new(move |request: &Request| {
+            ranged_reply(&served, request)
+        }))
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file.bin");
+        std::fs::write(&dest, &body[..50_000]).unwrap();
+        write_resume_metadata(&dest, &url, &sha256_hex(&body)).unwrap();
+        write_resume_validator(&dest, Some("\"v1\"".to_string())).unwrap();
+
+        let outcome = test_downloader()
+            .download_to_file(&url, &dest, |_| {})
+            .await
+            .unwrap();
+
+        let gets: Vec<Request> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.method == "GET")
+            .cloned()
+            .collect();
+        assert_eq!(gets.len(), 1);
+        assert_eq!(gets[0].range_start, Some(50_000));
+        assert_eq!(gets[0].if_range.as_deref(), Some("\"v1\""));
+        assert_eq!(outcome.sha256, sha256_hex(&body));
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn range_resume_restarts_when_the_remote_file_changed() {
+        let body = test_body(120_000);
+        let served = body.clone();
+        let (url, _) = serve(Arc::new(move |request: &Request| {
+            ranged_reply(&served, request)
+        }))
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file.bin");
+        // Bytes of an older version of the file
+        std::fs::write(&dest, vec![0xAA; 50_000]).unwrap();
+        write_resume_metadata(&dest, &url, "").unwrap();
+        write_resume_validator(&dest, Some("\"v0\"".to_string())).unwrap();
+
+        let outcome = test_downloader()
+            .download_to_file(&url, &dest, |_| {})
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.sha256, sha256_hex(&body));
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert_eq!(read_resume_validator(&dest).as_deref(), Some("\"v1\""));
+    }
+
+    #[tokio::test]
+    async fn complete_partial_file_is_not_downloaded_again() {
+        let body = test_body(80_000);
+        let served = body.clone();
+        let (url, requests) = serve(Arc::new(move |request: &Request| {
+            ranged_reply(&served, request)
+        }))
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file.bin");
+        std::fs::write(&dest, &body).unwrap();
+
+        let outcome = test_downloader()
+            .download_to_file(&url, &dest, |_| {})
+            .await
+            .unwrap();
+
+        // The server answers 416 to a range starting at the end of the file
+        assert_eq!(range_starts(&requests), vec![Some(body.len() as u64)]);
+        assert_eq!(outcome.size, body.len() as u64);
+        assert_eq!(outcome.sha256, sha256_hex(&body));
+    }
+
+    #[tokio::test]
+    async fn cancelled_download_keeps_the_partial_file() {
+        let body = test_body(100_000);
+        let served = body.clone();
+        let (url, _) = serve(Arc::new(move |request: &Request| {
+            let mut reply = ranged_reply(&served, request);
+            reply.chunk_delay = Some(Duration::from_millis(20));
+            reply
+        }))
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file.bin");
+
+        let mut downloader = test_downloader();
+        downloader.cancel_check = Some(Box::new(|| true));
+        let result = downloader.download_to_file(&url, &dest, |_| {}).await;
+
+        // Checked once per second, while the whole body would take two
+        assert!(matches!(result, Err(DownloadError::Cancelled)));
+        let kept = std::fs::read(&dest).unwrap();
+        assert!(!kept.is_empty() && kept.len() < body.len());
+        assert_eq!(kept, body[..kept.len()]);
+    }
+
+    #[tokio::test]
+    async fn response_ending_before_its_range_is_continued() {
+        let body = test_body(100_000);
+        let served = body.clone();
+        let gets = AtomicUsize::new(0);
+        let (url, requests) = serve(Arc::new(move |request: &Request| {
+            let mut reply = ranged_reply(&served, request);
+            if request.method == "GET" && gets.fetch_add(1, Ordering::SeqCst) == 0 {
+                // A complete response by its Content-Length, but short of the Content-Range
+                reply.body.truncate(30_000);
+            }
+            reply
+        }))
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file.bin");
+        std::fs::write(&dest, &body[..10_000]).unwrap();
+
+        let mut reports = Vec::new();
+        let outcome = test_downloader()
+            .download_to_file(&url, &dest, |progress| {
+                reports.push(progress.message.clone())
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(range_starts(&requests), vec![Some(10_000), Some(40_000)]);
+        assert!(reports
+            .iter()
+            .any(|message| message.starts_with("Connection lost")));
+        assert_eq!(outcome.sha256, sha256_hex(&body));
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+    }
+
+    /// Serves `body`, dropping the connection of the first GET after 70000 bytes
+    async fn serve_dropping_first_response(body: &[u8]) -> (String, Arc<Mutex<Vec<Request>>>) {
+        let served = body.to_vec();
+        let gets = AtomicUsize::new(0);
+        serve(Arc::new(move |request: &Request| {
+            let mut reply = ranged_reply(&served, request);
+            if request.method == "GET" && gets.fetch_add(1, Ordering::SeqCst) == 0 {
+                reply.content_length = Some(reply.body.len());
+                reply.body.truncate(70_000);
+            }
+            reply
+        }))
+        .await
+    }
+
+    #[tokio::test]
+    async fn preallocated_download_tracks_committed_bytes() {
+        let body = test_body(200_000);
+        let (url, requests) = serve_dropping_first_response(&body).await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file.bin");
+        write_resume_metadata(&dest, &url, &sha256_hex(&body)).unwrap();
+
+        // Give up at the first interruption
+        let mut downloader = test_downloader();
+        downloader.retry_policy.max_retries = 1;
+        let result = downloader.download_to_file(&url, &dest, |_| {}).await;
+        assert!(matches!(result, Err(DownloadError::Network(_))));
+        assert_eq!(std::fs::metadata(&dest).unwrap().len(), body.len() as u64);
+        assert_eq!(read_committed_bytes(&dest), Some(70_000));
+
+        // Only the committed bytes count when resuming
+        let outcome = test_downloader()
+            .download_to_file(&url, &dest, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(range_starts(&requests), vec![None, Some(70_000)]);
+        assert_eq!(outcome.sha256, sha256_hex(&body));
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert_eq!(read_committed_bytes(&dest), Some(body.len() as u64));
+    }
+
+    #[tokio::test]
+    async fn download_without_resume_metadata_appends() {
+        let body = test_body(200_000);
+        let (url, requests) = serve_dropping_first_response(&body).await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file.bin");
+
+        let mut downloader = test_downloader();
+        downloader.retry_policy.max_retries = 1;
+        let result = downloader.download_to_file(&url, &dest, |_| {}).await;
+        assert!(matches!(result, Err(DownloadError::Network(_))));
+        // Not pre-allocated: the file length is what was received
+        assert_eq!(std::fs::metadata(&dest).unwrap().len(), 70_000);
+        assert_eq!(read_committed_bytes(&dest), None);
+
+        let outcome = test_downloader()
+            .download_to_file(&url, &dest, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(range_starts(&requests), vec![None, Some(70_000)]);
+        assert_eq!(outcome.sha256, sha256_hex(&body));
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+    }
+}
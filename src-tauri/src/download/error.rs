@@ -0,0 +1,158 @@
+// Typed errors for download operations
+// Serialized to the frontend as `{ "kind": "...", "message": "...", ... }` so the UI
+// can branch on a stable discriminant instead of parsing error text.
+
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum DownloadError {
+    /// Connection, DNS, TLS or HTTP-level failure
+    Network(String),
+    /// Downloaded file does not match the expected SHA-256
+    Checksum { expected: String, got: String },
+    /// Still no SHA-256 match after deleting the partial file and downloading from byte zero.
+    /// The server validators help tell a bad mirror apart from a wrong configured hash.
+    ChecksumAfterRestart {
+        expected: String,
+        got: String,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+    /// Not enough disk space to write the download
+    DiskFull(String),
+    /// Download was cancelled by the user
+    Cancelled,
+    /// Platform, model or build variant not available in the configuration
+    Unsupported(String),
+    /// Archive could not be read, extracted or validated
+    Extraction(String),
+    /// Any other filesystem failure
+    Io(String),
+    /// Configuration, settings and other failures
+    Other(String),
+}
+
+impl DownloadError {
+    /// Wrap an I/O error with context, detecting a full disk
+    pub fn io(context: &str, error: std::io::Error) -> Self {
+        let message = format!("{}: {}", context, error);
+        if is_disk_full(&error) {
+            DownloadError::DiskFull(message)
+        } else {
+            DownloadError::Io(message)
+        }
+    }
+
+    /// Stable discriminant exposed to the frontend
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DownloadError::Network(_) => "network",
+            DownloadError::Checksum { .. } | DownloadError::ChecksumAfterRestart { .. } => {
+                "checksum"
+            }
+            DownloadError::DiskFull(_) => "disk_full",
+            DownloadError::Cancelled => "cancelled",
+            DownloadError::Unsupported(_) => "unsupported",
+            DownloadError::Extraction(_) => "extraction",
+            DownloadError::Io(_) => "io",
+            DownloadError::Other(_) => "other",
+        }
+    }
+
+    /// Finer-grained failure reason for the frontend, where the kind is not specific enough
+    pub fn reason(&self) -> Option<&'static str> {
+        match self {
+            DownloadError::Checksum { .. } => Some("checksum_mismatch"),
+            DownloadError::ChecksumAfterRestart { .. } => Some("checksum_mismatch_after_restart"),
+            _ => None,
+        }
+    }
+}
+
+/// ENOSPC on Unix, ERROR_DISK_FULL / ERROR_HANDLE_DISK_FULL on Windows
+fn is_disk_full(error: &std::io::Error) -> bool {
+    if error.kind() == std::io::ErrorKind::StorageFull {
+        return true;
+    }
+    #[cfg(unix)]
+    let codes = [libc::ENOSPC];
+    #[cfg(windows)]
+    let codes = [112, 39];
+    #[cfg(not(any(unix, windows)))]
+    let codes: [i32; 0] = [];
+
+    error.raw_os_error().is_some_and(|code| codes.contains(&code))
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::Network(message)
+            | DownloadError::DiskFull(message)
+            | DownloadError::Unsupported(message)
+            | DownloadError::Extraction(message)
+            | DownloadError::Io(message)
+            | DownloadError::Other(message) => write!(f, "{}", message),
+            DownloadError::Checksum { expected, got } => write!(
+                f,
+                "SHA-256 checksum verification failed (expected {}, got {})",
+                expected, got
+            ),
+            DownloadError::ChecksumAfterRestart {
+                expected,
+                got,
+                etag,
+                last_modified,
+            } => write!(
+                f,
+                "SHA-256 checksum verification failed again after restarting the download \
+                 (expected {}, got {}; server ETag: {}, Last-Modified: {})",
+                expected,
+                got,
+                etag.as_deref().unwrap_or("none"),
+                last_modified.as_deref().unwrap_or("none")
+            ),
+            DownloadError::Cancelled => write!(f, "Download cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+impl From<String> for DownloadError {
+    fn from(message: String) -> Self {
+        DownloadError::Other(message)
+    }
+}
+
+impl Serialize for DownloadError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("kind", self.kind())?;
+        map.serialize_entry("message", &self.to_string())?;
+        if let Some(reason) = self.reason() {
+            map.serialize_entry("reason", reason)?;
+        }
+        match self {
+            DownloadError::Checksum { expected, got } => {
+                map.serialize_entry("expected", expected)?;
+                map.serialize_entry("got", got)?;
+            }
+            DownloadError::ChecksumAfterRestart {
+                expected,
+                got,
+                etag,
+                last_modified,
+            } => {
+                map.serialize_entry("expected", expected)?;
+                map.serialize_entry("got", got)?;
+                map.serialize_entry("etag", etag)?;
+                map.serialize_entry("last_modified", last_modified)?;
+            }
+            _ => {}
+        }
+        map.end()
+    }
+}
@@ -1,108 +1,33 @@
-use super::download_utils::{get_platform_id, load_config, verify_sha256};
+use super::download_utils::{
+    calculate_sha256, emit_download_finished, emit_download_started, ensure_within_dir,
+    extract_progress_reporter, get_platform_id, is_zip_symlink, load_config,
+    remove_partial_download, resume_metadata_matches, write_resume_metadata, ExtractionLimits,
+    ExtractionProgress,
+};
+use super::engine::Downloader;
+use super::error::DownloadError;
 use crate::ipc_state::update_download_status;
-use crate::paths::{get_app_data_dir, get_bin_dir, get_llama_binary_path};
-use crate::types::DownloadProgress;
+use crate::paths::{
+    get_app_data_dir, get_bin_dir, get_llama_binary_path, get_llama_previous_dir,
+    get_llama_staging_dir, has_previous_llama_build,
+};
+use crate::system::recommended_llama_variant;
+use crate::types::{
+    DownloadProgress, ExtractProgress, LlamaBuildInfo, LlamaCppFile, LlamaCppPlatform,
+    LlamaUpdateCheck, LlamaVariants, VersionsConfig,
+};
 use flate2::read::GzDecoder;
-use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 use tauri::{AppHandle, Emitter};
-use tokio::io::{AsyncSeekExt, AsyncWriteExt};
-
-/// Maximum number of retry attempts for chunk read errors
-const MAX_CHUNK_RETRIES: u32 = 10;
-/// Base delay for exponential backoff (in milliseconds)
-const BASE_RETRY_DELAY_MS: u64 = 1000;
-/// Maximum delay between retries (in milliseconds)
-const MAX_RETRY_DELAY_MS: u64 = 30000;
-
-/// Create HTTP client for llama.cpp downloads
-fn create_http_client() -> Result<reqwest::Client, String> {
-    reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .redirect(reqwest::redirect::Policy::limited(10))
-        // Entire response (including body) must finish within this limit; short values abort large/slow downloads.
-        .timeout(std::time::Duration::from_secs(7200))
-        .connect_timeout(std::time::Duration::from_secs(30))
-        .pool_idle_timeout(std::time::Duration::from_secs(90))
-        .tcp_keepalive(std::time::Duration::from_secs(60))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))
-}
-
-/// Check if server supports Range requests
-async fn check_range_support(client: &reqwest::Client, url: &str) -> bool {
-    match client.head(url).send().await {
-        Ok(response) => {
-            let accepts_ranges = response
-                .headers()
-                .get("accept-ranges")
-                .map(|v| v.to_str().unwrap_or("") != "none")
-                .unwrap_or(false);
-            log::info!("Server range support: {}", accepts_ranges);
-            accepts_ranges
-        }
-        Err(e) => {
-            log::warn!("Failed to check range support: {}", e);
-            false
-        }
-    }
-}
-
-/// Calculate exponential backoff delay
-fn calculate_backoff_delay(attempt: u32) -> std::time::Duration {
-    let delay_ms = BASE_RETRY_DELAY_MS * 2u64.pow(attempt.min(10));
-    std::time::Duration::from_millis(delay_ms.min(MAX_RETRY_DELAY_MS))
-}
-
-/// Start or resume a download request from a given byte offset
-async fn start_download_request(
-    client: &reqwest::Client,
-    url: &str,
-    start_byte: u64,
-) -> Result<(reqwest::Response, Option<u64>), String> {
-    let mut request = client
-        .get(url)
-        .header("Accept", "*/*")
-        .header("Accept-Encoding", "identity");
-
-    if start_byte > 0 {
-        log::info!("Resuming download from byte {}", start_byte);
-        request = request.header("Range", format!("bytes={}-", start_byte));
-    }
-
-    let response = request
-        .send()
-        .await
-        .map_err(|e| format!("Failed to download: {}", e))?;
 
-    let status = response.status();
-    log::info!("HTTP response status: {}", status);
-
-    // 200 OK for new download, 206 Partial Content for resume
-    if !status.is_success() && status != reqwest::StatusCode::PARTIAL_CONTENT {
-        return Err(format!(
-            "HTTP error: {} - {}",
-            status.as_u16(),
-            status.canonical_reason().unwrap_or("Unknown")
-        ));
-    }
-
-    let total_size = if start_byte > 0 && status == reqwest::StatusCode::PARTIAL_CONTENT {
-        // For resumed downloads, parse Content-Range header to get total size
-        response
-            .headers()
-            .get("content-range")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|s| s.split('/').last())
-            .and_then(|s| s.parse::<u64>().ok())
-    } else {
-        response.content_length()
-    };
-
-    Ok((response, total_size))
-}
+/// Build variant stored under the plain platform key (CPU build, plus Metal on macOS)
+const DEFAULT_LLAMA_VARIANT: &str = "cpu";
+/// Download ID used to track llama.cpp in IPC state
+const LLAMA_DOWNLOAD_ID: &str = "llama.cpp";
 
 /// Local path for the downloaded archive (zip or tar.gz), derived from the URL.
 fn llama_download_archive_path(app_dir: &Path, url: &str) -> PathBuf {
@@ -139,85 +64,464 @@ fn get_version_file_path() -> Result<std::path::PathBuf, String> {
     Ok(bin_dir.join("llama-version.txt"))
 }
 
-/// Read the currently installed llama.cpp version
-fn read_installed_version() -> Result<String, String> {
+/// Contents of the version file
+struct InstalledBuild {
+    version: String,
+    variant: String,
+    /// Variant was picked from GPU detection rather than requested explicitly,
+    /// so a hardware change should switch to the newly recommended variant
+    auto_selected: bool,
+}
+
+/// Read the version file: the version, the build variant and how the variant was chosen
+fn read_version_file() -> Result<InstalledBuild, String> {
     let version_file = get_version_file_path()?;
     if !version_file.exists() {
         return Err("Version file not found".to_string());
     }
-    fs::read_to_string(version_file)
-        .map(|s| s.trim().to_string())
-        .map_err(|e| format!("Failed to read version file: {}", e))
+    let content = fs::read_to_string(version_file)
+        .map_err(|e| format!("Failed to read version file: {}", e))?;
+    let mut lines = content.lines().map(str::trim);
+    let version = lines.next().unwrap_or_default().to_string();
+    // Files written before variants existed only contain the version
+    let variant = lines
+        .next()
+        .filter(|v| !v.is_empty())
+        .unwrap_or(DEFAULT_LLAMA_VARIANT)
+        .to_string();
+    // ...and files written before selection tracking have no third line
+    let auto_selected = lines.next() != Some("manual");
+    Ok(InstalledBuild {
+        version,
+        variant,
+        auto_selected,
+    })
 }
 
-/// Write the installed llama.cpp version
-fn write_installed_version(version: &str) -> Result<(), String> {
+/// Read the currently installed llama.cpp version
+fn read_installed_version() -> Result<String, String> {
+    read_version_file().map(|build| build.version)
+}
+
+/// Read the currently installed llama.cpp build variant
+fn read_installed_variant() -> Result<String, String> {
+    read_version_file().map(|build| build.variant)
+}
+
+/// Write the installed llama.cpp version, build variant and how the variant was chosen
+fn write_installed_version(
+    version: &str,
+    variant: &str,
+    auto_selected: bool,
+) -> Result<(), String> {
     let version_file = get_version_file_path()?;
-    fs::write(version_file, version).map_err(|e| format!("Failed to write version file: {}", e))
+    let selection = if auto_selected { "auto" } else { "manual" };
+    fs::write(version_file, format!("{}\n{}\n{}\n", version, variant, selection))
+        .map_err(|e| format!("Failed to write version file: {}", e))
 }
 
-/// Check if llama.cpp needs to be updated
-fn needs_update(current_version: &str) -> Result<bool, String> {
-    match read_installed_version() {
-        Ok(installed_version) => Ok(installed_version != current_version),
+/// Check if llama.cpp needs to be updated (new version or different build variant)
+fn needs_update(current_version: &str, variant: &str) -> Result<bool, String> {
+    match read_version_file() {
+        Ok(installed) => Ok(installed.version != current_version || installed.variant != variant),
         Err(_) => Ok(true), // If we can't read version, assume update is needed
     }
 }
 
-/// Remove old llama.cpp files
-fn cleanup_old_llama_files(bin_dir: &std::path::Path) -> Result<(), String> {
-    // Try both with and without .exe extension for cross-platform compatibility
+/// Build variants available for a platform.
+/// The plain platform entry (e.g. `windows-x64`) is the CPU build; suffixed entries
+/// are GPU-specific builds (e.g. `windows-x64-cuda`, `windows-x64-vulkan`) or CPU builds
+/// for processors without AVX2 (`windows-x64-cpu-avx`, `windows-x64-cpu-noavx`).
+fn available_variants(config: &VersionsConfig, platform_id: &str) -> Vec<String> {
+    let prefix = format!("{}-", platform_id);
+    let mut variants: Vec<String> = config
+        .llama_cpp
+        .platforms
+        .keys()
+        .filter_map(|key| {
+            if key == platform_id {
+                Some(DEFAULT_LLAMA_VARIANT.to_string())
+            } else {
+                key.strip_prefix(&prefix).map(str::to_string)
+            }
+        })
+        .collect();
+    variants.sort();
+    variants.dedup();
+    variants
+}
+
+/// Look up the archive configuration for a platform and build variant
+fn variant_config<'a>(
+    config: &'a VersionsConfig,
+    platform_id: &str,
+    variant: &str,
+) -> Option<&'a LlamaCppPlatform> {
+    let platforms = &config.llama_cpp.platforms;
+    platforms
+        .get(&format!("{}-{}", platform_id, variant))
+        .or_else(|| {
+            (variant == DEFAULT_LLAMA_VARIANT)
+                .then(|| platforms.get(platform_id))
+                .flatten()
+        })
+}
+
+/// Resolve which build variant to use, and whether it was picked automatically.
+/// An explicit request must be available; otherwise keep a variant the user chose
+/// earlier, then use the one recommended for the detected GPU, then CPU. An
+/// automatically chosen variant follows the hardware, so a GPU change re-downloads.
+fn resolve_variant(
+    config: &VersionsConfig,
+    platform_id: &str,
+    requested: Option<String>,
+) -> Result<(String, bool), DownloadError> {
+    let available = available_variants(config, platform_id);
+
+    if let Some(variant) = requested {
+        if available.contains(&variant) {
+            return Ok((variant, false));
+        }
+        return Err(DownloadError::Unsupported(format!(
+            "llama.cpp variant '{}' is not available for platform '{}' (available: {})",
+            variant,
+            platform_id,
+            available.join(", ")
+        )));
+    }
+
+    if let Ok(installed) = read_version_file() {
+        if !installed.auto_selected && available.contains(&installed.variant) {
+            return Ok((installed.variant, false));
+        }
+    }
+
+    let recommended = recommended_llama_variant().to_string();
+    if available.contains(&recommended) {
+        return Ok((recommended, true));
+    }
+    if recommended.starts_with("cpu-") {
+        log::warn!(
+            "This CPU needs the '{}' llama.cpp build, which is not available for '{}'; \
+             the default build may fail with an illegal instruction",
+            recommended,
+            platform_id
+        );
+    }
+    Ok((DEFAULT_LLAMA_VARIANT.to_string(), true))
+}
+
+/// Directories inside `bin/` that are not part of the active installation
+const NON_ACTIVE_ENTRIES: [&str; 2] = ["staging", "previous"];
+
+/// Name of the llama-server binary on this platform
+fn llama_server_file_name() -> &'static str {
     #[cfg(target_os = "windows")]
-    let binary_names = vec!["llama-server.exe", "llama-server"];
-    
+    return "llama-server.exe";
+
     #[cfg(not(target_os = "windows"))]
-    let binary_names = vec!["llama-server"];
+    return "llama-server";
+}
 
-    for name in binary_names {
-        let binary_path = bin_dir.join(name);
-        if binary_path.exists() {
-            if let Err(e) = fs::remove_file(&binary_path) {
-                log::warn!("Failed to remove old binary {}: {}", name, e);
-            }
+/// Move every entry of `from` into `to` (renames, so symlinks are preserved), skipping `skip`
+fn move_dir_contents(from: &Path, to: &Path, skip: &[&str]) -> Result<(), DownloadError> {
+    fs::create_dir_all(to)
+        .map_err(|e| DownloadError::io(&format!("Failed to create {:?}", to), e))?;
+    let entries = fs::read_dir(from)
+        .map_err(|e| DownloadError::io(&format!("Failed to read {:?}", from), e))?;
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if skip.iter().any(|s| name == *s) {
+            continue;
         }
+        let target = to.join(&name);
+        fs::rename(entry.path(), &target).map_err(|e| {
+            DownloadError::io(&format!("Failed to move {:?} to {:?}", entry.path(), target), e)
+        })?;
     }
 
-    // Remove old shared libraries (.dylib/.metal on macOS, .dll on Windows)
-    if let Ok(entries) = fs::read_dir(bin_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                if name == "llama-version.txt" {
-                    continue;
-                }
-            }
-            if let Some(ext) = path.extension() {
-                if ext == "dylib" || ext == "metal" || ext == "dll" {
-                    if let Err(e) = fs::remove_file(&path) {
-                        log::warn!("Failed to remove {:?}: {}", path, e);
-                    }
-                }
-            }
-            // Also remove symlinks (macOS) that point to versioned dylibs
-            #[cfg(unix)]
-            if path.is_symlink() {
-                if let Err(e) = fs::remove_file(&path) {
-                    log::warn!("Failed to remove symlink {:?}: {}", path, e);
-                }
-            }
+    Ok(())
+}
+
+/// Remove a directory tree if it exists
+fn remove_dir_if_exists(dir: &Path) -> Result<(), DownloadError> {
+    if dir.exists() {
+        fs::remove_dir_all(dir)
+            .map_err(|e| DownloadError::io(&format!("Failed to remove {:?}", dir), e))?;
+    }
+    Ok(())
+}
+
+/// Run `llama-server --version` and return its output (llama.cpp prints it to stderr)
+fn run_llama_server_version(server_path: &Path) -> Result<String, String> {
+    let mut command = std::process::Command::new(server_path);
+    command.arg("--version");
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let output = command
+        .output()
+        .map_err(|e| format!("llama-server failed to run: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "llama-server --version exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let version_output = String::from_utf8_lossy(&output.stderr).to_string()
+        + &String::from_utf8_lossy(&output.stdout);
+    Ok(version_output.trim().to_string())
+}
+
+/// Parse the build number and commit from `version: 4567 (abc1234)`
+fn parse_llama_version_output(output: &str) -> (Option<u64>, Option<String>) {
+    let Some(line) = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("version:"))
+    else {
+        return (None, None);
+    };
+    let mut parts = line.split_whitespace();
+    let build_number = parts.next().and_then(|number| number.parse().ok());
+    let commit = parts
+        .next()
+        .map(|commit| commit.trim_matches(|c| c == '(' || c == ')').to_string())
+        .filter(|commit| !commit.is_empty());
+    (build_number, commit)
+}
+
+/// Check that a staged build contains llama-server and that it can run `--version`
+fn validate_staged_build(staging_dir: &Path) -> Result<(), String> {
+    let server_path = staging_dir.join(llama_server_file_name());
+    if !server_path.exists() {
+        return Err("llama-server binary not found in staged build".to_string());
+    }
+
+    let version_output =
+        run_llama_server_version(&server_path).map_err(|e| format!("Staged {}", e))?;
+    log::info!("Staged llama-server version: {}", version_output);
+
+    Ok(())
+}
+
+/// Activate the staged build: the current installation moves to `bin/previous/`,
+/// the staged files move into `bin/`. On failure the current installation is restored.
+fn promote_staged_build(
+    bin_dir: &Path,
+    staging_dir: &Path,
+    previous_dir: &Path,
+) -> Result<(), DownloadError> {
+    remove_dir_if_exists(previous_dir)?;
+    move_dir_contents(bin_dir, previous_dir, &NON_ACTIVE_ENTRIES)?;
+
+    if let Err(e) = move_dir_contents(staging_dir, bin_dir, &[]) {
+        log::error!("Failed to activate new llama.cpp build, restoring previous: {}", e);
+        // Anything that already moved over goes back to staging so `bin/` can be restored cleanly
+        let _ = move_dir_contents(bin_dir, staging_dir, &NON_ACTIVE_ENTRIES);
+        let _ = move_dir_contents(previous_dir, bin_dir, &[]);
+        return Err(e);
+    }
+
+    remove_dir_if_exists(staging_dir)?;
+    Ok(())
+}
+
+/// Per-file SHA-256 of the active build. It lives in `bin/` with the build, so promote
+/// and rollback move it along.
+const INSTALLED_MANIFEST_FILE: &str = "installed-manifest.json";
+
+/// Files in `bin/` that describe the build rather than being part of it
+const BUILD_METADATA_FILES: [&str; 2] = [INSTALLED_MANIFEST_FILE, "llama-version.txt"];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct InstalledManifest {
+    /// File name -> SHA-256
+    files: BTreeMap<String, String>,
+}
+
+/// Hash every file of the build in `dir` (a symlink is hashed as its target)
+fn build_installed_manifest(dir: &Path) -> Result<InstalledManifest, DownloadError> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| DownloadError::io(&format!("Failed to read {:?}", dir), e))?;
+    let mut files = BTreeMap::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+        if BUILD_METADATA_FILES.contains(&name.as_str()) || !path.is_file() {
+            continue;
+        }
+        files.insert(name, calculate_sha256(&path)?);
+    }
+    Ok(InstalledManifest { files })
+}
+
+fn write_installed_manifest(dir: &Path, manifest: &InstalledManifest) -> Result<(), DownloadError> {
+    let contents = serde_json::to_string_pretty(manifest)
+        .map_err(|e| DownloadError::Other(format!("Failed to serialize build manifest: {}", e)))?;
+    fs::write(dir.join(INSTALLED_MANIFEST_FILE), contents)
+        .map_err(|e| DownloadError::io("Failed to write build manifest", e))
+}
+
+/// Manifest of the active build; builds installed before manifests existed have none
+fn read_installed_manifest(bin_dir: &Path) -> Option<InstalledManifest> {
+    let contents = fs::read_to_string(bin_dir.join(INSTALLED_MANIFEST_FILE)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Files of a new build, split into those already installed with identical content
+/// and those to download
+struct DeltaPlan<'a> {
+    /// (new file, name of the installed file with the same SHA-256)
+    reuse: Vec<(&'a LlamaCppFile, String)>,
+    download: Vec<&'a LlamaCppFile>,
+}
+
+impl DeltaPlan<'_> {
+    fn download_bytes(&self) -> u64 {
+        self.download.iter().map(|file| file.size).sum()
+    }
+}
+
+/// Plan an update of the active build to `platform_config`. None when versions.json has
+/// no per-file manifest for the new build or the active build has no installed manifest,
+/// in which case the full archive is downloaded.
+fn plan_delta_update<'a>(
+    platform_config: &'a LlamaCppPlatform,
+    bin_dir: &Path,
+) -> Option<DeltaPlan<'a>> {
+    if platform_config.files.is_empty() {
+        return None;
+    }
+    let installed = read_installed_manifest(bin_dir)?;
+
+    let mut plan = DeltaPlan {
+        reuse: Vec::new(),
+        download: Vec::new(),
+    };
+    for file in &platform_config.files {
+        let existing = installed.files.iter().find(|(name, sha256)| {
+            sha256.eq_ignore_ascii_case(&file.sha256) && bin_dir.join(name).is_file()
+        });
+        match existing {
+            Some((name, _)) => plan.reuse.push((file, name.clone())),
+            None => plan.download.push(file),
         }
     }
+    Some(plan)
+}
+
+/// Assemble the new build in `staging_dir`: unchanged files are copied from the active
+/// build (and re-hashed, as they may have changed on disk), changed files are downloaded
+async fn stage_delta_update(
+    app: &AppHandle,
+    downloader: &Downloader,
+    plan: &DeltaPlan<'_>,
+    bin_dir: &Path,
+    staging_dir: &Path,
+) -> Result<(), DownloadError> {
+    let files = || {
+        plan.reuse
+            .iter()
+            .map(|(file, _)| *file)
+            .chain(plan.download.iter().copied())
+    };
+    if let Some(file) = files().find(|file| !is_plain_file_name(&file.name)) {
+        return Err(DownloadError::Other(format!(
+            "Invalid file name in llama.cpp manifest: {}",
+            file.name
+        )));
+    }
+    if !files().any(|file| file.name == llama_server_file_name()) {
+        return Err(DownloadError::Other(
+            "llama.cpp manifest does not include llama-server".to_string(),
+        ));
+    }
+
+    for (file, installed_name) in &plan.reuse {
+        let target = staging_dir.join(&file.name);
+        fs::copy(bin_dir.join(installed_name), &target)
+            .map_err(|e| DownloadError::io(&format!("Failed to copy {}", installed_name), e))?;
+        let actual = calculate_sha256(&target)?;
+        if !actual.eq_ignore_ascii_case(&file.sha256) {
+            return Err(DownloadError::Other(format!(
+                "Installed {} no longer matches the build manifest",
+                installed_name
+            )));
+        }
+    }
+
+    let total = plan.download_bytes();
+    let mut done = 0u64;
+    for file in &plan.download {
+        log::info!(
+            "Downloading changed llama.cpp file {} from: {}",
+            file.name,
+            file.url
+        );
+        let outcome = downloader
+            .download_verified(
+                &file.url,
+                &staging_dir.join(&file.name),
+                &file.sha256,
+                |progress| {
+                    let downloaded = done + progress.downloaded;
+                    let percentage =
+                        (total > 0).then(|| (downloaded as f64 / total as f64 * 100.0).min(100.0));
+                    let _ = update_download_status(LLAMA_DOWNLOAD_ID, true, percentage);
+                    let _ = app.emit(
+                        "download-progress",
+                        DownloadProgress {
+                            artifact: LLAMA_DOWNLOAD_ID.to_string(),
+                            downloaded,
+                            total: (total > 0).then_some(total),
+                            percentage,
+                            message: progress.message.clone(),
+                        },
+                    );
+                },
+            )
+            .await?;
+        done += outcome.size;
+    }
 
     Ok(())
 }
 
 /// Extract llama-server and related files from a `.zip` archive
 fn extract_llama_zip(
-    archive: &mut zip::ZipArchive<std::fs::File>,
-    bin_dir: &std::path::Path,
+    archive_path: &Path,
+    bin_dir: &Path,
+    mut limits: ExtractionLimits,
+    on_progress: &mut dyn FnMut(&ExtractProgress),
 ) -> Result<(), String> {
+    let file =
+        fs::File::open(archive_path).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Failed to read zip archive: {}", e))?;
     let mut found_server = false;
 
+    // Uncompressed size of the members we extract, from the central directory
+    let mut total_size = 0u64;
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index_raw(i)
+            .map_err(|e| format!("Failed to read file from archive: {}", e))?;
+        if !file.name().ends_with('/') && llama_member_should_extract(file.name()) {
+            total_size = total_size.saturating_add(file.size());
+        }
+    }
+    limits.check_declared("<all entries>", total_size)?;
+    let mut progress = ExtractionProgress::new(LLAMA_DOWNLOAD_ID, Some(total_size), on_progress);
+
     for i in 0..archive.len() {
         let mut file = archive
             .by_index(i)
@@ -230,6 +534,11 @@ fn extract_llama_zip(
             continue;
         }
 
+        if is_zip_symlink(&file) {
+            log::warn!("Skipping symlink archive entry: {}", file_name);
+            continue;
+        }
+
         if llama_member_should_extract(&file_name) {
             // Get just the filename without the path
             let filename = std::path::Path::new(&file_name)
@@ -238,13 +547,15 @@ fn extract_llama_zip(
                 .ok_or_else(|| format!("Invalid filename: {}", file_name))?;
 
             let output_path = bin_dir.join(filename);
+            ensure_within_dir(bin_dir, &output_path)?;
+            limits.check_declared(&file_name, file.size())?;
 
             log::info!("Extracting: {} -> {:?}", file_name, output_path);
 
-            let mut outfile = std::fs::File::create(&output_path)
+            let outfile = std::fs::File::create(&output_path)
                 .map_err(|e| format!("Failed to create output file: {}", e))?;
-            std::io::copy(&mut file, &mut outfile)
-                .map_err(|e| format!("Failed to extract file: {}", e))?;
+            progress.start_file(&file_name);
+            limits.copy(&file_name, &mut file, &mut progress.writer(outfile))?;
 
             // Check if this is the server binary (with or without .exe)
             if filename == "llama-server" || filename == "llama-server.exe" {
@@ -260,8 +571,47 @@ fn extract_llama_zip(
     Ok(())
 }
 
+/// Whether a symlink target is a bare file name, without directories or `..`
+fn is_plain_file_name(target: &str) -> bool {
+    let mut components = Path::new(target).components();
+    matches!(components.next(), Some(std::path::Component::Normal(_)))
+        && components.next().is_none()
+}
+
+/// Uncompressed size of the regular files `extract_llama_tar_gz` will write.
+/// A tar.gz has no index, so this decompresses the bundle once without writing anything;
+/// llama.cpp bundles are small enough for that to be cheap.
+fn llama_tar_gz_extract_size(archive_path: &Path) -> Result<u64, String> {
+    let file =
+        fs::File::open(archive_path).map_err(|e| format!("Failed to open tar.gz: {}", e))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut total_size = 0u64;
+    for entry_result in archive
+        .entries()
+        .map_err(|e| format!("Failed to read tar entries: {}", e))?
+    {
+        let entry = entry_result.map_err(|e| format!("Bad tar entry: {}", e))?;
+        let path_in = entry.path().map_err(|e| format!("Invalid tar path: {}", e))?;
+        if entry.header().entry_type().is_file()
+            && llama_member_should_extract(&path_in.to_string_lossy())
+        {
+            total_size = total_size.saturating_add(entry.size());
+        }
+    }
+    Ok(total_size)
+}
+
 /// Extract llama-server and related files from a `.tar.gz` release bundle
-fn extract_llama_tar_gz(archive_path: &Path, bin_dir: &Path) -> Result<(), String> {
+fn extract_llama_tar_gz(
+    archive_path: &Path,
+    bin_dir: &Path,
+    mut limits: ExtractionLimits,
+    on_progress: &mut dyn FnMut(&ExtractProgress),
+) -> Result<(), String> {
+    let total_size = llama_tar_gz_extract_size(archive_path)?;
+    limits.check_declared("<all entries>", total_size)?;
+    let mut progress = ExtractionProgress::new(LLAMA_DOWNLOAD_ID, Some(total_size), on_progress);
+
     let file =
         fs::File::open(archive_path).map_err(|e| format!("Failed to open tar.gz: {}", e))?;
     let dec = GzDecoder::new(file);
@@ -294,7 +644,12 @@ fn extract_llama_tar_gz(archive_path: &Path, bin_dir: &Path) -> Result<(), Strin
                 .map_err(|e| format!("Bad symlink target: {}", e))?
                 .map(|p| p.to_string_lossy().to_string())
                 .unwrap_or_default();
-            if !link_name.is_empty() && !target.is_empty() {
+            // Only sibling links (e.g. libllama.so -> libllama.so.0) stay inside bin_dir
+            if !is_plain_file_name(&target) {
+                log::warn!("Skipping symlink {} with unsafe target: {}", path_str, target);
+                continue;
+            }
+            if !link_name.is_empty() {
                 symlinks.push((link_name, target));
             }
             continue;
@@ -316,12 +671,14 @@ fn extract_llama_tar_gz(archive_path: &Path, bin_dir: &Path) -> Result<(), Strin
         }
 
         let output_path = bin_dir.join(filename);
+        ensure_within_dir(bin_dir, &output_path)?;
+        limits.check_declared(&path_str, entry.size())?;
         log::info!("Extracting: {} -> {:?}", path_str, output_path);
 
-        let mut outfile = fs::File::create(&output_path)
+        let outfile = fs::File::create(&output_path)
             .map_err(|e| format!("Failed to create output file: {}", e))?;
-        std::io::copy(&mut entry, &mut outfile)
-            .map_err(|e| format!("Failed to extract file: {}", e))?;
+        progress.start_file(&path_str);
+        limits.copy(&path_str, &mut entry, &mut progress.writer(outfile))?;
     }
 
     // Create symlinks (Unix) or copy the target file (Windows).
@@ -351,366 +708,472 @@ fn extract_llama_tar_gz(archive_path: &Path, bin_dir: &Path) -> Result<(), Strin
     Ok(())
 }
 
-#[tauri::command]
-pub async fn check_llama_version() -> Result<bool, String> {
-    let config = load_config()?;
-    let version = &config.llama_cpp.version;
+/// Delete a leftover llama.cpp archive when the installed build is already current
+pub(super) fn cleanup_stale_llama_archive() {
+    let Ok(app_dir) = get_app_data_dir() else {
+        return;
+    };
+    let Ok(config) = load_config() else {
+        return;
+    };
+
+    let installed = get_llama_binary_path().is_ok_and(|p| p.exists());
+    let current = read_installed_version().is_ok_and(|v| v == config.llama_cpp.version);
+    if !(installed && current) {
+        return;
+    }
 
-    needs_update(version)
+    for archive_name in ["llama-server.zip", "llama-server.tar.gz"] {
+        let archive_path = app_dir.join(archive_name);
+        if archive_path.exists() {
+            log::info!("Removing orphaned llama.cpp archive: {:?}", archive_path);
+            remove_partial_download(&archive_path);
+        }
+    }
 }
 
+/// Whether llama.cpp needs an update, and how much it would download
 #[tauri::command]
-pub async fn download_llama_cpp(app: AppHandle) -> Result<String, String> {
-    let bin_dir = get_bin_dir().map_err(|e| e.to_string())?;
-    let app_dir = get_app_data_dir().map_err(|e| e.to_string())?;
-
-    // Load llama.cpp configuration
+pub async fn check_llama_version(
+    variant: Option<String>,
+) -> Result<LlamaUpdateCheck, DownloadError> {
     let config = load_config()?;
     let platform_id = get_platform_id()?;
-
-    // Get the platform-specific configuration
-    let platform_config = config
-        .llama_cpp
-        .platforms
-        .get(&platform_id)
-        .ok_or_else(|| format!("Platform '{}' not supported in configuration", platform_id))?;
-
+    let (variant, _) = resolve_variant(&config, &platform_id, variant)?;
     let version = &config.llama_cpp.version;
-    let url = &platform_config.url;
 
-    let binary_path = get_llama_binary_path().map_err(|e| e.to_string())?;
-
-    // Check if llama.cpp is already installed with the correct version
-    if binary_path.exists() && !needs_update(version)? {
-        return Ok(format!("llama.cpp version {} is already installed", version));
+    if !needs_update(version, &variant)? {
+        return Ok(LlamaUpdateCheck {
+            needs_update: false,
+            delta: false,
+            download_bytes: Some(0),
+        });
     }
 
-    // If we need to update, remove old files
-    if binary_path.exists() {
-        let old_version = read_installed_version().unwrap_or_else(|_| "unknown".to_string());
-        log::info!(
-            "Updating llama.cpp from version {} to {}...",
-            old_version, version
-        );
-        cleanup_old_llama_files(&bin_dir)?;
+    let bin_dir = get_bin_dir().map_err(|e| DownloadError::Other(e.to_string()))?;
+    let installed = get_llama_binary_path().is_ok_and(|p| p.exists());
+    let plan = variant_config(&config, &platform_id, &variant)
+        .filter(|_| installed)
+        .and_then(|platform_config| plan_delta_update(platform_config, &bin_dir));
+
+    Ok(LlamaUpdateCheck {
+        needs_update: true,
+        delta: plan.is_some(),
+        download_bytes: plan.map(|plan| plan.download_bytes()),
+    })
+}
+
+/// Version reported by the installed llama-server binary, compared with the tracked version
+#[tauri::command]
+pub async fn get_llama_build_info() -> Result<LlamaBuildInfo, DownloadError> {
+    let server_path = get_llama_binary_path().map_err(|e| DownloadError::Other(e.to_string()))?;
+    if !server_path.exists() {
+        return Err(DownloadError::Other("llama.cpp is not installed".to_string()));
     }
 
-    let archive_path = llama_download_archive_path(&app_dir, url);
-    let alternate_archive = if url.ends_with(".tar.gz") {
-        app_dir.join("llama-server.zip")
-    } else {
-        app_dir.join("llama-server.tar.gz")
+    let version_output =
+        tauri::async_runtime::spawn_blocking(move || run_llama_server_version(&server_path))
+            .await
+            .map_err(|e| DownloadError::Other(e.to_string()))??;
+    let (build_number, commit) = parse_llama_version_output(&version_output);
+    let installed_version = read_installed_version().ok();
+
+    // Release tags are the build number prefixed with `b`, e.g. b4567
+    let tracked_build = installed_version
+        .as_deref()
+        .and_then(|version| version.trim_start_matches('b').parse::<u64>().ok());
+    let mismatch_warning = match (tracked_build, build_number) {
+        (Some(tracked), Some(reported)) if tracked != reported => Some(format!(
+            "llama-version.txt says {} but the llama-server binary reports build {}",
+            installed_version.as_deref().unwrap_or_default(),
+            reported
+        )),
+        _ => None,
     };
-    let _ = fs::remove_file(&alternate_archive);
+    if let Some(warning) = &mismatch_warning {
+        log::warn!("{}", warning);
+    }
 
-    log::info!("Downloading llama.cpp from: {}", url);
+    Ok(LlamaBuildInfo {
+        installed_version,
+        build_number,
+        commit,
+        version_output,
+        mismatch_warning,
+    })
+}
 
-    // Create HTTP client with proper headers
-    let client = create_http_client()?;
+#[tauri::command]
+pub async fn get_available_llama_variants() -> Result<LlamaVariants, DownloadError> {
+    let config = load_config()?;
+    let platform_id = get_platform_id()?;
+    let available = available_variants(&config, &platform_id);
 
-    // Check if server supports range requests for resume capability
-    let supports_resume = check_range_support(&client, url).await;
+    let recommended = recommended_llama_variant().to_string();
+    let recommended = if available.contains(&recommended) {
+        recommended
+    } else {
+        DEFAULT_LLAMA_VARIANT.to_string()
+    };
 
-    // Check if partial download exists
-    let mut downloaded: u64 = if supports_resume && archive_path.exists() {
-        let existing_size = tokio::fs::metadata(&archive_path)
-            .await
-            .map(|m| m.len())
-            .unwrap_or(0);
-        if existing_size > 0 {
-            log::info!(
-                "Found partial download: {:.2} MB, will attempt to resume",
-                existing_size as f64 / 1_048_576.0
-            );
-        }
-        existing_size
+    let installed = if get_llama_binary_path().is_ok_and(|p| p.exists()) {
+        read_installed_variant().ok()
     } else {
-        0
+        None
     };
 
-    let (response, total_size) = start_download_request(&client, url, downloaded).await?;
+    Ok(LlamaVariants {
+        available,
+        recommended,
+        installed,
+    })
+}
 
-    if let Some(size) = total_size {
-        log::info!("llama.cpp archive size: {:.2} MB", size as f64 / 1_048_576.0);
-    } else {
-        log::warn!("llama.cpp archive size: unknown (no Content-Length header)");
-    }
+/// Start from an empty staging directory
+fn reset_staging_dir(staging_dir: &Path) -> Result<(), DownloadError> {
+    remove_dir_if_exists(staging_dir)?;
+    fs::create_dir_all(staging_dir)
+        .map_err(|e| DownloadError::io("Failed to create staging directory", e))
+}
 
-    // Log some response headers for debugging
-    log::info!(
-        "Content-Type: {:?}",
-        response.headers().get("content-type")
-    );
-    log::info!(
-        "Content-Encoding: {:?}",
-        response.headers().get("content-encoding")
-    );
+/// Download the release archive and extract it into `staging_dir`. Returns the archive,
+/// which is kept until the new build is active so a failed activation does not re-download.
+async fn stage_full_archive(
+    app: &AppHandle,
+    downloader: &Downloader,
+    platform_config: &LlamaCppPlatform,
+    variant: &str,
+    app_dir: &Path,
+    staging_dir: &Path,
+) -> Result<PathBuf, DownloadError> {
+    let url = &platform_config.url;
+    let archive_path = llama_download_archive_path(app_dir, url);
+    let alternate_archive = if url.ends_with(".tar.gz") {
+        app_dir.join("llama-server.zip")
+    } else {
+        app_dir.join("llama-server.tar.gz")
+    };
+    remove_partial_download(&alternate_archive);
 
-    // Update IPC state - download started
-    let initial_percentage = total_size.map(|total| (downloaded as f64 / total as f64) * 100.0);
-    let _ = update_download_status(true, initial_percentage.or(Some(0.0)));
+    // A leftover archive from another version or build variant cannot be resumed
+    let expected_sha256 = &platform_config.sha256;
+    if archive_path.exists() && !resume_metadata_matches(&archive_path, url, expected_sha256) {
+        log::info!("Discarding partial llama.cpp download that does not match the current config");
+        remove_partial_download(&archive_path);
+    }
+    write_resume_metadata(&archive_path, url, expected_sha256)?;
+
+    log::info!("Downloading llama.cpp ({} build) from: {}", variant, url);
+
+    let result = downloader
+        .download_verified(url, &archive_path, expected_sha256, |progress| {
+            let _ = update_download_status(LLAMA_DOWNLOAD_ID, true, progress.percentage);
+            let _ = app.emit("download-progress", progress);
+        })
+        .await;
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            if e.kind() == "checksum" {
+                // Remove corrupted file
+                remove_partial_download(&archive_path);
+            }
+            return Err(e);
+        }
+    };
+    let downloaded = outcome.size;
 
-    // Emit initial progress
+    // Emit extraction progress
     let _ = app.emit(
         "download-progress",
         DownloadProgress {
+            artifact: LLAMA_DOWNLOAD_ID.to_string(),
             downloaded,
-            total: total_size,
-            percentage: initial_percentage.or(Some(0.0)),
-            message: "Starting llama.cpp download...".to_string(),
+            total: Some(downloaded),
+            percentage: Some(100.0),
+            message: "Extracting llama.cpp binary...".to_string(),
         },
     );
 
-    // Open file for writing (append if resuming)
-    let mut file = if downloaded > 0 {
-        let mut f = tokio::fs::OpenOptions::new()
-            .write(true)
-            .append(true)
-            .open(&archive_path)
-            .await
-            .map_err(|e| format!("Failed to open archive for resume: {}", e))?;
-        // Seek to end to ensure we're appending
-        f.seek(std::io::SeekFrom::End(0))
-            .await
-            .map_err(|e| format!("Failed to seek to end of file: {}", e))?;
-        f
+    let mut on_progress = extract_progress_reporter(app, LLAMA_DOWNLOAD_ID);
+    let limits = ExtractionLimits::for_archive(&archive_path).map_err(DownloadError::Extraction)?;
+    if url.ends_with(".tar.gz") {
+        extract_llama_tar_gz(&archive_path, staging_dir, limits, &mut on_progress)
+            .map_err(DownloadError::Extraction)?;
     } else {
-        tokio::fs::File::create(&archive_path)
-            .await
-            .map_err(|e| format!("Failed to create file: {}", e))?
-    };
-
-    let mut stream = response.bytes_stream();
-    let mut last_emit_mb = downloaded / (10 * 1024 * 1024);
-    let mut last_log_mb = downloaded / (50 * 1024 * 1024);
-    let mut consecutive_errors = 0u32;
-
-    log::info!("Starting download stream...");
+        extract_llama_zip(&archive_path, staging_dir, limits, &mut on_progress)
+            .map_err(DownloadError::Extraction)?;
+    }
 
-    loop {
-        match stream.next().await {
-            Some(Ok(chunk)) => {
-                // Reset error counter on successful chunk
-                consecutive_errors = 0;
+    Ok(archive_path)
+}
 
-                file.write_all(&chunk)
-                    .await
-                    .map_err(|e| format!("Failed to write chunk: {}", e))?;
+#[tauri::command]
+pub async fn download_llama_cpp(
+    app: AppHandle,
+    variant: Option<String>,
+) -> Result<String, DownloadError> {
+    emit_download_started(&app, LLAMA_DOWNLOAD_ID, "llama");
+    let result = install_llama_cpp(app.clone(), variant).await;
+    emit_download_finished(&app, LLAMA_DOWNLOAD_ID, "llama", &result);
+    if result.is_err() {
+        // Make sure a failed download never stays registered as active
+        let _ = update_download_status(LLAMA_DOWNLOAD_ID, false, None);
+    }
+    result
+}
 
-                downloaded += chunk.len() as u64;
+/// Download, verify and extract llama.cpp
+async fn install_llama_cpp(
+    app: AppHandle,
+    variant: Option<String>,
+) -> Result<String, DownloadError> {
+    let bin_dir = get_bin_dir().map_err(|e| DownloadError::Other(e.to_string()))?;
+    let app_dir = get_app_data_dir().map_err(|e| DownloadError::Other(e.to_string()))?;
 
-                // Log progress every 50 MB to console
-                let current_log_mb = downloaded / (50 * 1024 * 1024);
-                if current_log_mb > last_log_mb {
-                    last_log_mb = current_log_mb;
-                    let percentage =
-                        total_size.map(|total| (downloaded as f64 / total as f64) * 100.0);
-                    if let Some(pct) = percentage {
-                        log::info!(
-                            "Downloaded: {:.2} MB ({:.1}%)",
-                            downloaded as f64 / 1_048_576.0,
-                            pct
-                        );
-                    } else {
-                        log::info!("Downloaded: {:.2} MB", downloaded as f64 / 1_048_576.0);
-                    }
-                }
+    // Load llama.cpp configuration
+    let config = load_config()?;
+    let platform_id = get_platform_id()?;
+    let (variant, auto_selected) = resolve_variant(&config, &platform_id, variant)?;
 
-                // Emit progress every 10 MB to reduce event spam
-                let current_mb = downloaded / (10 * 1024 * 1024);
-                if current_mb > last_emit_mb
-                    || total_size.map_or(false, |total| downloaded >= total)
-                {
-                    last_emit_mb = current_mb;
-                    let percentage =
-                        total_size.map(|total| (downloaded as f64 / total as f64) * 100.0);
-                    let message = if let Some(total) = total_size {
-                        format!(
-                            "Downloading llama.cpp: {:.2} MB / {:.2} MB",
-                            downloaded as f64 / 1_048_576.0,
-                            total as f64 / 1_048_576.0,
-                        )
-                    } else {
-                        format!(
-                            "Downloading llama.cpp: {:.2} MB",
-                            downloaded as f64 / 1_048_576.0
-                        )
-                    };
-
-                    // Update IPC state with progress
-                    let _ = update_download_status(true, percentage);
+    // Get the platform- and variant-specific configuration
+    let platform_config = variant_config(&config, &platform_id, &variant).ok_or_else(|| {
+        DownloadError::Unsupported(format!(
+            "Platform '{}' not supported in configuration",
+            platform_id
+        ))
+    })?;
 
-                    let _ = app.emit(
-                        "download-progress",
-                        DownloadProgress {
-                            downloaded,
-                            total: total_size,
-                            percentage,
-                            message,
-                        },
-                    );
-                }
-            }
-            Some(Err(e)) => {
-                consecutive_errors += 1;
-                log::warn!(
-                    "Chunk read error (attempt {}/{}): {}",
-                    consecutive_errors,
-                    MAX_CHUNK_RETRIES,
-                    e
-                );
-
-                if consecutive_errors >= MAX_CHUNK_RETRIES {
-                    return Err(format!(
-                        "Failed to read chunk after {} retries: {}",
-                        MAX_CHUNK_RETRIES, e
-                    ));
-                }
+    let version = &config.llama_cpp.version;
 
-                if !supports_resume {
-                    return Err(format!(
-                        "Failed to read chunk and server does not support resume: {}",
-                        e
-                    ));
-                }
+    let binary_path = get_llama_binary_path().map_err(|e| DownloadError::Other(e.to_string()))?;
 
-                // Flush current data before reconnecting
-                file.flush()
-                    .await
-                    .map_err(|e| format!("Failed to flush file before retry: {}", e))?;
-                file.sync_all()
-                    .await
-                    .map_err(|e| format!("Failed to sync file before retry: {}", e))?;
-
-                // Calculate backoff delay
-                let delay = calculate_backoff_delay(consecutive_errors - 1);
-                log::info!("Waiting {:?} before retry...", delay);
-
-                let _ = app.emit(
-                    "download-progress",
-                    DownloadProgress {
-                        downloaded,
-                        total: total_size,
-                        percentage: total_size
-                            .map(|total| (downloaded as f64 / total as f64) * 100.0),
-                        message: format!(
-                            "Connection lost, retrying in {} seconds...",
-                            delay.as_secs()
-                        ),
-                    },
-                );
-
-                tokio::time::sleep(delay).await;
-
-                // Reconnect and resume from current position
-                log::info!("Attempting to resume download from byte {}", downloaded);
-
-                let (new_response, _) = start_download_request(&client, url, downloaded).await?;
-                stream = new_response.bytes_stream();
-
-                log::info!("Successfully resumed download");
-            }
-            None => {
-                // Stream ended
-                break;
-            }
-        }
+    // Check if llama.cpp is already installed with the correct version
+    if binary_path.exists() && !needs_update(version, &variant)? {
+        return Ok(format!(
+            "llama.cpp version {} ({}) is already installed",
+            version, variant
+        ));
     }
 
-    log::info!(
-        "Download completed! Total: {:.2} MB",
-        downloaded as f64 / 1_048_576.0
-    );
-
-    // Flush and sync file to ensure all data is written to disk
-    file.flush()
-        .await
-        .map_err(|e| format!("Failed to flush file: {}", e))?;
-
-    file.sync_all()
-        .await
-        .map_err(|e| format!("Failed to sync file: {}", e))?;
-
-    // Explicitly close file before verification to ensure all data is persisted
-    drop(file);
-
-    log::info!("File downloaded successfully: {} bytes", downloaded);
-
-    // Verify SHA-256 checksum
-    let expected_hash = &platform_config.sha256;
-    
-    if !expected_hash.is_empty() {
-        if let Err(e) = verify_sha256(&archive_path, expected_hash) {
-            // Remove corrupted file
-            fs::remove_file(&archive_path).ok();
-            // Clear IPC download status on error
-            let _ = update_download_status(false, None);
-            return Err(format!("Checksum verification failed: {}", e));
-        }
+    // Old files stay in place until the new build is extracted and validated
+    if binary_path.exists() {
+        let old_version = read_installed_version().unwrap_or_else(|_| "unknown".to_string());
+        let old_variant = read_installed_variant().unwrap_or_else(|_| "unknown".to_string());
+        log::info!(
+            "Updating llama.cpp from version {} ({}) to {} ({})...",
+            old_version, old_variant, version, variant
+        );
     }
 
-    // Emit extraction progress
-    let _ = app.emit(
-        "download-progress",
-        DownloadProgress {
-            downloaded,
-            total: total_size,
-            percentage: Some(100.0),
-            message: "Extracting llama.cpp binary...".to_string(),
-        },
-    );
+    let downloader = Downloader::new(LLAMA_DOWNLOAD_ID, "llama.cpp")?
+        .with_headers(&platform_config.headers)?
+        .with_download_id(LLAMA_DOWNLOAD_ID);
 
-    if url.ends_with(".tar.gz") {
-        if let Err(e) = extract_llama_tar_gz(&archive_path, &bin_dir) {
-            let _ = update_download_status(false, None);
-            return Err(e);
-        }
-    } else {
-        let file = match std::fs::File::open(&archive_path) {
-            Ok(f) => f,
-            Err(e) => {
-                let _ = update_download_status(false, None);
-                return Err(format!("Failed to open archive: {}", e));
-            }
-        };
+    // Build the new version in a staging directory so a bad release never replaces a working build
+    let staging_dir = get_llama_staging_dir().map_err(|e| DownloadError::Other(e.to_string()))?;
+    let previous_dir = get_llama_previous_dir().map_err(|e| DownloadError::Other(e.to_string()))?;
+    reset_staging_dir(&staging_dir)?;
 
-        let mut archive = match zip::ZipArchive::new(file) {
-            Ok(a) => a,
-            Err(e) => {
-                let _ = update_download_status(false, None);
-                return Err(format!("Failed to read zip archive: {}", e));
+    // When both builds have per-file manifests, only the changed files are downloaded
+    let delta_plan = if binary_path.exists() {
+        plan_delta_update(platform_config, &bin_dir)
+    } else {
+        None
+    };
+    let staged_delta = match delta_plan {
+        Some(plan) => {
+            log::info!(
+                "Updating llama.cpp ({} build): reusing {} file(s), downloading {} ({} bytes)",
+                variant,
+                plan.reuse.len(),
+                plan.download.len(),
+                plan.download_bytes()
+            );
+            match stage_delta_update(&app, &downloader, &plan, &bin_dir, &staging_dir).await {
+                Ok(()) => true,
+                Err(e @ (DownloadError::Cancelled | DownloadError::DiskFull(_))) => {
+                    let _ = remove_dir_if_exists(&staging_dir);
+                    return Err(e);
+                }
+                Err(e) => {
+                    log::warn!("Delta update failed, downloading the full archive: {}", e);
+                    reset_staging_dir(&staging_dir)?;
+                    false
+                }
             }
-        };
-
-        if let Err(e) = extract_llama_zip(&mut archive, &bin_dir) {
-            let _ = update_download_status(false, None);
-            return Err(e);
         }
-    }
+        None => false,
+    };
+
+    let archive_path = if staged_delta {
+        None
+    } else {
+        Some(
+            stage_full_archive(
+                &app,
+                &downloader,
+                platform_config,
+                &variant,
+                &app_dir,
+                &staging_dir,
+            )
+            .await?,
+        )
+    };
 
     // Make executable (Unix-like systems)
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
-        let mut perms = std::fs::metadata(&binary_path)
-            .map_err(|e| format!("Failed to get metadata: {}", e))?
+        let staged_binary = staging_dir.join(llama_server_file_name());
+        let mut perms = std::fs::metadata(&staged_binary)
+            .map_err(|e| DownloadError::io("Failed to get metadata", e))?
             .permissions();
         perms.set_mode(0o755);
-        std::fs::set_permissions(&binary_path, perms)
-            .map_err(|e| format!("Failed to set permissions: {}", e))?;
+        std::fs::set_permissions(&staged_binary, perms)
+            .map_err(|e| DownloadError::io("Failed to set permissions", e))?;
     }
 
-    fs::remove_file(&archive_path).ok();
+    if let Err(e) = validate_staged_build(&staging_dir) {
+        let _ = remove_dir_if_exists(&staging_dir);
+        return Err(DownloadError::Extraction(format!(
+            "New llama.cpp build failed validation, keeping current one: {}",
+            e
+        )));
+    }
+
+    // Per-file hashes let the next update download only what changed
+    let manifest = build_installed_manifest(&staging_dir)?;
+    write_installed_manifest(&staging_dir, &manifest)?;
+
+    promote_staged_build(&bin_dir, &staging_dir, &previous_dir)?;
+
+    if let Some(archive_path) = &archive_path {
+        remove_partial_download(archive_path);
+    }
 
     // Write version file to track installed version
-    write_installed_version(version)?;
+    write_installed_version(version, &variant, auto_selected)?;
 
     // Clear IPC download status on success
-    let _ = update_download_status(false, None);
+    let _ = update_download_status(LLAMA_DOWNLOAD_ID, false, None);
 
     Ok(format!(
-        "Downloaded llama.cpp version {} to: {:?}",
-        version, binary_path
+        "Downloaded llama.cpp version {} ({}) to: {:?}",
+        version, variant, binary_path
     ))
 }
 
+
+#[tauri::command]
+pub async fn rollback_llama_cpp() -> Result<String, DownloadError> {
+    if !has_previous_llama_build() {
+        return Err(DownloadError::Other(
+            "No previous llama.cpp version available to roll back to".to_string(),
+        ));
+    }
+
+    let bin_dir = get_bin_dir().map_err(|e| DownloadError::Other(e.to_string()))?;
+    let staging_dir = get_llama_staging_dir().map_err(|e| DownloadError::Other(e.to_string()))?;
+    let previous_dir = get_llama_previous_dir().map_err(|e| DownloadError::Other(e.to_string()))?;
+
+    let current_version = read_installed_version().unwrap_or_else(|_| "unknown".to_string());
+
+    // Swap current and previous builds, using staging as the temporary location
+    remove_dir_if_exists(&staging_dir)?;
+    move_dir_contents(&bin_dir, &staging_dir, &NON_ACTIVE_ENTRIES)?;
+
+    if let Err(e) = move_dir_contents(&previous_dir, &bin_dir, &[]) {
+        log::error!("Rollback failed, restoring current llama.cpp build: {}", e);
+        let _ = move_dir_contents(&bin_dir, &previous_dir, &NON_ACTIVE_ENTRIES);
+        let _ = move_dir_contents(&staging_dir, &bin_dir, &[]);
+        return Err(e);
+    }
+
+    // The build we rolled back from becomes the new rollback target
+    remove_dir_if_exists(&previous_dir)?;
+    fs::rename(&staging_dir, &previous_dir)
+        .map_err(|e| DownloadError::io("Failed to keep rolled-back build", e))?;
+
+    // Builds installed before version tracking have no version file
+    let InstalledBuild {
+        version,
+        variant,
+        auto_selected,
+    } = read_version_file().unwrap_or_else(|_| InstalledBuild {
+        version: "unknown".to_string(),
+        variant: DEFAULT_LLAMA_VARIANT.to_string(),
+        auto_selected: true,
+    });
+    write_installed_version(&version, &variant, auto_selected)?;
+
+    log::info!(
+        "Rolled back llama.cpp from version {} to {} ({})",
+        current_version, version, variant
+    );
+
+    Ok(format!(
+        "Rolled back llama.cpp to version {} ({})",
+        version, variant
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+
+    /// Append a regular file under a raw name, bypassing tar's own `..` check
+    fn append_file(builder: &mut tar::Builder<GzEncoder<fs::File>>, name: &str, data: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(data.len() as u64);
+        header.set_mode(0o755);
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+
+    fn append_symlink(builder: &mut tar::Builder<GzEncoder<fs::File>>, name: &str, target: &str) {
+        let mut header = tar::Header::new_gnu();
+        header.set_path(name).unwrap();
+        header.set_link_name(target).unwrap();
+        header.set_size(0);
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_cksum();
+        builder.append(&header, std::io::empty()).unwrap();
+    }
+
+    #[test]
+    fn tar_entries_are_confined_to_the_bin_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let archive_path = root.path().join("llama-server.tar.gz");
+        let bin_dir = root.path().join("app/bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+
+        let encoder = GzEncoder::new(
+            fs::File::create(&archive_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        append_file(&mut builder, "../../llama-server", b"server");
+        append_file(&mut builder, "build/bin/libllama.so.0", b"library");
+        append_symlink(&mut builder, "build/bin/libllama.so", "libllama.so.0");
+        append_symlink(&mut builder, "build/bin/libggml.so", "../../etc/passwd");
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let limits = ExtractionLimits::with_cap(&archive_path, 1).unwrap();
+        extract_llama_tar_gz(&archive_path, &bin_dir, limits, &mut |_| {}).unwrap();
+
+        assert_eq!(fs::read(bin_dir.join("llama-server")).unwrap(), b"server");
+        assert!(!root.path().join("llama-server").exists());
+        assert_eq!(fs::read(bin_dir.join("libllama.so.0")).unwrap(), b"library");
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(bin_dir.join("libllama.so")).unwrap(),
+            Path::new("libllama.so.0")
+        );
+        assert!(fs::symlink_metadata(bin_dir.join("libggml.so")).is_err());
+    }
+}
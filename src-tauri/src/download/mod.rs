@@ -1,12 +1,37 @@
 // Download module - coordinates all download operations
 
+mod bulk_download;
+mod catalog;
+mod credentials;
 mod download_utils;
+mod engine;
+mod error;
 mod llama_download;
 mod model_download;
+mod source_headers;
+mod verify;
 
 // Re-export Tauri commands
-pub use llama_download::{check_llama_version, download_llama_cpp};
+pub use bulk_download::download_all;
+pub use catalog::refresh_model_catalog;
+pub use credentials::{set_download_credential, test_download_credentials};
+pub use download_utils::{diagnose_connectivity, test_proxy, test_proxy_connection};
+pub(crate) use download_utils::redact_proxy_url;
+pub use llama_download::{
+    check_llama_version, download_llama_cpp, get_available_llama_variants, get_llama_build_info,
+    rollback_llama_cpp,
+};
+pub use error::DownloadError;
 pub use model_download::{
-    check_model_downloaded, delete_model, download_model_by_name, list_available_models,
+    check_model_downloaded, check_model_selectable, check_model_updates, configured_model_names,
+    declared_model_dimensions, delete_model, download_model_by_name, list_available_models,
+    recommended_embeddings_model, repair_model,
 };
+pub use verify::verify_installation;
+
 
+/// Remove partial downloads left behind by an interrupted session
+pub fn cleanup_stale_downloads() {
+    llama_download::cleanup_stale_llama_archive();
+    model_download::cleanup_stale_model_archives();
+}
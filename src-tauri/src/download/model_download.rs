@@ -1,355 +1,115 @@
-use super::download_utils::{load_config, verify_sha256};
+use super::download_utils::{
+    calculate_sha256, check_sha256, emit_download_finished, emit_download_started,
+    ensure_within_dir, extract_progress_reporter, is_zip_symlink, load_config,
+    remove_partial_download, resume_metadata_matches, write_resume_metadata, ExtractionLimits,
+    ExtractionProgress,
+};
+use super::engine::Downloader;
+use super::error::DownloadError;
 use crate::ipc_state::update_download_status;
-use crate::paths::{get_model_dir, is_model_downloaded};
-use crate::types::{DownloadProgress, ModelInfo};
-use futures_util::StreamExt;
+use crate::paths::{
+    dir_size, find_model_gguf, get_installed_model_version, get_model_dir, get_model_staging_dir,
+    get_model_verified_marker_path, get_model_version_path, get_models_root_dir,
+    is_model_downloaded, is_model_verified,
+};
+use crate::server_manager::release_server_files;
+use crate::types::{
+    DownloadProgress, ExtractProgress, ModelConfig, ModelInfo, ModelPurpose, ModelUpdateInfo,
+};
 use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Emitter};
-use tokio::io::{AsyncSeekExt, AsyncWriteExt};
-
-/// Maximum number of retry attempts for chunk read errors
-const MAX_CHUNK_RETRIES: u32 = 10;
-/// Base delay for exponential backoff (in milliseconds)
-const BASE_RETRY_DELAY_MS: u64 = 1000;
-/// Maximum delay between retries (in milliseconds)
-const MAX_RETRY_DELAY_MS: u64 = 30000;
-
-/// Create HTTP client for model downloads
-fn create_http_client() -> Result<reqwest::Client, String> {
-    reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .redirect(reqwest::redirect::Policy::limited(10))
-        // Applies to the full streamed body; multi-GB models need a generous limit on slower links.
-        .timeout(std::time::Duration::from_secs(14_400))
-        .connect_timeout(std::time::Duration::from_secs(30))
-        .pool_idle_timeout(std::time::Duration::from_secs(90))
-        .tcp_keepalive(std::time::Duration::from_secs(60))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))
-}
 
-/// Check if server supports Range requests
-async fn check_range_support(client: &reqwest::Client, url: &str) -> bool {
-    match client.head(url).send().await {
-        Ok(response) => {
-            let accepts_ranges = response
-                .headers()
-                .get("accept-ranges")
-                .map(|v| v.to_str().unwrap_or("") != "none")
-                .unwrap_or(false);
-            log::info!("Server range support: {}", accepts_ranges);
-            accepts_ranges
-        }
+/// Version of this build of the app
+const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Whether this app version satisfies the model's `min_app_version`.
+/// An unparseable requirement is logged and ignored rather than blocking the model.
+fn is_model_compatible(model_name: &str, model_config: &ModelConfig) -> bool {
+    let Some(min_version) = &model_config.min_app_version else {
+        return true;
+    };
+    let app_version = semver::Version::parse(APP_VERSION).expect("crate version is valid semver");
+    match semver::Version::parse(min_version.trim_start_matches('v')) {
+        Ok(min_version) => app_version >= min_version,
         Err(e) => {
-            log::warn!("Failed to check range support: {}", e);
-            false
+            log::warn!(
+                "Ignoring invalid min_app_version '{}' of model '{}': {}",
+                min_version,
+                model_name,
+                e
+            );
+            true
         }
     }
 }
 
-/// Calculate exponential backoff delay
-fn calculate_backoff_delay(attempt: u32) -> std::time::Duration {
-    let delay_ms = BASE_RETRY_DELAY_MS * 2u64.pow(attempt.min(10));
-    std::time::Duration::from_millis(delay_ms.min(MAX_RETRY_DELAY_MS))
+/// Download ID used to track this model in IPC state
+fn download_id(model_name: &str) -> String {
+    format!("model:{}", model_name)
 }
 
-/// Start or resume a download request from a given byte offset
-async fn start_download_request(
-    client: &reqwest::Client,
-    url: &str,
-    start_byte: u64,
-) -> Result<(reqwest::Response, Option<u64>), String> {
-    let mut request = client
-        .get(url)
-        .header("Accept", "*/*")
-        .header("Accept-Encoding", "identity");
-
-    if start_byte > 0 {
-        log::info!("Resuming download from byte {}", start_byte);
-        request = request.header("Range", format!("bytes={}-", start_byte));
-    }
+/// One downloadable archive of a model
+struct ModelArchive {
+    url: String,
+    sha256: String,
+    path: PathBuf,
+}
 
-    let response = request
-        .send()
-        .await
-        .map_err(|e| format!("Failed to download model: {}", e))?;
-
-    let status = response.status();
-    log::info!("HTTP response status: {}", status);
-
-    // 200 OK for new download, 206 Partial Content for resume
-    if !status.is_success() && status != reqwest::StatusCode::PARTIAL_CONTENT {
-        return Err(format!(
-            "HTTP error: {} - {}",
-            status.as_u16(),
-            status.canonical_reason().unwrap_or("Unknown")
-        ));
+/// Archives making up a model: one per shard for a split model, otherwise the single `url`
+fn model_archives(model_config: &ModelConfig, model_dir: &Path) -> Vec<ModelArchive> {
+    if model_config.shards.is_empty() {
+        return vec![ModelArchive {
+            url: model_config.url.clone(),
+            sha256: model_config.sha256.clone(),
+            path: model_dir.join("model.zip"),
+        }];
     }
 
-    let total_size = if start_byte > 0 && status == reqwest::StatusCode::PARTIAL_CONTENT {
-        // For resumed downloads, parse Content-Range header to get total size
-        response
-            .headers()
-            .get("content-range")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|s| s.split('/').last())
-            .and_then(|s| s.parse::<u64>().ok())
-    } else {
-        response.content_length()
-    };
-
-    Ok((response, total_size))
+    let count = model_config.shards.len();
+    model_config
+        .shards
+        .iter()
+        .enumerate()
+        .map(|(index, shard)| ModelArchive {
+            url: shard.url.clone(),
+            sha256: shard.sha256.clone(),
+            path: model_dir.join(format!("model-{:05}-of-{:05}.zip", index + 1, count)),
+        })
+        .collect()
 }
 
-/// Download file with progress tracking, retry logic and resume support
-async fn download_with_progress(
-    url: &str,
-    zip_path: &std::path::Path,
-    model_name: &str,
-    app: &AppHandle,
-) -> Result<u64, String> {
-    let client = create_http_client()?;
-
-    log::info!("Downloading model '{}' from: {}", model_name, url);
-
-    // Check if server supports range requests for resume capability
-    let supports_resume = check_range_support(&client, url).await;
-
-    // Check if partial download exists
-    let mut downloaded: u64 = if supports_resume && zip_path.exists() {
-        let existing_size = tokio::fs::metadata(zip_path)
-            .await
-            .map(|m| m.len())
-            .unwrap_or(0);
-        if existing_size > 0 {
-            log::info!(
-                "Found partial download: {:.2} MB, will attempt to resume",
-                existing_size as f64 / 1_048_576.0
-            );
-        }
-        existing_size
-    } else {
-        0
-    };
-
-    let (response, total_size) = start_download_request(&client, url, downloaded).await?;
-
-    if let Some(size) = total_size {
-        log::info!("Model size: {:.2} MB", size as f64 / 1_048_576.0);
-    } else {
-        log::warn!("Model size: unknown (no Content-Length header)");
+/// Progress of archive `index` out of `count` as progress of the whole model.
+/// Sizes of the later shards are unknown up front, so every shard weighs the same.
+fn combined_progress(
+    progress: &DownloadProgress,
+    index: usize,
+    count: usize,
+    done_bytes: u64,
+) -> DownloadProgress {
+    if count == 1 {
+        return progress.clone();
     }
-
-    // Log some response headers for debugging
-    log::info!(
-        "Content-Type: {:?}",
-        response.headers().get("content-type")
-    );
-    log::info!(
-        "Content-Encoding: {:?}",
-        response.headers().get("content-encoding")
-    );
-
-    // Update IPC state - download started
-    let initial_percentage = total_size.map(|total| (downloaded as f64 / total as f64) * 100.0);
-    let _ = update_download_status(true, initial_percentage.or(Some(0.0)));
-
-    // Emit initial progress
-    let _ = app.emit(
-        "download-progress",
-        DownloadProgress {
-            downloaded,
-            total: total_size,
-            percentage: initial_percentage.or(Some(0.0)),
-            message: format!("Starting model '{}' download...", model_name),
-        },
-    );
-
-    // Open file for writing (append if resuming)
-    let mut file = if downloaded > 0 {
-        let mut f = tokio::fs::OpenOptions::new()
-            .write(true)
-            .append(true)
-            .open(zip_path)
-            .await
-            .map_err(|e| format!("Failed to open zip file for resume: {}", e))?;
-        // Seek to end to ensure we're appending
-        f.seek(std::io::SeekFrom::End(0))
-            .await
-            .map_err(|e| format!("Failed to seek to end of file: {}", e))?;
-        f
-    } else {
-        tokio::fs::File::create(zip_path)
-            .await
-            .map_err(|e| format!("Failed to create zip file: {}", e))?
-    };
-
-    let mut stream = response.bytes_stream();
-    let mut last_emit_mb = downloaded / (10 * 1024 * 1024);
-    let mut last_log_mb = downloaded / (50 * 1024 * 1024);
-    let mut consecutive_errors = 0u32;
-
-    log::info!("Starting download stream...");
-
-    loop {
-        match stream.next().await {
-            Some(Ok(chunk)) => {
-                // Reset error counter on successful chunk
-                consecutive_errors = 0;
-
-                file.write_all(&chunk)
-                    .await
-                    .map_err(|e| format!("Failed to write chunk: {}", e))?;
-
-                downloaded += chunk.len() as u64;
-
-                // Log progress every 50 MB to console
-                let current_log_mb = downloaded / (50 * 1024 * 1024);
-                if current_log_mb > last_log_mb {
-                    last_log_mb = current_log_mb;
-                    let percentage =
-                        total_size.map(|total| (downloaded as f64 / total as f64) * 100.0);
-                    if let Some(pct) = percentage {
-                        log::info!(
-                            "Downloaded: {:.2} MB ({:.1}%)",
-                            downloaded as f64 / 1_048_576.0,
-                            pct
-                        );
-                    } else {
-                        log::info!("Downloaded: {:.2} MB", downloaded as f64 / 1_048_576.0);
-                    }
-                }
-
-                // Emit progress every 10 MB to reduce event spam
-                let current_mb = downloaded / (10 * 1024 * 1024);
-                if current_mb > last_emit_mb
-                    || total_size.map_or(false, |total| downloaded >= total)
-                {
-                    last_emit_mb = current_mb;
-                    let percentage =
-                        total_size.map(|total| (downloaded as f64 / total as f64) * 100.0);
-                    let message = if let Some(total) = total_size {
-                        format!(
-                            "Downloading model '{}': {:.2} MB / {:.2} MB",
-                            model_name,
-                            downloaded as f64 / 1_048_576.0,
-                            total as f64 / 1_048_576.0,
-                        )
-                    } else {
-                        format!(
-                            "Downloading model '{}': {:.2} MB",
-                            model_name,
-                            downloaded as f64 / 1_048_576.0
-                        )
-                    };
-
-                    // Update IPC state with progress
-                    let _ = update_download_status(true, percentage);
-
-                    let _ = app.emit(
-                        "download-progress",
-                        DownloadProgress {
-                            downloaded,
-                            total: total_size,
-                            percentage,
-                            message,
-                        },
-                    );
-                }
-            }
-            Some(Err(e)) => {
-                consecutive_errors += 1;
-                log::warn!(
-                    "Chunk read error (attempt {}/{}): {}",
-                    consecutive_errors,
-                    MAX_CHUNK_RETRIES,
-                    e
-                );
-
-                if consecutive_errors >= MAX_CHUNK_RETRIES {
-                    return Err(format!(
-                        "Failed to read chunk after {} retries: {}",
-                        MAX_CHUNK_RETRIES, e
-                    ));
-                }
-
-                if !supports_resume {
-                    return Err(format!(
-                        "Failed to read chunk and server does not support resume: {}",
-                        e
-                    ));
-                }
-
-                // Flush current data before reconnecting
-                file.flush()
-                    .await
-                    .map_err(|e| format!("Failed to flush file before retry: {}", e))?;
-                file.sync_all()
-                    .await
-                    .map_err(|e| format!("Failed to sync file before retry: {}", e))?;
-
-                // Calculate backoff delay
-                let delay = calculate_backoff_delay(consecutive_errors - 1);
-                log::info!("Waiting {:?} before retry...", delay);
-
-                let _ = app.emit(
-                    "download-progress",
-                    DownloadProgress {
-                        downloaded,
-                        total: total_size,
-                        percentage: total_size
-                            .map(|total| (downloaded as f64 / total as f64) * 100.0),
-                        message: format!(
-                            "Connection lost, retrying in {} seconds...",
-                            delay.as_secs()
-                        ),
-                    },
-                );
-
-                tokio::time::sleep(delay).await;
-
-                // Reconnect and resume from current position
-                log::info!("Attempting to resume download from byte {}", downloaded);
-
-                let (new_response, _) = start_download_request(&client, url, downloaded).await?;
-                stream = new_response.bytes_stream();
-
-                log::info!("Successfully resumed download");
-            }
-            None => {
-                // Stream ended
-                break;
-            }
-        }
+    let shard_fraction = progress.percentage.unwrap_or(0.0) / 100.0;
+    DownloadProgress {
+        artifact: progress.artifact.clone(),
+        downloaded: done_bytes + progress.downloaded,
+        total: None,
+        percentage: Some((index as f64 + shard_fraction) / count as f64 * 100.0),
+        message: format!("Shard {}/{}: {}", index + 1, count, progress.message),
     }
-
-    log::info!(
-        "Download completed! Total: {:.2} MB",
-        downloaded as f64 / 1_048_576.0
-    );
-
-    // Flush and sync file to ensure all data is written to disk
-    file.flush()
-        .await
-        .map_err(|e| format!("Failed to flush file: {}", e))?;
-
-    file.sync_all()
-        .await
-        .map_err(|e| format!("Failed to sync file: {}", e))?;
-
-    // Explicitly close file before verification to ensure all data is persisted
-    drop(file);
-
-    log::info!("File synced successfully: {} bytes", downloaded);
-
-    Ok(downloaded)
 }
 
-/// Extract model archive
+/// Extract model archive, reporting progress every 50 MB.
+/// Every extracted file is flushed to disk before this returns.
 fn extract_model_archive(
     zip_path: &std::path::Path,
     model_dir: &std::path::Path,
-) -> Result<(), String> {
+    model_name: &str,
+    mut limits: ExtractionLimits,
+    on_progress: &mut dyn FnMut(&ExtractProgress),
+) -> Result<Vec<PathBuf>, String> {
     let file =
         std::fs::File::open(zip_path).map_err(|e| format!("Failed to open zip file: {}", e))?;
 
@@ -358,19 +118,43 @@ fn extract_model_archive(
 
     let archive_len = archive.len();
     log::info!("Archive contains {} files", archive_len);
+    let mut extracted = Vec::new();
+
+    // Uncompressed size from the central directory, without decompressing anything
+    let mut total_size = 0u64;
+    for i in 0..archive_len {
+        let file = archive
+            .by_index_raw(i)
+            .map_err(|e| format!("Failed to read file from archive: {}", e))?;
+        if !file.name().ends_with('/') {
+            total_size = total_size.saturating_add(file.size());
+        }
+    }
+    limits.check_declared("<all entries>", total_size)?;
+    let mut progress = ExtractionProgress::new(model_name, Some(total_size), on_progress);
 
     for i in 0..archive_len {
         let mut file = archive
             .by_index(i)
             .map_err(|e| format!("Failed to read file from archive: {}", e))?;
+        let name = file.name().to_string();
 
+        // Absolute paths and `..` components are rejected by enclosed_name
         let outpath = match file.enclosed_name() {
             Some(path) => model_dir.join(path),
-            None => continue,
+            None => {
+                log::warn!("Skipping archive entry with unsafe path: {}", name);
+                continue;
+            }
         };
 
-        if file.name().ends_with('/') {
-            log::info!("Creating directory: {}", file.name());
+        if is_zip_symlink(&file) {
+            log::warn!("Skipping symlink archive entry: {}", name);
+            continue;
+        }
+
+        if name.ends_with('/') {
+            log::info!("Creating directory: {}", name);
             fs::create_dir_all(&outpath)
                 .map_err(|e| format!("Failed to create directory: {}", e))?;
         } else {
@@ -378,63 +162,279 @@ fn extract_model_archive(
                 "Extracting file {}/{}: {} ({:.2} MB)",
                 i + 1,
                 archive_len,
-                file.name(),
+                name,
                 file.size() as f64 / 1_048_576.0
             );
+            limits.check_declared(&name, file.size())?;
             if let Some(p) = outpath.parent() {
                 fs::create_dir_all(p)
                     .map_err(|e| format!("Failed to create parent directory: {}", e))?;
             }
-            let mut outfile = fs::File::create(&outpath)
+            ensure_within_dir(model_dir, &outpath)?;
+            let outfile = fs::File::create(&outpath)
                 .map_err(|e| format!("Failed to create output file: {}", e))?;
-            std::io::copy(&mut file, &mut outfile)
-                .map_err(|e| format!("Failed to extract file: {}", e))?;
+            progress.start_file(&name);
+            limits.copy(&name, &mut file, &mut progress.writer(&outfile))?;
+            outfile
+                .sync_all()
+                .map_err(|e| format!("Failed to flush extracted file: {}", e))?;
+            extracted.push(outpath);
         }
     }
 
     log::info!("Extraction completed successfully!");
+    Ok(extracted)
+}
+
+/// Verify the extracted .gguf against its configured checksum, returning the verified hash.
+/// A truncated extraction (e.g. disk filled mid-copy) would otherwise only surface
+/// as a confusing load failure in llama-server.
+fn verify_extracted_model(
+    extracted: &[PathBuf],
+    gguf_filename: &str,
+    expected_sha256: &str,
+) -> Result<String, DownloadError> {
+    let is_gguf = |path: &&PathBuf| path.extension().and_then(|s| s.to_str()) == Some("gguf");
+    let gguf_path = extracted
+        .iter()
+        .filter(is_gguf)
+        .find(|path| path.file_name().and_then(|s| s.to_str()) == Some(gguf_filename))
+        .or_else(|| extracted.iter().find(is_gguf))
+        .ok_or_else(|| {
+            DownloadError::Extraction("Model archive does not contain a .gguf file".to_string())
+        })?;
+
+    log::info!("Verifying SHA-256 of extracted model: {:?}", gguf_path);
+    let calculated_hash = calculate_sha256(gguf_path)?;
+    check_sha256(gguf_path, &calculated_hash, expected_sha256)?;
+    Ok(calculated_hash)
+}
+
+/// Extract the archives into `.staging/`, verify the model there, then move it into place.
+/// The archives are removed and the `.verified` marker that `is_model_downloaded` requires
+/// is written only after that, so a crash at any point never leaves a half-extracted model
+/// that counts as downloaded.
+/// On failure the staged files are removed but the archives are kept: they already passed
+/// their own checksums, so `repair_model` can re-extract without downloading again.
+/// On success the config version is recorded for `check_model_updates`.
+fn extract_and_verify_model(
+    model_name: &str,
+    model_config: &ModelConfig,
+    archives: &[ModelArchive],
+    model_dir: &Path,
+    app: &AppHandle,
+) -> Result<(), DownloadError> {
+    let marker_path = get_model_verified_marker_path(model_name)
+        .map_err(|e| DownloadError::Other(e.to_string()))?;
+    let version_path =
+        get_model_version_path(model_name).map_err(|e| DownloadError::Other(e.to_string()))?;
+    let staging_dir =
+        get_model_staging_dir(model_name).map_err(|e| DownloadError::Other(e.to_string()))?;
+    fs::remove_file(&marker_path).ok();
+    fs::remove_file(&version_path).ok();
+
+    remove_staging_dir(&staging_dir);
+    fs::create_dir_all(&staging_dir)
+        .map_err(|e| DownloadError::io("Failed to create staging directory", e))?;
+    let (staged, verified_hash) =
+        match stage_model(model_name, model_config, archives, &staging_dir, app) {
+            Ok(staged) => staged,
+            Err(e) => {
+                remove_staging_dir(&staging_dir);
+                return Err(e);
+            }
+        };
+
+    // Move the staged files into place; each rename is atomic
+    let mut installed = Vec::with_capacity(staged.len());
+    for path in &staged {
+        let relative = path.strip_prefix(&staging_dir).unwrap_or(path);
+        let target = model_dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| DownloadError::io("Failed to create model directory", e))?;
+        }
+        fs::rename(path, &target)
+            .map_err(|e| DownloadError::io("Failed to move extracted model into place", e))?;
+        installed.push(target);
+    }
+    sync_dir(model_dir);
+    remove_staging_dir(&staging_dir);
+
+    // An update may ship the weights under a new file name; drop the old .gguf files
+    // so the server does not pick a stale one
+    remove_stale_gguf_files(model_dir, &installed);
+
+    log::info!("Removing temporary zip files...");
+    for archive in archives {
+        remove_partial_download(&archive.path);
+    }
+
+    fs::write(&version_path, &model_config.version)
+        .map_err(|e| DownloadError::io("Failed to record model version", e))?;
+    // Empty when no `gguf_sha256` is configured: complete, but not verified
+    fs::File::create(&marker_path)
+        .and_then(|mut file| {
+            file.write_all(verified_hash.unwrap_or_default().as_bytes())?;
+            file.sync_all()
+        })
+        .map_err(|e| DownloadError::io("Failed to write verification marker", e))?;
+    sync_dir(model_dir);
     Ok(())
 }
 
-/// Common download logic for models
+/// Extract the archives into the staging directory and check the result is a complete model,
+/// verified against `gguf_sha256` when configured. Returns the staged files and the verified hash.
+fn stage_model(
+    model_name: &str,
+    model_config: &ModelConfig,
+    archives: &[ModelArchive],
+    staging_dir: &Path,
+    app: &AppHandle,
+) -> Result<(Vec<PathBuf>, Option<String>), DownloadError> {
+    let id = download_id(model_name);
+    let mut on_progress = extract_progress_reporter(app, &id);
+    let mut extracted = Vec::new();
+    for archive in archives {
+        let limits =
+            ExtractionLimits::for_archive(&archive.path).map_err(DownloadError::Extraction)?;
+        extracted.extend(
+            extract_model_archive(
+                &archive.path,
+                staging_dir,
+                model_name,
+                limits,
+                &mut on_progress,
+            )
+            .map_err(DownloadError::Extraction)?,
+        );
+    }
+    sync_dir(staging_dir);
+
+    // A split model is only loadable with every shard present
+    if find_model_gguf(staging_dir).is_none() {
+        return Err(DownloadError::Extraction(format!(
+            "Archives of model '{}' do not contain a complete .gguf model",
+            model_name
+        )));
+    }
+
+    // Verify the extracted model itself, not just the archive
+    let Some(expected_gguf_sha256) = model_config
+        .gguf_sha256
+        .as_deref()
+        .filter(|h| !h.is_empty())
+    else {
+        return Ok((extracted, None));
+    };
+    match verify_extracted_model(&extracted, &model_config.filename, expected_gguf_sha256) {
+        Ok(verified_hash) => Ok((extracted, Some(verified_hash))),
+        Err(e) => {
+            log::error!(
+                "Extracted model '{}' failed verification: {}",
+                model_name,
+                e
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Remove a staging directory left by this or an abandoned extraction
+fn remove_staging_dir(staging_dir: &Path) {
+    if staging_dir.exists() {
+        log::info!("Removing model staging directory: {:?}", staging_dir);
+        fs::remove_dir_all(staging_dir).ok();
+    }
+}
+
+/// Flush a directory entry (renames and new files) to disk.
+/// Directories cannot be opened for syncing on Windows, where renames are journaled anyway.
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Err(e) = fs::File::open(dir).and_then(|dir| dir.sync_all()) {
+        log::warn!("Failed to sync directory {:?}: {}", dir, e);
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+/// Remove .gguf files in the model directory that were not part of the latest extraction
+fn remove_stale_gguf_files(model_dir: &Path, extracted: &[PathBuf]) {
+    let Ok(entries) = fs::read_dir(model_dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let is_gguf = path.extension().and_then(|ext| ext.to_str()) == Some("gguf");
+        if is_gguf && !extracted.contains(&path) {
+            log::info!("Removing model file from previous version: {:?}", path);
+            fs::remove_file(&path).ok();
+        }
+    }
+}
+
+/// Common download logic for models: the archives are downloaded one after another,
+/// then extracted together
 async fn download_model_common(
     model_name: &str,
-    model_url: &str,
-    expected_sha256: &str,
+    model_config: &ModelConfig,
     app: AppHandle,
-) -> Result<String, String> {
-    let model_dir = get_model_dir(model_name).map_err(|e| e.to_string())?;
-    let zip_path = model_dir.join("model.zip");
+) -> Result<String, DownloadError> {
+    let model_dir = get_model_dir(model_name).map_err(|e| DownloadError::Other(e.to_string()))?;
+    let archives = model_archives(model_config, &model_dir);
+    let count = archives.len();
 
     log::info!(
-        "Starting model '{}' download from: {}",
-        model_name, model_url
+        "Starting model '{}' download ({} archive(s))",
+        model_name,
+        count
     );
-    log::info!("Download destination: {:?}", zip_path);
 
-    // Download with progress
-    let downloaded = match download_with_progress(model_url, &zip_path, model_name, &app).await {
-        Ok(size) => size,
-        Err(e) => {
-            // Clear IPC download status on error
-            let _ = update_download_status(false, None);
-            return Err(e);
-        }
-    };
+    let downloader = Downloader::new(model_name, &format!("model '{}'", model_name))?
+        .with_headers(&model_config.headers)?
+        .with_download_id(&download_id(model_name));
 
-    // Verify SHA-256 checksum
-    if let Err(e) = verify_sha256(&zip_path, expected_sha256) {
-        // Remove corrupted file
-        fs::remove_file(&zip_path).ok();
-        // Clear IPC download status on error
-        let _ = update_download_status(false, None);
-        return Err(format!("Model '{}' checksum verification failed: {}", model_name, e));
+    let mut downloaded = 0u64;
+    for (index, archive) in archives.iter().enumerate() {
+        log::info!("Downloading {:?} from: {}", archive.path, archive.url);
+
+        // A leftover archive from another URL or checksum cannot be resumed
+        if archive.path.exists()
+            && !resume_metadata_matches(&archive.path, &archive.url, &archive.sha256)
+        {
+            log::info!("Discarding partial download that does not match the current model config");
+            remove_partial_download(&archive.path);
+        }
+        write_resume_metadata(&archive.path, &archive.url, &archive.sha256)?;
+
+        let done_bytes = downloaded;
+        let result = downloader
+            .download_verified(&archive.url, &archive.path, &archive.sha256, |progress| {
+                let progress = combined_progress(progress, index, count, done_bytes);
+                let _ = update_download_status(&download_id(model_name), true, progress.percentage);
+                let _ = app.emit("download-progress", &progress);
+            })
+            .await;
+        match result {
+            Ok(outcome) => downloaded += outcome.size,
+            Err(e) => {
+                if e.kind() == "checksum" {
+                    // Remove corrupted file
+                    remove_partial_download(&archive.path);
+                    log::error!("Model '{}' checksum verification failed: {}", model_name, e);
+                }
+                // Clear IPC download status on error
+                let _ = update_download_status(&download_id(model_name), false, None);
+                return Err(e);
+            }
+        }
     }
 
     // Emit extraction progress
     let _ = app.emit(
         "download-progress",
         DownloadProgress {
+            artifact: model_name.to_string(),
             downloaded,
             total: Some(downloaded),
             percentage: Some(100.0),
@@ -444,19 +444,16 @@ async fn download_model_common(
 
     log::info!("Starting extraction...");
 
-    // Extract archive
-    if let Err(e) = extract_model_archive(&zip_path, &model_dir) {
+    // Extract archives
+    if let Err(e) = extract_and_verify_model(model_name, model_config, &archives, &model_dir, &app)
+    {
         // Clear IPC download status on error
-        let _ = update_download_status(false, None);
+        let _ = update_download_status(&download_id(model_name), false, None);
         return Err(e);
     }
 
-    // Remove zip file
-    log::info!("Removing temporary zip file...");
-    fs::remove_file(&zip_path).ok();
-
     // Clear IPC download status on success
-    let _ = update_download_status(false, None);
+    let _ = update_download_status(&download_id(model_name), false, None);
 
     log::info!("Model '{}' ready at: {:?}", model_name, model_dir);
     Ok(format!(
@@ -465,37 +462,233 @@ async fn download_model_common(
     ))
 }
 
+/// Reconcile leftover archives for models that are not extracted yet:
+/// keep them only if they can still be resumed against the current config
+pub(super) fn cleanup_stale_model_archives() {
+    let Ok(config) = load_config() else {
+        return;
+    };
+    let Ok(entries) = get_models_root_dir().and_then(|dir| Ok(fs::read_dir(dir)?)) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let model_name = entry.file_name().to_string_lossy().to_string();
+        let model_dir = entry.path();
+        // Nothing is extracting at startup, so a staging directory was abandoned by a crash
+        if let Ok(staging_dir) = get_model_staging_dir(&model_name) {
+            remove_staging_dir(&staging_dir);
+        }
+        let Ok(files) = fs::read_dir(&model_dir) else {
+            continue;
+        };
+        let zip_paths: Vec<PathBuf> = files
+            .flatten()
+            .map(|file| file.path())
+            .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("zip"))
+            .collect();
+        if zip_paths.is_empty() || is_model_downloaded(&model_name).unwrap_or(false) {
+            continue;
+        }
+
+        let archives = config
+            .models
+            .get(&model_name)
+            .map(|model| model_archives(model, &model_dir))
+            .unwrap_or_default();
+        for zip_path in zip_paths {
+            let resumable = archives.iter().any(|archive| {
+                archive.path == zip_path
+                    && resume_metadata_matches(&zip_path, &archive.url, &archive.sha256)
+            });
+            if !resumable {
+                log::info!("Removing stale partial download for model '{}'", model_name);
+                remove_partial_download(&zip_path);
+            }
+        }
+    }
+}
+
 #[tauri::command]
 pub async fn download_model_by_name(
     model_name: String,
     app: AppHandle,
-) -> Result<String, String> {
+) -> Result<String, DownloadError> {
     // Load config to get model URL and SHA-256
     let config = load_config()?;
 
     let model_config = config
         .models
         .get(&model_name)
-        .ok_or_else(|| format!("Model '{}' not found in configuration", model_name))?;
+        .ok_or_else(|| {
+            DownloadError::Unsupported(format!("Model '{}' not found in configuration", model_name))
+        })?;
+
+    if !is_model_compatible(&model_name, model_config) {
+        return Err(DownloadError::Unsupported(format!(
+            "Model '{}' requires Sigma Eclipse {} or newer (this is {}). \
+             Please update the app: updates are checked on startup, so restart it to \
+             get the update prompt.",
+            model_name,
+            model_config.min_app_version.as_deref().unwrap_or_default(),
+            APP_VERSION
+        )));
+    }
+
+    emit_download_started(&app, &model_name, "model");
+    let result = download_model_common(&model_name, model_config, app.clone()).await;
+    emit_download_finished(&app, &model_name, "model", &result);
+    result
+}
+
+
+/// Repair a model whose extracted files are missing or failed verification:
+/// re-extract from a kept archive when it still matches its checksum, otherwise re-download
+#[tauri::command]
+pub async fn repair_model(
+    model_name: String,
+    force: Option<bool>,
+    app: AppHandle,
+) -> Result<String, DownloadError> {
+    let config = load_config()?;
+    let model_config = config.models.get(&model_name).ok_or_else(|| {
+        DownloadError::Unsupported(format!("Model '{}' not found in configuration", model_name))
+    })?;
+
+    // Both ways replace the model files, so a server using them is stopped first (with force)
+    release_server_files(
+        &format!("Model '{}'", model_name),
+        Some(&model_name),
+        force.unwrap_or(false),
+    )
+    .map_err(|e| DownloadError::Other(e.to_string()))?;
+
+    let model_dir = get_model_dir(&model_name).map_err(|e| DownloadError::Other(e.to_string()))?;
+    let archives = model_archives(model_config, &model_dir);
+
+    // An extraction interrupted by a crash leaves its staging directory behind
+    let staging_dir =
+        get_model_staging_dir(&model_name).map_err(|e| DownloadError::Other(e.to_string()))?;
+    if staging_dir.exists() {
+        log::warn!("Found abandoned extraction of model '{}'", model_name);
+        remove_staging_dir(&staging_dir);
+    }
+
+    let archive_usable = |archive: &ModelArchive| {
+        archive.path.exists()
+            && resume_metadata_matches(&archive.path, &archive.url, &archive.sha256)
+            && calculate_sha256(&archive.path).is_ok_and(|hash| {
+                archive.sha256.is_empty() || hash.eq_ignore_ascii_case(&archive.sha256)
+            })
+    };
 
-    let model_url = &model_config.url;
-    let expected_sha256 = &model_config.sha256;
+    if archives.iter().all(archive_usable) {
+        log::info!("Repairing model '{}' from kept archives", model_name);
+        let result =
+            extract_and_verify_model(&model_name, model_config, &archives, &model_dir, &app);
+        // Extraction reports progress through the IPC download status
+        let _ = update_download_status(&download_id(&model_name), false, None);
+        result?;
+        return Ok(format!("Model '{}' re-extracted and verified", model_name));
+    }
 
-    download_model_common(&model_name, model_url, expected_sha256, app).await
+    log::info!("Repairing model '{}' by downloading it again", model_name);
+    if model_dir.exists() {
+        fs::remove_dir_all(&model_dir).map_err(|e| {
+            DownloadError::io(&format!("Failed to clear model '{}'", model_name), e)
+        })?;
+    }
+    download_model_by_name(model_name, app).await
 }
 
+/// Check that `model_name` can become the active model: it is a chat model in the model
+/// configuration, usable with this app version and downloaded
+pub fn check_model_selectable(model_name: &str) -> Result<(), DownloadError> {
+    let config = load_config()?;
+    let model_config = config.models.get(model_name).ok_or_else(|| {
+        DownloadError::Unsupported(format!("Model '{}' not found in configuration", model_name))
+    })?;
+    if model_config.purpose == ModelPurpose::Embedding {
+        return Err(DownloadError::Unsupported(format!(
+            "Model '{}' is an embedding model; run it with start_embeddings_server",
+            model_name
+        )));
+    }
+    if !is_model_compatible(model_name, model_config) {
+        return Err(DownloadError::Unsupported(format!(
+            "Model '{}' requires Sigma Eclipse {} or newer (this is {})",
+            model_name,
+            model_config.min_app_version.as_deref().unwrap_or_default(),
+            APP_VERSION
+        )));
+    }
+    if !is_model_downloaded(model_name).map_err(|e| DownloadError::Other(e.to_string()))? {
+        return Err(DownloadError::Other(format!(
+            "Model '{}' is not downloaded",
+            model_name
+        )));
+    }
+    Ok(())
+}
+
+/// The embedding model to propose: one tagged `"purpose": "embedding"` in the model
+/// configuration, a downloaded one first
+pub fn recommended_embeddings_model() -> Option<String> {
+    let config = load_config().ok()?;
+    let mut candidates: Vec<&String> = config
+        .models
+        .iter()
+        .filter(|(name, model_config)| {
+            model_config.purpose == ModelPurpose::Embedding
+                && is_model_compatible(name, model_config)
+        })
+        .map(|(name, _)| name)
+        .collect();
+    // Sort by name so the choice does not depend on map order
+    candidates.sort();
+    candidates
+        .iter()
+        .find(|name| is_model_downloaded(name).unwrap_or(false))
+        .or(candidates.first())
+        .map(|name| name.to_string())
+}
+
+/// Names of the models in the model configuration
+pub fn configured_model_names() -> Vec<String> {
+    load_config()
+        .map(|config| config.models.into_keys().collect())
+        .unwrap_or_default()
+}
+
+/// `size_bytes` and `layer_count` of a model as declared in the model configuration
+pub fn declared_model_dimensions(model_name: &str) -> (Option<u64>, Option<u32>) {
+    load_config()
+        .ok()
+        .and_then(|config| {
+            config
+                .models
+                .get(model_name)
+                .map(|model_config| (model_config.size_bytes, model_config.layer_count))
+        })
+        .unwrap_or_default()
+}
 
 #[tauri::command]
-pub async fn list_available_models() -> Result<Vec<ModelInfo>, String> {
+pub async fn list_available_models() -> Result<Vec<ModelInfo>, DownloadError> {
     let config = load_config()?;
     let mut models = Vec::new();
 
     for (name, model_config) in config.models.iter() {
         let is_downloaded = is_model_downloaded(name).unwrap_or(false);
-        let path = if is_downloaded {
-            get_model_dir(name)
-                .ok()
-                .map(|p| p.to_string_lossy().to_string())
+        let model_dir = if is_downloaded {
+            get_model_dir(name).ok()
+        } else {
+            None
+        };
+        let path = model_dir.as_ref().map(|p| p.to_string_lossy().to_string());
+        let size_bytes = model_dir.as_deref().map(dir_size);
+        let installed_version = if is_downloaded {
+            get_installed_model_version(name).ok().flatten()
         } else {
             None
         };
@@ -504,7 +697,13 @@ pub async fn list_available_models() -> Result<Vec<ModelInfo>, String> {
             name: name.clone(),
             version: model_config.version.clone(),
             is_downloaded,
+            is_verified: is_downloaded && is_model_verified(name).unwrap_or(false),
             path,
+            size_bytes,
+            installed_version,
+            compatible: is_model_compatible(name, model_config),
+            min_app_version: model_config.min_app_version.clone(),
+            purpose: model_config.purpose,
         });
     }
 
@@ -514,22 +713,143 @@ pub async fn list_available_models() -> Result<Vec<ModelInfo>, String> {
     Ok(models)
 }
 
+/// Compare the installed version of each downloaded model with the configured one.
+/// Models installed before versions were recorded report no update, as their version is unknown;
+/// `download_model_by_name` replaces a model in place when an update is available.
+#[tauri::command]
+pub async fn check_model_updates() -> Result<Vec<ModelUpdateInfo>, DownloadError> {
+    let config = load_config()?;
+    let mut updates = Vec::new();
+
+    for (name, model_config) in config.models.iter() {
+        if !is_model_downloaded(name).unwrap_or(false) {
+            continue;
+        }
+        let installed_version =
+            get_installed_model_version(name).map_err(|e| DownloadError::Other(e.to_string()))?;
+        let update_available = installed_version
+            .as_ref()
+            .is_some_and(|installed| *installed != model_config.version);
+        if update_available {
+            log::info!(
+                "Update available for model '{}': {:?} -> {}",
+                name,
+                installed_version,
+                model_config.version
+            );
+        }
+
+        updates.push(ModelUpdateInfo {
+            name: name.clone(),
+            installed_version,
+            latest_version: model_config.version.clone(),
+            update_available,
+        });
+    }
+
+    updates.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(updates)
+}
+
 #[tauri::command]
-pub async fn delete_model(model_name: String) -> Result<String, String> {
-    let model_dir = get_model_dir(&model_name).map_err(|e| e.to_string())?;
+pub async fn delete_model(
+    model_name: String,
+    force: Option<bool>,
+) -> Result<String, DownloadError> {
+    let model_dir = get_model_dir(&model_name).map_err(|e| DownloadError::Other(e.to_string()))?;
 
     if !model_dir.exists() {
-        return Err(format!("Model '{}' is not downloaded", model_name));
+        return Err(DownloadError::Other(format!("Model '{}' is not downloaded", model_name)));
     }
 
+    // With force the server is stopped first, otherwise deleting a model in use fails
+    release_server_files(
+        &format!("Model '{}'", model_name),
+        Some(&model_name),
+        force.unwrap_or(false),
+    )
+    .map_err(|e| DownloadError::Other(e.to_string()))?;
+
     fs::remove_dir_all(&model_dir)
-        .map_err(|e| format!("Failed to delete model '{}': {}", model_name, e))?;
+        .map_err(|e| DownloadError::io(&format!("Failed to delete model '{}'", model_name), e))?;
 
     Ok(format!("Model '{}' has been deleted", model_name))
 }
 
 #[tauri::command]
-pub async fn check_model_downloaded(model_name: String) -> Result<bool, String> {
-    is_model_downloaded(&model_name).map_err(|e| e.to_string())
+pub async fn check_model_downloaded(model_name: String) -> Result<bool, DownloadError> {
+    is_model_downloaded(&model_name).map_err(|e| DownloadError::Other(e.to_string()))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::write::FileOptions;
+
+    fn write_zip(path: &Path, build: impl FnOnce(&mut zip::ZipWriter<Cursor<Vec<u8>>>)) {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        build(&mut writer);
+        fs::write(path, writer.finish().unwrap().into_inner()).unwrap();
+    }
+
+    fn extract(zip_path: &Path, model_dir: &Path) -> Result<Vec<PathBuf>, String> {
+        let limits = ExtractionLimits::with_cap(zip_path, 100).unwrap();
+        extract_model_archive(zip_path, model_dir, "test-model", limits, &mut |_| {})
+    }
+
+    #[test]
+    fn unsafe_entries_are_skipped() {
+        let root = tempfile::tempdir().unwrap();
+        let zip_path = root.path().join("model.zip");
+        let model_dir = root.path().join("models/test-model");
+        fs::create_dir_all(&model_dir).unwrap();
+        write_zip(&zip_path, |writer| {
+            let options = FileOptions::default();
+            writer.start_file("../evil.txt", options).unwrap();
+            writer.write_all(b"evil").unwrap();
+            writer.start_file("/abs/evil.txt", options).unwrap();
+            writer.write_all(b"evil").unwrap();
+            writer
+                .add_symlink("ok/passwd", "/etc/passwd", options)
+                .unwrap();
+            writer.start_file("ok/model.gguf", options).unwrap();
+            writer.write_all(b"GGUF model data").unwrap();
+        });
+
+        let extracted = extract(&zip_path, &model_dir).unwrap();
+
+        assert_eq!(extracted, vec![model_dir.join("ok/model.gguf")]);
+        assert_eq!(
+            fs::read(model_dir.join("ok/model.gguf")).unwrap(),
+            b"GGUF model data"
+        );
+        assert!(!root.path().join("models/evil.txt").exists());
+        assert!(!model_dir.join("abs").exists());
+        assert!(fs::symlink_metadata(model_dir.join("ok/passwd")).is_err());
+    }
+
+    #[test]
+    fn size_bombs_are_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        let zip_path = root.path().join("model.zip");
+        let model_dir = root.path().join("test-model");
+        fs::create_dir_all(&model_dir).unwrap();
+        write_zip(&zip_path, |writer| {
+            let options =
+                FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+            writer.start_file("model.gguf", options).unwrap();
+            let zeros = vec![0u8; 1 << 20];
+            for _ in 0..50 {
+                writer.write_all(&zeros).unwrap();
+            }
+        });
+
+        let error = extract(&zip_path, &model_dir).unwrap_err();
+
+        assert!(error.contains("exceeds the extraction limit"), "{}", error);
+        assert!(!model_dir.join("model.gguf").exists());
+    }
+}
@@ -0,0 +1,120 @@
+// Custom HTTP headers per download source from versions.json
+// Some mirrors need an API key or a Referer. Values may reference environment variables
+// as `${ENV:NAME}` so secrets stay out of the committed config.
+
+use super::error::DownloadError;
+use crate::types::VersionsConfig;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+
+/// Headers managed by the HTTP client or only meaningful for a single connection
+const FORBIDDEN_HEADERS: &[&str] = &[
+    "host",
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Header names whose values are never logged, even when written literally in the config
+const SENSITIVE_HEADER_PARTS: &[&str] = &["authorization", "cookie", "key", "token", "secret"];
+
+const ENV_PREFIX: &str = "${ENV:";
+
+/// Check the headers of every download source; called whenever a config is loaded
+pub fn validate_config_headers(config: &VersionsConfig) -> Result<(), String> {
+    for (platform, platform_config) in &config.llama_cpp.platforms {
+        validate_headers(&platform_config.headers)
+            .map_err(|e| format!("llama.cpp platform '{}': {}", platform, e))?;
+    }
+    for (name, model_config) in &config.models {
+        validate_headers(&model_config.headers).map_err(|e| format!("model '{}': {}", name, e))?;
+    }
+    Ok(())
+}
+
+fn validate_headers(headers: &HashMap<String, String>) -> Result<(), String> {
+    for (name, value) in headers {
+        let header = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("invalid header name '{}'", name))?;
+        if FORBIDDEN_HEADERS.contains(&header.as_str()) {
+            return Err(format!(
+                "header '{}' cannot be set per download source",
+                name
+            ));
+        }
+        // Environment variables are only read at download time; check the syntax now
+        env_placeholders(value).map_err(|e| format!("header '{}': {}", name, e))?;
+    }
+    Ok(())
+}
+
+/// Names of the `${ENV:NAME}` placeholders in a header value
+fn env_placeholders(value: &str) -> Result<Vec<&str>, String> {
+    let mut names = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find(ENV_PREFIX) {
+        let after = &rest[start + ENV_PREFIX.len()..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| "unterminated ${ENV:...} placeholder".to_string())?;
+        let name = &after[..end];
+        if name.is_empty() {
+            return Err("empty environment variable name".to_string());
+        }
+        names.push(name);
+        rest = &after[end + 1..];
+    }
+    Ok(names)
+}
+
+/// Replace `${ENV:NAME}` placeholders with environment variable values
+fn substitute_env(header: &str, value: &str) -> Result<String, DownloadError> {
+    let mut resolved = value.to_string();
+    for name in env_placeholders(value).map_err(DownloadError::Other)? {
+        let env_value = std::env::var(name).map_err(|_| {
+            DownloadError::Other(format!(
+                "Environment variable '{}' for download header '{}' is not set",
+                name, header
+            ))
+        })?;
+        resolved = resolved.replace(&format!("{}{}}}", ENV_PREFIX, name), &env_value);
+    }
+    Ok(resolved)
+}
+
+fn is_sensitive(header: &HeaderName) -> bool {
+    SENSITIVE_HEADER_PARTS
+        .iter()
+        .any(|part| header.as_str().contains(part))
+}
+
+/// Build the headers of a download source, reading referenced environment variables.
+/// Values coming from the environment or under sensitive names are redacted in the log.
+pub fn resolve_source_headers(
+    headers: &HashMap<String, String>,
+) -> Result<HeaderMap, DownloadError> {
+    let mut resolved = HeaderMap::new();
+    for (name, value) in headers {
+        let header = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+            DownloadError::Other(format!("Invalid download header name '{}'", name))
+        })?;
+        let mut header_value = HeaderValue::from_str(&substitute_env(name, value)?).map_err(|_| {
+            DownloadError::Other(format!("Invalid value for download header '{}'", name))
+        })?;
+
+        if value.contains(ENV_PREFIX) || is_sensitive(&header) {
+            header_value.set_sensitive(true);
+            log::debug!("Download header {}: <redacted>", header);
+        } else {
+            log::debug!("Download header {}: {}", header, value);
+        }
+        resolved.insert(header, header_value);
+    }
+    Ok(resolved)
+}
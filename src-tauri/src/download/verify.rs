@@ -0,0 +1,178 @@
+// Installation verification
+// Re-hashes the installed llama-server binary and the extracted model files and compares
+// them against versions.json, to catch files corrupted on disk after installation.
+
+use super::download_utils::{calculate_sha256_with_progress, load_config};
+use super::error::DownloadError;
+use crate::paths::{get_llama_binary_path, get_model_dir, is_model_downloaded};
+use crate::types::{VerificationItem, VerifyProgress};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter};
+
+/// verify-progress is emitted after every this many bytes
+const PROGRESS_STEP_BYTES: u64 = 256 * 1024 * 1024;
+
+/// A file to verify and the hash it should have
+struct VerifyTarget {
+    item: String,
+    path: PathBuf,
+    expected: Option<String>,
+}
+
+/// Installed files to verify. Only the archives of llama.cpp are hashed in versions.json,
+/// so the binary is reported as skipped; for split models only the shard named `filename`
+/// has a configured hash.
+fn verify_targets() -> Result<Vec<VerifyTarget>, DownloadError> {
+    let mut targets = Vec::new();
+
+    let binary_path = get_llama_binary_path().map_err(|e| DownloadError::Other(e.to_string()))?;
+    if binary_path.exists() {
+        targets.push(VerifyTarget {
+            item: "llama.cpp".to_string(),
+            path: binary_path,
+            expected: None,
+        });
+    }
+
+    let config = load_config()?;
+    let mut models: Vec<_> = config.models.iter().collect();
+    models.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, model_config) in models {
+        if !is_model_downloaded(name).unwrap_or(false) {
+            continue;
+        }
+        let model_dir = get_model_dir(name).map_err(|e| DownloadError::Other(e.to_string()))?;
+        let mut gguf_files: Vec<PathBuf> = fs::read_dir(&model_dir)
+            .map_err(|e| DownloadError::io("Failed to read model directory", e))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("gguf"))
+            .collect();
+        gguf_files.sort();
+
+        let expected_gguf_sha256 = model_config
+            .gguf_sha256
+            .as_deref()
+            .filter(|h| !h.is_empty());
+        let single_file = gguf_files.len() == 1;
+        for path in gguf_files {
+            let is_configured_file =
+                path.file_name().and_then(|n| n.to_str()) == Some(model_config.filename.as_str());
+            targets.push(VerifyTarget {
+                item: name.clone(),
+                expected: expected_gguf_sha256
+                    .filter(|_| single_file || is_configured_file)
+                    .map(str::to_string),
+                path,
+            });
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Hash every target, emitting verify-progress events along the way
+fn verify_targets_blocking(app: &AppHandle, targets: Vec<VerifyTarget>) -> Vec<VerificationItem> {
+    let total: u64 = targets
+        .iter()
+        .map(|target| fs::metadata(&target.path).map(|m| m.len()).unwrap_or(0))
+        .sum();
+    let mut done = 0u64;
+    let mut report = Vec::with_capacity(targets.len());
+
+    for target in targets {
+        log::info!("Verifying {} ({:?})", target.item, target.path);
+        let path = target.path.to_string_lossy().to_string();
+        let mut last_emitted = 0u64;
+        let emit_progress = |hashed: u64| {
+            let _ = app.emit(
+                "verify-progress",
+                VerifyProgress {
+                    item: target.item.clone(),
+                    path: path.clone(),
+                    hashed: done + hashed,
+                    total,
+                    percentage: if total > 0 {
+                        (done + hashed) as f64 / total as f64 * 100.0
+                    } else {
+                        100.0
+                    },
+                },
+            );
+        };
+        emit_progress(0);
+
+        let result = calculate_sha256_with_progress(&target.path, &mut |hashed| {
+            if hashed - last_emitted >= PROGRESS_STEP_BYTES {
+                last_emitted = hashed;
+                emit_progress(hashed);
+            }
+        });
+        done += fs::metadata(&target.path).map(|m| m.len()).unwrap_or(0);
+
+        let item = match result {
+            Ok(actual) => {
+                let skipped = target.expected.is_none();
+                let ok = target
+                    .expected
+                    .as_deref()
+                    .is_some_and(|expected| expected.eq_ignore_ascii_case(&actual));
+                if !skipped && !ok {
+                    log::error!(
+                        "{} failed verification: {:?} expected {}, got {}",
+                        target.item,
+                        target.path,
+                        target.expected.as_deref().unwrap_or_default(),
+                        actual
+                    );
+                }
+                VerificationItem {
+                    item: target.item,
+                    path,
+                    expected: target.expected,
+                    actual: Some(actual),
+                    ok,
+                    skipped,
+                    error: None,
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to hash {:?}: {}", target.path, e);
+                VerificationItem {
+                    item: target.item,
+                    path,
+                    expected: target.expected,
+                    actual: None,
+                    ok: false,
+                    skipped: false,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+        report.push(item);
+    }
+
+    report
+}
+
+/// Recompute the SHA-256 of installed files and compare them against versions.json.
+/// Files without a configured hash are reported as skipped, not as failures.
+#[tauri::command]
+pub async fn verify_installation(app: AppHandle) -> Result<Vec<VerificationItem>, DownloadError> {
+    // Windows locks the binary and the mapped model file while llama-server runs
+    #[cfg(target_os = "windows")]
+    if crate::server_manager::get_status().is_ok_and(|(running, _)| running) {
+        return Err(DownloadError::Other(
+            "Stop the LLM before verifying the installation".to_string(),
+        ));
+    }
+
+    let targets = verify_targets()?;
+    log::info!("Verifying {} installed file(s)", targets.len());
+
+    // Hashing tens of GB must not block the async runtime
+    tokio::task::spawn_blocking(move || verify_targets_blocking(&app, targets))
+        .await
+        .map_err(|e| DownloadError::Other(format!("Verification task failed: {}", e)))
+}
@@ -0,0 +1,83 @@
+// Download history shared between the Tauri app and the Native Messaging Host
+// The download engine appends one entry per download to downloads_history.json,
+// so support can see when a download last ran and how it ended.
+
+use crate::paths::get_app_data_dir;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Only the most recent entries are kept
+const MAX_HISTORY_ENTRIES: usize = 100;
+
+/// Serializes read-modify-write cycles on the history file within this process
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+/// One finished (or failed) download
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadHistoryEntry {
+    /// Artifact that was downloaded (model name or llama.cpp)
+    pub item: String,
+    pub url: String,
+    /// RFC 3339 timestamps in local time
+    pub started_at: String,
+    pub finished_at: String,
+    /// Bytes on disk when the download ended
+    pub bytes: u64,
+    /// "completed", "failed" or "cancelled"
+    pub result: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Get path to the download history file
+fn get_history_path() -> Result<PathBuf> {
+    Ok(get_app_data_dir()?.join("downloads_history.json"))
+}
+
+/// Read the download history, oldest entry first
+pub fn read_download_history() -> Result<Vec<DownloadHistoryEntry>> {
+    let path = get_history_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = fs::read_to_string(&path).context("Failed to read download history")?;
+    // A corrupted history is not worth failing a download over
+    Ok(serde_json::from_str(&contents).unwrap_or_default())
+}
+
+fn write_download_history(entries: &[DownloadHistoryEntry]) -> Result<()> {
+    let path = get_history_path()?;
+    let contents =
+        serde_json::to_string_pretty(entries).context("Failed to serialize download history")?;
+    // Write then rename so the Native Host never reads a half-written file
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, contents).context("Failed to write download history")?;
+    fs::rename(&tmp_path, &path).context("Failed to replace download history")?;
+    Ok(())
+}
+
+/// Append an entry, dropping the oldest ones beyond the cap
+pub fn append_download_history(entry: DownloadHistoryEntry) -> Result<()> {
+    let _lock = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = read_download_history()?;
+    entries.push(entry);
+    let excess = entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+    entries.drain(..excess);
+    write_download_history(&entries)
+}
+
+#[tauri::command]
+pub fn get_download_history() -> Result<Vec<DownloadHistoryEntry>, String> {
+    read_download_history().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn clear_download_history() -> Result<String, String> {
+    let _lock = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    write_download_history(&[]).map_err(|e| e.to_string())?;
+    Ok("Download history cleared".to_string())
+}
@@ -2,27 +2,149 @@
 // Uses file-based state storage for cross-process communication
 
 use anyhow::{Context, Result};
+use fs4::fs_std::FileExt;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-/// IPC State stored in a JSON file
+/// Progress updates are written to the state file at most this often
+const PROGRESS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Download progress received since the last write of the state file
+static PENDING_PROGRESS: Mutex<PendingProgress> = Mutex::new(PendingProgress {
+    downloads: Vec::new(),
+    last_flush: None,
+});
+
+struct PendingProgress {
+    /// Latest progress per download ID not yet written
+    downloads: Vec<(String, Option<f64>)>,
+    last_flush: Option<Instant>,
+}
+
+impl PendingProgress {
+    fn set(&mut self, download_id: &str, progress: Option<f64>) {
+        match self.downloads.iter_mut().find(|(id, _)| id == download_id) {
+            Some(entry) => entry.1 = progress,
+            None => self.downloads.push((download_id.to_string(), progress)),
+        }
+    }
+
+    fn remove(&mut self, download_id: &str) {
+        self.downloads.retain(|(id, _)| id != download_id);
+    }
+
+    /// Apply buffered progress to downloads that are still active in `state`
+    fn apply_to(&mut self, state: &mut IpcState) {
+        for (id, progress) in self.downloads.drain(..) {
+            if let Some(entry) = state.active_downloads.get_mut(&id) {
+                *entry = progress;
+            }
+        }
+        state.download_progress = aggregate_download_progress(&state.active_downloads);
+        self.last_flush = Some(Instant::now());
+    }
+
+    /// Time to write: the interval has passed and a download moved to another whole percent
+    fn should_flush(&self, state: &IpcState) -> bool {
+        if self
+            .last_flush
+            .is_some_and(|at| at.elapsed() < PROGRESS_FLUSH_INTERVAL)
+        {
+            return false;
+        }
+        self.downloads.iter().any(|(id, progress)| {
+            let written = state.active_downloads.get(id).copied().flatten();
+            written.map(f64::floor) != progress.map(f64::floor)
+        })
+    }
+}
+
+/// A running llama-server, one per model
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerEntry {
+    /// Model the server was started with (empty for a server recorded before the model was)
+    pub model: String,
+    pub pid: u32,
+    /// Start time of the process (Unix timestamp in seconds), see `server_started_at`
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    /// Process that launched the server (Tauri app or Native Messaging Host)
+    #[serde(default)]
+    pub owner_pid: Option<u32>,
+    pub port: u16,
+    pub ctx_size: u32,
+    pub gpu_layers: u32,
+    /// Started with `--embeddings`
+    #[serde(default)]
+    pub embeddings: bool,
+    /// Address the server listens on
+    pub host: String,
+    /// Command line the server was started with, program first
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// A stop is in progress (it may take a while to exit)
+    #[serde(default)]
+    pub stopping: bool,
+}
+
+/// IPC State stored in a JSON file.
+/// The `server_*` fields describe the primary server (the first of `servers`), for
+/// readers that predate running several servers.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpcState {
+    /// Running servers, one per model
+    #[serde(default)]
+    pub servers: Vec<ServerEntry>,
+    /// Server started by start_embeddings_server, kept apart from the chat `servers`
+    #[serde(default)]
+    pub embeddings_server: Option<ServerEntry>,
     /// Server process ID if running
     pub server_pid: Option<u32>,
+    /// Start time of the server process (Unix timestamp in seconds), to tell it apart
+    /// from an unrelated process that later got the same PID
+    #[serde(default)]
+    pub server_started_at: Option<u64>,
+    /// Process that launched the server (Tauri app or Native Messaging Host)
+    #[serde(default)]
+    pub server_owner_pid: Option<u32>,
     /// Is server running
     pub server_running: bool,
     /// Is download in progress
     pub is_downloading: bool,
-    /// Current download progress percentage
+    /// Current download progress percentage (aggregate across active downloads)
     pub download_progress: Option<f64>,
+    /// Active downloads keyed by download ID, with their individual progress
+    #[serde(default)]
+    pub active_downloads: HashMap<String, Option<f64>>,
+    /// Downloads asked to stop (cancel_download) that have not finished yet; the process
+    /// running a download checks this and aborts it
+    #[serde(default)]
+    pub cancelled_downloads: Vec<String>,
     /// Server port
     pub server_port: Option<u16>,
     /// Server context size
     pub server_ctx_size: Option<u32>,
     /// Server GPU layers
     pub server_gpu_layers: Option<u32>,
+    /// Model the server was started with
+    #[serde(default)]
+    pub server_model: Option<String>,
+    /// Server was started with `--embeddings`
+    #[serde(default)]
+    pub server_embeddings: bool,
+    /// Address the server listens on
+    #[serde(default)]
+    pub server_host: Option<String>,
+    /// Command line the server was started with, program first
+    #[serde(default)]
+    pub server_args: Vec<String>,
+    /// A stop of the recorded server is in progress (it may take a while to exit)
+    #[serde(default)]
+    pub server_stopping: bool,
     /// Tauri app process ID if running
     pub tauri_app_pid: Option<u32>,
     /// Tauri app last heartbeat timestamp (Unix timestamp in seconds)
@@ -32,19 +154,84 @@ pub struct IpcState {
 impl Default for IpcState {
     fn default() -> Self {
         Self {
+            servers: Vec::new(),
+            embeddings_server: None,
             server_pid: None,
+            server_started_at: None,
+            server_owner_pid: None,
             server_running: false,
             is_downloading: false,
             download_progress: None,
+            active_downloads: HashMap::new(),
+            cancelled_downloads: Vec::new(),
             server_port: None,
             server_ctx_size: None,
             server_gpu_layers: None,
+            server_model: None,
+            server_embeddings: false,
+            server_host: None,
+            server_args: Vec::new(),
+            server_stopping: false,
             tauri_app_pid: None,
             tauri_app_heartbeat: None,
         }
     }
 }
 
+impl IpcState {
+    /// The server running `model`, or the primary server with None
+    pub fn server_for(&self, model: Option<&str>) -> Option<&ServerEntry> {
+        match model {
+            Some(model) => self.servers.iter().find(|server| server.model == model),
+            None => self.servers.first(),
+        }
+    }
+
+    /// A state file written before `servers` existed records its server only in the
+    /// `server_*` fields
+    fn adopt_legacy_server(&mut self) {
+        if !self.servers.is_empty() || !self.server_running {
+            return;
+        }
+        let Some(pid) = self.server_pid else {
+            return;
+        };
+        self.servers.push(ServerEntry {
+            model: self.server_model.clone().unwrap_or_default(),
+            pid,
+            started_at: self.server_started_at,
+            owner_pid: self.server_owner_pid,
+            port: self.server_port.unwrap_or_default(),
+            ctx_size: self.server_ctx_size.unwrap_or_default(),
+            gpu_layers: self.server_gpu_layers.unwrap_or_default(),
+            embeddings: self.server_embeddings,
+            host: self.server_host.clone().unwrap_or_default(),
+            args: self.server_args.clone(),
+            stopping: self.server_stopping,
+        });
+    }
+
+    /// Mirror the primary server into the `server_*` fields
+    fn sync_primary_server(&mut self) {
+        let primary = self.servers.first().cloned();
+        self.server_running = primary.is_some();
+        self.server_pid = primary.as_ref().map(|server| server.pid);
+        self.server_started_at = primary.as_ref().and_then(|server| server.started_at);
+        self.server_owner_pid = primary.as_ref().and_then(|server| server.owner_pid);
+        self.server_port = primary.as_ref().map(|server| server.port);
+        self.server_ctx_size = primary.as_ref().map(|server| server.ctx_size);
+        self.server_gpu_layers = primary.as_ref().map(|server| server.gpu_layers);
+        self.server_model = primary
+            .as_ref()
+            .map(|server| server.model.clone())
+            .filter(|model| !model.is_empty());
+        self.server_embeddings = primary.as_ref().is_some_and(|server| server.embeddings);
+        self.server_host = primary.as_ref().map(|server| server.host.clone());
+        self.server_stopping = primary.as_ref().is_some_and(|server| server.stopping);
+        self.server_args = primary.map(|server| server.args).unwrap_or_default();
+    }
+}
+
 /// Get path to IPC state file
 pub fn get_ipc_state_path() -> Result<PathBuf> {
     let app_data = dirs::data_dir()
@@ -57,51 +244,234 @@ pub fn get_ipc_state_path() -> Result<PathBuf> {
 
 /// Read IPC state from file
 pub fn read_ipc_state() -> Result<IpcState> {
-    let path = get_ipc_state_path()?;
-    
+    read_ipc_state_from(&get_ipc_state_path()?)
+}
+
+fn read_ipc_state_from(path: &Path) -> Result<IpcState> {
     if !path.exists() {
         return Ok(IpcState::default());
     }
     
-    let contents = fs::read_to_string(&path)
+    let contents = fs::read_to_string(path)
         .context("Failed to read IPC state file")?;
     
-    let state: IpcState = serde_json::from_str(&contents)
-        .unwrap_or_default();
+    let mut state: IpcState = serde_json::from_str(&contents).unwrap_or_else(|e| {
+        log::warn!("Invalid IPC state file, using defaults: {}", e);
+        IpcState::default()
+    });
+    state.adopt_legacy_server();
     
     Ok(state)
 }
 
-/// Write IPC state to file
-pub fn write_ipc_state(state: &IpcState) -> Result<()> {
-    let path = get_ipc_state_path()?;
+/// Take the lock that both the Tauri app and the Native Messaging Host hold while they
+/// read, change and write the state, so neither overwrites the other's changes. It is
+/// released when the returned file is dropped. Not reentrant, also within one process.
+fn lock_ipc_state(path: &Path) -> Result<File> {
+    let file =
+        File::create(path.with_extension("lock")).context("Failed to open IPC state lock file")?;
+    file.lock_exclusive().context("Failed to lock IPC state")?;
+    Ok(file)
+}
+
+/// Change the IPC state under the lock (see `lock_ipc_state`)
+pub fn update_ipc_state(update: impl FnOnce(&mut IpcState)) -> Result<()> {
+    update_ipc_state_at(&get_ipc_state_path()?, update)
+}
+
+fn update_ipc_state_at(path: &Path, update: impl FnOnce(&mut IpcState)) -> Result<()> {
+    let _lock = lock_ipc_state(path)?;
+    let mut state = read_ipc_state_from(path)?;
+    update(&mut state);
+    write_ipc_state(path, &state)
+}
+
+/// Write IPC state to file. Callers hold `lock_ipc_state`, which also keeps writers
+/// from sharing the temporary file.
+fn write_ipc_state(path: &Path, state: &IpcState) -> Result<()> {
     let contents = serde_json::to_string_pretty(state)
         .context("Failed to serialize IPC state")?;
     
-    fs::write(&path, contents)
+    // Write then rename so readers never see a truncated file
+    let tmp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    fs::write(&tmp_path, contents)
         .context("Failed to write IPC state file")?;
+    fs::rename(&tmp_path, path)
+        .context("Failed to replace IPC state file")?;
     
     Ok(())
 }
 
-/// Update server status in IPC state
-pub fn update_server_status(running: bool, pid: Option<u32>) -> Result<()> {
-    let mut state = read_ipc_state()?;
-    state.server_running = running;
-    state.server_pid = pid;
-    write_ipc_state(&state)?;
-    Ok(())
+/// Fill in the start time of a started server and the process that launched it (this one)
+fn stamp_started_server(server: &mut ServerEntry) {
+    server.started_at = Some(process_start_time(server.pid).unwrap_or_else(current_timestamp));
+    server.owner_pid = Some(std::process::id());
+    server.stopping = false;
+}
+
+/// Record a started server, replacing an earlier entry for its model. Its start time
+/// and the process that launched it (this one) are recorded with it.
+pub fn register_server(mut server: ServerEntry) -> Result<()> {
+    stamp_started_server(&mut server);
+    update_ipc_state(|state| {
+        state
+            .servers
+            .retain(|entry| entry.model != server.model && entry.pid != server.pid);
+        state.servers.push(server);
+        state.sync_primary_server();
+    })
+}
+
+/// Record a started embeddings server, with its start time and launching process
+pub fn register_embeddings_server(mut server: ServerEntry) -> Result<()> {
+    stamp_started_server(&mut server);
+    update_ipc_state(|state| state.embeddings_server = Some(server))
+}
+
+/// Mark server `pid` as being stopped, so supervisors do not take its exit for a crash
+pub fn mark_server_stopping(pid: u32) -> Result<()> {
+    update_ipc_state(|state| {
+        let servers = state
+            .servers
+            .iter_mut()
+            .chain(state.embeddings_server.as_mut());
+        for server in servers.filter(|server| server.pid == pid) {
+            server.stopping = true;
+        }
+        state.sync_primary_server();
+    })
 }
 
-/// Update download status in IPC state
-pub fn update_download_status(is_downloading: bool, progress: Option<f64>) -> Result<()> {
-    let mut state = read_ipc_state()?;
-    state.is_downloading = is_downloading;
-    state.download_progress = progress;
-    write_ipc_state(&state)?;
+/// Forget server `pid` and its config once it is stopped. Nothing changes when it is
+/// not recorded: `pid` was an untracked (orphaned) one.
+pub fn clear_server_state(pid: u32) -> Result<()> {
+    update_ipc_state(|state| {
+        state.servers.retain(|server| server.pid != pid);
+        if state
+            .embeddings_server
+            .as_ref()
+            .is_some_and(|server| server.pid == pid)
+        {
+            state.embeddings_server = None;
+        }
+        state.sync_primary_server();
+    })
+}
+
+/// Forget every server launched by process `owner_pid`
+pub fn clear_servers_owned_by(owner_pid: u32) -> Result<()> {
+    update_ipc_state(|state| {
+        state
+            .servers
+            .retain(|server| server.owner_pid != Some(owner_pid));
+        if state
+            .embeddings_server
+            .as_ref()
+            .is_some_and(|server| server.owner_pid == Some(owner_pid))
+        {
+            state.embeddings_server = None;
+        }
+        state.sync_primary_server();
+    })
+}
+
+/// Average progress of active downloads that report a percentage
+fn aggregate_download_progress(active_downloads: &HashMap<String, Option<f64>>) -> Option<f64> {
+    let known: Vec<f64> = active_downloads.values().flatten().copied().collect();
+    if known.is_empty() {
+        None
+    } else {
+        Some(known.iter().sum::<f64>() / known.len() as f64)
+    }
+}
+
+/// Update status of a single download in IPC state.
+/// `is_downloading` only flips to false once the last active download finishes.
+/// Starts and finishes are written right away; progress of a running download is
+/// buffered and written at most once per second, or with the next heartbeat.
+pub fn update_download_status(
+    download_id: &str,
+    is_downloading: bool,
+    progress: Option<f64>,
+) -> Result<()> {
+    let mut pending = PENDING_PROGRESS.lock().unwrap_or_else(|e| e.into_inner());
+    record_download_status(
+        &get_ipc_state_path()?,
+        &mut pending,
+        download_id,
+        is_downloading,
+        progress,
+    )?;
     Ok(())
 }
 
+/// Record the status in the state file at `path`, or buffer it in `pending`.
+/// Returns whether the file was written.
+fn record_download_status(
+    path: &Path,
+    pending: &mut PendingProgress,
+    download_id: &str,
+    is_downloading: bool,
+    progress: Option<f64>,
+) -> Result<bool> {
+    let _lock = lock_ipc_state(path)?;
+    let mut state = read_ipc_state_from(path)?;
+    if is_downloading {
+        // A cancelled download reports progress until it notices; it is no longer active
+        if state.cancelled_downloads.iter().any(|id| id == download_id) {
+            return Ok(false);
+        }
+        pending.set(download_id, progress);
+        if state.active_downloads.contains_key(download_id) && !pending.should_flush(&state) {
+            return Ok(false);
+        }
+        state.active_downloads.insert(download_id.to_string(), progress);
+    } else {
+        pending.remove(download_id);
+        state.active_downloads.remove(download_id);
+        state.cancelled_downloads.retain(|id| id != download_id);
+    }
+    state.is_downloading = !state.active_downloads.is_empty();
+    pending.apply_to(&mut state);
+    write_ipc_state(path, &state)?;
+    Ok(true)
+}
+
+/// Ask the download `download_id` (every active download with None) to stop, wherever it
+/// runs. It is no longer reported as active right away; returns the IDs cancelled.
+pub fn cancel_downloads(download_id: Option<&str>) -> Result<Vec<String>> {
+    let path = get_ipc_state_path()?;
+    let _lock = lock_ipc_state(&path)?;
+    let mut state = read_ipc_state_from(&path)?;
+    let cancelled: Vec<String> = state
+        .active_downloads
+        .keys()
+        .filter(|id| download_id.is_none_or(|download_id| download_id == id.as_str()))
+        .cloned()
+        .collect();
+    if cancelled.is_empty() {
+        return Ok(cancelled);
+    }
+
+    for id in &cancelled {
+        state.active_downloads.remove(id);
+        if !state.cancelled_downloads.contains(id) {
+            state.cancelled_downloads.push(id.clone());
+        }
+    }
+    state.is_downloading = !state.active_downloads.is_empty();
+    state.download_progress = aggregate_download_progress(&state.active_downloads);
+    write_ipc_state(&path, &state)?;
+    Ok(cancelled)
+}
+
+/// Whether cancel_downloads was called for the running download `download_id`
+pub fn is_download_cancelled(download_id: &str) -> bool {
+    read_ipc_state()
+        .map(|state| state.cancelled_downloads.iter().any(|id| id == download_id))
+        .unwrap_or(false)
+}
+
 /// Check if process is actually running (cross-platform)
 pub fn is_process_running(pid: u32) -> bool {
     #[cfg(unix)]
@@ -131,6 +501,44 @@ pub fn is_process_running(pid: u32) -> bool {
     }
 }
 
+/// Start times may differ by this much between the recorded and the reported value
+/// (some platforms derive them from boot time and uptime)
+const START_TIME_TOLERANCE_SECS: u64 = 2;
+
+/// Start time of a process as a Unix timestamp in seconds, None when it cannot be read
+pub fn process_start_time(pid: u32) -> Option<u64> {
+    let pid = sysinfo::Pid::from_u32(pid);
+    let mut system = sysinfo::System::new();
+    if !system.refresh_process(pid) {
+        return None;
+    }
+    system.process(pid).map(|process| process.start_time())
+}
+
+/// Whether `pid` is still the server process started at `started_at`. After a reboot or
+/// PID wraparound an unrelated process may have the same PID. Without a recorded start
+/// time (older state files), or when it cannot be read, only the PID is checked.
+pub fn is_server_process(pid: u32, started_at: Option<u64>) -> bool {
+    if !is_process_running(pid) {
+        return false;
+    }
+    match (started_at, process_start_time(pid)) {
+        (Some(expected), Some(actual)) => {
+            let matches = expected.abs_diff(actual) <= START_TIME_TOLERANCE_SECS;
+            if !matches {
+                log::warn!(
+                    "PID {} belongs to a process started at {}, not the server started at {}",
+                    pid,
+                    actual,
+                    expected
+                );
+            }
+            matches
+        }
+        _ => true,
+    }
+}
+
 /// Heartbeat timeout in seconds (if no heartbeat for this long, app is considered dead)
 pub const HEARTBEAT_TIMEOUT_SECS: u64 = 10;
 
@@ -142,22 +550,24 @@ pub fn current_timestamp() -> u64 {
         .as_secs()
 }
 
-/// Update Tauri app heartbeat (called periodically by Tauri app)
+/// Update Tauri app heartbeat (called periodically by Tauri app).
+/// Buffered download progress is written along with it.
 pub fn update_tauri_app_heartbeat(pid: u32) -> Result<()> {
-    let mut state = read_ipc_state()?;
-    state.tauri_app_pid = Some(pid);
-    state.tauri_app_heartbeat = Some(current_timestamp());
-    write_ipc_state(&state)?;
-    Ok(())
+    let mut pending = PENDING_PROGRESS.lock().unwrap_or_else(|e| e.into_inner());
+
+    update_ipc_state(|state| {
+        state.tauri_app_pid = Some(pid);
+        state.tauri_app_heartbeat = Some(current_timestamp());
+        pending.apply_to(state);
+    })
 }
 
 /// Clear Tauri app status (called when Tauri app exits)
 pub fn clear_tauri_app_status() -> Result<()> {
-    let mut state = read_ipc_state()?;
-    state.tauri_app_pid = None;
-    state.tauri_app_heartbeat = None;
-    write_ipc_state(&state)?;
-    Ok(())
+    update_ipc_state(|state| {
+        state.tauri_app_pid = None;
+        state.tauri_app_heartbeat = None;
+    })
 }
 
 /// Check if Tauri app is running based on heartbeat and PID
@@ -180,3 +590,188 @@ pub fn is_tauri_app_running() -> Result<bool> {
     Ok(is_process_running(pid))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_pending() -> PendingProgress {
+        PendingProgress {
+            downloads: Vec::new(),
+            last_flush: None,
+        }
+    }
+
+    #[test]
+    fn aggregate_progress_averages_downloads_that_report_one() {
+        let mut downloads = HashMap::new();
+        assert_eq!(aggregate_download_progress(&downloads), None);
+
+        downloads.insert("model:a".to_string(), None);
+        assert_eq!(aggregate_download_progress(&downloads), None);
+
+        downloads.insert("model:b".to_string(), Some(40.0));
+        downloads.insert("llama".to_string(), Some(80.0));
+        assert_eq!(aggregate_download_progress(&downloads), Some(60.0));
+    }
+
+    #[test]
+    fn overlapping_downloads_stay_active_until_both_finish() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ipc_state.json");
+        let mut pending = new_pending();
+
+        record_download_status(&path, &mut pending, "model:a", true, Some(0.0)).unwrap();
+        record_download_status(&path, &mut pending, "model:b", true, None).unwrap();
+        let state = read_ipc_state_from(&path).unwrap();
+        assert!(state.is_downloading);
+        assert_eq!(state.active_downloads.len(), 2);
+
+        record_download_status(&path, &mut pending, "model:a", true, Some(30.0)).unwrap();
+        record_download_status(&path, &mut pending, "model:b", true, Some(70.0)).unwrap();
+        // Progress may still be buffered; the heartbeat writes it
+        update_ipc_state_at(&path, |state| pending.apply_to(state)).unwrap();
+        let state = read_ipc_state_from(&path).unwrap();
+        assert_eq!(state.active_downloads["model:a"], Some(30.0));
+        assert_eq!(state.active_downloads["model:b"], Some(70.0));
+        assert_eq!(state.download_progress, Some(50.0));
+
+        record_download_status(&path, &mut pending, "model:a", false, None).unwrap();
+        let state = read_ipc_state_from(&path).unwrap();
+        assert!(state.is_downloading);
+        assert!(!state.active_downloads.contains_key("model:a"));
+        assert_eq!(state.download_progress, Some(70.0));
+
+        record_download_status(&path, &mut pending, "model:b", false, None).unwrap();
+        let state = read_ipc_state_from(&path).unwrap();
+        assert!(!state.is_downloading);
+        assert!(state.active_downloads.is_empty());
+        assert_eq!(state.download_progress, None);
+    }
+
+    fn state_with(download_id: &str, progress: Option<f64>) -> IpcState {
+        let mut state = IpcState::default();
+        state
+            .active_downloads
+            .insert(download_id.to_string(), progress);
+        state
+    }
+
+    #[test]
+    fn should_flush_waits_for_a_new_whole_percent() {
+        let state = state_with("model:a", Some(10.2));
+        let mut pending = new_pending();
+        assert!(!pending.should_flush(&state));
+
+        pending.set("model:a", Some(10.7));
+        assert!(!pending.should_flush(&state));
+
+        pending.set("model:a", Some(11.0));
+        assert!(pending.should_flush(&state));
+
+        let state = state_with("model:a", None);
+        pending.set("model:a", Some(0.0));
+        assert!(pending.should_flush(&state));
+    }
+
+    #[test]
+    fn should_flush_waits_for_the_interval() {
+        let state = state_with("model:a", Some(10.0));
+        let mut pending = new_pending();
+        pending.set("model:a", Some(50.0));
+
+        pending.last_flush = Some(Instant::now());
+        assert!(!pending.should_flush(&state));
+
+        pending.last_flush = Instant::now().checked_sub(PROGRESS_FLUSH_INTERVAL * 2);
+        assert!(pending.should_flush(&state));
+    }
+
+    #[test]
+    fn rapid_progress_is_written_rarely_and_always_parses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ipc_state.json");
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        // Readers do not take the lock, so every version of the file must parse
+        let reader = {
+            let path = path.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut reads = 0;
+                while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                    if let Ok(contents) = fs::read_to_string(&path) {
+                        serde_json::from_str::<IpcState>(&contents)
+                            .unwrap_or_else(|e| panic!("unparsable state file: {}", e));
+                        reads += 1;
+                    }
+                }
+                reads
+            })
+        };
+
+        let mut pending = new_pending();
+        let mut writes = 0;
+        for step in 0..1000 {
+            // Still within the interval, however long the updates take on this machine
+            pending.last_flush = Some(Instant::now());
+            let progress = Some(step as f64 / 10.0);
+            if record_download_status(&path, &mut pending, "model:a", true, progress).unwrap() {
+                writes += 1;
+            }
+        }
+        // The start is written, the progress after it waits for the interval
+        assert_eq!(writes, 1);
+
+        // Once the interval has passed, each new whole percent is written
+        for percent in 1..100 {
+            pending.last_flush = None;
+            let progress = Some(percent as f64 + 0.5);
+            assert!(
+                record_download_status(&path, &mut pending, "model:a", true, progress).unwrap()
+            );
+        }
+        record_download_status(&path, &mut pending, "model:a", false, None).unwrap();
+
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert!(reader.join().unwrap() > 0);
+        let state = read_ipc_state_from(&path).unwrap();
+        assert!(!state.is_downloading);
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ipc_state.json");
+        const THREADS: usize = 8;
+        const UPDATES: usize = 25;
+
+        let writers: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for update in 0..UPDATES {
+                        let id = format!("download:{}:{}", thread, update);
+                        update_ipc_state_at(&path, |state| {
+                            state
+                                .active_downloads
+                                .insert(id.clone(), Some(update as f64));
+                            state.cancelled_downloads.push(id);
+                            state.tauri_app_heartbeat =
+                                Some(state.tauri_app_heartbeat.unwrap_or(0) + 1);
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let state = read_ipc_state_from(&path).unwrap();
+        assert_eq!(state.active_downloads.len(), THREADS * UPDATES);
+        assert_eq!(state.cancelled_downloads.len(), THREADS * UPDATES);
+        assert_eq!(state.tauri_app_heartbeat, Some((THREADS * UPDATES) as u64));
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
@@ -7,30 +8,50 @@ use tauri::{Emitter, Manager};
 use tauri_plugin_updater::UpdaterExt;
 
 // Module declarations
-mod download;
+pub mod download;
+pub mod download_history;
 pub mod ipc_state;
 mod native_messaging;
 mod paths;
 mod server;
+pub mod server_failure;
+pub mod server_logs;
 pub mod server_manager;
 pub mod settings;
 pub mod system;
-mod types;
+pub mod types;
 
 // Re-export command functions
 use download::{
-    check_llama_version, check_model_downloaded, delete_model, download_llama_cpp,
-    download_model_by_name, list_available_models,
+    check_llama_version, check_model_downloaded, check_model_updates, delete_model,
+    diagnose_connectivity, download_all, download_llama_cpp, download_model_by_name,
+    get_available_llama_variants, get_llama_build_info, list_available_models,
+    refresh_model_catalog, repair_model, rollback_llama_cpp, set_download_credential,
+    test_download_credentials, test_proxy, test_proxy_connection, verify_installation,
+};
+use server::{
+    get_orphaned_servers, get_server_metrics, get_server_status, kill_orphaned_servers,
+    restart_server, rotate_api_key, start_embeddings_server, start_server,
+    stop_embeddings_server, stop_server, switch_model, test_inference,
 };
-use server::{get_server_status, start_server, stop_server};
 use settings::{
-    get_active_model_command, get_settings_command, set_active_model_command,
-    set_ctx_size_command, set_gpu_layers_command, set_port_command,
+    get_active_model_command, get_settings_command, set_active_model_command, set_api_key_command,
+    set_auto_restart_command, set_auto_select_port_command, set_batch_sizes_command,
+    set_bind_host_command, set_ctx_size_command, set_dns_override_command,
+    set_download_retry_policy_command, set_download_speed_limit_command,
+    set_download_stall_timeout_command, set_embeddings_mode, set_extra_server_args_command,
+    set_flash_attn_command, set_gpu_layers_command, set_max_auto_restarts_command,
+    set_max_extract_size_command, set_models_dir_command, set_parallel_slots_command,
+    set_persist_kv_cache_command, set_port_command, set_prefer_ipv4_command, set_proxy_no_proxy_command, set_proxy_url_command,
+    set_server_start_timeout_command, set_stop_grace_period_command, set_threads_command,
 };
+use download_history::{clear_download_history, get_download_history};
+use server_logs::{get_server_log_tail, get_server_logs};
 use native_messaging::{get_native_messaging_status, install_native_messaging};
 use system::{
-    clear_all_data, clear_binaries, clear_models, get_app_data_path, get_logs_path,
-    get_recommended_settings, get_system_memory_gb,
+    clear_all_data, clear_binaries, clear_kv_cache, clear_models, get_app_data_path, get_logs_path,
+    get_recommended_settings, get_storage_info, get_storage_usage, get_system_memory_gb,
+    migrate_models, refresh_gpu_detection,
 };
 use types::ServerState;
 
@@ -105,30 +126,85 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_opener::init())
         .manage(ServerState {
-            process: Mutex::new(None),
+            servers: Mutex::new(HashMap::new()),
+            embeddings: Mutex::new(None),
         })
         .invoke_handler(tauri::generate_handler![
             check_llama_version,
             download_llama_cpp,
+            get_available_llama_variants,
+            get_llama_build_info,
+            rollback_llama_cpp,
             download_model_by_name,
+            download_all,
             list_available_models,
+            refresh_model_catalog,
             check_model_downloaded,
+            check_model_updates,
             delete_model,
+            repair_model,
+            verify_installation,
+            get_download_history,
+            clear_download_history,
             get_active_model_command,
             set_active_model_command,
             get_settings_command,
             set_port_command,
             set_ctx_size_command,
             set_gpu_layers_command,
+            set_proxy_url_command,
+            set_proxy_no_proxy_command,
+            set_download_speed_limit_command,
+            set_download_retry_policy_command,
+            set_download_stall_timeout_command,
+            set_max_extract_size_command,
+            set_server_start_timeout_command,
+            set_auto_restart_command,
+            set_max_auto_restarts_command,
+            set_stop_grace_period_command,
+            set_auto_select_port_command,
+            set_embeddings_mode,
+            set_extra_server_args_command,
+            set_persist_kv_cache_command,
+            set_threads_command,
+            set_parallel_slots_command,
+            set_batch_sizes_command,
+            set_flash_attn_command,
+            set_api_key_command,
+            set_bind_host_command,
+            set_prefer_ipv4_command,
+            set_dns_override_command,
+            set_models_dir_command,
+            test_proxy,
+            test_proxy_connection,
+            diagnose_connectivity,
+            set_download_credential,
+            test_download_credentials,
             start_server,
             stop_server,
+            restart_server,
+            rotate_api_key,
             get_server_status,
+            get_server_metrics,
+            switch_model,
+            start_embeddings_server,
+            stop_embeddings_server,
+            test_inference,
+            get_orphaned_servers,
+            kill_orphaned_servers,
+            get_server_logs,
+            get_server_log_tail,
             get_app_data_path,
             get_logs_path,
             get_system_memory_gb,
+            get_storage_info,
+            get_storage_usage,
+            migrate_models,
             get_recommended_settings,
+            refresh_gpu_detection,
             clear_binaries,
             clear_models,
+            clear_kv_cache,
             clear_all_data,
             install_native_messaging,
             get_native_messaging_status,
@@ -147,8 +223,8 @@ pub fn run() {
             #[cfg(any(target_os = "macos", windows, target_os = "linux"))]
             app.handle().plugin(tauri_plugin_updater::Builder::new().build())?;
             
-            // Install native messaging manifests on startup (macOS and Windows)
-            #[cfg(any(target_os = "macos", target_os = "windows"))]
+            // Install native messaging manifests on startup (macOS, Windows and Linux)
+            #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
             {
                 if let Err(e) = native_messaging::install_native_messaging_manifests() {
                     log::warn!("Failed to install native messaging manifests: {}", e);
@@ -163,10 +239,45 @@ pub fn run() {
                     if let Err(e) = ipc_state::update_tauri_app_heartbeat(pid) {
                         log::warn!("Failed to update heartbeat: {}", e);
                     }
+                    server_logs::flush_server_logs();
                     thread::sleep(Duration::from_secs(3));
                 }
             });
             
+            // A crash of the previous session may have left its llama-server running
+            if let Err(e) = server_manager::reconcile_server_state() {
+                log::warn!("Failed to reconcile server state: {}", e);
+            }
+            // Untracked servers are left to the user (kill_orphaned_servers); the UI
+            // should also call get_orphaned_servers on load in case it missed the event
+            match server_manager::find_orphaned_servers() {
+                Ok(orphans) if !orphans.is_empty() => {
+                    log::warn!(
+                        "Found {} untracked llama-server process(es): {:?}",
+                        orphans.len(),
+                        orphans.iter().map(|orphan| orphan.pid).collect::<Vec<_>>()
+                    );
+                    if let Err(e) = app.emit("orphan-server-detected", &orphans) {
+                        log::warn!("Failed to emit orphan-server-detected event: {}", e);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to look for orphaned servers: {}", e),
+            }
+            
+            // Restart llama-server after a crash when auto_restart is enabled
+            server::spawn_server_supervisor(app.handle().clone());
+            
+            // Drop leftover archives that can no longer be resumed
+            download::cleanup_stale_downloads();
+            
+            // Refresh the signed remote model catalog in the background (no-op if not configured)
+            tauri::async_runtime::spawn(async {
+                if let Err(e) = refresh_model_catalog().await {
+                    log::warn!("{}", e);
+                }
+            });
+            
             // Check for updates on startup (desktop only)
             #[cfg(any(target_os = "macos", windows, target_os = "linux"))]
             {
@@ -196,40 +307,38 @@ pub fn run() {
                     }
                 }
             }
-            // Handle all exit scenarios - stop server before quitting
+            // Handle all exit scenarios - stop servers before quitting
             tauri::RunEvent::ExitRequested { .. } | tauri::RunEvent::Exit => {
-                log::info!("App is exiting, stopping server...");
+                log::info!("App is exiting, stopping servers...");
                 
                 // Clear Tauri app status from IPC state
                 if let Err(e) = ipc_state::clear_tauri_app_status() {
                     log::warn!("Failed to clear Tauri app status: {}", e);
                 }
                 
-                // Always update server status in IPC state first (in case we don't have the child handle)
-                // This ensures the state is cleared even if the process was started elsewhere
-                if let Err(e) = ipc_state::update_server_status(false, None) {
+                // Update server status in IPC state first (in case we don't have a child handle).
+                // Servers started by the Native Messaging Host keep running and stay recorded.
+                if let Err(e) = ipc_state::clear_servers_owned_by(std::process::id()) {
                     log::warn!("Failed to clear server status in IPC state: {}", e);
                 }
                 
-                // Get server state and stop server if running
+                // Stop every server this app started
                 if let Some(state) = app_handle.try_state::<ServerState>() {
-                    let mut process_guard = state.process.lock().unwrap();
-                    if let Some(mut child) = process_guard.take() {
-                        log::info!("Killing server process...");
+                    let mut servers = state.servers.lock().unwrap();
+                    let embeddings = state.embeddings.lock().unwrap().take();
+                    let servers = servers
+                        .drain()
+                        .chain(embeddings.map(|server| ("embeddings".to_string(), server)));
+                    for (model, mut server) in servers {
+                        log::info!("Stopping server process for {}...", model);
                         
-                        // On Unix, kill the entire process group
-                        #[cfg(unix)]
-                        {
-                            let pid = child.id() as i32;
-                            unsafe {
-                                libc::kill(-pid, libc::SIGTERM);
-                                std::thread::sleep(std::time::Duration::from_millis(100));
-                                libc::kill(-pid, libc::SIGKILL);
-                            }
+                        // Gets the grace period to exit before its process group is killed
+                        if let Err(e) = server_manager::stop_server_by_pid(server.child.id()) {
+                            log::warn!("Failed to stop server for {}: {}", model, e);
                         }
                         
-                        let _ = child.kill();
-                        let _ = child.wait();
+                        let _ = server.child.kill();
+                        let _ = server.child.wait();
                         log::info!("Server process stopped");
                     }
                 }
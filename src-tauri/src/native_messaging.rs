@@ -1,5 +1,6 @@
 // Native Messaging Host manifest installation
-// Automatically installs the manifest for Sigma browser extension
+// Automatically installs the manifest for the Sigma browser extension (Sigma and other
+// Chromium browsers, and Firefox when a Firefox extension ID is configured)
 
 use anyhow::{Context, Result};
 use serde_json::json;
@@ -9,21 +10,29 @@ use std::path::PathBuf;
 /// Extension ID for the Sigma Eclipse browser extension (loaded from .env at build time)
 const EXTENSION_ID: &str = env!("EXTENSION_ID");
 
+/// More extension IDs allowed to connect, comma separated (loaded from .env at build time)
+const EXTRA_EXTENSION_IDS: Option<&str> = option_env!("EXTENSION_IDS");
+
+/// ID of the Firefox build of the extension (e.g. `sigma-eclipse@example.com`); without
+/// it no Firefox manifest is installed
+const FIREFOX_EXTENSION_ID: Option<&str> = option_env!("FIREFOX_EXTENSION_ID");
+
 /// Native messaging host name
 const HOST_NAME: &str = "com.sigma_eclipse.host";
 
-/// Get the path to the native messaging host binary inside the app bundle
-#[cfg(target_os = "macos")]
+/// Get the path to the native messaging host binary inside the app bundle (macOS) or
+/// next to the app binary (Linux packages)
+#[cfg(any(target_os = "macos", target_os = "linux"))]
 fn get_host_binary_path() -> Result<PathBuf> {
     // Get the path to the current executable
     let exe_path = std::env::current_exe().context("Failed to get current executable path")?;
     
-    // The binary should be in the same directory (Contents/MacOS/)
-    let macos_dir = exe_path
+    // The binary should be in the same directory (Contents/MacOS/ on macOS)
+    let exe_dir = exe_path
         .parent()
-        .context("Failed to get MacOS directory")?;
+        .context("Failed to get executable directory")?;
     
-    let host_path = macos_dir.join("sigma-eclipse-host");
+    let host_path = exe_dir.join("sigma-eclipse-host");
     
     if host_path.exists() {
         Ok(host_path)
@@ -72,69 +81,194 @@ fn get_host_binary_path() -> Result<PathBuf> {
     }
 }
 
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
 fn get_host_binary_path() -> Result<PathBuf> {
     anyhow::bail!("Native messaging installation not yet supported on this platform")
 }
 
-/// Get the Sigma browser Native Messaging Hosts directory for the current user
+/// Manifest flavour: Chromium browsers list allowed origins, Firefox extension IDs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ManifestFormat {
+    Chromium,
+    Firefox,
+}
+
+/// A browser the manifest is installed for
+struct BrowserTarget {
+    /// Shown in logs and the status
+    name: &'static str,
+    format: ManifestFormat,
+    /// Directory the manifest file is written to
+    hosts_dir: PathBuf,
+    /// HKCU keys pointing at the manifest file, which is how browsers find it on Windows
+    #[cfg(target_os = "windows")]
+    registry_keys: Vec<String>,
+}
+
+impl BrowserTarget {
+    fn manifest_path(&self) -> PathBuf {
+        self.hosts_dir.join(format!("{}.json", HOST_NAME))
+    }
+}
+
+/// Browsers to install the manifest for on macOS, each with its Native Messaging Hosts
+/// directory for the current user
 #[cfg(target_os = "macos")]
-fn get_sigma_native_hosts_dir() -> Result<PathBuf> {
-    let home = dirs::home_dir().context("Failed to get home directory")?;
-    Ok(home
+fn browser_targets() -> Result<Vec<BrowserTarget>> {
+    let support = dirs::home_dir()
+        .context("Failed to get home directory")?
         .join("Library")
-        .join("Application Support")
-        .join("Sigma")
-        .join("NativeMessagingHosts"))
+        .join("Application Support");
+    let mut targets = vec![BrowserTarget {
+        name: "Sigma",
+        format: ManifestFormat::Chromium,
+        hosts_dir: support.join("Sigma").join("NativeMessagingHosts"),
+    }];
+    if FIREFOX_EXTENSION_ID.is_some() {
+        targets.push(BrowserTarget {
+            name: "Firefox",
+            format: ManifestFormat::Firefox,
+            hosts_dir: support.join("Mozilla").join("NativeMessagingHosts"),
+        });
+    }
+    Ok(targets)
 }
 
-/// Get the directory where manifest file will be stored on Windows
-/// Note: On Windows, the manifest file path is registered in Windows Registry
+/// Browsers to install the manifest for on Linux. Sigma may use Chrome's directory like
+/// it uses Chrome's registry key on Windows, so Chrome and Chromium get it too.
+#[cfg(target_os = "linux")]
+fn browser_targets() -> Result<Vec<BrowserTarget>> {
+    let home = dirs::home_dir().context("Failed to get home directory")?;
+    let config = dirs::config_dir().unwrap_or_else(|| home.join(".config"));
+    let mut targets: Vec<BrowserTarget> = [
+        ("Sigma", "Sigma"),
+        ("Google Chrome", "google-chrome"),
+        ("Chromium", "chromium"),
+    ]
+    .into_iter()
+    .map(|(name, dir)| BrowserTarget {
+        name,
+        format: ManifestFormat::Chromium,
+        hosts_dir: config.join(dir).join("NativeMessagingHosts"),
+    })
+    .collect();
+    if FIREFOX_EXTENSION_ID.is_some() {
+        targets.push(BrowserTarget {
+            name: "Firefox",
+            format: ManifestFormat::Firefox,
+            hosts_dir: home.join(".mozilla").join("native-messaging-hosts"),
+        });
+    }
+    Ok(targets)
+}
+
+/// Browsers to install the manifest for on Windows. The manifest file location is
+/// arbitrary there: browsers find it through the registry.
 #[cfg(target_os = "windows")]
-fn get_sigma_native_hosts_dir() -> Result<PathBuf> {
+fn browser_targets() -> Result<Vec<BrowserTarget>> {
     let app_data = dirs::data_local_dir()
         .context("Failed to get local app data directory")?;
-    Ok(app_data
-        .join("Sigma")
-        .join("NativeMessagingHosts"))
+    // Sigma browser may use Chrome's path or its own path
+    let mut targets = vec![BrowserTarget {
+        name: "Sigma",
+        format: ManifestFormat::Chromium,
+        hosts_dir: app_data.join("Sigma").join("NativeMessagingHosts"),
+        registry_keys: vec![
+            format!("Software\\Sigma\\NativeMessagingHosts\\{}", HOST_NAME),
+            format!("Software\\Google\\Chrome\\NativeMessagingHosts\\{}", HOST_NAME),
+        ],
+    }];
+    if FIREFOX_EXTENSION_ID.is_some() {
+        targets.push(BrowserTarget {
+            name: "Firefox",
+            format: ManifestFormat::Firefox,
+            hosts_dir: app_data.join("Mozilla").join("NativeMessagingHosts"),
+            registry_keys: vec![format!(
+                "Software\\Mozilla\\NativeMessagingHosts\\{}",
+                HOST_NAME
+            )],
+        });
+    }
+    Ok(targets)
 }
 
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
-fn get_sigma_native_hosts_dir() -> Result<PathBuf> {
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn browser_targets() -> Result<Vec<BrowserTarget>> {
     anyhow::bail!("Not supported on this platform")
 }
 
-/// Generate the manifest JSON content
-fn generate_manifest(host_binary_path: &PathBuf) -> String {
-    let manifest = json!({
+/// IDs of the Chromium extensions allowed to connect: `EXTENSION_ID` and the ones in
+/// `EXTENSION_IDS`, without duplicates
+fn extension_ids() -> Vec<&'static str> {
+    let mut ids = vec![EXTENSION_ID];
+    let extra = EXTRA_EXTENSION_IDS
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty());
+    for id in extra {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+/// Whether `id` looks like a Chromium extension ID: 32 characters from a to p
+fn is_valid_extension_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|byte| (b'a'..=b'p').contains(&byte))
+}
+
+/// Generate the manifest JSON content. Fails on an extension ID Chromium would not
+/// accept, which would otherwise only show up as the extension failing to connect.
+fn generate_manifest(host_binary_path: &PathBuf, format: ManifestFormat) -> Result<String> {
+    let mut manifest = json!({
         "name": HOST_NAME,
         "description": "Sigma Eclipse LLM Native Messaging Host",
         "path": host_binary_path.to_string_lossy(),
         "type": "stdio",
-        "allowed_origins": [
-            format!("chrome-extension://{}/", EXTENSION_ID)
-        ]
     });
+    match format {
+        ManifestFormat::Chromium => {
+            let ids = extension_ids();
+            if let Some(id) = ids.iter().find(|id| !is_valid_extension_id(id)) {
+                anyhow::bail!("Invalid extension ID: {:?}", id);
+            }
+            let origins: Vec<String> = ids
+                .iter()
+                .map(|id| format!("chrome-extension://{}/", id))
+                .collect();
+            manifest["allowed_origins"] = json!(origins);
+        }
+        ManifestFormat::Firefox => {
+            manifest["allowed_extensions"] =
+                json!(FIREFOX_EXTENSION_ID.into_iter().collect::<Vec<_>>());
+        }
+    }
     
-    serde_json::to_string_pretty(&manifest).unwrap()
+    Ok(serde_json::to_string_pretty(&manifest).unwrap())
 }
 
 /// Install the native messaging manifest for a specific browser (macOS/Linux)
 #[cfg(not(target_os = "windows"))]
-fn install_manifest_for_browser(hosts_dir: &PathBuf, host_binary_path: &PathBuf) -> Result<()> {
+fn install_manifest_for_browser(target: &BrowserTarget, host_binary_path: &PathBuf) -> Result<()> {
     // Create the directory if it doesn't exist
-    fs::create_dir_all(hosts_dir)
-        .with_context(|| format!("Failed to create directory: {:?}", hosts_dir))?;
+    fs::create_dir_all(&target.hosts_dir)
+        .with_context(|| format!("Failed to create directory: {:?}", target.hosts_dir))?;
     
     // Generate manifest content
-    let manifest_content = generate_manifest(host_binary_path);
+    let manifest_content = generate_manifest(host_binary_path, target.format)?;
     
     // Write the manifest file
-    let manifest_path = hosts_dir.join(format!("{}.json", HOST_NAME));
+    let manifest_path = target.manifest_path();
     fs::write(&manifest_path, &manifest_content)
         .with_context(|| format!("Failed to write manifest: {:?}", manifest_path))?;
     
-    log::info!("Installed native messaging manifest: {:?}", manifest_path);
+    log::info!(
+        "Installed native messaging manifest for {}: {:?}",
+        target.name,
+        manifest_path
+    );
     
     Ok(())
 }
@@ -144,35 +278,32 @@ fn install_manifest_for_browser(hosts_dir: &PathBuf, host_binary_path: &PathBuf)
 /// 1. Write the manifest JSON file
 /// 2. Register the manifest path in Windows Registry (multiple browser paths)
 #[cfg(target_os = "windows")]
-fn install_manifest_for_browser(hosts_dir: &PathBuf, host_binary_path: &PathBuf) -> Result<()> {
+fn install_manifest_for_browser(target: &BrowserTarget, host_binary_path: &PathBuf) -> Result<()> {
     use winreg::enums::*;
     use winreg::RegKey;
     
     // Create the directory if it doesn't exist
-    fs::create_dir_all(hosts_dir)
-        .with_context(|| format!("Failed to create directory: {:?}", hosts_dir))?;
+    fs::create_dir_all(&target.hosts_dir)
+        .with_context(|| format!("Failed to create directory: {:?}", target.hosts_dir))?;
     
     // Generate manifest content
-    let manifest_content = generate_manifest(host_binary_path);
+    let manifest_content = generate_manifest(host_binary_path, target.format)?;
     
     // Write the manifest file
-    let manifest_path = hosts_dir.join(format!("{}.json", HOST_NAME));
+    let manifest_path = target.manifest_path();
     fs::write(&manifest_path, &manifest_content)
         .with_context(|| format!("Failed to write manifest: {:?}", manifest_path))?;
     
-    log::info!("Installed native messaging manifest file: {:?}", manifest_path);
+    log::info!(
+        "Installed native messaging manifest file for {}: {:?}",
+        target.name,
+        manifest_path
+    );
     
     let manifest_path_str = manifest_path.to_string_lossy().to_string();
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
     
-    // Registry paths for different browsers
-    // Sigma browser may use Chrome's path or its own path
-    let registry_paths = [
-        format!("Software\\Sigma\\NativeMessagingHosts\\{}", HOST_NAME),
-        format!("Software\\Google\\Chrome\\NativeMessagingHosts\\{}", HOST_NAME),
-    ];
-    
-    for registry_path in &registry_paths {
+    for registry_path in &target.registry_keys {
         match hkcu.create_subkey(registry_path) {
             Ok((key, _)) => {
                 if let Err(e) = key.set_value("", &manifest_path_str) {
@@ -190,7 +321,7 @@ fn install_manifest_for_browser(hosts_dir: &PathBuf, host_binary_path: &PathBuf)
     Ok(())
 }
 
-/// Install native messaging manifests for Sigma browser
+/// Install native messaging manifests for every supported browser
 pub fn install_native_messaging_manifests() -> Result<()> {
     log::info!("Installing native messaging manifests...");
     
@@ -202,15 +333,9 @@ pub fn install_native_messaging_manifests() -> Result<()> {
         anyhow::bail!("Host binary not found at {:?}", host_binary_path);
     }
     
-    // Install for Sigma browser
-    match get_sigma_native_hosts_dir() {
-        Ok(sigma_dir) => {
-            if let Err(e) = install_manifest_for_browser(&sigma_dir, &host_binary_path) {
-                log::warn!("Failed to install Sigma browser manifest: {}", e);
-            }
-        }
-        Err(e) => {
-            log::warn!("Sigma browser not supported: {}", e);
+    for target in browser_targets()? {
+        if let Err(e) = install_manifest_for_browser(&target, &host_binary_path) {
+            log::warn!("Failed to install {} manifest: {}", target.name, e);
         }
     }
     
@@ -219,60 +344,67 @@ pub fn install_native_messaging_manifests() -> Result<()> {
     Ok(())
 }
 
-/// Check if native messaging is properly configured (macOS/Linux)
+/// Check whether the manifest for a browser is in place (macOS/Linux)
 #[cfg(not(target_os = "windows"))]
-pub fn check_native_messaging_status() -> Result<NativeMessagingStatus> {
-    let host_binary_path = get_host_binary_path().ok();
-    let host_exists = host_binary_path.as_ref().map(|p| p.exists()).unwrap_or(false);
-    
-    let sigma_manifest_exists = get_sigma_native_hosts_dir()
-        .map(|dir| dir.join(format!("{}.json", HOST_NAME)).exists())
-        .unwrap_or(false);
-    
-    Ok(NativeMessagingStatus {
-        host_binary_path,
-        host_exists,
-        sigma_manifest_installed: sigma_manifest_exists,
-    })
+fn is_manifest_installed(target: &BrowserTarget) -> bool {
+    target.manifest_path().exists()
 }
 
-/// Check if native messaging is properly configured (Windows)
+/// Check whether the manifest for a browser is in place (Windows)
+/// Both the file and at least one registry entry must exist for proper installation
 #[cfg(target_os = "windows")]
-pub fn check_native_messaging_status() -> Result<NativeMessagingStatus> {
+fn is_manifest_installed(target: &BrowserTarget) -> bool {
     use winreg::enums::*;
     use winreg::RegKey;
     
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    target.manifest_path().exists()
+        && target
+            .registry_keys
+            .iter()
+            .any(|key| hkcu.open_subkey(key).is_ok())
+}
+
+/// Check if native messaging is properly configured
+pub fn check_native_messaging_status() -> Result<NativeMessagingStatus> {
     let host_binary_path = get_host_binary_path().ok();
     let host_exists = host_binary_path.as_ref().map(|p| p.exists()).unwrap_or(false);
     
-    // Check if manifest file exists
-    let manifest_file_exists = get_sigma_native_hosts_dir()
-        .map(|dir| dir.join(format!("{}.json", HOST_NAME)).exists())
-        .unwrap_or(false);
-    
-    // Check if any registry key exists (Sigma or Chrome)
-    let registry_exists = {
-        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-        let sigma_path = format!("Software\\Sigma\\NativeMessagingHosts\\{}", HOST_NAME);
-        let chrome_path = format!("Software\\Google\\Chrome\\NativeMessagingHosts\\{}", HOST_NAME);
-        hkcu.open_subkey(&sigma_path).is_ok() || hkcu.open_subkey(&chrome_path).is_ok()
-    };
-    
-    // Both file and at least one registry entry must exist for proper installation
-    let sigma_manifest_installed = manifest_file_exists && registry_exists;
+    let browsers: Vec<BrowserManifestStatus> = browser_targets()
+        .unwrap_or_default()
+        .iter()
+        .map(|target| BrowserManifestStatus {
+            browser: target.name.to_string(),
+            manifest_path: target.manifest_path(),
+            installed: is_manifest_installed(target),
+        })
+        .collect();
+    let sigma_manifest_installed = browsers
+        .iter()
+        .any(|status| status.browser == "Sigma" && status.installed);
     
     Ok(NativeMessagingStatus {
         host_binary_path,
         host_exists,
         sigma_manifest_installed,
+        browsers,
     })
 }
 
+#[derive(Debug, serde::Serialize)]
+pub struct BrowserManifestStatus {
+    pub browser: String,
+    pub manifest_path: PathBuf,
+    pub installed: bool,
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct NativeMessagingStatus {
     pub host_binary_path: Option<PathBuf>,
     pub host_exists: bool,
     pub sigma_manifest_installed: bool,
+    /// One entry per browser the manifest is installed for on this platform
+    pub browsers: Vec<BrowserManifestStatus>,
 }
 
 /// Tauri command to install native messaging manifests
@@ -1,6 +1,6 @@
 use anyhow::{anyhow, Result};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[cfg(target_os = "windows")]
 use std::os::windows::ffi::OsStrExt;
@@ -57,6 +57,37 @@ pub fn get_app_data_dir() -> Result<PathBuf> {
     Ok(app_dir)
 }
 
+// Get path to logs directory (llama-server output)
+pub fn get_logs_dir() -> Result<PathBuf> {
+    let logs_dir = get_app_data_dir()?.join("logs");
+    fs::create_dir_all(&logs_dir)?;
+    Ok(logs_dir)
+}
+
+// Get path to the directory the Tauri app log (sigma-eclipse-<timestamp>.log) is written to.
+// Same location as Tauri's app_log_dir, which the native host cannot ask for.
+pub fn get_app_log_dir() -> Result<PathBuf> {
+    #[cfg(target_os = "macos")]
+    let dir = dirs::home_dir()
+        .ok_or_else(|| anyhow!("Failed to get home directory"))?
+        .join("Library")
+        .join("Logs")
+        .join("com.sigma-eclipse.llm");
+    #[cfg(not(target_os = "macos"))]
+    let dir = dirs::data_local_dir()
+        .ok_or_else(|| anyhow!("Failed to get local data directory"))?
+        .join("com.sigma-eclipse.llm")
+        .join("logs");
+    Ok(dir)
+}
+
+// Get path to the KV cache directory (llama-server --slot-save-path, one subdirectory per model)
+pub fn get_kv_cache_dir() -> Result<PathBuf> {
+    let kv_cache_dir = get_app_data_dir()?.join("kv-cache");
+    fs::create_dir_all(&kv_cache_dir)?;
+    Ok(kv_cache_dir)
+}
+
 // Get path to bin directory
 pub fn get_bin_dir() -> Result<PathBuf> {
     let app_dir = get_app_data_dir()?;
@@ -78,10 +109,37 @@ pub fn get_llama_binary_path() -> Result<PathBuf> {
     Ok(binary_path)
 }
 
-// Get path to models root directory
+// Get path to the staging directory a new llama.cpp build is extracted into before activation
+pub fn get_llama_staging_dir() -> Result<PathBuf> {
+    Ok(get_bin_dir()?.join("staging"))
+}
+
+// Get path to the directory holding the previously installed llama.cpp build (for rollback)
+pub fn get_llama_previous_dir() -> Result<PathBuf> {
+    Ok(get_bin_dir()?.join("previous"))
+}
+
+// Check if a previous llama.cpp build is available to roll back to
+pub fn has_previous_llama_build() -> bool {
+    let Ok(previous_dir) = get_llama_previous_dir() else {
+        return false;
+    };
+    ["llama-server", "llama-server.exe"]
+        .iter()
+        .any(|name| previous_dir.join(name).exists())
+}
+
+// Get path to the default models root directory inside the app data dir
+pub fn get_default_models_root_dir() -> Result<PathBuf> {
+    Ok(get_app_data_dir()?.join("models"))
+}
+
+// Get path to models root directory (the configured one, or the default)
 pub fn get_models_root_dir() -> Result<PathBuf> {
-    let app_dir = get_app_data_dir()?;
-    let models_dir = app_dir.join("models");
+    let models_dir = match crate::settings::get_models_dir()? {
+        Some(dir) => dir,
+        None => get_default_models_root_dir()?,
+    };
     fs::create_dir_all(&models_dir)?;
     Ok(models_dir)
 }
@@ -94,24 +152,214 @@ pub fn get_model_dir(model_name: &str) -> Result<PathBuf> {
     Ok(model_dir)
 }
 
+/// Split GGUF shard name "<base>-00001-of-00003.gguf" as (base, index, count)
+fn parse_gguf_shard_name(file_name: &str) -> Option<(&str, u32, u32)> {
+    let stem = file_name.strip_suffix(".gguf")?;
+    let (rest, count) = stem.rsplit_once("-of-")?;
+    let (base, index) = rest.rsplit_once('-')?;
+    if index.len() != 5 || count.len() != 5 {
+        return None;
+    }
+    let index = index.parse().ok()?;
+    let count = count.parse().ok()?;
+    (index >= 1 && index <= count).then_some((base, index, count))
+}
+
+/// File names of all shards of the split model `base` with `count` shards
+fn gguf_shard_names(base: &str, count: u32) -> impl Iterator<Item = String> + '_ {
+    (1..=count).map(move |index| format!("{}-{:05}-of-{:05}.gguf", base, index, count))
+}
+
+/// The .gguf llama-server should load: a single-file model, or the first shard of a split
+/// model (llama-server finds the others itself) when all of its shards are present
+pub fn find_model_gguf(model_dir: &Path) -> Option<PathBuf> {
+    let mut names: Vec<String> = fs::read_dir(model_dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".gguf"))
+        .collect();
+    names.sort();
+
+    for name in &names {
+        match parse_gguf_shard_name(name) {
+            None => return Some(model_dir.join(name)),
+            Some((base, 1, count)) => {
+                let missing: Vec<String> = gguf_shard_names(base, count)
+                    .filter(|shard| !names.contains(shard))
+                    .collect();
+                if missing.is_empty() {
+                    return Some(model_dir.join(name));
+                }
+                log::warn!(
+                    "Split model in {:?} is missing shards: {}",
+                    model_dir,
+                    missing.join(", ")
+                );
+            }
+            Some(_) => {}
+        }
+    }
+    None
+}
+
+/// Size of the model weights: all shards for a split model, otherwise the file itself
+pub fn gguf_model_size(model_path: &Path) -> std::io::Result<u64> {
+    let shard = model_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(parse_gguf_shard_name);
+    match (shard, model_path.parent()) {
+        (Some((base, _, count)), Some(dir)) => gguf_shard_names(base, count)
+            .map(|name| fs::metadata(dir.join(name)).map(|m| m.len()))
+            .sum(),
+        _ => Ok(fs::metadata(model_path)?.len()),
+    }
+}
+
+/// Longest metadata key read from a .gguf header; real keys are a few dozen bytes
+const MAX_GGUF_KEY_LEN: u64 = 1024;
+
+/// `<architecture>.block_count` from the metadata of a .gguf file (the first shard of a
+/// split model), None when the header does not record it
+pub fn gguf_block_count(model_path: &Path) -> std::io::Result<Option<u32>> {
+    use std::io::{BufReader, Error, ErrorKind, Read};
+
+    fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+    fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        reader.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+    fn skip(reader: &mut impl Read, len: u64) -> std::io::Result<()> {
+        let skipped = std::io::copy(&mut reader.take(len), &mut std::io::sink())?;
+        if skipped < len {
+            return Err(Error::from(ErrorKind::UnexpectedEof));
+        }
+        Ok(())
+    }
+    /// Skip a metadata value of GGUF type `value_type`
+    fn skip_value(reader: &mut impl Read, value_type: u32) -> std::io::Result<()> {
+        match value_type {
+            0 | 1 | 7 => skip(reader, 1),
+            2 | 3 => skip(reader, 2),
+            4..=6 => skip(reader, 4),
+            10..=12 => skip(reader, 8),
+            8 => {
+                let len = read_u64(reader)?;
+                skip(reader, len)
+            }
+            9 => {
+                let element_type = read_u32(reader)?;
+                let count = read_u64(reader)?;
+                for _ in 0..count {
+                    skip_value(reader, element_type)?;
+                }
+                Ok(())
+            }
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unknown GGUF value type {}", value_type),
+            )),
+        }
+    }
+
+    let mut reader = BufReader::new(fs::File::open(model_path)?);
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"GGUF" {
+        return Err(Error::new(ErrorKind::InvalidData, "Not a GGUF file"));
+    }
+    let _version = read_u32(&mut reader)?;
+    let _tensor_count = read_u64(&mut reader)?;
+    let kv_count = read_u64(&mut reader)?;
+
+    for _ in 0..kv_count {
+        let key_len = read_u64(&mut reader)?;
+        if key_len > MAX_GGUF_KEY_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "GGUF metadata key too long",
+            ));
+        }
+        let mut key = vec![0u8; key_len as usize];
+        reader.read_exact(&mut key)?;
+        let value_type = read_u32(&mut reader)?;
+        if key.ends_with(b".block_count") {
+            match value_type {
+                4 | 5 => return Ok(Some(read_u32(&mut reader)?)),
+                10 | 11 => return Ok(u32::try_from(read_u64(&mut reader)?).ok()),
+                _ => {}
+            }
+        }
+        skip_value(&mut reader, value_type)?;
+    }
+    Ok(None)
+}
+
 // Get path to model file (.gguf)
 pub fn get_model_file_path(model_name: &str) -> Result<PathBuf> {
     let model_dir = get_model_dir(model_name)?;
-    
-    // Look for any .gguf file in the model directory
-    if let Ok(entries) = fs::read_dir(&model_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("gguf") {
-                return Ok(path);
-            }
-        }
+
+    // A single-file model, or the first shard of a complete split model
+    if let Some(path) = find_model_gguf(&model_dir) {
+        return Ok(path);
     }
     
     // Fallback: if no .gguf found, return default name
     Ok(model_dir.join("model.gguf"))
 }
 
+// Get path to the marker recording the verified SHA-256 of the extracted .gguf
+pub fn get_model_verified_marker_path(model_name: &str) -> Result<PathBuf> {
+    Ok(get_model_dir(model_name)?.join(".verified"))
+}
+
+// Get path to the directory archives are extracted into before being moved into place
+pub fn get_model_staging_dir(model_name: &str) -> Result<PathBuf> {
+    Ok(get_model_dir(model_name)?.join(".staging"))
+}
+
+// Check if the extracted model passed SHA-256 verification (files may exist without it)
+pub fn is_model_verified(model_name: &str) -> Result<bool> {
+    let marker = get_model_verified_marker_path(model_name)?;
+    Ok(fs::read_to_string(marker).is_ok_and(|hash| !hash.trim().is_empty()))
+}
+
+// Get path to the file recording the config version of the extracted model
+pub fn get_model_version_path(model_name: &str) -> Result<PathBuf> {
+    Ok(get_model_dir(model_name)?.join("model-version.txt"))
+}
+
+// Read the installed model version (None for models extracted before versions were recorded)
+pub fn get_installed_model_version(model_name: &str) -> Result<Option<String>> {
+    let version_path = get_model_version_path(model_name)?;
+    Ok(fs::read_to_string(version_path)
+        .ok()
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty()))
+}
+
+/// Total size of all files under `path`.
+/// Unreadable entries (e.g. a file removed mid-walk) count as 0 instead of failing.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            Ok(file_type) if file_type.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
 // Check if model is downloaded
 pub fn is_model_downloaded(model_name: &str) -> Result<bool> {
     let model_dir = get_model_dir(model_name)?;
@@ -121,16 +369,81 @@ pub fn is_model_downloaded(model_name: &str) -> Result<bool> {
         return Ok(false);
     }
     
-    // Look for any .gguf file in the directory
-    if let Ok(entries) = fs::read_dir(&model_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("gguf") {
-                return Ok(true);
-            }
-        }
+    // A split model only counts once every shard is present
+    if find_model_gguf(&model_dir).is_none() {
+        return Ok(false);
     }
-    
-    Ok(false)
+
+    // The marker is written last, once the extracted files are in place and the archives
+    // are gone. Models installed before the marker existed count when nothing is left over
+    // from an unfinished extraction.
+    if get_model_verified_marker_path(model_name)?.exists() {
+        return Ok(true);
+    }
+    let has_leftovers = get_model_staging_dir(model_name)?.exists()
+        || fs::read_dir(&model_dir)?
+            .flatten()
+            .any(|entry| entry.path().extension().and_then(|ext| ext.to_str()) == Some("zip"));
+    Ok(!has_leftovers)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gguf_string(out: &mut Vec<u8>, value: &str) {
+        out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        out.extend_from_slice(value.as_bytes());
+    }
+
+    /// A .gguf header with a string, a string array and `qwen3.block_count`
+    fn write_gguf(path: &Path, block_count: Option<u32>) {
+        let mut out = b"GGUF".to_vec();
+        out.extend_from_slice(&3u32.to_le_bytes());
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&(2 + block_count.is_some() as u64).to_le_bytes());
+
+        gguf_string(&mut out, "general.architecture");
+        out.extend_from_slice(&8u32.to_le_bytes());
+        gguf_string(&mut out, "qwen3");
+
+        gguf_string(&mut out, "tokenizer.ggml.tokens");
+        out.extend_from_slice(&9u32.to_le_bytes());
+        out.extend_from_slice(&8u32.to_le_bytes());
+        out.extend_from_slice(&3u64.to_le_bytes());
+        for token in ["<s>", "hello", "world"] {
+            gguf_string(&mut out, token);
+        }
+
+        if let Some(block_count) = block_count {
+            gguf_string(&mut out, "qwen3.block_count");
+            out.extend_from_slice(&4u32.to_le_bytes());
+            out.extend_from_slice(&block_count.to_le_bytes());
+        }
+        fs::write(path, out).unwrap();
+    }
+
+    #[test]
+    fn block_count_is_read_from_the_gguf_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        write_gguf(&path, Some(36));
+        assert_eq!(gguf_block_count(&path).unwrap(), Some(36));
+    }
+
+    #[test]
+    fn missing_block_count_is_none() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        write_gguf(&path, None);
+        assert_eq!(gguf_block_count(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        fs::write(&path, b"PK\x03\x04 not a model").unwrap();
+        assert!(gguf_block_count(&path).is_err());
+    }
+}
@@ -1,149 +1,1077 @@
-use crate::ipc_state::update_server_status;
-use crate::server_manager::{get_status, start_server_process, stop_server_by_pid, ServerConfig};
-use crate::settings::get_server_settings;
-use crate::types::{ServerState, ServerStatus};
-use std::io::{BufRead, BufReader};
-use tauri::State;
+use crate::ipc_state::{clear_server_state, ServerEntry};
+use crate::download::check_model_selectable;
+use crate::server_failure::detect_server_failure;
+use crate::server_logs::{capture_server_output, server_output_tail, CRASH_OUTPUT_TAIL_LINES};
+use crate::server_manager::{
+    check_server_health, embeddings_server_status, fetch_server_metrics, find_embeddings_server,
+    find_orphaned_servers, find_server, reserve_restart_attempt, restart_backoff, running_servers,
+    server_address, server_exit_was_requested, start_embeddings_server_process,
+    start_server_process, stop_orphaned_servers, stop_server_and_wait, stop_server_and_wait_with,
+    stop_server_by_pid, stop_server_by_pid_with, validate_config, wait_until_ready, ServerConfig,
+    ServerReadiness, StopMode, StopOutcome, CRASH_LOOP_WINDOW, STOP_GRACE_PERIOD,
+};
+use crate::settings::{
+    generate_api_key, get_active_model, get_api_key, get_auto_restart, get_server_settings,
+    get_server_start_timeout_secs, set_active_model, set_gpu_layers, set_server_settings,
+};
+use crate::types::{
+    InferenceTestError, InferenceTestResult, ManagedServer, OrphanedServer, ServerMetrics,
+    ServerStartError, ServerState, ServerStatus, StopServerResult, StoppedServer,
+};
+use futures_util::StreamExt;
+use std::process::ExitStatus;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
 
+/// Interval between /health polls while the model loads
+const READINESS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Interval at which the supervisor checks whether the server process exited
+const SUPERVISOR_INTERVAL: Duration = Duration::from_secs(2);
+
+/// PIDs of the servers that became ready; only their crashes are restarted, not failed starts
+static SUPERVISED_PIDS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// PIDs of servers that reported a GPU out-of-memory error on stderr
+static GPU_OOM_PIDS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Lines of server output appended to a failed start's error or sent with server-crashed
+const STARTUP_ERROR_TAIL_LINES: usize = 20;
+
+/// test_inference fails when the completion takes longer than this
+const INFERENCE_TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Enough tokens to see the model generate, few enough to be quick on any hardware
+const INFERENCE_TEST_MAX_TOKENS: u32 = 16;
+
+const DEFAULT_TEST_PROMPT: &str = "Say hello in one short sentence.";
+
+/// How long to wait for the stderr reader to catch up after the server exited
+const OOM_CHECK_GRACE: Duration = Duration::from_millis(500);
+
+/// Lowercased stderr fragments printed by the CUDA, Vulkan and Metal backends when the
+/// model does not fit in VRAM; a line matches when it contains every fragment of one entry.
+/// Generic allocation failures are left out: they also mean host memory ran out, which
+/// fewer GPU layers would not fix.
+const GPU_OOM_SIGNATURES: &[&[&str]] = &[
+    &["cudamalloc failed"],
+    &["erroroutofdevicememory"],
+    // ggml_metal_buffer_type_alloc_buffer: error: failed to allocate buffer, size = ...
+    &["metal", "failed to allocate buffer"],
+];
+
+/// The model a command applies to: the one given, or the active model
+fn resolve_model(model: Option<String>) -> Result<String, String> {
+    match model {
+        Some(model) => Ok(model),
+        None => get_active_model().map_err(|e| e.to_string()),
+    }
+}
+
+/// Start llama-server for `model` (the active model when omitted) and wait until it
+/// answers /health, so the first request after this returns does not fail while the
+/// model is still loading. Servers of other models keep running.
 #[tauri::command]
 pub async fn start_server(
     state: State<'_, ServerState>,
-) -> Result<String, String> {
-    let mut process_guard = state.process.lock().unwrap();
+    app: AppHandle,
+    force: Option<bool>,
+    model: Option<String>,
+) -> Result<String, ServerStartError> {
+    let force = force.unwrap_or(false);
+    let model = resolve_model(model)?;
+    let mut gpu_layers_override = None;
 
-    // Check if local process is running
-    if let Some(ref mut child) = *process_guard {
-        match child.try_wait() {
-            Ok(None) => return Err("Server is already running".to_string()),
-            Ok(Some(_)) => {
-                *process_guard = None;
+    loop {
+        let (port, ctx_size, gpu_layers, pid) =
+            spawn_server(&state, &model, force, None, gpu_layers_override)?;
+
+        let _ = app.emit(
+            "server-starting",
+            serde_json::json!({ "port": port, "pid": pid, "model_name": model }),
+        );
+        match wait_for_server_ready(&state, &app, &model, port, pid).await {
+            Ok(()) => {
+                if gpu_layers_override.is_some() {
+                    // Remember the value that fits so the next start does not run out of VRAM again
+                    if let Err(e) = set_gpu_layers(gpu_layers) {
+                        log::warn!("Failed to save reduced GPU layers: {}", e);
+                    }
+                }
+                return Ok(format!(
+                    "Server for {} started on port {} (PID: {}, ctx: {}, gpu layers: {})",
+                    model, port, pid, ctx_size, gpu_layers
+                ));
             }
-            Err(_) => {
-                *process_guard = None;
+            Err(e) if gpu_layers > 0 && gpu_oom_detected(pid).await => {
+                let reduced = gpu_layers / 2;
+                log::warn!(
+                    "Server ran out of GPU memory with {} GPU layers, retrying with {}: {}",
+                    gpu_layers,
+                    reduced,
+                    e
+                );
+                let _ = app.emit(
+                    "gpu-layers-reduced",
+                    serde_json::json!({ "from": gpu_layers, "to": reduced }),
+                );
+                gpu_layers_override = Some(reduced);
             }
+            Err(e) => return Err(e),
         }
     }
+}
+
+/// Spawn the server process for `model` with output logging; returns
+/// (port, ctx_size, gpu_layers, pid). `port_override` and `gpu_layers_override` replace
+/// the configured values for this start only.
+fn spawn_server(
+    state: &State<'_, ServerState>,
+    model: &str,
+    force: bool,
+    port_override: Option<u16>,
+    gpu_layers_override: Option<u32>,
+) -> Result<(u16, u32, u32, u32), String> {
+    let mut servers = state.servers.lock().unwrap();
+
+    // Check if local process is running
+    if let Some(server) = servers.get_mut(model) {
+        if let Ok(None) = server.child.try_wait() {
+            return Err(format!("Server for {} is already running", model));
+        }
+        servers.remove(model);
+    }
 
     // Get settings from settings.json
     let (port, ctx_size, gpu_layers) = get_server_settings().map_err(|e| e.to_string())?;
+    let port = port_override.unwrap_or(port);
+    let gpu_layers = gpu_layers_override.unwrap_or(gpu_layers);
 
     // Use shared server manager to start process
     let config = ServerConfig {
+        model: Some(model.to_string()),
         port,
         ctx_size,
         gpu_layers,
+        force,
     };
 
-    let mut child = start_server_process(config, true).map_err(|e| e.to_string())?;
+    let (mut child, port) = start_server_process(config, true).map_err(|e| e.to_string())?;
     let pid = child.id();
 
     // Capture stdout and stderr for logging in Tauri context
-    if let Some(stdout) = child.stdout.take() {
-        std::thread::spawn(move || {
-            let reader = BufReader::new(stdout);
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    log::info!("[llama.cpp] {}", line);
-                }
+    capture_server_output(&mut child, move |line| {
+        if is_gpu_oom_line(line) {
+            GPU_OOM_PIDS.lock().unwrap().push(pid);
+        }
+    });
+
+    servers.insert(model.to_string(), ManagedServer { child, port });
+
+    Ok((port, ctx_size, gpu_layers, pid))
+}
+
+/// Whether a llama-server stderr line reports that GPU memory ran out
+fn is_gpu_oom_line(line: &str) -> bool {
+    let line = line.to_lowercase();
+    GPU_OOM_SIGNATURES
+        .iter()
+        .any(|fragments| fragments.iter().all(|fragment| line.contains(fragment)))
+}
+
+/// Whether the server with `pid` reported a GPU out-of-memory error.
+/// The stderr reader may still be draining the pipe when the exit is noticed.
+async fn gpu_oom_detected(pid: u32) -> bool {
+    let started = Instant::now();
+    loop {
+        {
+            let mut oom_pids = GPU_OOM_PIDS.lock().unwrap();
+            if let Some(index) = oom_pids.iter().position(|&oom_pid| oom_pid == pid) {
+                oom_pids.remove(index);
+                return true;
             }
-        });
+        }
+        if started.elapsed() >= OOM_CHECK_GRACE {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
     }
+}
+
+/// Append the last `max_lines` lines of output of server `pid` to an error
+fn with_output_tail(pid: u32, message: String, max_lines: usize) -> String {
+    match server_output_tail(pid, max_lines) {
+        Some(tail) => format!("{}\nLast server output:\n{}", message, tail),
+        None => message,
+    }
+}
 
-    if let Some(stderr) = child.stderr.take() {
-        std::thread::spawn(move || {
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    log::warn!("[llama.cpp] {}", line);
+/// Error for server `pid` that failed to start: `message` with the known failure found in
+/// its output first, then the last lines of output
+fn startup_error(pid: u32, message: String) -> ServerStartError {
+    let failure = detect_server_failure(Some(pid));
+    let message = match &failure {
+        Some(failure) => {
+            log::error!("Server failed to start: {:?}", failure.code);
+            format!("{}. {} ({})", failure.message, failure.remediation, message)
+        }
+        None => message,
+    };
+    ServerStartError {
+        message: with_output_tail(pid, message, STARTUP_ERROR_TAIL_LINES),
+        failure,
+    }
+}
+
+/// Poll /health until the server of `model` (process `pid`) is ready, emitting `server-loading` about once
+/// a second. Fails when the process exits during startup, or stops it when it is not
+/// ready within the configured timeout; either error explains a known llama.cpp failure
+/// and ends with the last lines of server output.
+async fn wait_for_server_ready(
+    state: &State<'_, ServerState>,
+    app: &AppHandle,
+    model: &str,
+    port: u16,
+    pid: u32,
+) -> Result<(), ServerStartError> {
+    let timeout = Duration::from_secs(get_server_start_timeout_secs().map_err(|e| e.to_string())?);
+    let started = Instant::now();
+    let mut last_emitted_secs = None;
+    log::info!("Waiting up to {:?} for the server to become ready", timeout);
+
+    loop {
+        let elapsed_secs = started.elapsed().as_secs();
+        if last_emitted_secs != Some(elapsed_secs) {
+            last_emitted_secs = Some(elapsed_secs);
+            let _ = app.emit(
+                "server-loading",
+                serde_json::json!({
+                    "port": port,
+                    "model_name": model,
+                    "elapsed_secs": elapsed_secs,
+                    "timeout_secs": timeout.as_secs(),
+                }),
+            );
+        }
+
+        // The output logging threads keep running while we wait
+        let exit = {
+            let mut servers = state.servers.lock().unwrap();
+            let exit = match servers.get_mut(model).map(|server| server.child.try_wait()) {
+                Some(Ok(None)) => None,
+                Some(Ok(Some(status))) => {
+                    Some(format!("LLM exited during startup with status: {}", status))
+                }
+                Some(Err(e)) => Some(format!("Failed to check LLM status: {}", e)),
+                None => Some("LLM was stopped during startup".to_string()),
+            };
+            if exit.is_some() {
+                if let Some(server) = servers.remove(model) {
+                    let _ = clear_server_state(server.child.id());
                 }
             }
-        });
+            exit
+        };
+        if let Some(message) = exit {
+            // Let the stderr reader catch up with the lines printed before the exit
+            tokio::time::sleep(OOM_CHECK_GRACE).await;
+            return Err(startup_error(pid, message));
+        }
+
+        if check_server_health(port).await == ServerReadiness::Ready {
+            log::info!(
+                "Server for {} ready after {:.1}s",
+                model,
+                started.elapsed().as_secs_f64()
+            );
+            if let Some(server) = state.servers.lock().unwrap().get(model) {
+                SUPERVISED_PIDS.lock().unwrap().push(server.child.id());
+            }
+            return Ok(());
+        }
+
+        if started.elapsed() >= timeout {
+            log::error!(
+                "Server for {} not ready after {:?}, stopping it",
+                model,
+                timeout
+            );
+            let server = state.servers.lock().unwrap().remove(model);
+            if let Some(mut server) = server {
+                let _ = stop_server_by_pid(server.child.id());
+                let _ = server.child.kill();
+                let _ = server.child.wait();
+            }
+            return Err(startup_error(
+                pid,
+                format!(
+                    "LLM did not become ready within {} seconds",
+                    timeout.as_secs()
+                ),
+            ));
+        }
+
+        tokio::time::sleep(READINESS_POLL_INTERVAL).await;
     }
+}
 
-    *process_guard = Some(child);
+/// Stop the servers of `model` (every server with None), wherever they were started,
+/// and wait until they are gone; returns the PID and outcome of each
+async fn stop_servers(
+    state: &State<'_, ServerState>,
+    model: Option<&str>,
+    mode: StopMode,
+) -> Result<Vec<(u32, StopOutcome)>, String> {
+    // Read before the local servers are taken out of the state, so an error leaves them tracked
+    let recorded = running_servers().map_err(|e| e.to_string())?;
+    let local: Vec<ManagedServer> = {
+        let mut servers = state.servers.lock().unwrap();
+        match model {
+            Some(model) => servers.remove(model).into_iter().collect(),
+            None => servers.drain().map(|(_, server)| server).collect(),
+        }
+    };
+    let local_pids: Vec<u32> = local.iter().map(|server| server.child.id()).collect();
 
-    Ok(format!(
-        "Server started on port {} (PID: {}, ctx: {}, gpu layers: {})",
-        port, pid, ctx_size, gpu_layers
-    ))
+    // Servers running elsewhere (e.g., via Native Host)
+    let remote: Vec<u32> = recorded
+        .into_iter()
+        .filter(|server| model.is_none() || model == Some(server.model.as_str()))
+        .map(|server| server.pid)
+        .filter(|pid| !local_pids.contains(pid))
+        .collect();
+
+    // The servers may take their grace period to exit. A failed stop does not keep the
+    // others running: every server is stopped and the failures are reported together.
+    let (stopped, errors) = tauri::async_runtime::spawn_blocking(move || {
+        let mut stopped = Vec::new();
+        let mut errors = Vec::new();
+        for mut server in local {
+            let pid = server.child.id();
+            let outcome = stop_server_by_pid_with(pid, mode);
+            // Also clean up local Child handle
+            let _ = server.child.kill();
+            let _ = server.child.wait();
+            match outcome {
+                Ok(outcome) => stopped.push((pid, outcome)),
+                Err(e) => errors.push(format!("PID {}: {}", pid, e)),
+            }
+        }
+        for pid in remote {
+            // Started by the native host, which reaps it
+            match stop_server_and_wait_with(pid, mode) {
+                Ok(outcome) => stopped.push((pid, outcome)),
+                Err(e) => errors.push(format!("PID {}: {}", pid, e)),
+            }
+        }
+        (stopped, errors)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if !errors.is_empty() {
+        return Err(format!(
+            "Failed to stop {} of {} servers: {}",
+            errors.len(),
+            errors.len() + stopped.len(),
+            errors.join("; ")
+        ));
+    }
+    Ok(stopped)
 }
 
+/// Stop the server of `model`, wherever it was started; without a model every running
+/// server is stopped. Each server gets `timeout_ms` (the stop grace period setting when
+/// omitted) to shut down on its own before it is killed; with `force` it is killed
+/// right away.
 #[tauri::command]
-pub async fn stop_server(state: State<'_, ServerState>) -> Result<String, String> {
-    let mut process_guard = state.process.lock().unwrap();
-
-    if let Some(mut child) = process_guard.take() {
-        let pid = child.id();
-        
-        // Use shared server manager to stop
-        stop_server_by_pid(pid).map_err(|e| e.to_string())?;
-        
-        // Also clean up local Child handle
+pub async fn stop_server(
+    state: State<'_, ServerState>,
+    app: AppHandle,
+    model: Option<String>,
+    force: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> Result<StopServerResult, String> {
+    let force = force.unwrap_or(false);
+    let mode = match timeout_ms {
+        _ if force => StopMode::Force,
+        Some(timeout_ms) => StopMode::Timeout(Duration::from_millis(timeout_ms)),
+        None => StopMode::Graceful,
+    };
+    let _ = app.emit(
+        "server-stopping",
+        serde_json::json!({ "model_name": model, "force": force, "timeout_ms": timeout_ms }),
+    );
+
+    let started = Instant::now();
+    let stopped = stop_servers(&state, model.as_deref(), mode).await?;
+    let message = match stopped.as_slice() {
+        [] => {
+            return Err(match model {
+                Some(model) => format!("{} is not running", model),
+                None => "LLM is not running".to_string(),
+            })
+        }
+        [(pid, outcome)] => format!("Server {} (PID: {})", outcome.description(), pid),
+        _ => format!("Stopped {} servers", stopped.len()),
+    };
+
+    Ok(StopServerResult {
+        message,
+        stopped: stopped
+            .into_iter()
+            .map(|(pid, outcome)| StoppedServer { pid, outcome })
+            .collect(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Start a llama-server that serves embeddings of `model_name` next to the chat servers,
+/// on `port` or the next free port, and wait until it answers /health
+#[tauri::command]
+pub async fn start_embeddings_server(
+    state: State<'_, ServerState>,
+    model_name: String,
+    port: u16,
+) -> Result<String, String> {
+    reap_embeddings_server(&state);
+    if state.embeddings.lock().unwrap().is_some() {
+        return Err("Embeddings server is already running".to_string());
+    }
+
+    let (mut child, port) =
+        start_embeddings_server_process(&model_name, port, true).map_err(|e| e.to_string())?;
+    let pid = child.id();
+    capture_server_output(&mut child, |_| {});
+
+    let timeout = Duration::from_secs(get_server_start_timeout_secs().map_err(|e| e.to_string())?);
+    if let Err(e) = wait_until_ready(&mut child, port, timeout).await {
+        log::error!(
+            "Embeddings server for {} failed to start: {}",
+            model_name,
+            e
+        );
+        let _ = stop_server_by_pid(pid);
         let _ = child.kill();
         let _ = child.wait();
-        
-        Ok("Server stopped".to_string())
-    } else {
-        // Check if server is running elsewhere (e.g., via Native Host)
-        if let Ok((is_running, Some(pid))) = get_status() {
-            if is_running {
-                stop_server_by_pid(pid).map_err(|e| e.to_string())?;
-                return Ok(format!("Server stopped (PID: {})", pid));
-            }
+        return Err(with_output_tail(
+            pid,
+            e.to_string(),
+            STARTUP_ERROR_TAIL_LINES,
+        ));
+    }
+
+    *state.embeddings.lock().unwrap() = Some(ManagedServer { child, port });
+    Ok(format!(
+        "Embeddings server for {} started on port {} (PID: {})",
+        model_name, port, pid
+    ))
+}
+
+/// Stop the embeddings server, wherever it was started
+#[tauri::command]
+pub async fn stop_embeddings_server(state: State<'_, ServerState>) -> Result<String, String> {
+    let local = state.embeddings.lock().unwrap().take();
+    let remote = find_embeddings_server().map_err(|e| e.to_string())?;
+
+    let (pid, outcome) = tauri::async_runtime::spawn_blocking(move || match (local, remote) {
+        (Some(mut server), _) => {
+            let pid = server.child.id();
+            let outcome = stop_server_by_pid(pid);
+            let _ = server.child.kill();
+            let _ = server.child.wait();
+            Ok(Some((pid, outcome?)))
         }
-        
-        Err("LLM is not running".to_string())
+        // Started by the native host, which reaps it
+        (None, Some(server)) => Ok(Some((server.pid, stop_server_and_wait(server.pid)?))),
+        (None, None) => Ok::<_, anyhow::Error>(None),
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?
+    .ok_or("Embeddings server is not running")?;
+    Ok(format!(
+        "Embeddings server {} (PID: {})",
+        outcome.description(),
+        pid
+    ))
+}
+
+/// Drop the embeddings server started by this app when its process is gone. It is not
+/// restarted: nothing depends on it the way the chat server's clients do.
+fn reap_embeddings_server(state: &ServerState) {
+    let mut embeddings = state.embeddings.lock().unwrap();
+    let Some(server) = embeddings.as_mut() else {
+        return;
+    };
+    let exit = match server.child.try_wait() {
+        Ok(None) => return,
+        Ok(Some(status)) => status.to_string(),
+        Err(e) => e.to_string(),
+    };
+    let pid = server.child.id();
+    log::warn!("Embeddings server (PID: {}) exited: {}", pid, exit);
+    let _ = clear_server_state(pid);
+    *embeddings = None;
+}
+
+/// Stop the server of `model` (the active model when omitted), wherever it was started,
+/// save the new settings (omitted ones stay as they are) and start it again, waiting
+/// until it is ready
+#[tauri::command]
+pub async fn restart_server(
+    state: State<'_, ServerState>,
+    app: AppHandle,
+    port: Option<u16>,
+    ctx_size: Option<u32>,
+    gpu_layers: Option<u32>,
+    model: Option<String>,
+) -> Result<String, ServerStartError> {
+    let model = resolve_model(model)?;
+    let (current_port, current_ctx_size, current_gpu_layers) =
+        get_server_settings().map_err(|e| e.to_string())?;
+    let config = ServerConfig {
+        model: Some(model.clone()),
+        port: port.unwrap_or(current_port),
+        ctx_size: ctx_size.unwrap_or(current_ctx_size),
+        gpu_layers: gpu_layers.unwrap_or(current_gpu_layers),
+        force: false,
+    };
+    // Refuse bad settings before the running server is stopped
+    validate_config(&config).map_err(|e| e.to_string())?;
+
+    stop_servers(&state, Some(&model), StopMode::Graceful).await?;
+
+    set_server_settings(config.port, config.ctx_size, config.gpu_layers)
+        .map_err(|e| e.to_string())?;
+    start_server(state, app, None, Some(model)).await
+}
+
+/// Replace the server API key with a new random one and restart the running servers so
+/// the old key stops working. The browser extension gets the new key from the native host.
+#[tauri::command]
+pub async fn rotate_api_key(
+    state: State<'_, ServerState>,
+    app: AppHandle,
+) -> Result<String, String> {
+    generate_api_key().map_err(|e| e.to_string())?;
+    log::info!("Server API key rotated");
+
+    let models: Vec<String> = running_servers()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|server| server.model)
+        .collect();
+    if models.is_empty() {
+        return Ok("API key rotated; it applies on the next server start".to_string());
+    }
+    for model in models {
+        restart_server(state.clone(), app.clone(), None, None, None, Some(model)).await?;
     }
+    Ok("API key rotated and server restarted".to_string())
 }
 
+/// Make `model_name` the active model. A running server of the previous active model is
+/// replaced by one of the new model on the same port; when that model fails to load,
+/// the previous one is restored.
 #[tauri::command]
-pub async fn get_server_status(state: State<'_, ServerState>) -> Result<ServerStatus, String> {
-    let mut process_guard = state.process.lock().unwrap();
-
-    // First check local process
-    if let Some(ref mut child) = *process_guard {
-        match child.try_wait() {
-            Ok(None) => {
-                return Ok(ServerStatus {
-                    is_running: true,
-                    message: "LLM is running".to_string(),
-                });
-            }
-            Ok(Some(status)) => {
-                *process_guard = None;
-                // Update IPC state
-                let _ = update_server_status(false, None);
-                return Ok(ServerStatus {
-                    is_running: false,
-                    message: format!("LLM exited with status: {}", status),
-                });
+pub async fn switch_model(
+    state: State<'_, ServerState>,
+    app: AppHandle,
+    model_name: String,
+) -> Result<String, String> {
+    check_model_selectable(&model_name).map_err(|e| e.to_string())?;
+    let previous_model = get_active_model().map_err(|e| e.to_string())?;
+
+    // Keep the port the server actually runs on, which auto_select_port may have changed
+    let running_port = find_server(Some(&previous_model))
+        .map_err(|e| e.to_string())?
+        .map(|server| server.port);
+
+    set_active_model(model_name.clone()).map_err(|e| e.to_string())?;
+    let Some(port) = running_port else {
+        return Ok(format!("Active model set to: {}", model_name));
+    };
+    if previous_model == model_name {
+        return Ok(format!("{} is already the active model", model_name));
+    }
+
+    let _ = app.emit(
+        "server-restarting",
+        serde_json::json!({ "from": previous_model, "to": model_name }),
+    );
+    stop_servers(&state, Some(&previous_model), StopMode::Graceful).await?;
+
+    // The new model may already have a server of its own
+    if let Ok(Some(server)) = find_server(Some(&model_name)) {
+        return Ok(format!("Switched to {} (PID: {})", model_name, server.pid));
+    }
+
+    let error = match restart_with_active_model(&state, &app, Some(port)).await {
+        Ok(pid) => return Ok(format!("Switched to {} (PID: {})", model_name, pid)),
+        Err(e) => e,
+    };
+
+    log::error!(
+        "Failed to load {}, restoring {}: {}",
+        model_name,
+        previous_model,
+        error
+    );
+    set_active_model(previous_model.clone()).map_err(|e| e.to_string())?;
+    match restart_with_active_model(&state, &app, Some(port)).await {
+        Ok(_) => Err(format!(
+            "Failed to load {}, restored {}: {}",
+            model_name, previous_model, error
+        )),
+        Err(e) => Err(format!(
+            "Failed to load {} ({}) and to restore {}: {}",
+            model_name, error, previous_model, e
+        )),
+    }
+}
+
+/// Start the server with the active model and wait until it is ready; returns its PID
+async fn restart_with_active_model(
+    state: &State<'_, ServerState>,
+    app: &AppHandle,
+    port: Option<u16>,
+) -> Result<u32, String> {
+    let model_name = get_active_model().map_err(|e| e.to_string())?;
+    let (port, _, _, pid) = spawn_server(state, &model_name, false, port, None)?;
+    wait_for_server_ready(state, app, &model_name, port, pid).await?;
+    let _ = app.emit(
+        "server-ready",
+        serde_json::json!({ "port": port, "pid": pid, "model_name": model_name }),
+    );
+    Ok(pid)
+}
+
+/// Status of a live server process, refined by llama-server's /health endpoint
+async fn running_status(server: &ServerEntry) -> ServerStatus {
+    let readiness = check_server_health(server.port).await;
+    let message = match readiness {
+        ServerReadiness::Ready => format!("LLM is running (PID: {})", server.pid),
+        _ => "LLM is starting (loading model)...".to_string(),
+    };
+    ServerStatus::running(readiness, message, server)
+}
+
+fn stopped_status(message: String) -> ServerStatus {
+    ServerStatus::stopped(message)
+}
+
+/// Take the servers started by this app whose process is gone, as (model, PID, exit status)
+fn take_exited_servers(state: &ServerState) -> Vec<(String, u32, Result<ExitStatus, String>)> {
+    let mut servers = state.servers.lock().unwrap();
+    let exited: Vec<(String, Result<ExitStatus, String>)> = servers
+        .iter_mut()
+        .filter_map(|(model, server)| match server.child.try_wait() {
+            Ok(None) => None,
+            Ok(Some(status)) => Some((model.clone(), Ok(status))),
+            Err(e) => Some((model.clone(), Err(e.to_string()))),
+        })
+        .collect();
+    exited
+        .into_iter()
+        .filter_map(|(model, exit)| {
+            let server = servers.remove(&model)?;
+            Some((model, server.child.id(), exit))
+        })
+        .collect()
+}
+
+/// Status of the server of `model`, or of the primary server when omitted, with the
+/// embeddings server if one is running
+#[tauri::command]
+pub async fn get_server_status(
+    state: State<'_, ServerState>,
+    app: AppHandle,
+    model: Option<String>,
+) -> Result<ServerStatus, String> {
+    let mut status = chat_server_status(&state, &app, model).await;
+    reap_embeddings_server(&state);
+    status.embeddings_server = embeddings_server_status().await;
+    Ok(status)
+}
+
+async fn chat_server_status(
+    state: &ServerState,
+    app: &AppHandle,
+    model: Option<String>,
+) -> ServerStatus {
+    // First check local processes; the lock must be released before the health check
+    let mut exit_message = None;
+    for (name, pid, exit) in take_exited_servers(state) {
+        let message = match exit {
+            Ok(status) => {
+                // The supervisor updates the IPC state and restarts a crashed server
+                handle_server_exit(app.clone(), name.clone(), pid, status);
+                with_output_tail(
+                    pid,
+                    format!("LLM exited with status: {}", status),
+                    CRASH_OUTPUT_TAIL_LINES,
+                )
             }
             Err(e) => {
-                *process_guard = None;
                 // Update IPC state
-                let _ = update_server_status(false, None);
-                return Ok(ServerStatus {
-                    is_running: false,
-                    message: format!("Failed to check LLM status: {}", e),
-                });
+                let _ = clear_server_state(pid);
+                format!("Failed to check LLM status: {}", e)
             }
+        };
+        if model.is_none() || model.as_deref() == Some(name.as_str()) {
+            exit_message = Some(message);
         }
     }
+    if let Some(message) = exit_message {
+        return stopped_status(message);
+    }
 
     // Check shared IPC state (may be running via Native Host)
-    match get_status() {
-        Ok((is_running, pid)) => Ok(ServerStatus {
-            is_running,
-            message: if is_running {
-                format!("LLM is running (PID: {})", pid.unwrap_or(0))
-            } else {
-                "LLM is not running".to_string()
-            },
-        }),
-        Err(e) => Ok(ServerStatus {
-            is_running: false,
-            message: format!("Failed to check status: {}", e),
-        }),
+    match find_server(model.as_deref()) {
+        Ok(Some(server)) => running_status(&server).await,
+        Ok(None) => stopped_status("LLM is not running".to_string()),
+        Err(e) => stopped_status(format!("Failed to check status: {}", e)),
     }
 }
 
+/// Send a short chat completion to the active model to check the whole pipeline, starting
+/// its server first when it is not running. Emits `inference-test-stage` with `starting`
+/// (only when the server is started), `loading` and `generating`.
+#[tauri::command]
+pub async fn test_inference(
+    state: State<'_, ServerState>,
+    app: AppHandle,
+    prompt: Option<String>,
+) -> Result<InferenceTestResult, InferenceTestError> {
+    let emit_stage = |stage: &str| {
+        let _ = app.emit(
+            "inference-test-stage",
+            serde_json::json!({ "stage": stage }),
+        );
+    };
+    let model = get_active_model().map_err(InferenceTestError::other)?;
+
+    let started_server = find_server(Some(&model))
+        .map_err(InferenceTestError::other)?
+        .is_none();
+    if started_server {
+        emit_stage("starting");
+        start_server(state.clone(), app.clone(), None, None)
+            .await
+            .map_err(|e| InferenceTestError::ServerStart {
+                message: e.message,
+                failure: e.failure,
+            })?;
+    }
+    let Some(server) = find_server(Some(&model)).map_err(InferenceTestError::other)? else {
+        return Err(InferenceTestError::ServerStart {
+            message: format!("Server for {} exited right after starting", model),
+            failure: detect_server_failure(None),
+        });
+    };
+
+    // A server started elsewhere may still be loading the model
+    emit_stage("loading");
+    let timeout =
+        Duration::from_secs(get_server_start_timeout_secs().map_err(InferenceTestError::other)?);
+    let loading_since = Instant::now();
+    while check_server_health(server.port).await != ServerReadiness::Ready {
+        if loading_since.elapsed() >= timeout {
+            return Err(InferenceTestError::Timeout {
+                message: format!(
+                    "{} did not finish loading within {} seconds",
+                    model,
+                    timeout.as_secs()
+                ),
+            });
+        }
+        tokio::time::sleep(READINESS_POLL_INTERVAL).await;
+    }
+
+    emit_stage("generating");
+    let prompt = prompt.unwrap_or_else(|| DEFAULT_TEST_PROMPT.to_string());
+    let (text, tokens, time_to_first_token, tokens_per_sec) =
+        stream_test_completion(server.port, &prompt).await?;
+    log::info!(
+        "Inference test of {}: {} tokens, first after {:?}",
+        model,
+        tokens,
+        time_to_first_token
+    );
+
+    Ok(InferenceTestResult {
+        model_name: model,
+        text,
+        started_server,
+        time_to_first_token_ms: time_to_first_token.map(|elapsed| elapsed.as_millis() as u64),
+        tokens,
+        tokens_per_sec,
+    })
+}
+
+/// A failed test request: refused connections and timeouts are told apart for the UI
+fn inference_request_error(error: reqwest::Error) -> InferenceTestError {
+    let message = error.to_string();
+    if error.is_timeout() {
+        InferenceTestError::Timeout { message }
+    } else if error.is_connect() {
+        InferenceTestError::ConnectionRefused { message }
+    } else {
+        InferenceTestError::Other { message }
+    }
+}
+
+/// POST a streamed chat completion of `prompt` to the server on `port`; returns the text,
+/// the number of tokens, the time to the first token and the generation speed (as
+/// llama-server reports it, else measured)
+async fn stream_test_completion(
+    port: u16,
+    prompt: &str,
+) -> Result<(String, u32, Option<Duration>, Option<f64>), InferenceTestError> {
+    let client = reqwest::Client::builder()
+        .timeout(INFERENCE_TEST_TIMEOUT)
+        .no_proxy()
+        .build()
+        .map_err(InferenceTestError::other)?;
+    let mut request = client
+        .post(format!(
+            "http://{}/v1/chat/completions",
+            server_address(port)
+        ))
+        .json(&serde_json::json!({
+            "messages": [{ "role": "user", "content": prompt }],
+            "max_tokens": INFERENCE_TEST_MAX_TOKENS,
+            "stream": true,
+        }));
+    if let Some(api_key) = get_api_key().map_err(InferenceTestError::other)? {
+        request = request.bearer_auth(api_key);
+    }
+
+    let started = Instant::now();
+    let response = request.send().await.map_err(inference_request_error)?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(InferenceTestError::Http {
+            status: status.as_u16(),
+            message: format!("HTTP {}: {}", status, body.trim()),
+        });
+    }
+
+    // Server-sent events: one `data: {json}` line per token, then `data: [DONE]`
+    let mut stream = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut text = String::new();
+    let mut tokens = 0u32;
+    let mut time_to_first_token = None;
+    let mut reported_speed = None;
+    while let Some(chunk) = stream.next().await {
+        buffer.extend_from_slice(&chunk.map_err(inference_request_error)?);
+        while let Some(end) = buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                continue;
+            };
+            let Ok(event) = serde_json::from_str::<serde_json::Value>(data) else {
+                continue;
+            };
+            if let Some(content) = event["choices"][0]["delta"]["content"].as_str() {
+                if !content.is_empty() {
+                    time_to_first_token.get_or_insert(started.elapsed());
+                    text.push_str(content);
+                    tokens += 1;
+                }
+            }
+            // llama-server adds its timings to the last event
+            if let Some(speed) = event["timings"]["predicted_per_second"].as_f64() {
+                reported_speed = Some(speed);
+            }
+        }
+    }
+
+    let measured_speed = time_to_first_token
+        .map(|first| started.elapsed().saturating_sub(first).as_secs_f64())
+        .filter(|&secs| tokens > 1 && secs > 0.0)
+        .map(|secs| (tokens - 1) as f64 / secs);
+    Ok((
+        text,
+        tokens,
+        time_to_first_token,
+        reported_speed.or(measured_speed),
+    ))
+}
+
+/// Slot usage, generation speed and KV cache usage of the server of `model`, or of the
+/// primary server when omitted
+#[tauri::command]
+pub async fn get_server_metrics(model: Option<String>) -> Result<ServerMetrics, String> {
+    fetch_server_metrics(model.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// llama-server processes from our bin directory that are running untracked
+#[tauri::command]
+pub async fn get_orphaned_servers() -> Result<Vec<OrphanedServer>, String> {
+    find_orphaned_servers().map_err(|e| e.to_string())
+}
+
+/// Stop every orphaned llama-server, e.g. after the UI got `orphan-server-detected`
+#[tauri::command]
+pub async fn kill_orphaned_servers() -> Result<String, String> {
+    let stopped = tauri::async_runtime::spawn_blocking(stop_orphaned_servers)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Stopped {} orphaned server process(es)",
+        stopped.len()
+    ))
+}
+
+// ============================================================================
+// Crash Supervisor
+// ============================================================================
+
+/// Watch the server processes started by this app and hand an unexpected exit to
+/// `handle_server_exit`. Processes removed by stop_server are never seen here.
+pub fn spawn_server_supervisor(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SUPERVISOR_INTERVAL);
+
+        let state = app.state::<ServerState>();
+        reap_embeddings_server(&state);
+        for (model, pid, exit) in take_exited_servers(&state) {
+            match exit {
+                Ok(status) => handle_server_exit(app.clone(), model, pid, status),
+                Err(e) => {
+                    log::warn!("Failed to check server (PID: {}): {}", pid, e);
+                    let _ = clear_server_state(pid);
+                }
+            }
+        }
+    });
+}
+
+/// Handle a reaped server process of `model`: clear its IPC state and restart it after
+/// a crash when auto_restart is enabled. Runs on its own thread because of the grace
+/// period and restart backoff.
+fn handle_server_exit(app: AppHandle, model: String, pid: u32, status: ExitStatus) {
+    std::thread::spawn(move || {
+        std::thread::sleep(STOP_GRACE_PERIOD);
+
+        // An external stop (e.g. from the native host) has already cleared the IPC state
+        if server_exit_was_requested(pid) {
+            log::info!("Server (PID: {}) was stopped: {}", pid, status);
+            return;
+        }
+        let _ = clear_server_state(pid);
+
+        let was_ready = {
+            let mut supervised = SUPERVISED_PIDS.lock().unwrap();
+            let index = supervised
+                .iter()
+                .position(|&supervised_pid| supervised_pid == pid);
+            index.map(|index| supervised.remove(index)).is_some()
+        };
+        if status.success() || !was_ready {
+            log::info!("Server (PID: {}) exited: {}", pid, status);
+            return;
+        }
+        let output_tail = server_output_tail(pid, CRASH_OUTPUT_TAIL_LINES);
+        let failure = detect_server_failure(Some(pid));
+        log::error!(
+            "Server (PID: {}) crashed: {}{}\nLast server output:\n{}",
+            pid,
+            status,
+            failure
+                .as_ref()
+                .map(|failure| format!(" ({})", failure.message))
+                .unwrap_or_default(),
+            output_tail.as_deref().unwrap_or("(none)")
+        );
+
+        let auto_restart = get_auto_restart().unwrap_or(false);
+        let attempt = if auto_restart {
+            reserve_restart_attempt()
+        } else {
+            None
+        };
+        let _ = app.emit(
+            "server-crashed",
+            serde_json::json!({
+                "pid": pid,
+                "model_name": model,
+                "exit_status": status.to_string(),
+                "output_tail": output_tail,
+                "failure": failure,
+                "will_restart": attempt.is_some(),
+            }),
+        );
+
+        if !auto_restart {
+            return;
+        }
+        let Some(attempt) = attempt else {
+            log::error!(
+                "Server crashed too often within {:?}, not restarting it again",
+                CRASH_LOOP_WINDOW
+            );
+            return;
+        };
+
+        let delay = restart_backoff(attempt);
+        log::info!("Restarting server in {:?} (attempt {})", delay, attempt);
+        std::thread::sleep(delay);
+
+        match spawn_server(&app.state::<ServerState>(), &model, false, None, None) {
+            Ok((port, _, _, new_pid)) => {
+                SUPERVISED_PIDS.lock().unwrap().push(new_pid);
+                log::info!("Server for {} restarted (PID: {})", model, new_pid);
+                let _ = app.emit(
+                    "server-restarted",
+                    serde_json::json!({
+                        "pid": new_pid,
+                        "port": port,
+                        "model_name": model,
+                        "attempt": attempt,
+                        "exit_status": status.to_string(),
+                    }),
+                );
+            }
+            Err(e) => log::error!("Failed to restart server: {}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpu_oom_lines_are_recognised() {
+        for line in [
+            "ggml_backend_cuda_buffer_type_alloc_buffer: allocating 8192.00 MiB on device 0: cudaMalloc failed: out of memory",
+            "ggml_vulkan: Device memory allocation of size 4294967296 failed. vk::Device::allocateMemory: ErrorOutOfDeviceMemory",
+            "ggml_metal_buffer_type_alloc_buffer: error: failed to allocate buffer, size = 8192.00 MiB",
+            "ggml_backend_metal_buffer_type_alloc_buffer: error: failed to allocate buffer, size = 512.00 MiB",
+        ] {
+            assert!(is_gpu_oom_line(line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn host_memory_failures_are_not_gpu_oom() {
+        for line in [
+            "ggml_backend_cpu_buffer_type_alloc_buffer: failed to allocate buffer of size 8589934592",
+            "llama_model_load: error loading model: unable to allocate CPU buffer",
+            "terminate called after throwing an instance of 'std::bad_alloc': out of memory",
+            "llama_init_from_model: failed to allocate compute buffers",
+        ] {
+            assert!(!is_gpu_oom_line(line), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+// Translation of llama-server startup failures for users
+// A bad .gguf, too little memory or a broken GPU driver all end in the same non-zero exit
+// status; the reason is only in the server output, which is matched against the messages
+// llama.cpp prints for each case.
+
+use crate::server_logs::read_server_logs;
+use crate::types::{ServerFailure, ServerFailureCode};
+
+/// Lowercased output fragments of each failure, most specific first: a model that does
+/// not fit also logs the generic "failed to load model"
+const FAILURE_SIGNATURES: &[(ServerFailureCode, &[&str])] = &[
+    (
+        ServerFailureCode::PortBindFailed,
+        &[
+            "couldn't bind",
+            "failed to bind",
+            "address already in use",
+            "only one usage of each socket address",
+        ],
+    ),
+    (
+        ServerFailureCode::OutOfMemory,
+        &[
+            "unable to allocate",
+            "failed to allocate",
+            "cudamalloc failed",
+            "out of memory",
+            "outofdevicememory",
+            "outofmemory",
+            "std::bad_alloc",
+        ],
+    ),
+    (
+        ServerFailureCode::UnsupportedModel,
+        &[
+            "unknown model architecture",
+            "unknown pre-tokenizer type",
+            "invalid ggml type",
+        ],
+    ),
+    (
+        ServerFailureCode::GpuBackendInitFailed,
+        &[
+            "failed to initialize cuda",
+            "no cuda-capable device",
+            "cuda driver version is insufficient",
+            "failed to initialize vulkan",
+            "errorincompatibledriver",
+            "errorinitializationfailed",
+            "failed to initialize the metal",
+            "ggml_metal_init: error",
+        ],
+    ),
+    (
+        ServerFailureCode::ModelFileInvalid,
+        &[
+            "failed to open gguf",
+            "invalid magic",
+            "not within the file bounds",
+            "failed to read tensor",
+            "error loading model",
+            "failed to load model",
+        ],
+    ),
+];
+
+/// The failure reported by the output of server `pid` (with None, the server whose output
+/// was written last), if it matches a known one
+pub fn detect_server_failure(pid: Option<u32>) -> Option<ServerFailure> {
+    let lines = read_server_logs(pid).ok()?;
+    classify_server_output(lines.iter().map(|line| line.message.as_str()))
+}
+
+/// The most specific known failure in `lines` of llama-server output
+pub fn classify_server_output<'a>(
+    lines: impl IntoIterator<Item = &'a str> + Clone,
+) -> Option<ServerFailure> {
+    FAILURE_SIGNATURES.iter().find_map(|(code, signatures)| {
+        let mut matching = lines.clone().into_iter().filter(|line| {
+            let line = line.to_lowercase();
+            signatures.iter().any(|signature| line.contains(signature))
+        });
+        let first = matching.next()?;
+        // Only some of the allocation errors state their size, not always the first one
+        let requested_bytes = match code {
+            ServerFailureCode::OutOfMemory => {
+                parse_allocation_size(first).or_else(|| matching.find_map(parse_allocation_size))
+            }
+            _ => None,
+        };
+        Some(describe_failure(*code, requested_bytes, first))
+    })
+}
+
+fn describe_failure(
+    code: ServerFailureCode,
+    requested_bytes: Option<u64>,
+    output_line: &str,
+) -> ServerFailure {
+    let (message, remediation) = match code {
+        ServerFailureCode::ModelFileInvalid => (
+            "The model file is missing or damaged".to_string(),
+            "Repair the model or delete and download it again.",
+        ),
+        ServerFailureCode::UnsupportedModel => (
+            "This llama.cpp version does not support the model".to_string(),
+            "Update llama.cpp, or choose another model.",
+        ),
+        ServerFailureCode::OutOfMemory => (
+            match requested_bytes {
+                Some(bytes) => format!(
+                    "Not enough memory to load the model ({:.1} GB could not be allocated)",
+                    bytes as f64 / 1_073_741_824.0
+                ),
+                None => "Not enough memory to load the model".to_string(),
+            },
+            "Lower the GPU layers or the context size, close other applications, \
+             or choose a smaller model.",
+        ),
+        ServerFailureCode::GpuBackendInitFailed => (
+            "The GPU could not be initialized".to_string(),
+            "Update the GPU driver, or set GPU layers to 0 to run on the CPU.",
+        ),
+        ServerFailureCode::PortBindFailed => (
+            "The server port is already in use".to_string(),
+            "Choose another port, enable automatic port selection, or stop the program \
+             using it.",
+        ),
+    };
+
+    ServerFailure {
+        code,
+        message,
+        remediation: remediation.to_string(),
+        requested_bytes,
+        output_line: output_line.to_string(),
+    }
+}
+
+/// Size in an allocation error: "allocating 2048.00 MiB", "buffer, size = 512.00 MiB"
+/// or "buffer of size 1342177280" (bytes)
+fn parse_allocation_size(line: &str) -> Option<u64> {
+    let tokens: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ':' || c == '=')
+        .filter(|token| !token.is_empty())
+        .collect();
+
+    tokens.iter().enumerate().find_map(|(index, token)| {
+        let value: f64 = token.parse().ok()?;
+        let multiplier = match tokens.get(index + 1).map(|unit| unit.to_lowercase()) {
+            Some(unit) if unit == "gib" || unit == "gb" => 1_073_741_824.0,
+            Some(unit) if unit == "mib" || unit == "mb" => 1_048_576.0,
+            Some(unit) if unit == "kib" || unit == "kb" => 1024.0,
+            _ if index > 0 && tokens[index - 1].eq_ignore_ascii_case("size") => 1.0,
+            _ => return None,
+        };
+        Some((value * multiplier) as u64)
+    })
+}
@@ -0,0 +1,446 @@
+// Recent llama-server output shared between the Tauri app and the Native Messaging Host
+// The process that started a server keeps its last lines in memory and mirrors them to
+// server_logs/<pid>.json (at most once per second), so the other process can return them
+// too. Each server, one per model, has its own lines.
+// Every run is also written in full to logs/llama-server-<timestamp>.log, which survives
+// crashes of the server and of the process that started it.
+
+use crate::paths::{get_app_data_dir, get_app_log_dir, get_logs_dir};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Only the most recent lines are kept
+const MAX_LOG_LINES: usize = 500;
+
+/// Server log files kept in the logs directory; older ones are deleted
+const MAX_SERVER_LOG_FILES: usize = 5;
+
+/// Lines of server output reported with a crash or an unexpected exit
+pub const CRASH_OUTPUT_TAIL_LINES: usize = 30;
+
+/// Snapshot file is written at most this often
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Output of this many servers is kept (in memory and as snapshots), so the output of
+/// one that exited stays available after others were started
+const MAX_SERVER_LOG_BUFFERS: usize = 8;
+
+/// Output of the servers this process started, oldest start first
+static SERVER_LOGS: Mutex<Vec<ServerLogBuffer>> = Mutex::new(Vec::new());
+
+struct ServerLogBuffer {
+    pid: u32,
+    lines: VecDeque<ServerLogLine>,
+    /// Lines were added since the last snapshot
+    dirty: bool,
+    last_snapshot: Option<Instant>,
+}
+
+impl ServerLogBuffer {
+    fn new(pid: u32) -> Self {
+        Self {
+            pid,
+            lines: VecDeque::new(),
+            dirty: false,
+            last_snapshot: None,
+        }
+    }
+
+    fn push(&mut self, line: &str) {
+        if self.lines.len() == MAX_LOG_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(ServerLogLine {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            level: classify_line(line),
+            message: line.to_string(),
+        });
+        self.dirty = true;
+    }
+}
+
+/// Start an empty buffer for server `pid`, replacing one of an earlier process with the
+/// same PID, and drop the oldest beyond `MAX_SERVER_LOG_BUFFERS`
+fn start_buffer(buffers: &mut Vec<ServerLogBuffer>, pid: u32) {
+    buffers.retain(|buffer| buffer.pid != pid);
+    buffers.push(ServerLogBuffer::new(pid));
+    let excess = buffers.len().saturating_sub(MAX_SERVER_LOG_BUFFERS);
+    buffers.drain(..excess);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerLogLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+/// One line of llama-server output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerLogLine {
+    /// RFC 3339 timestamp in local time
+    pub timestamp: String,
+    pub level: ServerLogLevel,
+    pub message: String,
+}
+
+/// llama.cpp writes almost everything to stderr, so the level comes from the text
+fn classify_line(line: &str) -> ServerLogLevel {
+    let lower = line.to_lowercase();
+    if lower.contains("error") || lower.contains("failed") || lower.contains("out of memory") {
+        ServerLogLevel::Error
+    } else if lower.contains("warn") {
+        ServerLogLevel::Warn
+    } else {
+        ServerLogLevel::Info
+    }
+}
+
+fn get_snapshot_dir() -> Result<PathBuf> {
+    let dir = get_app_data_dir()?.join("server_logs");
+    fs::create_dir_all(&dir).context("Failed to create server logs directory")?;
+    Ok(dir)
+}
+
+fn snapshot_path(dir: &Path, pid: u32) -> PathBuf {
+    dir.join(format!("{}.json", pid))
+}
+
+/// Snapshot files in `dir`, least recently written first
+fn snapshot_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = fs::read_dir(dir)
+        .context("Failed to read server logs directory")?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    files.sort();
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Delete all but the `keep` most recently written snapshots in `dir`
+fn prune_snapshots(dir: &Path, keep: usize) -> Result<()> {
+    let files = snapshot_files(dir)?;
+    for old in &files[..files.len().saturating_sub(keep)] {
+        if let Err(e) = fs::remove_file(old) {
+            log::warn!("Failed to delete old server log snapshot {:?}: {}", old, e);
+        }
+    }
+    Ok(())
+}
+
+fn write_snapshot(pid: u32, lines: &VecDeque<ServerLogLine>) -> Result<()> {
+    let path = snapshot_path(&get_snapshot_dir()?, pid);
+    let contents = serde_json::to_string(lines).context("Failed to serialize server logs")?;
+    // Write then rename so the other process never reads a half-written file
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, contents).context("Failed to write server logs")?;
+    fs::rename(&tmp_path, &path).context("Failed to replace server logs")?;
+    Ok(())
+}
+
+/// Record a line of output of server `pid`
+pub fn push_server_log(pid: u32, line: &str) {
+    let mut buffers = SERVER_LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(buffer) = buffers.iter_mut().find(|buffer| buffer.pid == pid) else {
+        return;
+    };
+    buffer.push(line);
+
+    let due = buffer
+        .last_snapshot
+        .is_none_or(|at| at.elapsed() >= SNAPSHOT_INTERVAL);
+    if due {
+        flush_buffer(buffer);
+    }
+}
+
+fn flush_buffer(buffer: &mut ServerLogBuffer) {
+    if !buffer.dirty {
+        return;
+    }
+    if let Err(e) = write_snapshot(buffer.pid, &buffer.lines) {
+        log::warn!("Failed to save server logs: {}", e);
+    }
+    buffer.dirty = false;
+    buffer.last_snapshot = Some(Instant::now());
+}
+
+/// Write lines not yet in the snapshot (called periodically, as output may stop mid-interval)
+pub fn flush_server_logs() {
+    let mut buffers = SERVER_LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    for buffer in buffers.iter_mut() {
+        flush_buffer(buffer);
+    }
+}
+
+/// Start collecting the output of the new server `pid`, dropping that of the oldest
+/// servers beyond `MAX_SERVER_LOG_BUFFERS`
+fn start_server_logs(pid: u32) {
+    let mut buffers = SERVER_LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    start_buffer(&mut buffers, pid);
+    let buffer = buffers.last_mut().expect("buffer was just started");
+    buffer.dirty = true;
+    flush_buffer(buffer);
+
+    match get_snapshot_dir() {
+        Ok(dir) => {
+            if let Err(e) = prune_snapshots(&dir, MAX_SERVER_LOG_BUFFERS) {
+                log::warn!("Failed to delete old server log snapshots: {}", e);
+            }
+        }
+        Err(e) => log::warn!("Failed to delete old server log snapshots: {}", e),
+    }
+}
+
+/// Recent output of server `pid`, oldest first: this process's buffer when it started
+/// the server, otherwise the snapshot written by the process that did. With None, that
+/// of the server whose output was written last.
+pub fn read_server_logs(pid: Option<u32>) -> Result<Vec<ServerLogLine>> {
+    let dir = get_snapshot_dir()?;
+    let path = match pid {
+        Some(pid) => {
+            let buffers = SERVER_LOGS.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(buffer) = buffers.iter().find(|buffer| buffer.pid == pid) {
+                return Ok(buffer.lines.iter().cloned().collect());
+            }
+            snapshot_path(&dir, pid)
+        }
+        None => match snapshot_files(&dir)?.pop() {
+            Some(path) => path,
+            None => return Ok(Vec::new()),
+        },
+    };
+
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(&path).context("Failed to read server logs")?;
+    Ok(serde_json::from_str(&contents).unwrap_or_default())
+}
+
+/// The last `max_lines` lines of output of server `pid`, for error messages after a
+/// failed start or a crash. Returns None when the server printed nothing.
+pub fn server_output_tail(pid: u32, max_lines: usize) -> Option<String> {
+    let lines = read_server_logs(Some(pid)).ok()?;
+    let tail: Vec<&str> = lines
+        .iter()
+        .skip(lines.len().saturating_sub(max_lines))
+        .map(|line| line.message.as_str())
+        .collect();
+    if tail.is_empty() {
+        None
+    } else {
+        Some(tail.join("\n"))
+    }
+}
+
+/// The server log files in `dir`, oldest first (their names sort by start time)
+fn server_log_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .context("Failed to read logs directory")?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("llama-server-") && name.ends_with(".log"))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Create the log file for a new server run and delete all but the newest
+/// `MAX_SERVER_LOG_FILES`
+fn create_server_log_file() -> Result<(PathBuf, File)> {
+    let dir = get_logs_dir()?;
+    let path = dir.join(format!(
+        "llama-server-{}.log",
+        chrono::Local::now().format("%Y%m%d-%H%M%S-%3f")
+    ));
+    let file = File::create(&path).with_context(|| format!("Failed to create {:?}", path))?;
+
+    let files = server_log_files(&dir)?;
+    for old in &files[..files.len().saturating_sub(MAX_SERVER_LOG_FILES)] {
+        if let Err(e) = fs::remove_file(old) {
+            log::warn!("Failed to delete old server log {:?}: {}", old, e);
+        }
+    }
+    Ok((path, file))
+}
+
+fn write_log_file_line(file: &Option<Arc<Mutex<File>>>, line: &str) {
+    if let Some(file) = file {
+        let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        let _ = writeln!(file, "[{}] {}", timestamp, line);
+    }
+}
+
+/// The last `max_lines` lines of the newest server log file (empty when there is none)
+pub fn read_server_log_tail(max_lines: usize) -> Result<Vec<String>> {
+    let Some(path) = server_log_files(&get_logs_dir()?)?.pop() else {
+        return Ok(Vec::new());
+    };
+    read_log_file_tail(&path, max_lines)
+}
+
+/// Directory of the llama-server log files
+pub fn server_logs_dir() -> Result<PathBuf> {
+    get_logs_dir()
+}
+
+/// The newest log file of the Tauri app, None when the app never ran
+pub fn latest_app_log_file() -> Option<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(get_app_log_dir().ok()?)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("sigma-eclipse-") && name.ends_with(".log"))
+        })
+        .collect();
+    // Names carry the start time, so the last one is the current or latest session
+    files.sort();
+    files.pop()
+}
+
+/// The last `max_lines` lines of a log file
+pub fn read_log_file_tail(path: &Path, max_lines: usize) -> Result<Vec<String>> {
+    let contents = fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let contents = String::from_utf8_lossy(&contents);
+    let lines: Vec<&str> = contents.lines().collect();
+    Ok(lines[lines.len().saturating_sub(max_lines)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect())
+}
+
+/// Read the piped stdout/stderr of a server process on background threads, writing each
+/// line to a new server log file and keeping it in the buffer of that server.
+/// `on_stderr_line` sees every stderr line first.
+pub fn capture_server_output(child: &mut Child, on_stderr_line: impl Fn(&str) + Send + 'static) {
+    let pid = child.id();
+    start_server_logs(pid);
+
+    let log_file = match create_server_log_file() {
+        Ok((path, file)) => {
+            log::info!("Writing llama-server output to {:?}", path);
+            Some(Arc::new(Mutex::new(file)))
+        }
+        Err(e) => {
+            log::warn!("Failed to create server log file: {}", e);
+            None
+        }
+    };
+
+    if let Some(stdout) = child.stdout.take() {
+        let log_file = log_file.clone();
+        std::thread::spawn(move || {
+            let reader = BufReader::new(stdout);
+            for line in reader.lines().map_while(|line| line.ok()) {
+                write_log_file_line(&log_file, &line);
+                push_server_log(pid, &line);
+            }
+        });
+    }
+
+    if let Some(stderr) = child.stderr.take() {
+        std::thread::spawn(move || {
+            let reader = BufReader::new(stderr);
+            for line in reader.lines().map_while(|line| line.ok()) {
+                on_stderr_line(&line);
+                write_log_file_line(&log_file, &line);
+                push_server_log(pid, &line);
+            }
+        });
+    }
+}
+
+/// Recent output of server `pid`, or of the server whose output was written last
+#[tauri::command]
+pub fn get_server_logs(pid: Option<u32>) -> Result<Vec<ServerLogLine>, String> {
+    read_server_logs(pid).map_err(|e| e.to_string())
+}
+
+/// The last `lines` lines of the newest llama-server log file
+#[tauri::command]
+pub fn get_server_log_tail(lines: u32) -> Result<Vec<String>, String> {
+    read_server_log_tail(lines as usize).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(buffers: &[ServerLogBuffer], pid: u32) -> Vec<String> {
+        let buffer = buffers.iter().find(|buffer| buffer.pid == pid).unwrap();
+        buffer
+            .lines
+            .iter()
+            .map(|line| line.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn servers_keep_their_own_output() {
+        let mut buffers = Vec::new();
+        start_buffer(&mut buffers, 100);
+        start_buffer(&mut buffers, 200);
+        for (pid, line) in [(100, "a1"), (200, "b1"), (100, "a2"), (200, "b2")] {
+            let buffer = buffers.iter_mut().find(|buffer| buffer.pid == pid).unwrap();
+            buffer.push(line);
+        }
+
+        assert_eq!(messages(&buffers, 100), ["a1", "a2"]);
+        assert_eq!(messages(&buffers, 200), ["b1", "b2"]);
+
+        // A new process with a reused PID starts out empty, the other server keeps its lines
+        start_buffer(&mut buffers, 100);
+        assert!(messages(&buffers, 100).is_empty());
+        assert_eq!(messages(&buffers, 200), ["b1", "b2"]);
+    }
+
+    #[test]
+    fn only_the_latest_servers_are_kept() {
+        let mut buffers = Vec::new();
+        for pid in 0..MAX_SERVER_LOG_BUFFERS as u32 + 3 {
+            start_buffer(&mut buffers, pid);
+        }
+        let pids: Vec<u32> = buffers.iter().map(|buffer| buffer.pid).collect();
+        assert_eq!(
+            pids,
+            (3..MAX_SERVER_LOG_BUFFERS as u32 + 3).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn old_snapshots_are_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        for pid in [10, 20, 30] {
+            fs::write(snapshot_path(dir.path(), pid), "[]").unwrap();
+            // Modification times must differ for the order to be defined
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        prune_snapshots(dir.path(), 2).unwrap();
+
+        assert_eq!(
+            snapshot_files(dir.path()).unwrap(),
+            [snapshot_path(dir.path(), 20), snapshot_path(dir.path(), 30)]
+        );
+        assert!(dir.path().join("notes.txt").exists());
+    }
+}
@@ -1,75 +1,494 @@
 // Shared server management logic
 // Used by both Tauri commands and Native Messaging Host
 
-use crate::ipc_state::{is_process_running, read_ipc_state, update_server_status};
-use crate::paths::{get_llama_binary_path, get_model_file_path, get_short_path};
-use crate::settings::get_active_model;
+use crate::ipc_state::{
+    clear_server_state, is_process_running, is_server_process, mark_server_stopping,
+    read_ipc_state, register_embeddings_server, register_server, ServerEntry,
+};
+use crate::paths::{
+    get_bin_dir, get_kv_cache_dir, get_llama_binary_path, get_model_file_path, get_short_path,
+    has_previous_llama_build,
+};
+use crate::settings::{
+    get_active_model, get_api_key, get_auto_select_port, get_batch_sizes, get_bind_host,
+    get_embeddings, get_extra_server_args, get_flash_attn, get_max_auto_restarts,
+    get_parallel_slots, get_persist_kv_cache, get_server_settings, get_stop_grace_period,
+    get_threads,
+};
+use crate::system::{check_model_fits_in_memory, logical_cpu_count, recommended_thread_count};
+use crate::types::{EmbeddingsServerStatus, OrphanedServer, ServerMetrics};
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener};
 use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a /health request may take before the server counts as not ready
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// stop_server_by_pid marks the stop in IPC state before signalling the server and
+/// clears the state once it is gone; waiting this long tells an external stop from a crash
+pub const STOP_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// Window in which automatic restarts count toward `max_auto_restarts`
+pub const CRASH_LOOP_WINDOW: Duration = Duration::from_secs(300);
+
+/// Times of recent automatic restarts by this process, for crash-loop detection
+static RECENT_RESTARTS: Mutex<Vec<Instant>> = Mutex::new(Vec::new());
+
+/// How long a restart waits for the old server process to disappear
+const SERVER_EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many ports above the configured one are tried when it is taken
+const PORT_SCAN_RANGE: u16 = 100;
+
+/// Context of the embeddings server: it embeds passages, not conversations
+const EMBEDDINGS_CTX_SIZE: u32 = 2048;
+
+/// Interval between /health polls while a model loads
+const READINESS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long llama-server gets to exit before it is killed, when the setting cannot be read
+const DEFAULT_STOP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// How often a stopping server is checked for having exited
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Job objects of the servers started by this process, as (pid, job handle). A job kills
+/// its server and the server's descendants when the handle is closed, so it stays open
+/// until stop (or until this process exits).
+#[cfg(windows)]
+static SERVER_JOBS: std::sync::Mutex<Vec<(u32, usize)>> = std::sync::Mutex::new(Vec::new());
+
+/// Attaching to another console changes process-wide state
+#[cfg(windows)]
+static CONSOLE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Whether llama-server is actually serving requests, not just alive as a process
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerReadiness {
+    #[default]
+    NotRunning,
+    /// Process is alive but still loading the model (or not answering)
+    Starting,
+    Ready,
+}
+
+impl ServerReadiness {
+    /// Same name as the serialized form, for hand-built JSON responses
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServerReadiness::NotRunning => "not_running",
+            ServerReadiness::Starting => "starting",
+            ServerReadiness::Ready => "ready",
+        }
+    }
+}
+
+/// How a stopped server went away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopOutcome {
+    /// Exited on its own within the grace period
+    Graceful,
+    /// Ignored the stop request and was killed
+    ForceKilled,
+}
+
+impl StopOutcome {
+    pub fn description(&self) -> &'static str {
+        match self {
+            StopOutcome::Graceful => "stopped gracefully",
+            StopOutcome::ForceKilled => "force killed",
+        }
+    }
+}
+
+/// How long a stop lets the server shut down on its own before it is killed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StopMode {
+    /// The stop_grace_period_secs setting
+    #[default]
+    Graceful,
+    /// A grace period chosen by the caller
+    Timeout(Duration),
+    /// Kill right away, for a server that hangs
+    Force,
+}
+
+/// Arguments set from `ServerConfig` and the active model, not allowed in `extra_server_args`
+const MANAGED_SERVER_ARGS: &[&str] = &[
+    "-m",
+    "--model",
+    "--port",
+    "-c",
+    "--ctx-size",
+    "-ngl",
+    "--gpu-layers",
+    "--n-gpu-layers",
+    "-t",
+    "--threads",
+    "-np",
+    "--parallel",
+    "--api-key",
+    "--api-key-file",
+    "--host",
+    "--embedding",
+    "--embeddings",
+    "--slot-save-path",
+];
+
+/// Smallest context a parallel slot may get (ctx_size is split evenly between slots)
+pub const MIN_SLOT_CTX_SIZE: u32 = 2048;
 
 /// Configuration for starting the server
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
+    /// Model to serve; None for the active model
+    pub model: Option<String>,
+    /// Port to start on; an instance started next to other servers takes the next free one
     pub port: u16,
     pub ctx_size: u32,
     pub gpu_layers: u32,
+    /// Start even when the model is estimated not to fit in memory
+    pub force: bool,
 }
 
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
+            model: None,
             port: 10345,
             ctx_size: 8192,
             gpu_layers: 0,
+            force: false,
         }
     }
 }
 
+/// Context sizes accepted from settings
+pub const CTX_SIZE_RANGE: std::ops::RangeInclusive<u32> = 6000..=100000;
+
+/// GPU layers accepted from settings; 41 offloads every layer of the bundled models
+pub const GPU_LAYERS_RANGE: std::ops::RangeInclusive<u32> = 0..=41;
+
+/// Ports accepted from settings; lower ones need administrator rights on Unix
+pub const PORT_RANGE: std::ops::RangeInclusive<u16> = 1024..=65535;
+
 /// Validate server configuration
 pub fn validate_config(config: &ServerConfig) -> Result<()> {
-    if config.ctx_size < 6000 || config.ctx_size > 100000 {
-        anyhow::bail!("Context size must be between 6000 and 100000");
+    if !CTX_SIZE_RANGE.contains(&config.ctx_size) {
+        anyhow::bail!(
+            "Context size must be between {} and {}",
+            CTX_SIZE_RANGE.start(),
+            CTX_SIZE_RANGE.end()
+        );
+    }
+
+    if !GPU_LAYERS_RANGE.contains(&config.gpu_layers) {
+        anyhow::bail!(
+            "GPU layers must be between {} and {}",
+            GPU_LAYERS_RANGE.start(),
+            GPU_LAYERS_RANGE.end()
+        );
     }
 
-    if config.gpu_layers > 41 {
-        anyhow::bail!("GPU layers must be between 0 and 41");
+    if !PORT_RANGE.contains(&config.port) {
+        anyhow::bail!(
+            "Port must be between {} and {}",
+            PORT_RANGE.start(),
+            PORT_RANGE.end()
+        );
     }
 
     Ok(())
 }
 
-/// Check if server is already running via IPC state
-pub fn check_server_running() -> Result<Option<u32>> {
+/// Range of `--batch-size` and `--ubatch-size` accepted from settings
+const BATCH_SIZE_RANGE: std::ops::RangeInclusive<u32> = 32..=16384;
+
+/// Reject batch sizes outside `BATCH_SIZE_RANGE` or a physical batch larger than the
+/// logical one
+pub fn validate_batch_sizes(batch_size: u32, ubatch_size: u32) -> Result<()> {
+    for (name, size) in [
+        ("Batch size", batch_size),
+        ("Micro-batch size", ubatch_size),
+    ] {
+        if !BATCH_SIZE_RANGE.contains(&size) {
+            anyhow::bail!(
+                "{} must be between {} and {}",
+                name,
+                BATCH_SIZE_RANGE.start(),
+                BATCH_SIZE_RANGE.end()
+            );
+        }
+    }
+    if ubatch_size > batch_size {
+        anyhow::bail!(
+            "Micro-batch size {} cannot exceed the batch size {}",
+            ubatch_size,
+            batch_size
+        );
+    }
+    Ok(())
+}
+
+/// Reject a slot count that leaves each slot less than `MIN_SLOT_CTX_SIZE` tokens
+pub fn validate_parallel_slots(slots: u32, ctx_size: u32) -> Result<()> {
+    if slots == 0 {
+        anyhow::bail!("Parallel slots must be at least 1");
+    }
+    let slot_ctx_size = ctx_size / slots;
+    if slot_ctx_size < MIN_SLOT_CTX_SIZE {
+        anyhow::bail!(
+            "{} parallel slots would leave each only {} of {} context tokens (minimum {}); \
+             use fewer slots or a larger context size",
+            slots,
+            slot_ctx_size,
+            ctx_size,
+            MIN_SLOT_CTX_SIZE
+        );
+    }
+    Ok(())
+}
+
+/// Thread count for llama-server: the configured value, clamped to the logical CPUs
+/// (more threads than CPUs only adds contention), or one per physical core
+pub fn resolve_thread_count(configured: Option<u32>) -> u32 {
+    let max = logical_cpu_count();
+    match configured {
+        Some(threads) if threads > max => {
+            log::warn!(
+                "Configured {} threads but only {} CPUs are available, using {}",
+                threads,
+                max,
+                max
+            );
+            max
+        }
+        Some(threads) => threads.max(1),
+        None => recommended_thread_count().clamp(1, max),
+    }
+}
+
+/// Characters that only mean something to a shell. Arguments are passed to llama-server
+/// as they are, so these come from a command line pasted as a whole.
+const SHELL_METACHARACTERS: &[char] = &['|', '&', ';', '<', '>', '$', '`', '\n', '\r'];
+
+/// Reject extra arguments that would override the ones the app manages (`--port 1`
+/// as well as `--port=1`) and ones containing shell syntax
+pub fn validate_extra_server_args(args: &[String]) -> Result<()> {
+    for arg in args {
+        if arg.contains(SHELL_METACHARACTERS) {
+            anyhow::bail!(
+                "Extra server argument '{}' contains shell syntax; arguments are passed to \
+                 llama-server directly, one per entry",
+                arg
+            );
+        }
+        let key = arg.split('=').next().unwrap_or(arg);
+        if MANAGED_SERVER_ARGS.contains(&key) {
+            anyhow::bail!(
+                "'{}' is managed by the app and cannot be set as an extra server argument",
+                key
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Whether nothing is listening on `port` at `host`
+fn is_port_free(host: IpAddr, port: u16) -> bool {
+    TcpListener::bind((host, port)).is_ok()
+}
+
+/// First free port at `host` from `start` up to `PORT_SCAN_RANGE` above it
+pub fn find_free_port(host: IpAddr, start: u16) -> Option<u16> {
+    (start..=start.saturating_add(PORT_SCAN_RANGE)).find(|&port| is_port_free(host, port))
+}
+
+/// PID and name of the process listening on TCP `port`, when the platform tells
+/// (`lsof` on Unix, `netstat` on Windows)
+fn find_port_owner(port: u16) -> Option<(u32, String)> {
+    #[cfg(unix)]
+    let pid = {
+        let output = Command::new("lsof")
+            .args(["-nP", "-t", "-sTCP:LISTEN", &format!("-iTCP:{}", port)])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.trim().parse::<u32>().ok())?
+    };
+
+    #[cfg(windows)]
+    let pid = {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let output = Command::new("netstat")
+            .args(["-ano", "-p", "TCP"])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()?;
+        let local_suffix = format!(":{}", port);
+        // "TCP  0.0.0.0:10345  0.0.0.0:0  LISTENING  1234"; listening sockets have no
+        // remote port, which (unlike the state name) is not localized
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                match fields.as_slice() {
+                    [_, local, remote, _, pid]
+                        if local.ends_with(&local_suffix) && remote.ends_with(":0") =>
+                    {
+                        pid.parse::<u32>().ok()
+                    }
+                    _ => None,
+                }
+            })?
+    };
+
+    let mut system = sysinfo::System::new();
+    let sys_pid = sysinfo::Pid::from_u32(pid);
+    system.refresh_process(sys_pid);
+    let name = system
+        .process(sys_pid)
+        .map(|process| process.name().to_string())
+        .unwrap_or_else(|| "unknown process".to_string());
+    Some((pid, name))
+}
+
+/// The port to start llama-server on at `host`: the configured one when it is free,
+/// otherwise the next free port above it if `auto_select_port` is enabled
+pub fn select_server_port(host: IpAddr, port: u16) -> Result<u16> {
+    if is_port_free(host, port) {
+        return Ok(port);
+    }
+
+    if !get_auto_select_port().context("Failed to get port selection setting")? {
+        let owner = match find_port_owner(port) {
+            Some((pid, name)) => format!("{} (PID: {})", name, pid),
+            None => "another process".to_string(),
+        };
+        anyhow::bail!(
+            "Port {} is already in use by {}. Choose a different port \
+             or enable automatic port selection.",
+            port,
+            owner
+        );
+    }
+
+    let free_port = find_free_port(host, port).with_context(|| {
+        format!(
+            "Port {} is already in use and no free port was found up to {}",
+            port,
+            port.saturating_add(PORT_SCAN_RANGE)
+        )
+    })?;
+    log::warn!(
+        "Port {} is in use, starting the server on port {}",
+        port,
+        free_port
+    );
+    Ok(free_port)
+}
+
+/// Port for a server started next to the running `servers`: the first free port from
+/// `start` that none of them recorded (one still loading the model may not listen yet)
+fn select_instance_port(host: IpAddr, start: u16, servers: &[ServerEntry]) -> Result<u16> {
+    let last = start.saturating_add(PORT_SCAN_RANGE);
+    let mut candidate = start;
+    while let Some(port) = find_free_port(host, candidate).filter(|&port| port <= last) {
+        if !servers.iter().any(|server| server.port == port) {
+            return Ok(port);
+        }
+        candidate = port.saturating_add(1);
+    }
+    anyhow::bail!(
+        "No free port found for another server from {} up to {}",
+        start,
+        last
+    )
+}
+
+/// Servers recorded in IPC state that are still running; the ones that are gone
+/// (or whose PID was reused) are cleaned up
+pub fn running_servers() -> Result<Vec<ServerEntry>> {
     let state = read_ipc_state()?;
-    
-    if state.server_running {
-        if let Some(pid) = state.server_pid {
-            if is_process_running(pid) {
-                return Ok(Some(pid));
-            }
-            // Process is stale, clean up
-            update_server_status(false, None)?;
+    let mut running = Vec::new();
+    for server in state.servers {
+        if is_server_process(server.pid, server.started_at) {
+            running.push(server);
+        } else {
+            clear_server_state(server.pid)?;
         }
     }
-    
+    Ok(running)
+}
+
+/// The running server of `model`, or the primary server with None
+pub fn find_server(model: Option<&str>) -> Result<Option<ServerEntry>> {
+    let mut servers = running_servers()?.into_iter();
+    Ok(match model {
+        Some(model) => servers.find(|server| server.model == model),
+        None => servers.next(),
+    })
+}
+
+/// The running embeddings server; a recorded one that is gone is cleaned up
+pub fn find_embeddings_server() -> Result<Option<ServerEntry>> {
+    let Some(server) = read_ipc_state()?.embeddings_server else {
+        return Ok(None);
+    };
+    if is_server_process(server.pid, server.started_at) {
+        return Ok(Some(server));
+    }
+    clear_server_state(server.pid)?;
     Ok(None)
 }
 
-/// Start the llama-server process
+/// Every running server the app tracks: the chat servers and the embeddings server
+pub fn tracked_servers() -> Result<Vec<ServerEntry>> {
+    let mut servers = running_servers()?;
+    servers.extend(find_embeddings_server()?);
+    Ok(servers)
+}
+
+/// PID of the primary server if one is running, via IPC state
+pub fn check_server_running() -> Result<Option<u32>> {
+    Ok(find_server(None)?.map(|server| server.pid))
+}
+
+/// Start a llama-server process for `config.model`; returns it with the port it was
+/// started on, which differs from `config.port` when that port was taken and another
+/// one was selected. Servers of other models keep running.
 pub fn start_server_process(
     config: ServerConfig,
     capture_output: bool,
-) -> Result<Child> {
+) -> Result<(Child, u16)> {
     // Validate configuration
     validate_config(&config)?;
 
+    let active_model = match config.model.clone() {
+        Some(model) => model,
+        None => get_active_model().context("Failed to get active model")?,
+    };
+
     // Check if already running
-    if let Some(pid) = check_server_running()? {
-        anyhow::bail!("Server is already running (PID: {})", pid);
+    if let Some(server) = running_servers()?
+        .into_iter()
+        .find(|server| server.model == active_model)
+    {
+        anyhow::bail!(
+            "Server for {} is already running (PID: {})",
+            active_model,
+            server.pid
+        );
     }
 
     let binary_path = get_llama_binary_path().context("Failed to get binary path")?;
-    let active_model = get_active_model().context("Failed to get active model")?;
     let model_path = get_model_file_path(&active_model).context("Failed to get model path")?;
 
     // Check if binary exists
@@ -82,43 +501,245 @@ pub fn start_server_process(
         anyhow::bail!("Model '{}' not found. Please download it first.", active_model);
     }
 
+    // Refuse to start a model that clearly won't fit, unless forced
+    if let Err(e) = check_model_fits_in_memory(&model_path, config.ctx_size, config.gpu_layers) {
+        if !config.force {
+            anyhow::bail!("Model '{}' is unlikely to fit in memory: {}", active_model, e);
+        }
+        log::warn!("Starting model '{}' despite memory check (forced): {}", active_model, e);
+    }
+
+    // A taken port would only show up as llama-server exiting right after start
+    let host = get_bind_host().context("Failed to get server host")?;
+    let servers = tracked_servers()?;
+    let port = if servers.is_empty() {
+        select_server_port(host, config.port)?
+    } else {
+        select_instance_port(host, config.port, &servers)?
+    };
+
     // Convert paths to short format on Windows to handle Cyrillic characters
     let binary_path_safe = get_short_path(&binary_path).context("Failed to get short path for binary")?;
     let model_path_safe = get_short_path(&model_path).context("Failed to get short path for model")?;
 
     log::info!("Starting llama-server with binary: {:?}", binary_path_safe);
     log::info!("Using model: {:?}", model_path_safe);
-    log::info!("Config: port={}, ctx_size={}, gpu_layers={}", 
-        config.port, config.ctx_size, config.gpu_layers);
+    log::info!("Config: host={}, port={}, ctx_size={}, gpu_layers={}", 
+        host, port, config.ctx_size, config.gpu_layers);
 
     // Build command
     let mut command = Command::new(&binary_path_safe);
     command
         .arg("-m")
         .arg(&model_path_safe)
+        .arg("--host")
+        .arg(host.to_string())
         .arg("--port")
-        .arg(config.port.to_string())
+        .arg(port.to_string())
         .arg("--ctx-size")
         .arg(config.ctx_size.to_string())
         .arg("--n-gpu-layers")
         .arg(config.gpu_layers.to_string());
 
-    // Metal + flash-attention "auto" has triggered SIGABRT on some macOS / llama.cpp builds.
-    #[cfg(target_os = "macos")]
-    {
-        command.arg("--flash-attn").arg("off");
+    let flash_attn = get_flash_attn().context("Failed to get flash attention mode")?;
+    command.arg("--flash-attn").arg(flash_attn.as_arg());
+
+    let threads = resolve_thread_count(get_threads().context("Failed to get thread count")?);
+    log::info!("Using {} threads", threads);
+    command.arg("--threads").arg(threads.to_string());
+
+    // settings.json may be edited by hand, and ctx_size may have shrunk since slots were set
+    let parallel_slots = get_parallel_slots().context("Failed to get parallel slots")?;
+    validate_parallel_slots(parallel_slots, config.ctx_size)?;
+    if parallel_slots > 1 {
+        log::info!(
+            "Using {} parallel slots ({} context tokens each)",
+            parallel_slots,
+            config.ctx_size / parallel_slots
+        );
     }
-    #[cfg(not(target_os = "macos"))]
-    {
-        command.arg("--flash-attn").arg("auto");
+    command.arg("--parallel").arg(parallel_slots.to_string());
+
+    // An embedding input must fit in one physical batch, so the batches match in that mode
+    let embeddings = get_embeddings().context("Failed to get embeddings mode")?;
+    let (batch_size, ubatch_size) = get_batch_sizes().context("Failed to get batch sizes")?;
+    validate_batch_sizes(batch_size, ubatch_size)?;
+    let ubatch_size = if embeddings { batch_size } else { ubatch_size };
+    command
+        .arg("--batch-size")
+        .arg(batch_size.to_string())
+        .arg("--ubatch-size")
+        .arg(ubatch_size.to_string());
+    if embeddings {
+        log::info!("Embeddings mode enabled");
+        command.arg("--embeddings");
+    }
+    // Generation speed and KV cache usage for get_server_metrics
+    command.arg("--metrics");
+
+    // Enables the /slots save and restore actions. Saved slots only fit the model that
+    // wrote them, so each model gets its own directory.
+    if get_persist_kv_cache().context("Failed to get KV cache persistence")? {
+        let slot_dir = get_kv_cache_dir()
+            .context("Failed to get KV cache directory")?
+            .join(&active_model);
+        std::fs::create_dir_all(&slot_dir).context("Failed to create KV cache directory")?;
+        let slot_dir =
+            get_short_path(&slot_dir).context("Failed to get short path for KV cache")?;
+        log::info!("Slots can be saved to {:?}", slot_dir);
+        command.arg("--slot-save-path").arg(&slot_dir);
+    }
+
+    // Passed like --api-key, but through the environment so it does not show up in the
+    // process list
+    let api_key = get_api_key().context("Failed to get API key")?;
+    if !host.is_loopback() {
+        let auth = if api_key.is_some() {
+            ""
+        } else {
+            " WITHOUT an API key"
+        };
+        log::warn!(
+            "llama-server listens on {}, reachable from the network{}",
+            host,
+            auth
+        );
+    }
+    if let Some(api_key) = api_key {
+        log::info!("API key authentication enabled");
+        command.env("LLAMA_ARG_API_KEY", api_key);
+    }
+
+    // User arguments come last so they override the defaults above.
+    // settings.json may be edited by hand, so check them again.
+    let extra_args = get_extra_server_args().context("Failed to get extra server arguments")?;
+    validate_extra_server_args(&extra_args)?;
+    if !extra_args.is_empty() {
+        log::info!("Extra server arguments: {:?}", extra_args);
+        command.args(&extra_args);
+    }
+
+    // Recorded for get_server_status; the API key is passed in the environment
+    let argv: Vec<String> = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect();
+
+    let child = spawn_server_command(command, capture_output)?;
+    let pid = child.id();
+
+    // Record the server and its config in IPC state
+    register_server(ServerEntry {
+        model: active_model,
+        pid,
+        port,
+        ctx_size: config.ctx_size,
+        gpu_layers: config.gpu_layers,
+        embeddings,
+        host: host.to_string(),
+        args: argv,
+        ..Default::default()
+    })?;
+
+    Ok((child, port))
+}
+
+/// Start a llama-server that serves embeddings of `model_name` (`--embedding`, a small
+/// context) next to the chat servers, on `port` or the next free port; returns it with
+/// the port it was started on
+pub fn start_embeddings_server_process(
+    model_name: &str,
+    port: u16,
+    capture_output: bool,
+) -> Result<(Child, u16)> {
+    if let Some(server) = find_embeddings_server()? {
+        anyhow::bail!(
+            "Embeddings server is already running ({}, PID: {})",
+            server.model,
+            server.pid
+        );
+    }
+
+    let binary_path = get_llama_binary_path().context("Failed to get binary path")?;
+    let model_path = get_model_file_path(model_name).context("Failed to get model path")?;
+    if !binary_path.exists() {
+        anyhow::bail!("llama.cpp not found. Please download it first.");
     }
+    if !model_path.exists() {
+        anyhow::bail!(
+            "Model '{}' not found. Please download it first.",
+            model_name
+        );
+    }
+
+    let host = get_bind_host().context("Failed to get server host")?;
+    let servers = tracked_servers()?;
+    let port = if servers.is_empty() {
+        select_server_port(host, port)?
+    } else {
+        select_instance_port(host, port, &servers)?
+    };
+    let (_, _, gpu_layers) = get_server_settings().context("Failed to get server settings")?;
+    let threads = resolve_thread_count(get_threads().context("Failed to get thread count")?);
 
+    let binary_path_safe =
+        get_short_path(&binary_path).context("Failed to get short path for binary")?;
+    let model_path_safe =
+        get_short_path(&model_path).context("Failed to get short path for model")?;
+    log::info!(
+        "Starting embeddings server with model {:?} on {}:{}",
+        model_path_safe,
+        host,
+        port
+    );
+
+    // An embedding input must fit in one physical batch
+    let mut command = Command::new(&binary_path_safe);
     command
+        .arg("-m")
+        .arg(&model_path_safe)
+        .arg("--host")
+        .arg(host.to_string())
+        .arg("--port")
+        .arg(port.to_string())
+        .arg("--ctx-size")
+        .arg(EMBEDDINGS_CTX_SIZE.to_string())
         .arg("--batch-size")
-        .arg("2048")
+        .arg(EMBEDDINGS_CTX_SIZE.to_string())
         .arg("--ubatch-size")
-        .arg("512");
+        .arg(EMBEDDINGS_CTX_SIZE.to_string())
+        .arg("--n-gpu-layers")
+        .arg(gpu_layers.to_string())
+        .arg("--threads")
+        .arg(threads.to_string())
+        .arg("--embedding");
+    if let Some(api_key) = get_api_key().context("Failed to get API key")? {
+        command.env("LLAMA_ARG_API_KEY", api_key);
+    }
+    let argv: Vec<String> = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect();
 
+    let child = spawn_server_command(command, capture_output)?;
+    register_embeddings_server(ServerEntry {
+        model: model_name.to_string(),
+        pid: child.id(),
+        port,
+        ctx_size: EMBEDDINGS_CTX_SIZE,
+        gpu_layers,
+        embeddings: true,
+        host: host.to_string(),
+        args: argv,
+        ..Default::default()
+    })?;
+
+    Ok((child, port))
+}
+
+/// Spawn a llama-server command in a process group of its own (Unix) or a job (Windows)
+/// without a console window, with its output piped when `capture_output`
+fn spawn_server_command(mut command: Command, capture_output: bool) -> Result<Child> {
     // Configure stdio
     if capture_output {
         command.stdout(Stdio::piped()).stderr(Stdio::piped());
@@ -142,85 +763,665 @@ pub fn start_server_process(
     }
 
     // Spawn process
-    let child = command.spawn().context("Failed to start server process")?;
+    let child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) if has_previous_llama_build() => {
+            anyhow::bail!(
+                "Failed to start server process: {}. If this started after a llama.cpp update, \
+                 roll back to the previous version.",
+                e
+            );
+        }
+        Err(e) => return Err(e).context("Failed to start server process"),
+    };
     let pid = child.id();
 
     log::info!("Server started with PID: {}", pid);
 
-    // Update IPC state
-    update_server_status(true, Some(pid))?;
-
-    // Update config in IPC state
-    let mut state = read_ipc_state()?;
-    state.server_port = Some(config.port);
-    state.server_ctx_size = Some(config.ctx_size);
-    state.server_gpu_layers = Some(config.gpu_layers);
-    crate::ipc_state::write_ipc_state(&state)?;
+    #[cfg(windows)]
+    if let Err(e) = assign_to_server_job(&child) {
+        log::warn!("Server child processes may outlive it: {}", e);
+    }
 
     Ok(child)
 }
 
+/// Put the server into a job object that kills it and its descendants when closed
+#[cfg(windows)]
+fn assign_to_server_job(child: &Child) -> Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+
+    unsafe {
+        let job = CreateJobObjectW(None, PCWSTR::null()).context("Failed to create job object")?;
+
+        let mut info = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        let assigned = SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &info as *const _ as *const std::ffi::c_void,
+            std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        )
+        .and_then(|_| AssignProcessToJobObject(job, HANDLE(child.as_raw_handle() as _)));
+        if let Err(e) = assigned {
+            let _ = CloseHandle(job);
+            return Err(e).context("Failed to assign server to job object");
+        }
+
+        SERVER_JOBS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((child.id(), job.0 as usize));
+    }
+    Ok(())
+}
+
+/// Close the job of the server with `pid`, killing anything still running in it.
+/// Returns false when this process did not start that server.
+#[cfg(windows)]
+fn close_server_job(pid: u32) -> bool {
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+
+    let mut jobs = SERVER_JOBS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(index) = jobs.iter().position(|&(job_pid, _)| job_pid == pid) else {
+        return false;
+    };
+    let (_, job) = jobs.remove(index);
+    unsafe {
+        let _ = CloseHandle(HANDLE(job as _));
+    }
+    true
+}
+
+/// Send Ctrl+C to the console of the server, which llama-server handles like SIGINT,
+/// and wait up to `grace_period` for it to exit. Returns whether it exited.
+#[cfg(windows)]
+fn stop_gracefully(pid: u32, grace_period: Duration) -> Result<bool> {
+    use windows::Win32::Foundation::BOOL;
+    use windows::Win32::System::Console::{
+        AttachConsole, FreeConsole, GenerateConsoleCtrlEvent, SetConsoleCtrlHandler,
+        ATTACH_PARENT_PROCESS, CTRL_C_EVENT,
+    };
+
+    let _console_guard = CONSOLE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    // The server runs with its own hidden console (CREATE_NO_WINDOW); a console control
+    // event only reaches processes sharing the sender's console, so join it for a moment
+    unsafe {
+        let _ = FreeConsole();
+        if let Err(e) = AttachConsole(pid) {
+            let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+            return Err(e).context("Failed to attach to server console");
+        }
+        // Group 0 is every process on the console, including this one: ignore it here
+        let _ = SetConsoleCtrlHandler(None, BOOL::from(true));
+        let sent = GenerateConsoleCtrlEvent(CTRL_C_EVENT, 0);
+
+        let exited = sent.is_ok() && wait_for_exit(pid, grace_period);
+
+        // Restore the handler only after leaving the console, so the event cannot reach us
+        let _ = FreeConsole();
+        let _ = SetConsoleCtrlHandler(None, BOOL::from(false));
+        // Debug builds log to the console of the terminal that started them
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+
+        sent.context("Failed to send Ctrl+C")?;
+        Ok(exited)
+    }
+}
+
+/// Whether `pid` is gone. Unlike `is_process_running` this counts an exited child of
+/// this process that was not reaped yet (a zombie) as gone.
+fn has_process_exited(pid: u32) -> bool {
+    use sysinfo::{Pid, ProcessStatus, System};
+
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    if !system.refresh_process(pid) {
+        return true;
+    }
+    !system
+        .process(pid)
+        .is_some_and(|process| process.status() != ProcessStatus::Zombie)
+}
+
+/// Poll every `STOP_POLL_INTERVAL` until `pid` exits or `timeout` passes; returns whether
+/// it exited
+fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+    let started = Instant::now();
+    loop {
+        if has_process_exited(pid) {
+            return true;
+        }
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(STOP_POLL_INTERVAL);
+    }
+}
+
 /// Stop the server by PID
-pub fn stop_server_by_pid(pid: u32) -> Result<()> {
-    log::info!("Stopping server (PID: {})", pid);
+pub fn stop_server_by_pid(pid: u32) -> Result<StopOutcome> {
+    stop_server_by_pid_with(pid, StopMode::Graceful)
+}
+
+/// Stop the server by PID, giving it the time `mode` allows to exit on its own
+pub fn stop_server_by_pid_with(pid: u32, mode: StopMode) -> Result<StopOutcome> {
+    log::info!("Stopping server (PID: {}, {:?})", pid, mode);
+
+    // Supervisors must not take a slow graceful exit for a crash
+    mark_server_stopping(pid)?;
+
+    let outcome = terminate_server_process(pid, mode);
+
+    // Update IPC state and clear config
+    clear_server_state(pid)?;
+
+    log::info!("Server {} (PID: {})", outcome.description(), pid);
+
+    Ok(outcome)
+}
+
+/// Stop a server this process did not spawn and wait until the process is gone, so the
+/// next start neither finds it in the IPC state nor its port taken. The process that
+/// spawned it reaps it; a child of this process is reaped with `Child::wait` instead.
+pub fn stop_server_and_wait(pid: u32) -> Result<StopOutcome> {
+    stop_server_and_wait_with(pid, StopMode::Graceful)
+}
+
+/// `stop_server_and_wait` with the grace period of `mode`
+pub fn stop_server_and_wait_with(pid: u32, mode: StopMode) -> Result<StopOutcome> {
+    let outcome = stop_server_by_pid_with(pid, mode)?;
+
+    let started = Instant::now();
+    while is_process_running(pid) {
+        if started.elapsed() >= SERVER_EXIT_TIMEOUT {
+            anyhow::bail!(
+                "Server (PID: {}) did not exit within {} seconds",
+                pid,
+                SERVER_EXIT_TIMEOUT.as_secs()
+            );
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok(outcome)
+}
+
+/// Stop a llama-server process (and its children) without touching IPC state. Unless
+/// forced, it gets the grace period of `mode` to shut down on its own before it is killed.
+fn terminate_server_process(pid: u32, mode: StopMode) -> StopOutcome {
+    let grace_period = match mode {
+        StopMode::Graceful => Some(get_stop_grace_period().unwrap_or_else(|e| {
+            log::warn!("Failed to read stop grace period: {}", e);
+            DEFAULT_STOP_GRACE_PERIOD
+        })),
+        StopMode::Timeout(timeout) => Some(timeout),
+        StopMode::Force => None,
+    };
 
     #[cfg(unix)]
     {
         let pid_i32 = pid as i32;
+        // Try graceful shutdown first
+        if let Some(grace_period) = grace_period {
+            unsafe {
+                libc::kill(-pid_i32, libc::SIGTERM);
+            }
+            if wait_for_exit(pid, grace_period) {
+                return StopOutcome::Graceful;
+            }
+            log::warn!("Server did not exit within {:?}, killing it", grace_period);
+        }
         unsafe {
-            // Try graceful shutdown first
-            libc::kill(-pid_i32, libc::SIGTERM);
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            // Force kill if still running
             libc::kill(-pid_i32, libc::SIGKILL);
         }
+        StopOutcome::ForceKilled
     }
 
     #[cfg(windows)]
     {
-        use std::os::windows::process::CommandExt;
-        use std::process::Command;
-        const CREATE_NO_WINDOW: u32 = 0x08000000;
-        let _ = Command::new("taskkill")
-            .args(["/F", "/PID", &pid.to_string()])
-            .creation_flags(CREATE_NO_WINDOW)
-            .output();
-    }
+        // Try graceful shutdown first, like SIGTERM on Unix
+        let exited = match grace_period.map(|grace_period| stop_gracefully(pid, grace_period)) {
+            Some(Ok(exited)) => exited,
+            Some(Err(e)) => {
+                log::warn!("Failed to send Ctrl+C to server: {}", e);
+                false
+            }
+            None => false,
+        };
+        if !exited && grace_period.is_some() {
+            log::warn!("Server did not exit gracefully, killing it");
+        }
 
-    // Update IPC state
-    update_server_status(false, None)?;
+        // Closing the job kills whatever is left of the server and its children.
+        // A server started by the other process is not in our job: kill its tree directly.
+        if !close_server_job(pid) && !exited {
+            use std::os::windows::process::CommandExt;
+            use std::process::Command;
+            const CREATE_NO_WINDOW: u32 = 0x08000000;
+            let _ = Command::new("taskkill")
+                .args(["/F", "/T", "/PID", &pid.to_string()])
+                .creation_flags(CREATE_NO_WINDOW)
+                .output();
+        }
 
-    // Clear config
-    let mut state = read_ipc_state()?;
-    state.server_port = None;
-    state.server_ctx_size = None;
-    state.server_gpu_layers = None;
-    crate::ipc_state::write_ipc_state(&state)?;
+        if exited {
+            StopOutcome::Graceful
+        } else {
+            StopOutcome::ForceKilled
+        }
+    }
+}
 
-    log::info!("Server stopped");
+/// Make sure no running server uses files about to be deleted: the model `model_name`,
+/// or with None the binary or every model. Deleting them under a running server fails
+/// halfway on Windows (locked files) and pulls the mapped model away elsewhere, so this
+/// fails unless `force`, which stops the servers first.
+pub fn release_server_files(what: &str, model_name: Option<&str>, force: bool) -> Result<()> {
+    let mut servers = tracked_servers()?;
+    if let Some(model_name) = model_name {
+        // Servers started before the model was recorded run the active model
+        let active_model = get_active_model().unwrap_or_default();
+        servers.retain(|server| {
+            let running_model = if server.model.is_empty() {
+                &active_model
+            } else {
+                &server.model
+            };
+            running_model == model_name
+        });
+    }
+    let Some(first) = servers.first() else {
+        return Ok(());
+    };
 
+    if !force {
+        anyhow::bail!(
+            "{} is in use by the running LLM (PID: {}). Stop the LLM first.",
+            what,
+            first.pid
+        );
+    }
+    for server in &servers {
+        log::info!("Stopping the LLM (PID: {}) to delete {}", server.pid, what);
+        stop_server_by_pid(server.pid)?;
+    }
     Ok(())
 }
 
-/// Get current server status from IPC state
-pub fn get_status() -> Result<(bool, Option<u32>)> {
-    let state = read_ipc_state()?;
+/// Reconcile IPC state with reality when the app starts. A server whose launching
+/// process is gone (the app crashed before its exit handler ran) is stopped; one still
+/// owned by a live process, such as the Native Messaging Host, is adopted as it is.
+pub fn reconcile_server_state() -> Result<()> {
+    // Clears the state of servers that are no longer running
+    for server in tracked_servers()? {
+        match server.owner_pid {
+            Some(owner) if !is_process_running(owner) => {
+                log::warn!(
+                    "Stopping llama-server (PID: {}) left running by a previous session (PID: {})",
+                    server.pid,
+                    owner
+                );
+                stop_server_by_pid(server.pid)?;
+            }
+            _ => log::info!("Adopting running llama-server (PID: {})", server.pid),
+        }
+    }
+    Ok(())
+}
 
-    let is_running = if state.server_running {
-        if let Some(pid) = state.server_pid {
-            is_process_running(pid)
-        } else {
-            false
+/// llama-server processes running from the app's bin directory that IPC state does not
+/// track, e.g. left behind after the state file was cleared
+pub fn find_orphaned_servers() -> Result<Vec<OrphanedServer>> {
+    use sysinfo::{ProcessRefreshKind, System, UpdateKind};
+
+    let bin_dir = get_bin_dir()?;
+    let bin_dir = bin_dir.canonicalize().unwrap_or(bin_dir);
+    let tracked: Vec<u32> = tracked_servers()?.iter().map(|server| server.pid).collect();
+
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessRefreshKind::new().with_exe(UpdateKind::Always));
+
+    let mut orphans: Vec<OrphanedServer> = system
+        .processes()
+        .values()
+        // On Linux threads are listed too
+        .filter(|process| process.thread_kind().is_none())
+        .filter(|process| !tracked.contains(&process.pid().as_u32()))
+        .filter_map(|process| {
+            let exe = process.exe()?;
+            let is_server = exe.file_stem().and_then(|stem| stem.to_str()) == Some("llama-server");
+            (is_server && exe.starts_with(&bin_dir)).then(|| OrphanedServer {
+                pid: process.pid().as_u32(),
+                path: exe.to_string_lossy().to_string(),
+                started_at: process.start_time(),
+            })
+        })
+        .collect();
+    orphans.sort_by_key(|orphan| orphan.pid);
+    Ok(orphans)
+}
+
+/// Stop every orphaned llama-server; returns the PIDs that were stopped
+pub fn stop_orphaned_servers() -> Result<Vec<u32>> {
+    let orphans = find_orphaned_servers()?;
+    for orphan in &orphans {
+        log::warn!(
+            "Stopping orphaned llama-server (PID: {}): {}",
+            orphan.pid,
+            orphan.path
+        );
+        stop_server_by_pid(orphan.pid)?;
+    }
+    Ok(orphans.iter().map(|orphan| orphan.pid).collect())
+}
+
+/// Address for reaching the server on `port` from this machine: the host it was started
+/// on (or the configured one), with "all interfaces" replaced by loopback
+fn server_connect_host(port: u16) -> IpAddr {
+    let host = read_ipc_state()
+        .ok()
+        .and_then(|state| {
+            state
+                .servers
+                .into_iter()
+                .chain(state.embeddings_server)
+                .find(|server| server.port == port)
+        })
+        .and_then(|server| server.host.parse().ok())
+        .or_else(|| get_bind_host().ok())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    match host {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        host => host,
+    }
+}
+
+/// Address for reaching the server on `port` from this machine
+pub fn server_address(port: u16) -> SocketAddr {
+    SocketAddr::new(server_connect_host(port), port)
+}
+
+/// GET /health on the local llama-server.
+/// It answers 503 while the model is loading and 200 once requests can be served;
+/// no answer within the timeout means it is still starting or wedged.
+pub async fn check_server_health(port: u16) -> ServerReadiness {
+    let client = match reqwest::Client::builder()
+        .timeout(HEALTH_CHECK_TIMEOUT)
+        .no_proxy()
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            log::warn!("Failed to create health check client: {}", e);
+            return ServerReadiness::Starting;
         }
-    } else {
-        false
     };
 
-    // Update state if stale
-    if state.server_running && !is_running {
-        update_server_status(false, None)?;
+    match client
+        .get(format!("http://{}/health", server_address(port)))
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_success() => ServerReadiness::Ready,
+        Ok(response) => {
+            log::debug!("Server health check: HTTP {}", response.status());
+            ServerReadiness::Starting
+        }
+        Err(e) => {
+            log::debug!("Server health check failed: {}", e);
+            ServerReadiness::Starting
+        }
+    }
+}
+
+/// Health of the running server of `model` (the primary server with None) on the port
+/// recorded when it was started; the primary falls back to the configured port
+pub async fn check_running_server_health(model: Option<&str>) -> ServerReadiness {
+    let port = read_ipc_state()
+        .ok()
+        .and_then(|state| state.server_for(model).map(|server| server.port))
+        .or_else(|| {
+            model
+                .is_none()
+                .then(|| get_server_settings().ok().map(|(port, _, _)| port))
+                .flatten()
+        });
+    match port {
+        Some(port) => check_server_health(port).await,
+        None => ServerReadiness::Starting,
+    }
+}
+
+/// Poll /health until the server `child` on `port` is ready. Fails when it exits first or
+/// is not ready within `timeout`; stopping it is left to the caller.
+pub async fn wait_until_ready(child: &mut Child, port: u16, timeout: Duration) -> Result<()> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().context("Failed to check server status")? {
+            anyhow::bail!("Server exited during startup with status: {}", status);
+        }
+        if check_server_health(port).await == ServerReadiness::Ready {
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            anyhow::bail!(
+                "Server did not become ready within {} seconds",
+                timeout.as_secs()
+            );
+        }
+        tokio::time::sleep(READINESS_POLL_INTERVAL).await;
+    }
+}
+
+/// Status of the embeddings server, None when it is not running
+pub async fn embeddings_server_status() -> Option<EmbeddingsServerStatus> {
+    let server = find_embeddings_server().ok().flatten()?;
+    Some(EmbeddingsServerStatus {
+        state: check_server_health(server.port).await,
+        pid: server.pid,
+        port: server.port,
+        model_name: server.model,
+        started_at: server.started_at,
+    })
+}
+
+/// GET `path` on the local llama-server; None when it fails or answers with an error
+/// (llama-server answers 501 for disabled endpoints and 503 while loading)
+async fn fetch_server_endpoint(
+    client: &reqwest::Client,
+    port: u16,
+    path: &str,
+    api_key: Option<&str>,
+) -> Option<reqwest::Response> {
+    let url = format!("http://{}{}", server_address(port), path);
+    let mut request = client.get(url);
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    match request.send().await {
+        Ok(response) if response.status().is_success() => Some(response),
+        Ok(response) => {
+            log::debug!("Server {}: HTTP {}", path, response.status());
+            None
+        }
+        Err(e) => {
+            log::debug!("Server {} failed: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Value of an unlabeled metric in Prometheus text format
+fn parse_prometheus_metric(text: &str, name: &str) -> Option<f64> {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .find_map(|line| {
+            let (metric, value) = line.split_once(char::is_whitespace)?;
+            (metric == name).then(|| value.trim().parse().ok())?
+        })
+}
+
+/// Live numbers from the /health, /slots, /metrics and /props endpoints of the running
+/// server of `model` (the primary server with None). Whatever cannot be read is left
+/// out, down to the process-based status.
+pub async fn fetch_server_metrics(model: Option<&str>) -> Result<ServerMetrics> {
+    let Some(port) = find_server(model)?.map(|server| server.port) else {
+        return Ok(ServerMetrics::default());
+    };
+
+    let status = check_server_health(port).await;
+    let mut metrics = ServerMetrics {
+        status,
+        ..Default::default()
+    };
+    if status != ServerReadiness::Ready {
+        return Ok(metrics);
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(HEALTH_CHECK_TIMEOUT)
+        .no_proxy()
+        .build()
+        .context("Failed to create metrics client")?;
+    let api_key = get_api_key().context("Failed to get API key")?;
+    let api_key = api_key.as_deref();
+
+    if let Some(response) = fetch_server_endpoint(&client, port, "/slots", api_key).await {
+        if let Ok(slots) = response.json::<Vec<serde_json::Value>>().await {
+            // Older builds report "state" (0 = idle) instead of "is_processing"
+            let processing = slots
+                .iter()
+                .filter(|slot| {
+                    slot.get("is_processing")
+                        .and_then(|value| value.as_bool())
+                        .or_else(|| {
+                            slot.get("state")
+                                .and_then(|value| value.as_u64())
+                                .map(|state| state != 0)
+                        })
+                        .unwrap_or(false)
+                })
+                .count() as u32;
+            metrics.slots_processing = Some(processing);
+            metrics.slots_idle = Some(slots.len() as u32 - processing);
+        }
+    }
+
+    if let Some(response) = fetch_server_endpoint(&client, port, "/metrics", api_key).await {
+        if let Ok(text) = response.text().await {
+            metrics.tokens_predicted_per_sec =
+                parse_prometheus_metric(&text, "llamacpp:predicted_tokens_seconds");
+            metrics.kv_cache_used = parse_prometheus_metric(&text, "llamacpp:kv_cache_usage_ratio");
+        }
+    }
+
+    if let Some(response) = fetch_server_endpoint(&client, port, "/props", api_key).await {
+        if let Ok(props) = response.json::<serde_json::Value>().await {
+            metrics.model_path = props
+                .get("model_path")
+                .and_then(|value| value.as_str())
+                .map(str::to_string);
+        }
+    }
+
+    Ok(metrics)
+}
+
+/// Get current status of the primary server from IPC state: whether any server runs
+/// and the PID of the first
+pub fn get_status() -> Result<(bool, Option<u32>)> {
+    let pid = check_server_running()?;
+    Ok((pid.is_some(), pid))
+}
+
+
+// ============================================================================
+// Crash Restart
+// ============================================================================
+
+/// Whether the exit of server `pid` was requested. Call after `STOP_GRACE_PERIOD`:
+/// a stop clears its IPC state, so a server still recorded there crashed.
+pub fn server_exit_was_requested(pid: u32) -> bool {
+    !read_ipc_state().is_ok_and(|state| {
+        state
+            .servers
+            .iter()
+            .any(|server| server.pid == pid && !server.stopping)
+    })
+}
+
+/// Count a restart against the crash-loop budget; returns the attempt number,
+/// or None once `max_auto_restarts` happened within `CRASH_LOOP_WINDOW`
+pub fn reserve_restart_attempt() -> Option<u32> {
+    let max_restarts = get_max_auto_restarts().unwrap_or(3).max(1) as usize;
+    let mut recent = RECENT_RESTARTS.lock().unwrap_or_else(|e| e.into_inner());
+    recent.retain(|at| at.elapsed() < CRASH_LOOP_WINDOW);
+    if recent.len() >= max_restarts {
+        return None;
     }
+    recent.push(Instant::now());
+    Some(recent.len() as u32)
+}
 
-    Ok((is_running, state.server_pid))
+/// Delay before restart attempt `attempt` (1-based): 1 s, 2 s, 4 s, ... capped at 1 minute
+pub fn restart_backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1u64 << attempt.saturating_sub(1).min(6)).min(Duration::from_secs(60))
 }
 
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Child, Command};
+
+    /// A stand-in for llama-server in its own process group, like the real one
+    fn spawn_server(script: &str) -> Child {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]).process_group(0);
+        command.spawn().unwrap()
+    }
+
+    #[test]
+    fn server_that_exits_on_sigterm_stops_gracefully() {
+        let mut child = spawn_server("exec sleep 30");
+        let outcome =
+            terminate_server_process(child.id(), StopMode::Timeout(Duration::from_secs(5)));
+        assert_eq!(outcome, StopOutcome::Graceful);
+        assert!(child.wait().is_ok());
+    }
+
+    #[test]
+    fn server_that_ignores_sigterm_is_killed_after_the_grace_period() {
+        let mut child = spawn_server("trap '' TERM; sleep 30");
+        // Let the shell install its trap before it is signalled
+        std::thread::sleep(Duration::from_millis(200));
+
+        let started = Instant::now();
+        let outcome =
+            terminate_server_process(child.id(), StopMode::Timeout(Duration::from_millis(300)));
+        assert_eq!(outcome, StopOutcome::ForceKilled);
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert!(wait_for_exit(child.id(), Duration::from_secs(5)));
+        let _ = child.wait();
+    }
+
+    #[test]
+    fn forced_stop_kills_right_away() {
+        let mut child = spawn_server("trap '' TERM; sleep 30");
+        let started = Instant::now();
+        let outcome = terminate_server_process(child.id(), StopMode::Force);
+        assert_eq!(outcome, StopOutcome::ForceKilled);
+        assert!(wait_for_exit(child.id(), Duration::from_secs(5)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let _ = child.wait();
+    }
+}
@@ -1,9 +1,21 @@
+use crate::download::redact_proxy_url;
 use crate::paths::get_app_data_dir;
-use crate::system::calculate_recommended_settings;
-use crate::types::AppSettings;
-use anyhow::Result;
-use std::fs;
-use std::path::PathBuf;
+use crate::server_manager::{
+    validate_batch_sizes, validate_extra_server_args, validate_parallel_slots, CTX_SIZE_RANGE,
+    GPU_LAYERS_RANGE, PORT_RANGE,
+};
+use crate::system::calculate_default_settings;
+use crate::types::{AppSettings, FlashAttn, SETTINGS_SCHEMA_VERSION};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::Write;
+use std::net::IpAddr;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
 
 /// Get path to settings file
 fn get_settings_path() -> Result<PathBuf> {
@@ -13,7 +25,7 @@ fn get_settings_path() -> Result<PathBuf> {
 
 /// Create default settings based on system recommended values
 fn create_default_settings() -> AppSettings {
-    match calculate_recommended_settings() {
+    match calculate_default_settings() {
         Ok(recommended) => {
             log::info!(
                 "Creating default settings from recommended: model={}, ctx_size={}, gpu_layers={}",
@@ -21,12 +33,15 @@ fn create_default_settings() -> AppSettings {
                 recommended.recommended_ctx_size,
                 recommended.recommended_gpu_layers
             );
-            AppSettings {
+            let mut settings = AppSettings {
                 active_model: recommended.recommended_model,
                 port: 10345,
                 ctx_size: recommended.recommended_ctx_size,
                 gpu_layers: recommended.recommended_gpu_layers,
-            }
+                ..AppSettings::default()
+            };
+            clamp_server_settings(&mut settings);
+            settings
         }
         Err(e) => {
             log::warn!("Failed to get recommended settings, using hardcoded defaults: {}", e);
@@ -35,30 +50,173 @@ fn create_default_settings() -> AppSettings {
     }
 }
 
-/// Load settings from settings.json
+/// Copy of the last settings.json that could be read, replaced on every save
+fn get_settings_backup_path(settings_path: &Path) -> PathBuf {
+    settings_path.with_extension("json.bak")
+}
+
+/// Load settings from settings.json. A file that cannot be read (e.g. cut short by a
+/// crash or a full disk) is replaced by the backup, or by defaults without one.
 pub fn load_settings() -> Result<AppSettings> {
-    let settings_path = get_settings_path()?;
-    
+    load_settings_from(&get_settings_path()?)
+}
+
+fn load_settings_from(settings_path: &Path) -> Result<AppSettings> {
     if !settings_path.exists() {
         // Create default settings based on system recommendations
         let settings = create_default_settings();
         // Save them so they persist
-        save_settings(&settings)?;
+        save_settings_to(settings_path, &settings)?;
         return Ok(settings);
     }
     
-    let content = fs::read_to_string(&settings_path)?;
-    let settings: AppSettings = serde_json::from_str(&content)?;
-    
+    let content = fs::read_to_string(settings_path)?;
+    let (mut settings, migrated) = match parse_settings(&content) {
+        Ok(parsed) => parsed,
+        Err(e) => return recover_settings(settings_path, e),
+    };
+
+    if migrated {
+        // Keep the file as it was, so a wrong migration step can be undone by hand.
+        // It stays the backup until the settings are next changed.
+        let backup_path = get_settings_backup_path(settings_path);
+        fs::write(&backup_path, &content)?;
+        log::info!(
+            "Settings migrated to schema version {}, previous file kept at {:?}",
+            SETTINGS_SCHEMA_VERSION,
+            backup_path
+        );
+    }
+    // A hand-edited file may hold values llama-server would only refuse at start
+    let clamped = clamp_server_settings(&mut settings);
+    if migrated || clamped {
+        save_settings_to(settings_path, &settings)?;
+    }
+
+    Ok(settings)
+}
+
+/// `value` limited to `range`, with a warning when it was outside
+fn clamp_setting<T: Copy + Ord + Display>(name: &str, value: T, range: &RangeInclusive<T>) -> T {
+    let clamped = value.clamp(*range.start(), *range.end());
+    if clamped != value {
+        log::warn!("{} {} is out of range, using {}", name, value, clamped);
+    }
+    clamped
+}
+
+/// Limit port, context size and GPU layers to the ranges validate_config accepts;
+/// returns whether any of them changed
+fn clamp_server_settings(settings: &mut AppSettings) -> bool {
+    let clamped = (
+        clamp_setting("Port", settings.port, &PORT_RANGE),
+        clamp_setting("Context size", settings.ctx_size, &CTX_SIZE_RANGE),
+        clamp_setting("GPU layers", settings.gpu_layers, &GPU_LAYERS_RANGE),
+    );
+    let changed = clamped != (settings.port, settings.ctx_size, settings.gpu_layers);
+    (settings.port, settings.ctx_size, settings.gpu_layers) = clamped;
+    changed
+}
+
+/// Parse the contents of settings.json, upgrading an older layout; returns the settings
+/// and whether they were migrated
+fn parse_settings(content: &str) -> Result<(AppSettings, bool)> {
+    let mut value: serde_json::Value = serde_json::from_str(content)?;
+    let migrated = migrate_settings(&mut value)?;
+    Ok((serde_json::from_value(value)?, migrated))
+}
+
+/// Replace an unreadable settings.json with the backup, or with defaults when the backup
+/// is unusable too. The unreadable file is kept as settings.json.corrupt.
+fn recover_settings(settings_path: &Path, error: anyhow::Error) -> Result<AppSettings> {
+    log::error!("Failed to parse {:?}: {}", settings_path, error);
+    let corrupt_path = settings_path.with_extension("json.corrupt");
+    if let Err(e) = fs::copy(settings_path, &corrupt_path) {
+        log::warn!("Failed to keep a copy of the corrupt settings: {}", e);
+    }
+
+    let backup_path = get_settings_backup_path(settings_path);
+    let backup = fs::read_to_string(&backup_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| parse_settings(&content));
+    let mut settings = match backup {
+        Ok((settings, _)) => {
+            log::warn!("Settings restored from {:?}", backup_path);
+            settings
+        }
+        Err(e) => {
+            log::warn!("Settings backup unusable ({}), using defaults", e);
+            create_default_settings()
+        }
+    };
+    clamp_server_settings(&mut settings);
+    save_settings_to(settings_path, &settings)?;
+
     Ok(settings)
 }
 
-/// Save settings to settings.json
+/// Upgrade a settings.json layout written by an older version, one schema version at a
+/// time; returns whether anything changed. Files from a newer version are left alone.
+fn migrate_settings(value: &mut serde_json::Value) -> Result<bool> {
+    let settings = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("settings.json does not contain an object"))?;
+    let version = settings
+        .get("schema_version")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(0);
+    if version >= SETTINGS_SCHEMA_VERSION as u64 {
+        return Ok(false);
+    }
+
+    if version < 1 {
+        // The bytes-per-second speed cap became download_speed_limit_mbps
+        if let Some(bytes_per_sec) = settings.remove("max_download_bytes_per_sec") {
+            if !settings.contains_key("download_speed_limit_mbps") {
+                let mbps = bytes_per_sec
+                    .as_u64()
+                    .filter(|&bytes_per_sec| bytes_per_sec > 0)
+                    .map(|bytes_per_sec| {
+                        ((bytes_per_sec as f64 / 125_000.0).round() as u32).max(1)
+                    });
+                settings.insert("download_speed_limit_mbps".to_string(), mbps.into());
+            }
+        }
+    }
+
+    settings.insert("schema_version".to_string(), SETTINGS_SCHEMA_VERSION.into());
+    Ok(true)
+}
+
+/// Save settings to settings.json, keeping the file it replaces as the backup
 pub fn save_settings(settings: &AppSettings) -> Result<()> {
-    let settings_path = get_settings_path()?;
+    save_settings_to(&get_settings_path()?, settings)
+}
+
+fn save_settings_to(settings_path: &Path, settings: &AppSettings) -> Result<()> {
     let content = serde_json::to_string_pretty(settings)?;
-    fs::write(&settings_path, content)?;
-    
+    // Write then rename so a crash or a full disk never leaves a truncated settings file.
+    // The app and the native host both save, so each uses its own temporary file.
+    let tmp_path = settings_path.with_extension(format!("json.{}.tmp", std::process::id()));
+    let written = File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(anyhow!("Failed to write settings: {}", e));
+    }
+
+    // Only a file that can still be read is worth falling back to
+    let previous_is_valid =
+        fs::read_to_string(settings_path).is_ok_and(|previous| parse_settings(&previous).is_ok());
+    if previous_is_valid {
+        if let Err(e) = fs::copy(settings_path, get_settings_backup_path(settings_path)) {
+            log::warn!("Failed to back up settings: {}", e);
+        }
+    }
+    fs::rename(&tmp_path, settings_path)?;
+
     Ok(())
 }
 
@@ -83,26 +241,440 @@ pub fn get_server_settings() -> Result<(u16, u32, u32)> {
     Ok((settings.port, settings.ctx_size, settings.gpu_layers))
 }
 
-/// Set server port
-pub fn set_port(port: u16) -> Result<()> {
+/// Set port, ctx_size and gpu_layers in one write
+pub fn set_server_settings(port: u16, ctx_size: u32, gpu_layers: u32) -> Result<()> {
     let mut settings = load_settings()?;
     settings.port = port;
+    settings.ctx_size = ctx_size;
+    settings.gpu_layers = gpu_layers;
+    clamp_server_settings(&mut settings);
     save_settings(&settings)?;
     Ok(())
 }
 
-/// Set context size
-pub fn set_ctx_size(ctx_size: u32) -> Result<()> {
+/// Set server port, clamped to `PORT_RANGE`; returns the stored value
+pub fn set_port(port: u16) -> Result<u16> {
     let mut settings = load_settings()?;
-    settings.ctx_size = ctx_size;
+    settings.port = clamp_setting("Port", port, &PORT_RANGE);
+    save_settings(&settings)?;
+    Ok(settings.port)
+}
+
+/// Set context size, clamped to `CTX_SIZE_RANGE`; returns the stored value
+pub fn set_ctx_size(ctx_size: u32) -> Result<u32> {
+    let mut settings = load_settings()?;
+    settings.ctx_size = clamp_setting("Context size", ctx_size, &CTX_SIZE_RANGE);
+    save_settings(&settings)?;
+    Ok(settings.ctx_size)
+}
+
+/// Set GPU layers, clamped to `GPU_LAYERS_RANGE`; returns the stored value
+pub fn set_gpu_layers(gpu_layers: u32) -> Result<u32> {
+    let mut settings = load_settings()?;
+    settings.gpu_layers = clamp_setting("GPU layers", gpu_layers, &GPU_LAYERS_RANGE);
+    save_settings(&settings)?;
+    Ok(settings.gpu_layers)
+}
+
+/// Get the configured download proxy URL (None if unset or blank)
+pub fn get_proxy_url() -> Result<Option<String>> {
+    let settings = load_settings()?;
+    Ok(settings.proxy_url.filter(|url| !url.trim().is_empty()))
+}
+
+/// Set download proxy URL (None clears it)
+pub fn set_proxy_url(proxy_url: Option<String>) -> Result<()> {
+    if let Some(ref url) = proxy_url {
+        reqwest::Proxy::all(url.as_str())
+            .map_err(|e| anyhow::anyhow!("Invalid proxy URL '{}': {}", redact_proxy_url(url), e))?;
+    }
+    let mut settings = load_settings()?;
+    settings.proxy_url = proxy_url;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get hosts that bypass the download proxy
+pub fn get_proxy_no_proxy() -> Result<Vec<String>> {
+    let settings = load_settings()?;
+    Ok(settings.proxy_no_proxy)
+}
+
+/// Set hosts that bypass the download proxy (blank entries are dropped)
+pub fn set_proxy_no_proxy(no_proxy: Vec<String>) -> Result<()> {
+    let mut settings = load_settings()?;
+    settings.proxy_no_proxy = no_proxy
+        .into_iter()
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .collect();
     save_settings(&settings)?;
     Ok(())
 }
 
-/// Set GPU layers
-pub fn set_gpu_layers(gpu_layers: u32) -> Result<()> {
+/// Get whether downloads connect over IPv4 only
+pub fn get_prefer_ipv4() -> Result<bool> {
+    let settings = load_settings()?;
+    Ok(settings.prefer_ipv4)
+}
+
+/// Set whether downloads connect over IPv4 only
+pub fn set_prefer_ipv4(enabled: bool) -> Result<()> {
     let mut settings = load_settings()?;
-    settings.gpu_layers = gpu_layers;
+    settings.prefer_ipv4 = enabled;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get the fixed addresses of download hosts
+pub fn get_dns_overrides() -> Result<HashMap<String, IpAddr>> {
+    let settings = load_settings()?;
+    Ok(settings.dns_overrides)
+}
+
+/// Pin a download host to an address, or remove its override (when `ip` is None)
+pub fn set_dns_override(host: &str, ip: Option<IpAddr>) -> Result<()> {
+    let host = host.trim().to_lowercase();
+    if host.is_empty() {
+        anyhow::bail!("Host name must not be empty");
+    }
+    let mut settings = load_settings()?;
+    match ip {
+        Some(ip) => settings.dns_overrides.insert(host, ip),
+        None => settings.dns_overrides.remove(&host),
+    };
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get download speed limit in megabits per second (None = unlimited)
+pub fn get_download_speed_limit_mbps() -> Result<Option<u32>> {
+    let settings = load_settings()?;
+    Ok(settings.download_speed_limit_mbps.filter(|&limit| limit > 0))
+}
+
+/// Set download speed limit in megabits per second (None or 0 = unlimited)
+pub fn set_download_speed_limit_mbps(limit: Option<u32>) -> Result<()> {
+    let mut settings = load_settings()?;
+    settings.download_speed_limit_mbps = limit.filter(|&limit| limit > 0);
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Set download retry count and backoff bounds
+pub fn set_download_retry_policy(
+    max_retries: u32,
+    base_delay_ms: u64,
+    max_delay_ms: u64,
+) -> Result<()> {
+    if max_retries == 0 {
+        anyhow::bail!("Retry count must be at least 1");
+    }
+    if base_delay_ms > max_delay_ms {
+        anyhow::bail!(
+            "Base retry delay ({} ms) must not exceed maximum retry delay ({} ms)",
+            base_delay_ms,
+            max_delay_ms
+        );
+    }
+    let mut settings = load_settings()?;
+    settings.download_max_retries = max_retries;
+    settings.download_base_retry_delay_ms = base_delay_ms;
+    settings.download_max_retry_delay_ms = max_delay_ms;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Set how long a download may receive no data before reconnecting, in seconds
+pub fn set_download_stall_timeout_secs(timeout_secs: u64) -> Result<()> {
+    if timeout_secs == 0 {
+        anyhow::bail!("Stall timeout must be at least 1 second");
+    }
+    let mut settings = load_settings()?;
+    settings.download_stall_timeout_secs = timeout_secs;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get the absolute cap on data extracted from one archive, in GB
+pub fn get_max_extract_size_gb() -> Result<u64> {
+    let settings = load_settings()?;
+    Ok(settings.max_extract_size_gb)
+}
+
+/// Set the absolute cap on data extracted from one archive, in GB
+pub fn set_max_extract_size_gb(size_gb: u64) -> Result<()> {
+    if size_gb == 0 {
+        anyhow::bail!("Extraction size limit must be at least 1 GB");
+    }
+    let mut settings = load_settings()?;
+    settings.max_extract_size_gb = size_gb;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get how long start_server waits for the server to become ready, in seconds
+pub fn get_server_start_timeout_secs() -> Result<u64> {
+    let settings = load_settings()?;
+    Ok(settings.server_start_timeout_secs)
+}
+
+/// Set how long start_server waits for the server to become ready, in seconds
+pub fn set_server_start_timeout_secs(timeout_secs: u64) -> Result<()> {
+    if timeout_secs == 0 {
+        anyhow::bail!("Server start timeout must be at least 1 second");
+    }
+    let mut settings = load_settings()?;
+    settings.server_start_timeout_secs = timeout_secs;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get whether a crashed server is restarted automatically
+pub fn get_auto_restart() -> Result<bool> {
+    let settings = load_settings()?;
+    Ok(settings.auto_restart)
+}
+
+/// Set whether a crashed server is restarted automatically
+pub fn set_auto_restart(enabled: bool) -> Result<()> {
+    let mut settings = load_settings()?;
+    settings.auto_restart = enabled;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get how many automatic restarts are allowed within the crash-loop window
+pub fn get_max_auto_restarts() -> Result<u32> {
+    let settings = load_settings()?;
+    Ok(settings.max_auto_restarts)
+}
+
+/// Set how many automatic restarts are allowed within the crash-loop window
+pub fn set_max_auto_restarts(max_restarts: u32) -> Result<()> {
+    if max_restarts == 0 {
+        anyhow::bail!("Restart limit must be at least 1 (disable auto restart instead)");
+    }
+    let mut settings = load_settings()?;
+    settings.max_auto_restarts = max_restarts;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get how long a stopped server gets to exit before it is killed
+pub fn get_stop_grace_period() -> Result<Duration> {
+    let settings = load_settings()?;
+    Ok(Duration::from_secs(settings.stop_grace_period_secs))
+}
+
+/// Set how long a stopped server gets to exit before it is killed (0 = kill right away)
+pub fn set_stop_grace_period_secs(secs: u64) -> Result<()> {
+    if secs > 60 {
+        anyhow::bail!("Stop grace period must be at most 60 seconds");
+    }
+    let mut settings = load_settings()?;
+    settings.stop_grace_period_secs = secs;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get whether a free port is picked when the configured one is in use
+pub fn get_auto_select_port() -> Result<bool> {
+    let settings = load_settings()?;
+    Ok(settings.auto_select_port)
+}
+
+/// Set whether a free port is picked when the configured one is in use
+pub fn set_auto_select_port(enabled: bool) -> Result<()> {
+    let mut settings = load_settings()?;
+    settings.auto_select_port = enabled;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get whether llama-server may save slots to the kv-cache directory
+pub fn get_persist_kv_cache() -> Result<bool> {
+    let settings = load_settings()?;
+    Ok(settings.persist_kv_cache)
+}
+
+/// Set whether llama-server may save slots to the kv-cache directory
+pub fn set_persist_kv_cache(enabled: bool) -> Result<()> {
+    let mut settings = load_settings()?;
+    settings.persist_kv_cache = enabled;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get whether llama-server is started in embeddings mode
+pub fn get_embeddings() -> Result<bool> {
+    let settings = load_settings()?;
+    Ok(settings.embeddings)
+}
+
+/// Set whether llama-server is started in embeddings mode
+pub fn set_embeddings(enabled: bool) -> Result<()> {
+    let mut settings = load_settings()?;
+    settings.embeddings = enabled;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get the extra llama-server arguments, without empty entries
+pub fn get_extra_server_args() -> Result<Vec<String>> {
+    let settings = load_settings()?;
+    Ok(settings
+        .extra_server_args
+        .into_iter()
+        .map(|arg| arg.trim().to_string())
+        .filter(|arg| !arg.is_empty())
+        .collect())
+}
+
+/// Set the extra llama-server arguments; rejects arguments the app manages itself
+pub fn set_extra_server_args(args: Vec<String>) -> Result<()> {
+    let args: Vec<String> = args
+        .into_iter()
+        .map(|arg| arg.trim().to_string())
+        .filter(|arg| !arg.is_empty())
+        .collect();
+    validate_extra_server_args(&args)?;
+    let mut settings = load_settings()?;
+    settings.extra_server_args = args;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get the configured llama-server thread count (None = one per physical core)
+pub fn get_threads() -> Result<Option<u32>> {
+    let settings = load_settings()?;
+    Ok(settings.threads)
+}
+
+/// Set the llama-server thread count (None = one per physical core)
+pub fn set_threads(threads: Option<u32>) -> Result<()> {
+    if threads == Some(0) {
+        anyhow::bail!("Thread count must be at least 1");
+    }
+    let mut settings = load_settings()?;
+    settings.threads = threads;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get the number of llama-server parallel slots
+pub fn get_parallel_slots() -> Result<u32> {
+    let settings = load_settings()?;
+    Ok(settings.parallel_slots)
+}
+
+/// Set the number of llama-server parallel slots, checked against the context size
+pub fn set_parallel_slots(slots: u32) -> Result<()> {
+    let mut settings = load_settings()?;
+    validate_parallel_slots(slots, settings.ctx_size)?;
+    settings.parallel_slots = slots;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get the llama-server batch and micro-batch sizes
+pub fn get_batch_sizes() -> Result<(u32, u32)> {
+    let settings = load_settings()?;
+    Ok((settings.batch_size, settings.ubatch_size))
+}
+
+/// Set the llama-server batch and micro-batch sizes together, since one bounds the other
+pub fn set_batch_sizes(batch_size: u32, ubatch_size: u32) -> Result<()> {
+    validate_batch_sizes(batch_size, ubatch_size)?;
+    let mut settings = load_settings()?;
+    settings.batch_size = batch_size;
+    settings.ubatch_size = ubatch_size;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get the llama-server flash attention mode
+pub fn get_flash_attn() -> Result<FlashAttn> {
+    let settings = load_settings()?;
+    Ok(settings.flash_attn)
+}
+
+/// Set the llama-server flash attention mode
+pub fn set_flash_attn(mode: FlashAttn) -> Result<()> {
+    let mut settings = load_settings()?;
+    settings.flash_attn = mode;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Get the key llama-server requires on API requests
+pub fn get_api_key() -> Result<Option<String>> {
+    let settings = load_settings()?;
+    Ok(settings.api_key)
+}
+
+/// Set or clear the key llama-server requires on API requests
+pub fn set_api_key(api_key: Option<String>) -> Result<()> {
+    let api_key = api_key
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty());
+    let mut settings = load_settings()?;
+    settings.api_key = api_key;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+/// Replace the API key with a new random one (32 bytes, hex-encoded)
+pub fn generate_api_key() -> Result<()> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to generate API key: {}", e))?;
+    let api_key: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    set_api_key(Some(api_key))
+}
+
+/// Get the address llama-server listens on
+pub fn get_bind_host() -> Result<IpAddr> {
+    let settings = load_settings()?;
+    settings
+        .bind_host
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid server host '{}'", settings.bind_host))
+}
+
+/// Set the address llama-server listens on (an IP address, e.g. 127.0.0.1 or 0.0.0.0).
+/// Anything but loopback exposes the model to the network and needs `confirm_exposure`.
+pub fn set_bind_host(host: &str, confirm_exposure: bool) -> Result<IpAddr> {
+    let host: IpAddr = host.trim().parse().map_err(|_| {
+        anyhow::anyhow!("Server host must be an IP address such as 127.0.0.1 or 0.0.0.0")
+    })?;
+    if !host.is_loopback() && !confirm_exposure {
+        anyhow::bail!(
+            "Listening on {} makes the model reachable from the network; confirm to continue",
+            host
+        );
+    }
+    let mut settings = load_settings()?;
+    settings.bind_host = host.to_string();
+    save_settings(&settings)?;
+    Ok(host)
+}
+
+/// Get the configured models directory (None = default inside the app data dir)
+pub fn get_models_dir() -> Result<Option<PathBuf>> {
+    let settings = load_settings()?;
+    Ok(settings.models_dir)
+}
+
+/// Set the models directory without moving anything; it must be an existing directory
+pub fn set_models_dir(models_dir: Option<PathBuf>) -> Result<()> {
+    if let Some(dir) = &models_dir {
+        if !dir.is_dir() {
+            anyhow::bail!("Models directory does not exist: {:?}", dir);
+        }
+    }
+    let mut settings = load_settings()?;
+    settings.models_dir = models_dir;
     save_settings(&settings)?;
     Ok(())
 }
@@ -125,21 +697,352 @@ pub async fn get_settings_command() -> Result<AppSettings, String> {
     load_settings().map_err(|e| e.to_string())
 }
 
+/// Returns the stored port, which may have been clamped
 #[tauri::command]
-pub async fn set_port_command(port: u16) -> Result<String, String> {
-    set_port(port).map_err(|e| e.to_string())?;
-    Ok(format!("Port set to: {}", port))
+pub async fn set_port_command(port: u16) -> Result<u16, String> {
+    set_port(port).map_err(|e| e.to_string())
 }
 
+/// Returns the stored context size, which may have been clamped
 #[tauri::command]
-pub async fn set_ctx_size_command(ctx_size: u32) -> Result<String, String> {
-    set_ctx_size(ctx_size).map_err(|e| e.to_string())?;
-    Ok(format!("Context size set to: {}", ctx_size))
+pub async fn set_ctx_size_command(ctx_size: u32) -> Result<u32, String> {
+    set_ctx_size(ctx_size).map_err(|e| e.to_string())
 }
 
+/// Returns the stored GPU layers, which may have been clamped
 #[tauri::command]
-pub async fn set_gpu_layers_command(gpu_layers: u32) -> Result<String, String> {
-    set_gpu_layers(gpu_layers).map_err(|e| e.to_string())?;
-    Ok(format!("GPU layers set to: {}", gpu_layers))
+pub async fn set_gpu_layers_command(gpu_layers: u32) -> Result<u32, String> {
+    set_gpu_layers(gpu_layers).map_err(|e| e.to_string())
 }
 
+
+#[tauri::command]
+pub async fn set_proxy_url_command(proxy_url: Option<String>) -> Result<String, String> {
+    let proxy_url = proxy_url.filter(|url| !url.trim().is_empty());
+    set_proxy_url(proxy_url.clone()).map_err(|e| e.to_string())?;
+    Ok(match proxy_url {
+        Some(url) => format!("Proxy set to: {}", redact_proxy_url(&url)),
+        None => "Proxy cleared".to_string(),
+    })
+}
+
+#[tauri::command]
+pub async fn set_prefer_ipv4_command(enabled: bool) -> Result<String, String> {
+    set_prefer_ipv4(enabled).map_err(|e| e.to_string())?;
+    Ok(if enabled {
+        "Downloads will connect over IPv4 only".to_string()
+    } else {
+        "Downloads will use IPv4 and IPv6".to_string()
+    })
+}
+
+#[tauri::command]
+pub async fn set_dns_override_command(host: String, ip: Option<String>) -> Result<String, String> {
+    let ip = ip
+        .map(|ip| {
+            ip.trim()
+                .parse::<IpAddr>()
+                .map_err(|_| format!("Invalid IP address: {}", ip))
+        })
+        .transpose()?;
+    set_dns_override(&host, ip).map_err(|e| e.to_string())?;
+    Ok(match ip {
+        Some(ip) => format!("{} now resolves to {}", host.trim(), ip),
+        None => format!("DNS override for {} removed", host.trim()),
+    })
+}
+
+#[tauri::command]
+pub async fn set_proxy_no_proxy_command(no_proxy: Vec<String>) -> Result<String, String> {
+    set_proxy_no_proxy(no_proxy).map_err(|e| e.to_string())?;
+    let no_proxy = get_proxy_no_proxy().map_err(|e| e.to_string())?;
+    Ok(if no_proxy.is_empty() {
+        "Proxy bypass list cleared".to_string()
+    } else {
+        format!("Proxy bypass list set to: {}", no_proxy.join(", "))
+    })
+}
+
+#[tauri::command]
+pub async fn set_download_retry_policy_command(
+    max_retries: u32,
+    base_delay_ms: u64,
+    max_delay_ms: u64,
+) -> Result<String, String> {
+    set_download_retry_policy(max_retries, base_delay_ms, max_delay_ms)
+        .map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Download retries set to: {} (backoff {} ms to {} ms)",
+        max_retries, base_delay_ms, max_delay_ms
+    ))
+}
+
+#[tauri::command]
+pub async fn set_download_stall_timeout_command(timeout_secs: u64) -> Result<String, String> {
+    set_download_stall_timeout_secs(timeout_secs).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Download stall timeout set to: {} seconds",
+        timeout_secs
+    ))
+}
+
+#[tauri::command]
+pub async fn set_download_speed_limit_command(limit_mbps: Option<u32>) -> Result<String, String> {
+    set_download_speed_limit_mbps(limit_mbps).map_err(|e| e.to_string())?;
+    Ok(match limit_mbps.filter(|&limit| limit > 0) {
+        Some(limit) => format!("Download speed limit set to: {} Mbps", limit),
+        None => "Download speed limit removed".to_string(),
+    })
+}
+
+#[tauri::command]
+pub async fn set_max_extract_size_command(size_gb: u64) -> Result<String, String> {
+    set_max_extract_size_gb(size_gb).map_err(|e| e.to_string())?;
+    Ok(format!("Extraction size limit set to: {} GB", size_gb))
+}
+
+#[tauri::command]
+pub async fn set_server_start_timeout_command(timeout_secs: u64) -> Result<String, String> {
+    set_server_start_timeout_secs(timeout_secs).map_err(|e| e.to_string())?;
+    Ok(format!("Server start timeout set to: {} seconds", timeout_secs))
+}
+
+#[tauri::command]
+pub async fn set_auto_restart_command(enabled: bool) -> Result<String, String> {
+    set_auto_restart(enabled).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Automatic server restart {}",
+        if enabled { "enabled" } else { "disabled" }
+    ))
+}
+
+#[tauri::command]
+pub async fn set_max_auto_restarts_command(max_restarts: u32) -> Result<String, String> {
+    set_max_auto_restarts(max_restarts).map_err(|e| e.to_string())?;
+    Ok(format!("Automatic restart limit set to: {}", max_restarts))
+}
+
+#[tauri::command]
+pub async fn set_stop_grace_period_command(secs: u64) -> Result<String, String> {
+    set_stop_grace_period_secs(secs).map_err(|e| e.to_string())?;
+    Ok(format!("Server stop grace period set to: {} seconds", secs))
+}
+
+/// Takes effect the next time the server starts
+#[tauri::command]
+pub async fn set_embeddings_mode(enabled: bool) -> Result<String, String> {
+    set_embeddings(enabled).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Embeddings mode {}",
+        if enabled { "enabled" } else { "disabled" }
+    ))
+}
+
+#[tauri::command]
+pub async fn set_auto_select_port_command(enabled: bool) -> Result<String, String> {
+    set_auto_select_port(enabled).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Automatic port selection {}",
+        if enabled { "enabled" } else { "disabled" }
+    ))
+}
+
+#[tauri::command]
+pub async fn set_persist_kv_cache_command(enabled: bool) -> Result<String, String> {
+    set_persist_kv_cache(enabled).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "KV cache persistence {} (applies on the next server start)",
+        if enabled { "enabled" } else { "disabled" }
+    ))
+}
+
+#[tauri::command]
+pub async fn set_threads_command(threads: Option<u32>) -> Result<String, String> {
+    set_threads(threads).map_err(|e| e.to_string())?;
+    Ok(match threads {
+        Some(threads) => format!("Server threads set to: {}", threads),
+        None => "Server threads set to one per physical core".to_string(),
+    })
+}
+
+/// Takes effect the next time the server starts
+#[tauri::command]
+pub async fn set_api_key_command(api_key: Option<String>) -> Result<String, String> {
+    let enabled = api_key.as_deref().is_some_and(|key| !key.trim().is_empty());
+    set_api_key(api_key).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Server API key {}",
+        if enabled { "set" } else { "removed" }
+    ))
+}
+
+/// Anything but loopback makes the model reachable from other devices: it must be
+/// confirmed with `confirm_exposure`, an API key is generated when none is set, the
+/// result carries a warning and `server-exposed` is emitted
+#[tauri::command]
+pub async fn set_bind_host_command(
+    app: AppHandle,
+    host: String,
+    confirm_exposure: bool,
+) -> Result<String, String> {
+    let host = set_bind_host(&host, confirm_exposure).map_err(|e| e.to_string())?;
+    if host.is_loopback() {
+        return Ok(format!("Server host set to: {}", host));
+    }
+
+    // Other devices must authenticate
+    let key_generated = get_api_key().map_err(|e| e.to_string())?.is_none();
+    if key_generated {
+        generate_api_key().map_err(|e| e.to_string())?;
+    }
+    log::warn!(
+        "Server host set to {}: the model will be reachable from the network",
+        host
+    );
+    let _ = app.emit(
+        "server-exposed",
+        serde_json::json!({
+            "host": host.to_string(),
+            "all_interfaces": host.is_unspecified(),
+            "api_key_generated": key_generated,
+        }),
+    );
+
+    let mut message = format!(
+        "WARNING: server host set to {}. From the next start, the model is reachable by {}.",
+        host,
+        if host.is_unspecified() {
+            "every device on your network"
+        } else {
+            "other devices on that network"
+        }
+    );
+    if key_generated {
+        message.push_str(" An API key was generated; clients must send it to connect.");
+    }
+    Ok(message)
+}
+
+#[tauri::command]
+pub async fn set_parallel_slots_command(slots: u32) -> Result<String, String> {
+    set_parallel_slots(slots).map_err(|e| e.to_string())?;
+    Ok(format!("Parallel slots set to: {}", slots))
+}
+
+/// Takes effect the next time the server starts
+#[tauri::command]
+pub async fn set_batch_sizes_command(batch_size: u32, ubatch_size: u32) -> Result<String, String> {
+    set_batch_sizes(batch_size, ubatch_size).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Batch size set to: {} (micro-batch {})",
+        batch_size, ubatch_size
+    ))
+}
+
+/// Takes effect the next time the server starts
+#[tauri::command]
+pub async fn set_flash_attn_command(mode: FlashAttn) -> Result<String, String> {
+    set_flash_attn(mode).map_err(|e| e.to_string())?;
+    Ok(format!("Flash attention set to: {}", mode.as_arg()))
+}
+
+#[tauri::command]
+pub async fn set_extra_server_args_command(args: Vec<String>) -> Result<String, String> {
+    set_extra_server_args(args).map_err(|e| e.to_string())?;
+    Ok("Extra server arguments saved; they apply on the next server start".to_string())
+}
+
+/// Point model storage at another directory. Existing models are not moved
+/// (use `migrate_models` for that), so they will need to be downloaded again.
+#[tauri::command]
+pub async fn set_models_dir_command(models_dir: Option<String>) -> Result<String, String> {
+    let models_dir = models_dir
+        .map(|dir| dir.trim().to_string())
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from);
+    set_models_dir(models_dir.clone()).map_err(|e| e.to_string())?;
+    Ok(match models_dir {
+        Some(dir) => format!("Models directory set to: {}", dir.display()),
+        None => "Models directory reset to default".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings_with_model(model: &str) -> AppSettings {
+        AppSettings {
+            active_model: model.to_string(),
+            ..AppSettings::default()
+        }
+    }
+
+    /// Save `model` twice, so the first save becomes the backup, then cut the file short
+    fn write_truncated_settings(settings_path: &Path, model: &str) {
+        save_settings_to(settings_path, &settings_with_model(model)).unwrap();
+        save_settings_to(settings_path, &settings_with_model("latest")).unwrap();
+        let content = fs::read_to_string(settings_path).unwrap();
+        fs::write(settings_path, &content[..content.len() / 2]).unwrap();
+    }
+
+    #[test]
+    fn truncated_settings_are_restored_from_the_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings_path = dir.path().join("settings.json");
+        write_truncated_settings(&settings_path, "backup-model");
+
+        let settings = load_settings_from(&settings_path).unwrap();
+
+        assert_eq!(settings.active_model, "backup-model");
+        // The restored settings are saved, the unreadable file kept aside
+        let (saved, _) = parse_settings(&fs::read_to_string(&settings_path).unwrap()).unwrap();
+        assert_eq!(saved.active_model, "backup-model");
+        assert!(parse_settings(
+            &fs::read_to_string(settings_path.with_extension("json.corrupt")).unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn truncated_settings_fall_back_to_defaults_without_a_usable_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings_path = dir.path().join("settings.json");
+        write_truncated_settings(&settings_path, "backup-model");
+        fs::write(
+            get_settings_backup_path(&settings_path),
+            "{\"active_model\":",
+        )
+        .unwrap();
+
+        let settings = load_settings_from(&settings_path).unwrap();
+
+        assert_eq!(
+            settings.active_model,
+            create_default_settings().active_model
+        );
+        assert_ne!(settings.active_model, "backup-model");
+        let (saved, _) = parse_settings(&fs::read_to_string(&settings_path).unwrap()).unwrap();
+        assert_eq!(saved.active_model, settings.active_model);
+    }
+
+    #[test]
+    fn migrated_settings_keep_the_previous_file_as_the_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings_path = dir.path().join("settings.json");
+        let mut old = serde_json::to_value(settings_with_model("old-model")).unwrap();
+        let old = old.as_object_mut().unwrap();
+        old.remove("schema_version");
+        old.insert("max_download_bytes_per_sec".to_string(), 1_250_000.into());
+        let content = serde_json::to_string_pretty(old).unwrap();
+        fs::write(&settings_path, &content).unwrap();
+
+        let settings = load_settings_from(&settings_path).unwrap();
+
+        assert_eq!(settings.schema_version, SETTINGS_SCHEMA_VERSION);
+        assert_eq!(
+            fs::read_to_string(get_settings_backup_path(&settings_path)).unwrap(),
+            content
+        );
+    }
+}
@@ -1,8 +1,24 @@
-use crate::paths::{get_app_data_dir, get_bin_dir, get_models_root_dir};
-use crate::types::{RecommendedSettings, ServerState};
+use crate::download::{
+    configured_model_names, declared_model_dimensions, recommended_embeddings_model,
+};
+use crate::ipc_state::read_ipc_state;
+use crate::paths::{
+    dir_size, get_app_data_dir, get_bin_dir, get_kv_cache_dir, get_logs_dir, get_model_file_path,
+    get_models_root_dir, gguf_block_count, gguf_model_size,
+};
+use crate::server_manager::{
+    check_server_running, release_server_files, stop_server_by_pid, MIN_SLOT_CTX_SIZE,
+};
+use crate::settings::{get_models_dir, set_models_dir};
+use crate::types::{
+    ModelsMigrationProgress, RecommendedSettings, ServerState, StorageInfo, StorageUsage,
+};
 use std::fs;
-use sysinfo::System;
-use tauri::State;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use sysinfo::{Disks, System};
+use tauri::{AppHandle, Emitter, Manager, State};
 
 #[tauri::command]
 pub fn get_app_data_path() -> Result<String, String> {
@@ -13,8 +29,8 @@ pub fn get_app_data_path() -> Result<String, String> {
 
 #[tauri::command]
 pub fn get_logs_path() -> Result<String, String> {
-    get_app_data_dir()
-        .map(|p| p.join("logs").to_string_lossy().to_string())
+    get_logs_dir()
+        .map(|p| p.to_string_lossy().to_string())
         .map_err(|e| e.to_string())
 }
 
@@ -29,27 +45,43 @@ pub fn get_system_memory_gb() -> Result<u64, String> {
     Ok(total_memory_gb)
 }
 
-// ============================================================================
-// GPU Detection (Windows only)
-// ============================================================================
+/// Number of logical CPUs (1 if unknown)
+pub fn logical_cpu_count() -> u32 {
+    std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(1)
+}
 
-#[cfg(target_os = "windows")]
-#[derive(Debug)]
-struct GpuInfo {
-    has_nvidia: bool,
-    vram_gb: u64,
-    is_10xx_series: bool,
+/// Default llama-server thread count: one per physical core.
+/// Hyper-threads share execution units, so using them slows token generation down.
+pub fn recommended_thread_count() -> u32 {
+    let physical = System::new()
+        .physical_core_count()
+        .map(|n| n as u32)
+        .filter(|&n| n > 0);
+    physical.unwrap_or_else(logical_cpu_count)
 }
 
-#[cfg(target_os = "windows")]
-impl Default for GpuInfo {
-    fn default() -> Self {
-        Self {
-            has_nvidia: false,
-            vram_gb: 0,
-            is_10xx_series: false,
-        }
-    }
+// ============================================================================
+// GPU Detection (Windows and Linux)
+// ============================================================================
+
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+#[derive(Debug, Default, Clone, Copy)]
+enum GpuInfo {
+    #[default]
+    None,
+    // Only detected on Windows
+    #[cfg_attr(target_os = "linux", allow(dead_code))]
+    /// `vram_gb` is the largest single card, `total_vram_gb` the sum over usable cards
+    Nvidia {
+        vram_gb: u64,
+        total_vram_gb: u64,
+        is_10xx_series: bool,
+    },
+    AmdGpu {
+        vram_gb: u64,
+    },
 }
 
 #[cfg(target_os = "windows")]
@@ -65,11 +97,17 @@ fn detect_10xx_series(output: &str) -> bool {
 }
 
 #[cfg(target_os = "windows")]
-fn parse_vram_from_wmic(output_str: &str) -> Option<u64> {
-    // wmic output format: "AdapterRAM  Name"
+fn is_amd_adapter_name(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    lower.contains("radeon") || lower.contains("amd")
+}
+
+#[cfg(target_os = "windows")]
+fn parse_vram_from_wmic(output_str: &str, is_vendor_line: fn(&str) -> bool) -> Option<u64> {
+    // wmic output format (also produced by the PowerShell fallback): "AdapterRAM  Name"
     // Example: "8589934592  NVIDIA GeForce GTX 1070"
     for line in output_str.lines() {
-        if line.contains("nvidia") || line.contains("NVIDIA") {
+        if is_vendor_line(line) {
             // Split line and find first numeric value
             let parts: Vec<&str> = line.split_whitespace().collect();
             
@@ -92,7 +130,7 @@ fn parse_vram_from_wmic(output_str: &str) -> Option<u64> {
 }
 
 #[cfg(target_os = "windows")]
-fn try_detect_via_wmic() -> Option<GpuInfo> {
+fn query_video_controllers_via_wmic() -> Option<String> {
     use std::os::windows::process::CommandExt;
     use std::process::Command;
 
@@ -103,24 +141,97 @@ fn try_detect_via_wmic() -> Option<GpuInfo> {
         .output()
         .ok()?;
 
-    let output_str = String::from_utf8(output.stdout).ok()?;
-    let lower_output = output_str.to_lowercase();
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Same query through CIM, for Windows 11 images where wmic has been removed.
+/// Prints "AdapterRAM Name" per adapter so the wmic parsing applies unchanged.
+#[cfg(target_os = "windows")]
+fn query_video_controllers_via_powershell() -> Option<String> {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
 
-    if !lower_output.contains("nvidia") {
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let output = Command::new("powershell")
+        .args(&[
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "Get-CimInstance Win32_VideoController | ForEach-Object { \"$($_.AdapterRAM) $($_.Name)\" }",
+        ])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
         return None;
     }
+    String::from_utf8(output.stdout).ok()
+}
+
+#[cfg(target_os = "windows")]
+fn query_video_controllers() -> Option<String> {
+    query_video_controllers_via_wmic().or_else(|| {
+        log::info!("wmic unavailable, querying video controllers via PowerShell CIM");
+        let output = query_video_controllers_via_powershell();
+        if output.is_none() {
+            log::warn!("Failed to query video controllers via wmic and PowerShell");
+        }
+        output
+    })
+}
+
+/// Cards with less memory than this (display-only or iGPU-like devices) are not
+/// counted towards the total, llama.cpp would only slow down splitting layers onto them
+#[cfg(target_os = "windows")]
+const MIN_SPLIT_GPU_VRAM_MB: u64 = 2048;
+
+/// VRAM reported by nvidia-smi, one line per GPU
+#[cfg(target_os = "windows")]
+struct NvidiaVram {
+    max_gb: u64,
+    total_gb: u64,
+}
+
+/// Parse `memory.total` lines (MiB) into the largest card and the total over usable cards
+#[cfg(target_os = "windows")]
+fn parse_nvidia_smi_memory(output: &str) -> Option<NvidiaVram> {
+    let cards: Vec<u64> = output
+        .lines()
+        .filter_map(|line| line.trim().parse::<u64>().ok())
+        .collect();
+    let max_mb = cards.iter().copied().max()?;
 
-    let gpu_info = GpuInfo {
-        has_nvidia: true,
-        is_10xx_series: detect_10xx_series(&output_str),
-        vram_gb: parse_vram_from_wmic(&output_str).unwrap_or(0),
+    let usable: Vec<u64> = cards
+        .iter()
+        .copied()
+        .filter(|&mb| mb >= MIN_SPLIT_GPU_VRAM_MB)
+        .collect();
+    if usable.len() < cards.len() {
+        log::info!(
+            "Ignoring {} Nvidia GPU(s) with less than {} MB VRAM",
+            cards.len() - usable.len(),
+            MIN_SPLIT_GPU_VRAM_MB
+        );
+    }
+    // Only small cards: the largest one is all there is
+    let total_mb = if usable.is_empty() {
+        max_mb
+    } else {
+        usable.iter().sum()
     };
 
-    Some(gpu_info)
+    Some(NvidiaVram {
+        max_gb: max_mb / 1024,
+        total_gb: total_mb / 1024,
+    })
 }
 
 #[cfg(target_os = "windows")]
-fn try_detect_vram_via_nvidia_smi() -> Option<u64> {
+fn try_detect_vram_via_nvidia_smi() -> Option<NvidiaVram> {
     use std::os::windows::process::CommandExt;
     use std::process::Command;
 
@@ -135,43 +246,177 @@ fn try_detect_vram_via_nvidia_smi() -> Option<u64> {
         .ok()?;
 
     let output_str = String::from_utf8(output.stdout).ok()?;
-    let vram_mb = output_str.trim().parse::<u64>().ok()?;
-    Some(vram_mb / 1024)
+    parse_nvidia_smi_memory(&output_str)
 }
 
 #[cfg(target_os = "windows")]
-fn detect_nvidia_gpu() -> GpuInfo {
-    let mut gpu_info = try_detect_via_wmic().unwrap_or_default();
+fn detect_nvidia_gpu(wmic_output: Option<&str>) -> Option<GpuInfo> {
+    let mut has_nvidia = wmic_output.is_some_and(|o| o.to_lowercase().contains("nvidia"));
+    let is_10xx_series = wmic_output.is_some_and(detect_10xx_series);
+    let mut vram_gb = match wmic_output {
+        Some(output) if has_nvidia => {
+            parse_vram_from_wmic(output, |line| line.to_lowercase().contains("nvidia"))
+                .unwrap_or(0)
+        }
+        _ => 0,
+    };
 
+    let mut total_vram_gb = vram_gb;
     if let Some(vram) = try_detect_vram_via_nvidia_smi() {
-        if !gpu_info.has_nvidia {
-            gpu_info.has_nvidia = true;
-        }
+        has_nvidia = true;
 
-        if vram > 0 && (gpu_info.vram_gb == 0 || vram > gpu_info.vram_gb) {
-            if gpu_info.vram_gb > 0 && gpu_info.vram_gb != vram {
+        if vram.max_gb > 0 && (vram_gb == 0 || vram.max_gb > vram_gb) {
+            if vram_gb > 0 && vram_gb != vram.max_gb {
                 log::info!(
                     "nvidia-smi VRAM override: {}GB -> {}GB",
-                    gpu_info.vram_gb,
-                    vram
+                    vram_gb,
+                    vram.max_gb
                 );
             }
-            gpu_info.vram_gb = vram;
+            vram_gb = vram.max_gb;
         }
-    } else if gpu_info.has_nvidia && gpu_info.vram_gb == 0 {
-        log::warn!("Detected Nvidia GPU but failed to determine VRAM via wmic or nvidia-smi");
+        total_vram_gb = vram.total_gb.max(vram_gb);
+    } else if has_nvidia && vram_gb == 0 {
+        log::warn!("Detected Nvidia GPU but failed to determine VRAM via CIM or nvidia-smi");
     }
 
-    log::info!(
-        "GPU detection: has_nvidia={}, vram={}GB, is_10xx={}",
-        gpu_info.has_nvidia,
-        gpu_info.vram_gb,
-        gpu_info.is_10xx_series
-    );
+    has_nvidia.then_some(GpuInfo::Nvidia {
+        vram_gb,
+        total_vram_gb,
+        is_10xx_series,
+    })
+}
+
+/// Largest dedicated memory size reported by the display adapter driver keys.
+/// wmic's AdapterRAM is a 32-bit value capped at 4 GB, the registry has the real size.
+#[cfg(target_os = "windows")]
+fn try_detect_vram_via_registry() -> Option<u64> {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let output = Command::new("reg")
+        .args(&[
+            "query",
+            r"HKLM\SYSTEM\CurrentControlSet\Control\Class\{4d36e968-e325-11ce-bfc1-08002be10318}",
+            "/s",
+            "/v",
+            "HardwareInformation.qwMemorySize",
+        ])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+
+    // Example: "    HardwareInformation.qwMemorySize    REG_QWORD    0x400000000"
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    output_str
+        .lines()
+        .filter(|line| line.contains("qwMemorySize"))
+        .filter_map(|line| line.split_whitespace().last())
+        .filter_map(|value| u64::from_str_radix(value.trim_start_matches("0x"), 16).ok())
+        .max()
+        .map(|bytes| bytes / (1024 * 1024 * 1024))
+}
 
+#[cfg(target_os = "windows")]
+fn detect_amd_gpu(wmic_output: Option<&str>) -> Option<GpuInfo> {
+    let output = wmic_output?;
+    if !output.lines().any(is_amd_adapter_name) {
+        return None;
+    }
+
+    let vram_gb = try_detect_vram_via_registry()
+        .filter(|&vram| vram > 0)
+        .or_else(|| parse_vram_from_wmic(output, is_amd_adapter_name))
+        .unwrap_or(0);
+
+    Some(GpuInfo::AmdGpu { vram_gb })
+}
+
+#[cfg(target_os = "windows")]
+fn probe_gpu() -> GpuInfo {
+    let wmic_output = query_video_controllers();
+    let gpu_info = detect_nvidia_gpu(wmic_output.as_deref())
+        .or_else(|| detect_amd_gpu(wmic_output.as_deref()))
+        .unwrap_or_default();
+
+    log::info!("GPU detection: {:?}", gpu_info);
     gpu_info
 }
 
+/// VRAM of AMD cards driven by amdgpu, read from sysfs (None if no AMD card is present)
+#[cfg(target_os = "linux")]
+fn try_detect_amd_vram_via_sysfs() -> Option<u64> {
+    const AMD_PCI_VENDOR_ID: &str = "0x1002";
+
+    let cards = fs::read_dir("/sys/class/drm").ok()?;
+    let mut found = false;
+    let mut vram_bytes = 0u64;
+
+    for entry in cards.flatten() {
+        let device_dir = entry.path().join("device");
+        let vendor = fs::read_to_string(device_dir.join("vendor")).unwrap_or_default();
+        if vendor.trim() != AMD_PCI_VENDOR_ID {
+            continue;
+        }
+        found = true;
+
+        if let Some(bytes) = fs::read_to_string(device_dir.join("mem_info_vram_total"))
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+        {
+            vram_bytes = vram_bytes.max(bytes);
+        }
+    }
+
+    found.then_some(vram_bytes / (1024 * 1024 * 1024))
+}
+
+#[cfg(target_os = "linux")]
+fn try_detect_amd_vram_via_rocm_smi() -> Option<u64> {
+    let output = std::process::Command::new("rocm-smi")
+        .args(["--showmeminfo", "vram"])
+        .output()
+        .ok()?;
+
+    // Example: "GPU[0]          : VRAM Total Memory (B): 17163091968"
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    output_str
+        .lines()
+        .filter(|line| line.contains("VRAM Total Memory (B)"))
+        .filter_map(|line| line.rsplit(':').next())
+        .filter_map(|value| value.trim().parse::<u64>().ok())
+        .max()
+        .map(|bytes| bytes / (1024 * 1024 * 1024))
+}
+
+#[cfg(target_os = "linux")]
+fn probe_gpu() -> GpuInfo {
+    let gpu_info = match try_detect_amd_vram_via_sysfs() {
+        Some(vram_gb) if vram_gb > 0 => GpuInfo::AmdGpu { vram_gb },
+        Some(_) => GpuInfo::AmdGpu {
+            vram_gb: try_detect_amd_vram_via_rocm_smi().unwrap_or(0),
+        },
+        None => try_detect_amd_vram_via_rocm_smi()
+            .map(|vram_gb| GpuInfo::AmdGpu { vram_gb })
+            .unwrap_or_default(),
+    };
+
+    log::info!("GPU detection: {:?}", gpu_info);
+    gpu_info
+}
+
+/// Result of the first GPU probe. Probing spawns wmic/nvidia-smi (a console flicker on
+/// Windows), so it only runs again after `refresh_gpu_detection`.
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+static DETECTED_GPU: std::sync::Mutex<Option<GpuInfo>> = std::sync::Mutex::new(None);
+
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn detect_gpu() -> GpuInfo {
+    let mut cached = DETECTED_GPU.lock().unwrap_or_else(|e| e.into_inner());
+    *cached.get_or_insert_with(probe_gpu)
+}
+
 // ============================================================================
 // Settings Calculation Helpers
 // ============================================================================
@@ -186,65 +431,255 @@ fn calculate_ctx_size_by_ram(memory_gb: u64) -> u32 {
     }
 }
 
+/// More memory usually means a faster machine that can serve several requests at once;
+/// each slot still needs at least `MIN_SLOT_CTX_SIZE` tokens of the context
+fn calculate_parallel_slots(memory_gb: u64, ctx_size: u32) -> u32 {
+    let slots = if memory_gb < 16 {
+        1
+    } else if memory_gb < 24 {
+        2
+    } else {
+        4
+    };
+    slots.min(ctx_size / MIN_SLOT_CTX_SIZE).max(1)
+}
+
+/// Large batches need large compute buffers, which machines with little memory lack
+fn calculate_batch_sizes(memory_gb: u64) -> (u32, u32) {
+    if memory_gb < 8 {
+        (512, 256)
+    } else if memory_gb < 16 {
+        (1024, 512)
+    } else {
+        (2048, 512)
+    }
+}
+
 // ============================================================================
 // Platform-specific Settings Logic
 // ============================================================================
 
+/// GPU layers offloaded when the whole model fits in VRAM, for models that do not
+/// declare `layer_count`
+const FULL_GPU_LAYERS: u32 = 41;
+
+/// KV cache and compute buffers, roughly 0.15 MB per context token
+const CONTEXT_GB_PER_TOKEN: f64 = 0.00015;
+
+/// VRAM kept free for the driver, the display and llama.cpp scratch buffers
+const VRAM_RESERVE_GB: f64 = 0.5;
+
+/// VRAM assumed for a GPU that does not report it (old drivers, some AMD cards)
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+const UNKNOWN_VRAM_GB: f64 = 4.0;
+
+/// Memory available to offloaded layers in GB, None without a usable GPU
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn gpu_memory_gb(_memory_gb: u64) -> Option<f64> {
+    match detect_gpu() {
+        GpuInfo::None => None,
+        _ => match detected_vram_gb() {
+            0 => Some(UNKNOWN_VRAM_GB),
+            vram_gb => Some(vram_gb as f64),
+        },
+    }
+}
+
+/// Apple Silicon GPUs share system memory: Metal can wire roughly two thirds of RAM,
+/// minus room for macOS and the browser
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+fn gpu_memory_gb(memory_gb: u64) -> Option<f64> {
+    const SYSTEM_RESERVE_GB: f64 = 3.0;
+
+    Some((memory_gb as f64 * 2.0 / 3.0 - SYSTEM_RESERVE_GB).max(0.0))
+}
+
+/// Intel Macs have no integrated Metal GPU worth offloading to
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "linux",
+    all(target_os = "macos", target_arch = "aarch64")
+)))]
+fn gpu_memory_gb(_memory_gb: u64) -> Option<f64> {
+    None
+}
+
+/// Size of a model's weights and its layers with a full offload: read from the downloaded
+/// .gguf, else `size_bytes` and `layer_count` from the configuration
+fn model_dimensions(
+    model_name: &str,
+    declared: (Option<u64>, Option<u32>),
+) -> (Option<u64>, Option<u32>) {
+    let path = get_model_file_path(model_name).ok();
+    let size_bytes = path
+        .as_deref()
+        .and_then(|path| gguf_model_size(path).ok())
+        .filter(|&size| size > 0);
+    // llama-server offloads the output layer on top of the repeating blocks
+    let layer_count = path
+        .as_deref()
+        .and_then(|path| gguf_block_count(path).ok().flatten())
+        .map(|block_count| block_count + 1);
+    (size_bytes.or(declared.0), layer_count.or(declared.1))
+}
+
+/// Layers that fit in `gpu_memory_gb` once the KV cache for `ctx_size` has its room.
+/// Each layer is assumed to cost an equal share of the weights.
+fn calculate_gpu_layers_fit(
+    gpu_memory_gb: f64,
+    model_size_bytes: u64,
+    layer_count: u32,
+    ctx_size: u32,
+) -> u32 {
+    let budget_gb = gpu_memory_gb - VRAM_RESERVE_GB - ctx_size as f64 * CONTEXT_GB_PER_TOKEN;
+    if budget_gb <= 0.0 {
+        return 0;
+    }
+    let layer_gb = model_size_bytes as f64 / 1_073_741_824.0 / layer_count.max(1) as f64;
+    ((budget_gb / layer_gb).floor() as u32).min(layer_count)
+}
+
+/// GPU layers to offload: as many as fit in `gpu_memory_gb`. Without a GPU whose memory
+/// is known (none, or one that is not detected, like NVIDIA cards on Linux) everything
+/// stays on the CPU; with an unknown model size the platform default stands.
+fn recommended_gpu_layers(
+    gpu_memory_gb: Option<f64>,
+    model_size_bytes: Option<u64>,
+    layer_count: u32,
+    ctx_size: u32,
+    platform_gpu_layers: u32,
+) -> u32 {
+    match (gpu_memory_gb, model_size_bytes) {
+        (Some(gpu_memory_gb), Some(model_size_bytes)) => {
+            calculate_gpu_layers_fit(gpu_memory_gb, model_size_bytes, layer_count, ctx_size)
+        }
+        (Some(_), None) => platform_gpu_layers.min(layer_count),
+        (None, _) => 0,
+    }
+}
+
+/// Settings for an AMD card: full offload of the full model when VRAM allows it
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn get_amd_settings(vram_gb: u64, memory_gb: u64) -> (String, u32, u32) {
+    if vram_gb < 7 {
+        ("model_s".to_string(), calculate_ctx_size_by_ram(memory_gb), 0)
+    } else {
+        ("model".to_string(), calculate_ctx_size_by_ram(memory_gb), FULL_GPU_LAYERS)
+    }
+}
+
+/// Returns (model, ctx_size, gpu_layers)
 #[cfg(target_os = "macos")]
-fn get_platform_settings(memory_gb: u64) -> (String, u32) {
+fn get_platform_settings(memory_gb: u64) -> (String, u32, u32) {
     let model = if memory_gb < 16 {
         "model_s".to_string()
     } else {
         "model".to_string()
     };
     let ctx = calculate_ctx_size_by_ram(memory_gb);
+    // Refined from the model size on Apple Silicon; Intel Macs keep everything on the CPU
+    #[cfg(target_arch = "aarch64")]
+    let gpu_layers = FULL_GPU_LAYERS;
+    #[cfg(not(target_arch = "aarch64"))]
+    let gpu_layers = 0;
     
     log::info!(
-        "[macOS] Settings: RAM={}GB, model={}, ctx={}",
-        memory_gb, model, ctx
+        "[macOS] Settings: RAM={}GB, model={}, ctx={}, gpu_layers={}",
+        memory_gb, model, ctx, gpu_layers
     );
     
-    (model, ctx)
+    (model, ctx, gpu_layers)
 }
 
+/// Returns (model, ctx_size, gpu_layers)
 #[cfg(target_os = "windows")]
-fn get_platform_settings(memory_gb: u64) -> (String, u32) {
-    let gpu_info = detect_nvidia_gpu();
-
-    let (model, ctx) = if !gpu_info.has_nvidia {
-        // No Nvidia GPU - use model_s with RAM-based settings
-        ("model_s".to_string(), calculate_ctx_size_by_ram(memory_gb))
-    } else if gpu_info.is_10xx_series {
-        // Nvidia 10XX series - always ctx 6000 regardless of VRAM
-        let model = if gpu_info.vram_gb < 7 {
-            "model_s".to_string()
-        } else {
-            "model".to_string()
-        };
-        (model, 12000)
-    } else if gpu_info.vram_gb < 7 {
-        // Nvidia GPU (non-10XX) with less than 8GB VRAM
-        ("model_s".to_string(), calculate_ctx_size_by_ram(memory_gb))
-    } else {
-        // Nvidia GPU (non-10XX) with 8GB+ VRAM
-        ("model".to_string(), calculate_ctx_size_by_ram(memory_gb))
+fn get_platform_settings(memory_gb: u64) -> (String, u32, u32) {
+    let gpu_info = detect_gpu();
+    if let GpuInfo::Nvidia {
+        vram_gb,
+        total_vram_gb,
+        ..
+    } = gpu_info
+    {
+        if total_vram_gb > vram_gb {
+            log::info!(
+                "Multiple Nvidia GPUs: largest card {}GB, {}GB combined",
+                vram_gb,
+                total_vram_gb
+            );
+        }
+    }
+
+    let (model, ctx, gpu_layers) = match gpu_info {
+        GpuInfo::None => {
+            // No discrete GPU - use model_s with RAM-based settings
+            ("model_s".to_string(), calculate_ctx_size_by_ram(memory_gb), FULL_GPU_LAYERS)
+        }
+        GpuInfo::Nvidia {
+            total_vram_gb,
+            is_10xx_series: true,
+            ..
+        } => {
+            // Nvidia 10XX series - always ctx 12000 regardless of VRAM
+            let model = if total_vram_gb < 7 {
+                "model_s".to_string()
+            } else {
+                "model".to_string()
+            };
+            (model, 12000, FULL_GPU_LAYERS)
+        }
+        // llama.cpp splits layers across GPUs, so the combined VRAM decides
+        GpuInfo::Nvidia { total_vram_gb, .. } if total_vram_gb < 7 => {
+            // Nvidia GPU (non-10XX) with less than 8GB VRAM
+            ("model_s".to_string(), calculate_ctx_size_by_ram(memory_gb), FULL_GPU_LAYERS)
+        }
+        GpuInfo::Nvidia { .. } => {
+            // Nvidia GPU (non-10XX) with 8GB+ VRAM
+            ("model".to_string(), calculate_ctx_size_by_ram(memory_gb), FULL_GPU_LAYERS)
+        }
+        GpuInfo::AmdGpu { vram_gb } => get_amd_settings(vram_gb, memory_gb),
     };
 
     log::info!(
-        "[Windows] Settings: RAM={}GB, GPU={}/{}GB/10xx={}, model={}, ctx={}",
+        "[Windows] Settings: RAM={}GB, GPU={:?}, model={}, ctx={}, gpu_layers={}",
         memory_gb,
-        if gpu_info.has_nvidia { "Nvidia" } else { "None" },
-        gpu_info.vram_gb,
-        gpu_info.is_10xx_series,
+        gpu_info,
         model,
-        ctx
+        ctx,
+        gpu_layers
     );
 
-    (model, ctx)
+    (model, ctx, gpu_layers)
 }
 
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
-fn get_platform_settings(memory_gb: u64) -> (String, u32) {
+/// Returns (model, ctx_size, gpu_layers)
+#[cfg(target_os = "linux")]
+fn get_platform_settings(memory_gb: u64) -> (String, u32, u32) {
+    let gpu_info = detect_gpu();
+
+    let (model, ctx, gpu_layers) = match gpu_info {
+        GpuInfo::AmdGpu { vram_gb } => get_amd_settings(vram_gb, memory_gb),
+        _ => {
+            let model = if memory_gb < 15 {
+                "model_s".to_string()
+            } else {
+                "model".to_string()
+            };
+            (model, calculate_ctx_size_by_ram(memory_gb), FULL_GPU_LAYERS)
+        }
+    };
+
+    log::info!(
+        "[Linux] Settings: RAM={}GB, GPU={:?}, model={}, ctx={}, gpu_layers={}",
+        memory_gb, gpu_info, model, ctx, gpu_layers
+    );
+
+    (model, ctx, gpu_layers)
+}
+
+/// Returns (model, ctx_size, gpu_layers)
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn get_platform_settings(memory_gb: u64) -> (String, u32, u32) {
     let model = if memory_gb < 15 {
         "model_s".to_string()
     } else {
@@ -257,7 +692,7 @@ fn get_platform_settings(memory_gb: u64) -> (String, u32) {
         memory_gb, model, ctx
     );
     
-    (model, ctx)
+    (model, ctx, FULL_GPU_LAYERS)
 }
 
 // ============================================================================
@@ -266,22 +701,64 @@ fn get_platform_settings(memory_gb: u64) -> (String, u32) {
 
 /// Get recommended settings based on system hardware (internal function)
 pub fn calculate_recommended_settings() -> Result<RecommendedSettings, String> {
+    recommend_settings(true)
+}
+
+/// Recommended settings for a settings.json that does not exist yet. Downloaded models are
+/// not looked at: finding them needs the models directory, which is read from settings.json.
+pub fn calculate_default_settings() -> Result<RecommendedSettings, String> {
+    recommend_settings(false)
+}
+
+fn recommend_settings(inspect_models: bool) -> Result<RecommendedSettings, String> {
     let memory_gb = get_system_memory_gb()?;
-    let (recommended_model, recommended_ctx_size) = get_platform_settings(memory_gb);
-    // Windows path assumes CUDA and full offload; macOS uses Metal with different limits.
-    // Intel Macs often cannot safely use GPU offload like Apple Silicon.
-    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-    let recommended_gpu_layers = 35_u32;
-    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
-    let recommended_gpu_layers = 0_u32;
-    #[cfg(not(target_os = "macos"))]
-    let recommended_gpu_layers = 41_u32;
+    let (recommended_model, recommended_ctx_size, platform_gpu_layers) =
+        get_platform_settings(memory_gb);
+    let (recommended_batch_size, recommended_ubatch_size) = calculate_batch_sizes(memory_gb);
+
+    let declared = declared_model_dimensions(&recommended_model);
+    let (model_size_bytes, layer_count) = if inspect_models {
+        model_dimensions(&recommended_model, declared)
+    } else {
+        declared
+    };
+    let model_layer_count = layer_count.unwrap_or(FULL_GPU_LAYERS);
+    let gpu_memory_gb = gpu_memory_gb(memory_gb);
+    let recommended_gpu_layers = recommended_gpu_layers(
+        gpu_memory_gb,
+        model_size_bytes,
+        model_layer_count,
+        recommended_ctx_size,
+        platform_gpu_layers,
+    );
+    log::info!(
+        "GPU layers: {}/{} of {} ({:?} bytes) with {:?}GB GPU memory and ctx={}",
+        recommended_gpu_layers,
+        model_layer_count,
+        recommended_model,
+        model_size_bytes,
+        gpu_memory_gb,
+        recommended_ctx_size
+    );
 
     Ok(RecommendedSettings {
         memory_gb,
         recommended_model,
         recommended_ctx_size,
         recommended_gpu_layers,
+        cpu_feature_level: cpu_feature_level().map(str::to_string),
+        recommended_threads: recommended_thread_count(),
+        recommended_parallel_slots: calculate_parallel_slots(memory_gb, recommended_ctx_size),
+        recommended_batch_size,
+        recommended_ubatch_size,
+        recommended_embeddings_model: if inspect_models {
+            recommended_embeddings_model()
+        } else {
+            None
+        },
+        gpu_memory_gb,
+        model_size_bytes,
+        model_layer_count,
     })
 }
 
@@ -290,26 +767,472 @@ pub fn get_recommended_settings() -> Result<RecommendedSettings, String> {
     calculate_recommended_settings()
 }
 
+/// Drop the cached GPU detection (e.g. after a driver install or plugging in an eGPU)
+/// and return the recommendations for the hardware detected now
+#[tauri::command]
+pub fn refresh_gpu_detection() -> Result<RecommendedSettings, String> {
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    {
+        *DETECTED_GPU.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        log::info!("GPU detection cache cleared");
+    }
+    calculate_recommended_settings()
+}
+
+/// Pick the llama.cpp build variant that best fits the detected GPU
+pub fn recommended_llama_variant() -> &'static str {
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    {
+        match detect_gpu() {
+            GpuInfo::Nvidia { .. } => return "cuda",
+            // Vulkan builds run on AMD cards without a ROCm install
+            GpuInfo::AmdGpu { .. } => return "vulkan",
+            GpuInfo::None => {}
+        }
+    }
+    // The default CPU build uses AVX2 and dies with an illegal instruction without it
+    match cpu_feature_level() {
+        Some("avx") => "cpu-avx",
+        Some("none") => "cpu-noavx",
+        _ => "cpu",
+    }
+}
+
+/// Highest x86 SIMD extension the CPU supports: "avx512", "avx2", "avx" or "none".
+/// None on other architectures, where llama.cpp builds do not depend on them.
+pub fn cpu_feature_level() -> Option<&'static str> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        let level = if is_x86_feature_detected!("avx512f") {
+            "avx512"
+        } else if is_x86_feature_detected!("avx2") {
+            "avx2"
+        } else if is_x86_feature_detected!("avx") {
+            "avx"
+        } else {
+            "none"
+        };
+        Some(level)
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        None
+    }
+}
+
 // ============================================================================
-// Process Management Helpers
+// Memory Pre-flight Check
 // ============================================================================
 
-fn stop_server_process(state: &State<'_, ServerState>) {
-    let mut process_guard = state.process.lock().unwrap();
-    if let Some(mut child) = process_guard.take() {
-        // On Unix, kill the entire process group
-        #[cfg(unix)]
+/// Dedicated VRAM of the detected GPU in GB (0 without a discrete GPU)
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn detected_vram_gb() -> u64 {
+    match detect_gpu() {
+        GpuInfo::Nvidia { total_vram_gb, .. } => total_vram_gb,
+        GpuInfo::AmdGpu { vram_gb } => vram_gb,
+        GpuInfo::None => 0,
+    }
+}
+
+/// Apple Silicon GPUs share system memory, other platforms have no detected VRAM
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn detected_vram_gb() -> u64 {
+    0
+}
+
+/// Fail when the model weights plus the KV cache for `ctx_size` clearly exceed
+/// system RAM plus the VRAM available to offloaded layers. llama-server would
+/// otherwise thrash or be OOM-killed, which looks like a random crash.
+pub fn check_model_fits_in_memory(
+    model_path: &Path,
+    ctx_size: u32,
+    gpu_layers: u32,
+) -> Result<(), String> {
+    let model_gb = gguf_model_size(model_path)
+        .map_err(|e| format!("Failed to read model file size: {}", e))? as f64
+        / 1_073_741_824.0;
+    let context_gb = ctx_size as f64 * CONTEXT_GB_PER_TOKEN;
+    let required_gb = model_gb + context_gb;
+
+    let memory_gb = get_system_memory_gb()?;
+    if required_gb <= memory_gb as f64 {
+        return Ok(());
+    }
+
+    // GPU detection spawns processes on Windows, so only look when RAM alone is not enough
+    let vram_gb = if gpu_layers > 0 { detected_vram_gb() } else { 0 };
+    let capacity_gb = (memory_gb + vram_gb) as f64;
+    log::info!(
+        "Memory check: model={:.1}GB, context={:.1}GB, RAM={}GB, VRAM={}GB",
+        model_gb, context_gb, memory_gb, vram_gb
+    );
+
+    if required_gb > capacity_gb {
+        return Err(format!(
+            "about {:.1} GB is needed ({:.1} GB weights + {:.1} GB context) but this system \
+             has {} GB RAM and {} GB VRAM. Choose a smaller model or context size, or start with \
+             force to try anyway.",
+            required_gb, model_gb, context_gb, memory_gb, vram_gb
+        ));
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// Storage Info
+// ============================================================================
+
+/// Disk space on the models volume and space used by downloaded models
+#[tauri::command]
+pub fn get_storage_info() -> Result<StorageInfo, String> {
+    let models_dir = get_models_root_dir().map_err(|e| e.to_string())?;
+    let models_dir = models_dir.canonicalize().unwrap_or(models_dir);
+
+    // Downloads land in the models directory, possibly outside the app data dir.
+    // Its volume is the disk with the longest matching mount point.
+    let disks = Disks::new_with_refreshed_list();
+    let disk = disks
+        .list()
+        .iter()
+        .filter(|disk| models_dir.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .ok_or_else(|| format!("Failed to find the volume containing {:?}", models_dir))?;
+
+    Ok(StorageInfo {
+        total_bytes: disk.total_space(),
+        free_bytes: disk.available_space(),
+        models_bytes: dir_size(&models_dir),
+    })
+}
+
+// ============================================================================
+// Storage Usage
+// ============================================================================
+
+/// A computed storage usage is reused for this long before walking the directories again
+const STORAGE_USAGE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+static STORAGE_USAGE_CACHE: std::sync::Mutex<Option<(Instant, StorageUsage)>> =
+    std::sync::Mutex::new(None);
+
+/// Forget the cached storage usage after files were removed or moved
+fn invalidate_storage_usage() {
+    *STORAGE_USAGE_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Log files and directories kept in the app data dir next to the Tauri log dir
+/// (native host log, server output snapshots)
+fn is_log_path(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    name == "logs" || name == "server_logs" || name == "server_logs.json" || name.ends_with(".log")
+}
+
+fn entry_size(entry: &fs::DirEntry) -> u64 {
+    match entry.file_type() {
+        Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+        _ => entry.metadata().map(|m| m.len()).unwrap_or(0),
+    }
+}
+
+fn compute_storage_usage(log_dir: Option<PathBuf>) -> Result<StorageUsage, String> {
+    let app_dir = get_app_data_dir().map_err(|e| e.to_string())?;
+    let bin_dir = get_bin_dir().map_err(|e| e.to_string())?;
+    let models_dir = get_models_root_dir().map_err(|e| e.to_string())?;
+
+    let mut models = Vec::new();
+    let mut other = 0;
+    let entries =
+        fs::read_dir(&models_dir).map_err(|e| format!("Failed to read models directory: {}", e))?;
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            models.push((name, dir_size(&entry.path())));
+        } else {
+            other += entry_size(&entry);
+        }
+    }
+    models.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let binaries = dir_size(&bin_dir);
+    let mut logs = log_dir.as_deref().map(dir_size).unwrap_or(0);
+    let kv_cache_dir = get_kv_cache_dir().map_err(|e| e.to_string())?;
+    let kv_cache = dir_size(&kv_cache_dir);
+
+    // Everything else in the app data dir: settings, state files, download leftovers.
+    // The models and log directories may or may not live inside it.
+    let entries =
+        fs::read_dir(&app_dir).map_err(|e| format!("Failed to read app data directory: {}", e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path == bin_dir
+            || path == models_dir
+            || path == kv_cache_dir
+            || log_dir.as_ref() == Some(&path)
+        {
+            continue;
+        }
+        if is_log_path(&path) {
+            logs += entry_size(&entry);
+        } else {
+            other += entry_size(&entry);
+        }
+    }
+
+    let models_total: u64 = models.iter().map(|(_, bytes)| bytes).sum();
+    Ok(StorageUsage {
+        total: models_total + binaries + logs + kv_cache + other,
+        models,
+        binaries,
+        logs,
+        kv_cache,
+        other,
+    })
+}
+
+/// Space used by each model, the llama.cpp binaries, logs and everything else the app
+/// stores. Cached for `STORAGE_USAGE_CACHE_TTL`, as walking large models is slow.
+#[tauri::command]
+pub async fn get_storage_usage(app: AppHandle) -> Result<StorageUsage, String> {
+    if let Some((computed_at, usage)) = STORAGE_USAGE_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+    {
+        if computed_at.elapsed() < STORAGE_USAGE_CACHE_TTL {
+            return Ok(usage.clone());
+        }
+    }
+
+    let log_dir = app.path().app_log_dir().ok();
+    let usage = tokio::task::spawn_blocking(move || compute_storage_usage(log_dir))
+        .await
+        .map_err(|e| format!("Storage usage task failed: {}", e))??;
+
+    *STORAGE_USAGE_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), usage.clone()));
+    Ok(usage)
+}
+
+// ============================================================================
+// Models Directory Migration
+// ============================================================================
+
+/// Bytes copied between two migration progress events
+const MIGRATION_PROGRESS_INTERVAL: u64 = 50 * 1024 * 1024;
+
+/// Reports migration progress as `models-migration-progress` events
+struct MigrationProgress<'a> {
+    app: &'a AppHandle,
+    total_bytes: u64,
+    moved_bytes: u64,
+    last_emit: u64,
+    current_model: String,
+}
+
+impl MigrationProgress<'_> {
+    fn add(&mut self, bytes: u64) {
+        self.moved_bytes += bytes;
+        if self.moved_bytes - self.last_emit >= MIGRATION_PROGRESS_INTERVAL
+            || self.moved_bytes >= self.total_bytes
         {
-            let pid = child.id() as i32;
-            unsafe {
-                libc::kill(-pid, libc::SIGTERM);
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                libc::kill(-pid, libc::SIGKILL);
+            self.emit();
+        }
+    }
+
+    fn emit(&mut self) {
+        self.last_emit = self.moved_bytes;
+        let _ = self.app.emit(
+            "models-migration-progress",
+            ModelsMigrationProgress {
+                moved_bytes: self.moved_bytes,
+                total_bytes: self.total_bytes,
+                percentage: if self.total_bytes > 0 {
+                    (self.moved_bytes as f64 / self.total_bytes as f64 * 100.0).min(100.0)
+                } else {
+                    100.0
+                },
+                current_model: self.current_model.clone(),
+            },
+        );
+    }
+}
+
+/// Recursively copy a directory, reporting copied bytes
+fn copy_dir_with_progress(
+    from: &Path,
+    to: &Path,
+    progress: &mut MigrationProgress,
+) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_with_progress(&entry.path(), &target, progress)?;
+            continue;
+        }
+
+        let mut reader = fs::File::open(entry.path())?;
+        let mut writer = fs::File::create(&target)?;
+        let mut buffer = vec![0u8; 1024 * 1024];
+        loop {
+            let bytes_read = reader.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
             }
+            writer.write_all(&buffer[..bytes_read])?;
+            progress.add(bytes_read as u64);
         }
+        writer.sync_all()?;
+    }
+    Ok(())
+}
+
+/// A model directory moved by `migrate_models`
+struct MovedModel {
+    from: PathBuf,
+    to: PathBuf,
+    /// Renamed in place (same volume) rather than copied
+    renamed: bool,
+}
 
-        let _ = child.kill();
-        let _ = child.wait();
+/// Undo a partial migration: rename moved directories back and drop copies
+fn rollback_migration(moved: &[MovedModel]) {
+    for model in moved {
+        let result = if model.renamed {
+            fs::rename(&model.to, &model.from)
+        } else {
+            fs::remove_dir_all(&model.to)
+        };
+        if let Err(e) = result {
+            log::error!("Failed to roll back migration of {:?}: {}", model.from, e);
+        }
+    }
+}
+
+fn migrate_models_blocking(new_dir: PathBuf, app: AppHandle) -> Result<String, String> {
+    if check_server_running().map_err(|e| e.to_string())?.is_some() {
+        return Err("Stop the LLM before moving models".to_string());
+    }
+    if read_ipc_state().map_err(|e| e.to_string())?.is_downloading {
+        return Err("Wait for downloads to finish before moving models".to_string());
+    }
+
+    let old_dir = get_models_root_dir().map_err(|e| e.to_string())?;
+    if !new_dir.is_dir() {
+        return Err(format!("Destination does not exist: {}", new_dir.display()));
+    }
+    let old_canonical = old_dir.canonicalize().map_err(|e| e.to_string())?;
+    let new_canonical = new_dir.canonicalize().map_err(|e| e.to_string())?;
+    if new_canonical == old_canonical {
+        return Err("Models are already stored in this directory".to_string());
+    }
+    if new_canonical.starts_with(&old_canonical) {
+        return Err("Destination must not be inside the current models directory".to_string());
+    }
+
+    let entries = model_dirs(&old_dir)?;
+    if let Some(conflict) = entries
+        .iter()
+        .filter_map(|path| path.file_name())
+        .find(|name| new_canonical.join(name).exists())
+    {
+        return Err(format!(
+            "Destination already contains a '{}' directory",
+            conflict.to_string_lossy()
+        ));
+    }
+
+    let mut progress = MigrationProgress {
+        app: &app,
+        total_bytes: entries.iter().map(|path| dir_size(path)).sum(),
+        moved_bytes: 0,
+        last_emit: 0,
+        current_model: String::new(),
+    };
+    log::info!(
+        "Moving {} models ({} bytes) from {:?} to {:?}",
+        entries.len(),
+        progress.total_bytes,
+        old_dir,
+        new_canonical
+    );
+
+    let mut moved = Vec::new();
+    for from in entries {
+        let name = from.file_name().unwrap_or_default().to_os_string();
+        let to = new_canonical.join(&name);
+        progress.current_model = name.to_string_lossy().to_string();
+        progress.emit();
+
+        // A rename is instant on the same volume; across volumes the data is copied
+        let size = dir_size(&from);
+        if fs::rename(&from, &to).is_ok() {
+            progress.add(size);
+            moved.push(MovedModel { from, to, renamed: true });
+            continue;
+        }
+        if let Err(e) = copy_dir_with_progress(&from, &to, &mut progress) {
+            let _ = fs::remove_dir_all(&to);
+            rollback_migration(&moved);
+            return Err(format!("Failed to copy {:?}: {}", from, e));
+        }
+        moved.push(MovedModel { from, to, renamed: false });
+    }
+
+    // Only switch over once every model is in place
+    if let Err(e) = set_models_dir(Some(new_canonical.clone())) {
+        rollback_migration(&moved);
+        return Err(format!("Failed to save models directory: {}", e));
+    }
+
+    for model in moved.iter().filter(|model| !model.renamed) {
+        if let Err(e) = fs::remove_dir_all(&model.from) {
+            log::warn!("Failed to remove old copy of {:?}: {}", model.from, e);
+        }
+    }
+
+    Ok(format!(
+        "Moved {} models to: {}",
+        moved.len(),
+        new_canonical.display()
+    ))
+}
+
+/// Move all model directories to `new_dir` and make it the configured models directory.
+/// The setting only changes after everything has been moved; a failure rolls back.
+#[tauri::command]
+pub async fn migrate_models(new_dir: String, app: AppHandle) -> Result<String, String> {
+    let new_dir = PathBuf::from(new_dir.trim());
+    let result = tokio::task::spawn_blocking(move || migrate_models_blocking(new_dir, app))
+        .await
+        .map_err(|e| format!("Model migration task failed: {}", e))?;
+    invalidate_storage_usage();
+    result
+}
+
+// ============================================================================
+// Process Management Helpers
+// ============================================================================
+
+fn stop_server_process(state: &State<'_, ServerState>) {
+    let mut servers = state.servers.lock().unwrap();
+    let embeddings = state.embeddings.lock().unwrap().take();
+    let all_servers = servers.drain().map(|(_, server)| server).chain(embeddings);
+    for mut server in all_servers {
+        // Gets the grace period to exit before its process group is killed
+        if let Err(e) = stop_server_by_pid(server.child.id()) {
+            log::warn!("Failed to stop server (PID: {}): {}", server.child.id(), e);
+        }
+        let _ = server.child.kill();
+        let _ = server.child.wait();
     }
 }
 
@@ -318,7 +1241,12 @@ fn stop_server_process(state: &State<'_, ServerState>) {
 // ============================================================================
 
 #[tauri::command]
-pub async fn clear_binaries(state: State<'_, ServerState>) -> Result<String, String> {
+pub async fn clear_binaries(
+    state: State<'_, ServerState>,
+    force: Option<bool>,
+) -> Result<String, String> {
+    // With force the server is stopped first, otherwise removing its binary fails
+    release_server_files("llama.cpp", None, force.unwrap_or(false)).map_err(|e| e.to_string())?;
     stop_server_process(&state);
 
     let bin_dir = get_bin_dir().map_err(|e| e.to_string())?;
@@ -328,27 +1256,90 @@ pub async fn clear_binaries(state: State<'_, ServerState>) -> Result<String, Str
             .map_err(|e| format!("Failed to remove bin directory: {}", e))?;
         log::info!("Removed bin directory: {:?}", bin_dir);
     }
+    invalidate_storage_usage();
 
     Ok("Binaries cleared successfully".to_string())
 }
 
-#[tauri::command]
-pub async fn clear_models() -> Result<String, String> {
-    let models_dir = get_models_root_dir().map_err(|e| e.to_string())?;
+/// Directories in `models_dir` that belong to a model: named after a configured model, or
+/// holding the `.verified` marker of one since dropped from the configuration. The models
+/// root may be a user-chosen folder, so nothing else in it is touched.
+fn model_dirs(models_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let configured = configured_model_names();
+    let entries =
+        fs::read_dir(models_dir).map_err(|e| format!("Failed to read models directory: {}", e))?;
+    Ok(entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str());
+            name.is_some_and(|name| configured.iter().any(|model| model == name))
+                || path.join(".verified").exists()
+        })
+        .collect())
+}
 
-    if models_dir.exists() {
-        fs::remove_dir_all(&models_dir)
-            .map_err(|e| format!("Failed to remove models directory: {}", e))?;
-        log::info!("Removed models directory: {:?}", models_dir);
+/// Remove every model directory, keeping the models root itself
+/// (it may be a user-chosen folder outside the app data dir)
+fn remove_model_dirs() -> Result<(), String> {
+    let models_dir = get_models_root_dir().map_err(|e| e.to_string())?;
+    for path in model_dirs(&models_dir)? {
+        fs::remove_dir_all(&path).map_err(|e| format!("Failed to remove {:?}: {}", path, e))?;
     }
+    log::info!("Removed models from: {:?}", models_dir);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn clear_models(force: Option<bool>) -> Result<String, String> {
+    release_server_files("The active model", None, force.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+    remove_model_dirs()?;
+    invalidate_storage_usage();
 
     Ok("Models cleared successfully".to_string())
 }
 
+/// Delete the slots llama-server saved with persist_kv_cache. Returns the space freed
+/// together with how the saved slots use disk space.
+#[tauri::command]
+pub async fn clear_kv_cache() -> Result<String, String> {
+    let kv_cache_dir = get_kv_cache_dir().map_err(|e| e.to_string())?;
+    let freed = dir_size(&kv_cache_dir);
+    let entries = fs::read_dir(&kv_cache_dir)
+        .map_err(|e| format!("Failed to read KV cache directory: {}", e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let result = if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        result.map_err(|e| format!("Failed to remove {:?}: {}", path, e))?;
+    }
+    log::info!("Cleared KV cache: {:?} ({} bytes)", kv_cache_dir, freed);
+    invalidate_storage_usage();
+
+    Ok(format!(
+        "Freed {:.1} MB of saved KV cache. Each saved conversation takes about {:.2} MB per \
+         context token it used, up to the full context size, and llama-server never deletes \
+         them on its own.",
+        freed as f64 / 1_048_576.0,
+        CONTEXT_GB_PER_TOKEN * 1024.0
+    ))
+}
+
 #[tauri::command]
 pub async fn clear_all_data(state: State<'_, ServerState>) -> Result<String, String> {
     stop_server_process(&state);
 
+    // Models may live outside the app data dir; clear them before settings are gone
+    if get_models_dir().ok().flatten().is_some() {
+        remove_model_dirs()?;
+    }
+
+    // Binaries, logs and the KV cache all live inside the app data dir
     let app_dir = get_app_data_dir().map_err(|e| e.to_string())?;
 
     if app_dir.exists() {
@@ -356,6 +1347,50 @@ pub async fn clear_all_data(state: State<'_, ServerState>) -> Result<String, Str
             .map_err(|e| format!("Failed to remove app data directory: {}", e))?;
         log::info!("Removed app data directory: {:?}", app_dir);
     }
+    invalidate_storage_usage();
 
     Ok("All data cleared successfully".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL_SIZE_BYTES: u64 = 4 * 1_073_741_824;
+
+    #[test]
+    fn no_gpu_keeps_every_layer_on_the_cpu() {
+        assert_eq!(
+            recommended_gpu_layers(None, Some(MODEL_SIZE_BYTES), 32, 8192, FULL_GPU_LAYERS),
+            0
+        );
+        assert_eq!(
+            recommended_gpu_layers(None, None, 32, 8192, FULL_GPU_LAYERS),
+            0
+        );
+    }
+
+    #[test]
+    fn gpu_with_unknown_vram_gets_a_partial_offload() {
+        // The VRAM assumed for a GPU that does not report it
+        let layers = recommended_gpu_layers(Some(4.0), Some(MODEL_SIZE_BYTES), 32, 8192, 32);
+        assert!(layers > 0 && layers < 32, "{}", layers);
+    }
+
+    #[test]
+    fn gpu_with_room_for_the_model_gets_every_layer() {
+        assert_eq!(
+            recommended_gpu_layers(Some(24.0), Some(MODEL_SIZE_BYTES), 32, 8192, 0),
+            32
+        );
+    }
+
+    #[test]
+    fn unknown_model_size_keeps_the_platform_default() {
+        assert_eq!(recommended_gpu_layers(Some(8.0), None, 32, 8192, 20), 20);
+        assert_eq!(
+            recommended_gpu_layers(Some(8.0), None, 32, 8192, FULL_GPU_LAYERS),
+            32
+        );
+    }
+}
@@ -1,33 +1,368 @@
+use crate::ipc_state::{current_timestamp, ServerEntry};
+use crate::server_manager::{ServerReadiness, StopOutcome};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
 use std::process::Child;
 use std::sync::Mutex;
 
-// Server state management
+/// A llama-server started by this app
+pub struct ManagedServer {
+    pub child: Child,
+    /// Port it was started on
+    pub port: u16,
+}
+
+// Server state management: the servers started by this app, keyed by model name,
+// and the embeddings server (start_embeddings_server)
 pub struct ServerState {
-    pub process: Mutex<Option<Child>>,
+    pub servers: Mutex<HashMap<String, ManagedServer>>,
+    pub embeddings: Mutex<Option<ManagedServer>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerStatus {
     pub is_running: bool,
+    /// Confirmed over HTTP: a running process may still be loading the model
+    pub state: ServerReadiness,
+    pub message: String,
+    pub pid: Option<u32>,
+    pub port: Option<u16>,
+    /// Model the running server was started with, which may differ from the active model
+    /// setting until the server restarts
+    pub model_name: Option<String>,
+    pub ctx_size: Option<u32>,
+    pub gpu_layers: Option<u32>,
+    /// The server was started with `--embeddings`, so `/embedding` is available
+    pub embeddings: bool,
+    /// Address llama-server listens on (`0.0.0.0` = every interface)
+    pub host: Option<String>,
+    /// Command line the server was started with, program first (empty when unknown)
+    pub args: Vec<String>,
+    /// Unix timestamp in seconds
+    pub started_at: Option<u64>,
+    pub uptime_secs: Option<u64>,
+    /// The dedicated embeddings server, None when it is not running
+    pub embeddings_server: Option<EmbeddingsServerStatus>,
+}
+
+/// The llama-server started by start_embeddings_server, reported with the chat server
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingsServerStatus {
+    pub state: ServerReadiness,
+    pub pid: u32,
+    pub port: u16,
+    pub model_name: String,
+    /// Unix timestamp in seconds
+    pub started_at: Option<u64>,
+}
+
+impl ServerStatus {
+    /// Status of a live server, with the settings recorded in IPC state when it started
+    pub fn running(readiness: ServerReadiness, message: String, server: &ServerEntry) -> Self {
+        Self {
+            is_running: true,
+            state: readiness,
+            message,
+            pid: Some(server.pid),
+            port: Some(server.port),
+            model_name: Some(server.model.clone()).filter(|model| !model.is_empty()),
+            ctx_size: Some(server.ctx_size),
+            gpu_layers: Some(server.gpu_layers),
+            embeddings: server.embeddings,
+            host: Some(server.host.clone()),
+            args: server.args.clone(),
+            started_at: server.started_at,
+            uptime_secs: server
+                .started_at
+                .map(|started_at| current_timestamp().saturating_sub(started_at)),
+            embeddings_server: None,
+        }
+    }
+
+    pub fn stopped(message: String) -> Self {
+        Self {
+            is_running: false,
+            state: ServerReadiness::NotRunning,
+            message,
+            pid: None,
+            port: None,
+            model_name: None,
+            ctx_size: None,
+            gpu_layers: None,
+            embeddings: false,
+            host: None,
+            args: Vec::new(),
+            started_at: None,
+            uptime_secs: None,
+            embeddings_server: None,
+        }
+    }
+}
+
+/// Live numbers from the running llama-server, returned by get_server_metrics. Fields
+/// are None while the model loads or when the endpoint they come from is disabled.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ServerMetrics {
+    pub status: ServerReadiness,
+    pub slots_idle: Option<u32>,
+    pub slots_processing: Option<u32>,
+    /// Average generation speed since the server started
+    pub tokens_predicted_per_sec: Option<f64>,
+    /// Share of the KV cache in use, from 0.0 to 1.0
+    pub kv_cache_used: Option<f64>,
+    pub model_path: Option<String>,
+}
+
+/// Result of test_inference: a short completion from the active model
+#[derive(Debug, Clone, Serialize)]
+pub struct InferenceTestResult {
+    pub model_name: String,
+    pub text: String,
+    /// The server was not running and was started for the test
+    pub started_server: bool,
+    /// None when the model answered with no text
+    pub time_to_first_token_ms: Option<u64>,
+    /// Tokens generated (at most 16)
+    pub tokens: u32,
+    pub tokens_per_sec: Option<f64>,
+}
+
+/// Why test_inference failed, serialized as `{ "kind": "...", "message": "..." }` so the
+/// UI can explain each case
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InferenceTestError {
+    /// The server was not running and could not be started
+    ServerStart {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        failure: Option<ServerFailure>,
+    },
+    /// Nothing accepted the connection: the server exited or listens elsewhere
+    ConnectionRefused {
+        message: String,
+    },
+    /// The server answered with an error status
+    Http {
+        status: u16,
+        message: String,
+    },
+    /// No complete answer within the time limit
+    Timeout {
+        message: String,
+    },
+    Other {
+        message: String,
+    },
+}
+
+impl InferenceTestError {
+    pub fn other(message: impl ToString) -> Self {
+        InferenceTestError::Other {
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A server stopped by stop_server
+#[derive(Debug, Clone, Serialize)]
+pub struct StoppedServer {
+    pub pid: u32,
+    /// "graceful" when it exited on its own, "force_killed" otherwise
+    pub outcome: StopOutcome,
+}
+
+/// Returned by stop_server
+#[derive(Debug, Clone, Serialize)]
+pub struct StopServerResult {
     pub message: String,
+    pub stopped: Vec<StoppedServer>,
+    /// From the stop request until every server was gone
+    pub duration_ms: u64,
+}
+
+/// Known llama.cpp failure recognised in the server output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerFailureCode {
+    /// The .gguf is missing, truncated or not a GGUF file
+    ModelFileInvalid,
+    /// The model architecture or quantization is unknown to this llama.cpp build
+    UnsupportedModel,
+    /// RAM or VRAM ran out while loading the model
+    OutOfMemory,
+    /// The CUDA, Vulkan or Metal backend could not be initialized
+    GpuBackendInitFailed,
+    /// Another process holds the port
+    PortBindFailed,
+}
+
+/// Why llama-server failed, translated from its output for the user
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerFailure {
+    pub code: ServerFailureCode,
+    pub message: String,
+    /// What the user can do about it
+    pub remediation: String,
+    /// Size of the allocation that failed, for out_of_memory when the output states it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_bytes: Option<u64>,
+    /// Server output line the failure was recognised in
+    pub output_line: String,
+}
+
+/// Error of start_server and restart_server: `message` always, `failure` when the
+/// server output matched a known llama.cpp failure
+#[derive(Debug, Serialize)]
+pub struct ServerStartError {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<ServerFailure>,
+}
+
+impl From<String> for ServerStartError {
+    fn from(message: String) -> Self {
+        ServerStartError {
+            message,
+            failure: None,
+        }
+    }
+}
+
+impl From<ServerStartError> for String {
+    fn from(error: ServerStartError) -> Self {
+        error.message
+    }
+}
+
+impl std::fmt::Display for ServerStartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+// A llama-server from the app's bin directory that IPC state does not track
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanedServer {
+    pub pid: u32,
+    /// Executable path
+    pub path: String,
+    /// Unix timestamp in seconds
+    pub started_at: u64,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct DownloadProgress {
+    pub artifact: String,
     pub downloaded: u64,
     pub total: Option<u64>,
     pub percentage: Option<f64>,
     pub message: String,
 }
 
+// Payload for extract-progress events, emitted while a downloaded archive is unpacked
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractProgress {
+    pub artifact: String,
+    pub extracted: u64,
+    pub total: Option<u64>,
+    pub percentage: Option<f64>,
+    /// Archive member currently being written
+    pub current_file: String,
+}
+
+// One installed file checked by verify_installation
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationItem {
+    /// "llama.cpp" or the model name
+    pub item: String,
+    pub path: String,
+    /// Configured SHA-256 (None when versions.json has no hash for this file)
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    pub ok: bool,
+    /// No hash configured, so nothing to compare against
+    pub skipped: bool,
+    pub error: Option<String>,
+}
+
+// Payload for verify-progress events
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyProgress {
+    pub item: String,
+    pub path: String,
+    /// Bytes hashed across all files so far, and in total
+    pub hashed: u64,
+    pub total: u64,
+    pub percentage: f64,
+}
+
+// Payload for download-started / download-completed / download-failed events
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadLifecycleEvent {
+    /// Model name or "llama.cpp"
+    pub artifact: String,
+    /// "model" or "llama"
+    pub artifact_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Error discriminant matching the `kind` of the command's DownloadError
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<&'static str>,
+    /// Finer-grained failure reason (e.g. "checksum_mismatch_after_restart")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+}
+
+// Outcome of one artifact in a bulk download
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkDownloadItem {
+    /// Model name or "llama.cpp"
+    pub artifact: String,
+    /// "model" or "llama"
+    pub artifact_type: String,
+    /// "downloaded", "skipped" or "failed"
+    pub status: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<&'static str>,
+}
+
+// Result of download_all, also sent as the `bulk-download-complete` event payload
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BulkDownloadReport {
+    pub items: Vec<BulkDownloadItem>,
+    pub downloaded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
 // LlamaCpp platform configuration
 #[derive(Debug, Deserialize)]
 pub struct LlamaCppPlatform {
     pub url: String,
     #[serde(default)]
     pub sha256: String,
+    /// Extra request headers for this download source; values may use `${ENV:NAME}`
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Individual files of the build, so an update can download only the changed ones
+    #[serde(default)]
+    pub files: Vec<LlamaCppFile>,
+}
+
+// One file of a llama.cpp build, as it lands in `bin/`
+#[derive(Debug, Deserialize)]
+pub struct LlamaCppFile {
+    pub name: String,
+    pub url: String,
+    pub sha256: String,
+    #[serde(default)]
+    pub size: u64,
 }
 
 // LlamaCpp version configuration
@@ -37,11 +372,83 @@ pub struct LlamaCppConfig {
     pub platforms: HashMap<String, LlamaCppPlatform>,
 }
 
+// llama.cpp build variants available for the current platform
+#[derive(Debug, Clone, Serialize)]
+pub struct LlamaVariants {
+    pub available: Vec<String>,
+    pub recommended: String,
+    pub installed: Option<String>,
+}
+
+// Result of get_llama_build_info
+#[derive(Debug, Clone, Serialize)]
+pub struct LlamaBuildInfo {
+    /// Version tracked in llama-version.txt
+    pub installed_version: Option<String>,
+    /// Build number and commit reported by `llama-server --version`
+    pub build_number: Option<u64>,
+    pub commit: Option<String>,
+    pub version_output: String,
+    /// Set when the tracked version and the binary disagree (e.g. after a manual copy)
+    pub mismatch_warning: Option<String>,
+}
+
+// Result of check_llama_version
+#[derive(Debug, Clone, Serialize)]
+pub struct LlamaUpdateCheck {
+    pub needs_update: bool,
+    /// Only the changed files will be downloaded
+    pub delta: bool,
+    /// Estimated bytes to download (None when the full archive is needed, size unknown)
+    pub download_bytes: Option<u64>,
+}
+
 // Model configuration from versions.json
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ModelConfig {
     pub version: String,
     pub filename: String,
+    /// Archive URL of a single-file model (unused when `shards` is set)
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub sha256: String,
+    /// SHA-256 of the extracted .gguf, verified after extraction when set.
+    /// For a split model this is the shard named `filename`.
+    #[serde(default)]
+    pub gguf_sha256: Option<String>,
+    /// Archives of a split model, each containing one `<name>-0000N-of-0000M.gguf` shard
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shards: Vec<ModelShard>,
+    /// Extra request headers for this download source (all shards); values may use `${ENV:NAME}`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Oldest app version (semver) that can run this model, e.g. for a new chat template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_app_version: Option<String>,
+    #[serde(default)]
+    pub purpose: ModelPurpose,
+    /// Approximate size of the extracted .gguf (all shards), used to recommend
+    /// gpu_layers before the model is downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+    /// Layers llama-server offloads with a full --n-gpu-layers (block count plus the output layer)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer_count: Option<u32>,
+}
+
+/// What a model is for; embedding models run on the embeddings server
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelPurpose {
+    #[default]
+    Chat,
+    Embedding,
+}
+
+// One archive of a split model
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ModelShard {
     pub url: String,
     #[serde(default)]
     pub sha256: String,
@@ -64,12 +471,39 @@ pub struct ModelInfo {
     pub name: String,
     pub version: String,
     pub is_downloaded: bool,
+    /// Extracted .gguf passed SHA-256 verification (false when no gguf_sha256 is configured)
+    pub is_verified: bool,
     pub path: Option<String>,
+    /// Total size of the model directory on disk (None when not downloaded)
+    pub size_bytes: Option<u64>,
+    /// Version recorded when the model was extracted (None when not downloaded or
+    /// installed before versions were recorded)
+    pub installed_version: Option<String>,
+    /// False when the model needs a newer app version (see `min_app_version`)
+    pub compatible: bool,
+    pub min_app_version: Option<String>,
+    pub purpose: ModelPurpose,
 }
 
+// Installed vs configured version of a downloaded model
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelUpdateInfo {
+    pub name: String,
+    pub installed_version: Option<String>,
+    pub latest_version: String,
+    pub update_available: bool,
+}
+
+/// Layout version of settings.json written by this version of the app
+pub const SETTINGS_SCHEMA_VERSION: u32 = 1;
+
 // Application settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
+    /// Layout version of the file (0 = written before versioning); older layouts are
+    /// upgraded when the settings are loaded
+    #[serde(default)]
+    pub schema_version: u32,
     #[serde(default = "default_active_model")]
     pub active_model: String,
     #[serde(default = "default_port")]
@@ -78,6 +512,112 @@ pub struct AppSettings {
     pub ctx_size: u32,
     #[serde(default = "default_gpu_layers")]
     pub gpu_layers: u32,
+    /// Proxy for downloads (http://, https:// or socks5:// URL).
+    /// When unset, the standard HTTP_PROXY/HTTPS_PROXY environment variables are used.
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    /// Hosts that bypass the proxy (same syntax as NO_PROXY, e.g. `localhost`, `.corp.local`).
+    /// When empty, the NO_PROXY environment variable is used.
+    #[serde(default)]
+    pub proxy_no_proxy: Vec<String>,
+    /// Download speed cap in megabits per second (None = unlimited)
+    #[serde(default)]
+    pub download_speed_limit_mbps: Option<u32>,
+    /// Consecutive chunk read failures tolerated before a download is abandoned
+    #[serde(default = "default_download_max_retries")]
+    pub download_max_retries: u32,
+    /// Base delay for exponential backoff between retries (in milliseconds)
+    #[serde(default = "default_download_base_retry_delay_ms")]
+    pub download_base_retry_delay_ms: u64,
+    /// Maximum delay between retries (in milliseconds)
+    #[serde(default = "default_download_max_retry_delay_ms")]
+    pub download_max_retry_delay_ms: u64,
+    /// Seconds without receiving any data before a download connection is considered stalled
+    #[serde(default = "default_download_stall_timeout_secs")]
+    pub download_stall_timeout_secs: u64,
+    /// Absolute cap on the data extracted from one archive (in GB)
+    #[serde(default = "default_max_extract_size_gb")]
+    pub max_extract_size_gb: u64,
+    /// Directory holding model subdirectories (None = `models` inside the app data dir)
+    #[serde(default)]
+    pub models_dir: Option<PathBuf>,
+    /// How long start_server waits for llama-server to load the model and answer /health
+    #[serde(default = "default_server_start_timeout_secs")]
+    pub server_start_timeout_secs: u64,
+    /// Restart llama-server when it crashes (bounded, with backoff)
+    #[serde(default)]
+    pub auto_restart: bool,
+    /// Automatic restarts allowed within five minutes before a crash loop is assumed
+    #[serde(default = "default_max_auto_restarts")]
+    pub max_auto_restarts: u32,
+    /// How long a stopped llama-server gets to exit on its own before it is killed
+    #[serde(default = "default_stop_grace_period_secs")]
+    pub stop_grace_period_secs: u64,
+    /// When the configured port is taken, start on the next free port above it instead of failing
+    #[serde(default)]
+    pub auto_select_port: bool,
+    /// Start llama-server with `--embeddings` so it serves the `/embedding` endpoint
+    #[serde(default)]
+    pub embeddings: bool,
+    /// Extra llama-server arguments, appended after the built-in flags. llama-server keeps
+    /// the last value of a repeated flag, so these override built-in defaults such as
+    /// `--batch-size`; model, port, context size and GPU layers cannot be set here.
+    #[serde(default)]
+    pub extra_server_args: Vec<String>,
+    /// llama-server `--threads` (None = one per physical core)
+    #[serde(default)]
+    pub threads: Option<u32>,
+    /// llama-server `--parallel`: requests served concurrently, each with ctx_size / slots tokens
+    #[serde(default = "default_parallel_slots")]
+    pub parallel_slots: u32,
+    /// llama-server `--batch-size`: prompt tokens processed per step
+    #[serde(default = "default_batch_size")]
+    pub batch_size: u32,
+    /// llama-server `--ubatch-size`: tokens per compute pass, which sizes the compute
+    /// buffers. Embeddings mode uses `batch_size` instead.
+    #[serde(default = "default_ubatch_size")]
+    pub ubatch_size: u32,
+    /// llama-server `--flash-attn`
+    #[serde(default = "default_flash_attn")]
+    pub flash_attn: FlashAttn,
+    /// Key llama-server requires on API requests (None = no authentication). Kept only
+    /// here and never logged; the native host hands it to the browser extension.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Address llama-server listens on. Anything but loopback exposes the model to the network.
+    #[serde(default = "default_bind_host")]
+    pub bind_host: String,
+    /// Connect over IPv4 only, for networks where IPv6 is advertised but broken
+    #[serde(default)]
+    pub prefer_ipv4: bool,
+    /// Fixed addresses for download hosts (lowercase host name -> IP), bypassing DNS
+    #[serde(default)]
+    pub dns_overrides: HashMap<String, IpAddr>,
+    /// Start llama-server with `--slot-save-path` in the kv-cache directory, so clients can
+    /// save a conversation's KV cache to disk and restore it after a restart
+    #[serde(default)]
+    pub persist_kv_cache: bool,
+}
+
+/// llama-server `--flash-attn` mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlashAttn {
+    /// On where the backend supports it
+    Auto,
+    On,
+    Off,
+}
+
+impl FlashAttn {
+    /// Value of the `--flash-attn` argument
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            FlashAttn::Auto => "auto",
+            FlashAttn::On => "on",
+            FlashAttn::Off => "off",
+        }
+    }
 }
 
 fn default_active_model() -> String {
@@ -96,17 +636,179 @@ fn default_gpu_layers() -> u32 {
     0
 }
 
+fn default_download_max_retries() -> u32 {
+    10
+}
+
+fn default_download_base_retry_delay_ms() -> u64 {
+    1000
+}
+
+fn default_download_max_retry_delay_ms() -> u64 {
+    30000
+}
+
+fn default_download_stall_timeout_secs() -> u64 {
+    30
+}
+
+fn default_max_extract_size_gb() -> u64 {
+    64
+}
+
+fn default_max_auto_restarts() -> u32 {
+    3
+}
+
+fn default_stop_grace_period_secs() -> u64 {
+    5
+}
+
+fn default_parallel_slots() -> u32 {
+    1
+}
+
+fn default_batch_size() -> u32 {
+    2048
+}
+
+fn default_ubatch_size() -> u32 {
+    512
+}
+
+/// Metal + flash-attention "auto" has triggered SIGABRT on some macOS / llama.cpp builds
+fn default_flash_attn() -> FlashAttn {
+    if cfg!(target_os = "macos") {
+        FlashAttn::Off
+    } else {
+        FlashAttn::Auto
+    }
+}
+
+fn default_bind_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_server_start_timeout_secs() -> u64 {
+    180
+}
+
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
+            schema_version: SETTINGS_SCHEMA_VERSION,
             active_model: default_active_model(),
             port: default_port(),
             ctx_size: default_ctx_size(),
             gpu_layers: default_gpu_layers(),
+            proxy_url: None,
+            proxy_no_proxy: Vec::new(),
+            download_speed_limit_mbps: None,
+            download_max_retries: default_download_max_retries(),
+            download_base_retry_delay_ms: default_download_base_retry_delay_ms(),
+            download_max_retry_delay_ms: default_download_max_retry_delay_ms(),
+            download_stall_timeout_secs: default_download_stall_timeout_secs(),
+            max_extract_size_gb: default_max_extract_size_gb(),
+            models_dir: None,
+            server_start_timeout_secs: default_server_start_timeout_secs(),
+            auto_restart: false,
+            max_auto_restarts: default_max_auto_restarts(),
+            stop_grace_period_secs: default_stop_grace_period_secs(),
+            auto_select_port: false,
+            embeddings: false,
+            extra_server_args: Vec::new(),
+            threads: None,
+            parallel_slots: default_parallel_slots(),
+            batch_size: default_batch_size(),
+            ubatch_size: default_ubatch_size(),
+            flash_attn: default_flash_attn(),
+            api_key: None,
+            bind_host: default_bind_host(),
+            prefer_ipv4: false,
+            dns_overrides: HashMap::new(),
+            persist_kv_cache: false,
         }
     }
 }
 
+// Result of a proxy connectivity test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyTestResult {
+    /// Redacted proxy URL, None when connecting directly
+    pub proxy: Option<String>,
+    pub status: u16,
+    pub latency_ms: u64,
+}
+
+// Result of connecting to one resolved address in diagnose_connectivity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressProbe {
+    pub address: String,
+    /// "ipv4" or "ipv6"
+    pub family: String,
+    /// TCP connect time (None when the connection failed)
+    pub connect_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+// Network diagnostics for a download URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectivityDiagnosis {
+    pub url: String,
+    pub host: String,
+    /// Redacted proxy URL, None when connecting directly
+    pub proxy: Option<String>,
+    pub prefer_ipv4: bool,
+    /// Address configured in dns_overrides for this host
+    pub dns_override: Option<String>,
+    /// DNS lookup time (None when overridden or the lookup failed)
+    pub dns_ms: Option<u64>,
+    /// Direct TCP connects to the first address of each family
+    pub probes: Vec<AddressProbe>,
+    /// Address and family the HTTP request actually used (None through a proxy)
+    pub used_address: Option<String>,
+    pub used_family: Option<String>,
+    pub status: Option<u16>,
+    /// Time until the response headers arrived
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+// Disk usage shown before downloads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageInfo {
+    /// Size of the volume holding the models directory
+    pub total_bytes: u64,
+    /// Space available to the current user on that volume
+    pub free_bytes: u64,
+    /// Combined size of everything under the models directory
+    pub models_bytes: u64,
+}
+
+// Space used by the app, broken down for the settings screen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageUsage {
+    /// (model name, bytes) for every directory in the models directory, largest first
+    pub models: Vec<(String, u64)>,
+    pub binaries: u64,
+    pub logs: u64,
+    /// Slots saved by llama-server when persist_kv_cache is enabled
+    pub kv_cache: u64,
+    /// Settings, state files and anything else not counted above
+    pub other: u64,
+    pub total: u64,
+}
+
+// Payload for models-migration-progress events, emitted while migrate_models copies data
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelsMigrationProgress {
+    pub moved_bytes: u64,
+    pub total_bytes: u64,
+    pub percentage: f64,
+    /// Model directory currently being moved
+    pub current_model: String,
+}
+
 // Recommended system settings based on available resources
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecommendedSettings {
@@ -114,5 +816,24 @@ pub struct RecommendedSettings {
     pub recommended_model: String,
     pub recommended_ctx_size: u32,
     pub recommended_gpu_layers: u32,
+    /// Highest x86 SIMD level detected ("avx512", "avx2", "avx", "none"; None on ARM).
+    /// Without AVX2 a slower CPU build of llama.cpp is chosen.
+    pub cpu_feature_level: Option<String>,
+    /// One thread per physical core
+    pub recommended_threads: u32,
+    /// Concurrent request slots that still leave each a usable context
+    pub recommended_parallel_slots: u32,
+    /// Smaller batches on machines with little memory
+    pub recommended_batch_size: u32,
+    pub recommended_ubatch_size: u32,
+    /// Model tagged `"purpose": "embedding"` in versions.json, for start_embeddings_server
+    pub recommended_embeddings_model: Option<String>,
+    /// Memory available to offloaded layers in GB: VRAM, or on Apple Silicon the share of RAM
+    /// Metal can use. 4 GB is assumed for a GPU that does not report it; None without a GPU.
+    pub gpu_memory_gb: Option<f64>,
+    /// Size of the recommended model's weights, None when unknown
+    pub model_size_bytes: Option<u64>,
+    /// Layers of the recommended model; recommended_gpu_layers of them fit on the GPU
+    pub model_layer_count: u32,
 }
 
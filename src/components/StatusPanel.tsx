@@ -14,7 +14,9 @@ export const StatusPanel = ({ status, onStartServer, onStopServer, isBusy }: Sta
     <div className="status-panel">
       <div className="status-text-wrapper">
         <div className="status-indicator">
-          <span>{status.is_running ? "Running" : "Stopped"}</span>
+          <span>
+            {status.state === "starting" ? "Starting" : status.is_running ? "Running" : "Stopped"}
+          </span>
         </div>
         <p className="status-message">{status.message}</p>
       </div>
@@ -1,6 +1,7 @@
 import { useState, useEffect, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { toast } from "sonner";
+import { errorMessage, LlamaUpdateCheck } from "../types";
 
 interface UseAutoDownloadProps {
   modelName: string;
@@ -44,8 +45,9 @@ export const useAutoDownload = ({
         let llamaExists = false;
 
         try {
-          needsLlamaUpdate = await invoke<boolean>("check_llama_version");
-          // If check_llama_version returns false, llama exists and is up to date
+          const llamaCheck = await invoke<LlamaUpdateCheck>("check_llama_version");
+          needsLlamaUpdate = llamaCheck.needs_update;
+          // If no update is needed, llama exists and is up to date
           llamaExists = true;
           if (!needsLlamaUpdate) {
             setIsLlamaAlreadyDownloaded(true);
@@ -92,8 +94,8 @@ export const useAutoDownload = ({
             toast.success(result, { id: toastId });
             addLog(result);
           } catch (error) {
-            toast.error(`Error: ${error}`, { id: toastId });
-            addLog(`Error: ${error}`);
+            toast.error(`Error: ${errorMessage(error)}`, { id: toastId });
+            addLog(`Error: ${errorMessage(error)}`);
           } finally {
             setIsDownloadingLlama(false);
             setDownloadProgress(null);
@@ -123,8 +125,8 @@ export const useAutoDownload = ({
             await invoke<string>("set_active_model_command", { modelName });
             addLog(`Active model set to: ${modelName}`);
           } catch (error) {
-            toast.error(`Error: ${error}`, { id: toastId });
-            addLog(`Error: ${error}`);
+            toast.error(`Error: ${errorMessage(error)}`, { id: toastId });
+            addLog(`Error: ${errorMessage(error)}`);
           } finally {
             setIsDownloadingModel(false);
             setDownloadProgress(null);
@@ -139,7 +141,7 @@ export const useAutoDownload = ({
         }
       } catch (error) {
         console.error("Failed to check files:", error);
-        addLog(`Failed to check files: ${error}`);
+        addLog(`Failed to check files: ${errorMessage(error)}`);
       }
     };
 
@@ -1,6 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
 import { toast } from "sonner";
-import { DownloadProgress } from "../types";
+import { DownloadProgress, errorMessage } from "../types";
 
 interface UseModelDownloadProps {
   baseModel: string;
@@ -50,8 +50,8 @@ export const useModelDownload = ({
       toast.success(result, { id: toastId });
       addLog(result);
     } catch (error) {
-      toast.error(`Error: ${error}`, { id: toastId });
-      addLog(`Error: ${error}`);
+      toast.error(`Error: ${errorMessage(error)}`, { id: toastId });
+      addLog(`Error: ${errorMessage(error)}`);
     } finally {
       setIsDownloadingLlama(false);
       setDownloadProgress(null);
@@ -83,8 +83,8 @@ export const useModelDownload = ({
       await invoke<string>("set_active_model_command", { modelName: currentModel });
       addLog(`Set active model to: ${currentModel}`);
     } catch (error) {
-      toast.error(`Error: ${error}`, { id: toastId });
-      addLog(`Error: ${error}`);
+      toast.error(`Error: ${errorMessage(error)}`, { id: toastId });
+      addLog(`Error: ${errorMessage(error)}`);
     } finally {
       setIsDownloadingModel(false);
       setDownloadProgress(null);
@@ -121,8 +121,8 @@ export const useModelDownload = ({
           toast.success(result, { id: toastId });
           addLog(result);
         } catch (error) {
-          toast.error(`Error: ${error}`, { id: toastId });
-          addLog(`Error downloading: ${error}`);
+          toast.error(`Error: ${errorMessage(error)}`, { id: toastId });
+          addLog(`Error downloading: ${errorMessage(error)}`);
           // Revert checkbox on error
           setIsUncensored(!checked);
           localStorage.setItem("isUncensored", (!checked).toString());
@@ -139,8 +139,8 @@ export const useModelDownload = ({
       addLog(`Active model set to: ${newModelName}`);
       toast.success(`Switched to ${checked ? "uncensored" : "censored"} model`);
     } catch (error) {
-      toast.error(`Error: ${error}`);
-      addLog(`Error switching model: ${error}`);
+      toast.error(`Error: ${errorMessage(error)}`);
+      addLog(`Error switching model: ${errorMessage(error)}`);
       // Revert checkbox on error
       setIsUncensored(!checked);
       localStorage.setItem("isUncensored", (!checked).toString());
@@ -1,7 +1,7 @@
 import { useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { toast } from "sonner";
-import { ServerStatus } from "../types";
+import { errorMessage, ServerStartError, ServerStatus, StopServerResult } from "../types";
 
 interface UseServerControlProps {
   addLog: (message: string) => void;
@@ -38,8 +38,13 @@ export const useServerControl = ({
       toast.success(result);
       addLog(result);
     } catch (error) {
-      toast.error(`Error: ${error}`);
-      addLog(`Error: ${error}`);
+      // A recognised llama-server failure comes with what the user can do about it
+      const remediation = (error as ServerStartError).failure?.remediation;
+      toast.error(`Error: ${errorMessage(error)}`, { description: remediation });
+      addLog(`Error: ${errorMessage(error)}`);
+      if (remediation) {
+        addLog(remediation);
+      }
     } finally {
       setIsBusy(false);
     }
@@ -51,9 +56,9 @@ export const useServerControl = ({
 
     addLog("Stopping server...");
     try {
-      const result = await invoke<string>("stop_server");
-      toast.success(result);
-      addLog(result);
+      const result = await invoke<StopServerResult>("stop_server");
+      toast.success(result.message);
+      addLog(`${result.message} in ${result.duration_ms} ms`);
     } catch (error) {
       toast.error(`Error: ${error}`);
       addLog(`Error: ${error}`);
@@ -72,7 +77,7 @@ export const useServerControl = ({
         toast.loading("Stopping server first...", { id: toastId });
 
         try {
-          await invoke<string>("stop_server");
+          await invoke<StopServerResult>("stop_server");
           addLog("Server stopped");
         } catch (error) {
           addLog(`Warning: Failed to stop server: ${error}`);
@@ -5,6 +5,7 @@ import { ServerStatus } from "../types";
 export const useServerStatus = () => {
   const [status, setStatus] = useState<ServerStatus>({
     is_running: false,
+    state: "not_running",
     message: "Not running",
   });
 
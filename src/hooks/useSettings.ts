@@ -114,7 +114,8 @@ export const useSettings = ({ addLog }: UseSettingsProps): UseSettingsReturn =>
   const handleCtxSizeChange = async (newCtxSize: number) => {
     setCtxSize(newCtxSize);
     try {
-      await invoke<string>("set_ctx_size_command", { ctxSize: newCtxSize });
+      // The backend clamps out-of-range values; show what was actually stored
+      setCtxSize(await invoke<number>("set_ctx_size_command", { ctxSize: newCtxSize }));
     } catch (error) {
       console.error("Failed to save ctx_size:", error);
     }
@@ -123,7 +124,7 @@ export const useSettings = ({ addLog }: UseSettingsProps): UseSettingsReturn =>
   const handleGpuLayersChange = async (newGpuLayers: number) => {
     setGpuLayers(newGpuLayers);
     try {
-      await invoke<string>("set_gpu_layers_command", { gpuLayers: newGpuLayers });
+      setGpuLayers(await invoke<number>("set_gpu_layers_command", { gpuLayers: newGpuLayers }));
     } catch (error) {
       console.error("Failed to save gpu_layers:", error);
     }
@@ -133,19 +134,17 @@ export const useSettings = ({ addLog }: UseSettingsProps): UseSettingsReturn =>
     try {
       const recommended = await invoke<RecommendedSettings>("get_recommended_settings");
 
-      // Apply recommended settings
-      setCtxSize(recommended.recommended_ctx_size);
-      setGpuLayers(recommended.recommended_gpu_layers);
-
-      // Save to backend
-      await invoke<string>("set_ctx_size_command", { ctxSize: recommended.recommended_ctx_size });
-      await invoke<string>("set_gpu_layers_command", {
+      // Save to backend and apply what was stored
+      const storedCtxSize = await invoke<number>("set_ctx_size_command", {
+        ctxSize: recommended.recommended_ctx_size,
+      });
+      const storedGpuLayers = await invoke<number>("set_gpu_layers_command", {
         gpuLayers: recommended.recommended_gpu_layers,
       });
+      setCtxSize(storedCtxSize);
+      setGpuLayers(storedGpuLayers);
 
-      addLog(
-        `Settings restored: ctx_size=${recommended.recommended_ctx_size}, gpu_layers=${recommended.recommended_gpu_layers}`
-      );
+      addLog(`Settings restored: ctx_size=${storedCtxSize}, gpu_layers=${storedGpuLayers}`);
     } catch (error) {
       console.error("Failed to restore defaults:", error);
       throw error; // Re-throw for caller to handle toast
@@ -1,20 +1,317 @@
+/** not_running / starting (process alive, model still loading) / ready (serving requests) */
+export type ServerReadiness = "not_running" | "starting" | "ready";
+
 export interface ServerStatus {
   is_running: boolean;
+  state: ServerReadiness;
+  message: string;
+  pid: number | null;
+  port: number | null;
+  /** Model the running server was started with; may differ from the active model setting */
+  model_name: string | null;
+  ctx_size: number | null;
+  gpu_layers: number | null;
+  /** Started with --embeddings, so /embedding is available */
+  embeddings: boolean;
+  /** Address llama-server listens on ("0.0.0.0" = every interface) */
+  host: string | null;
+  /** Command line the server was started with, program first (empty when unknown) */
+  args: string[];
+  /** Unix timestamp in seconds */
+  started_at: number | null;
+  uptime_secs: number | null;
+  /** The server started by start_embeddings_server, null when it is not running */
+  embeddings_server: EmbeddingsServerStatus | null;
+}
+
+export interface EmbeddingsServerStatus {
+  state: ServerReadiness;
+  pid: number;
+  port: number;
+  model_name: string;
+  /** Unix timestamp in seconds */
+  started_at: number | null;
+}
+
+/** Returned by get_server_metrics; fields are null while loading or when the endpoint is disabled */
+export interface ServerMetrics {
+  status: ServerReadiness;
+  slots_idle: number | null;
+  slots_processing: number | null;
+  tokens_predicted_per_sec: number | null;
+  /** Share of the KV cache in use, 0 to 1 */
+  kv_cache_used: number | null;
+  model_path: string | null;
+}
+
+/** Returned by test_inference */
+export interface InferenceTestResult {
+  model_name: string;
+  text: string;
+  /** The server was not running and was started for the test */
+  started_server: boolean;
+  time_to_first_token_ms: number | null;
+  tokens: number;
+  tokens_per_sec: number | null;
+}
+
+/** Known llama.cpp failure recognised in the server output */
+export type ServerFailureCode =
+  | "model_file_invalid"
+  | "unsupported_model"
+  | "out_of_memory"
+  | "gpu_backend_init_failed"
+  | "port_bind_failed";
+
+export interface ServerFailure {
+  code: ServerFailureCode;
+  message: string;
+  /** What the user can do about it */
+  remediation: string;
+  /** Size of the allocation that failed (out_of_memory only, when stated) */
+  requested_bytes?: number;
+  output_line: string;
+}
+
+/** Error of start_server and restart_server */
+export interface ServerStartError {
+  message: string;
+  failure?: ServerFailure;
+}
+
+/** Payload of the server-crashed event */
+export interface ServerCrashedEvent {
+  pid: number;
+  model_name: string;
+  exit_status: string;
+  output_tail: string | null;
+  failure: ServerFailure | null;
+  will_restart: boolean;
+}
+
+/** Error of test_inference */
+export type InferenceTestError =
+  | { kind: "server_start"; message: string; failure?: ServerFailure }
+  | { kind: "connection_refused"; message: string }
+  | { kind: "http"; status: number; message: string }
+  | { kind: "timeout"; message: string }
+  | { kind: "other"; message: string };
+
+/** Payload of the inference-test-stage event */
+export interface InferenceTestStage {
+  stage: "starting" | "loading" | "generating";
+}
+
+/** Returned by stop_server */
+export interface StopServerResult {
   message: string;
+  stopped: { pid: number; outcome: "graceful" | "force_killed" }[];
+  /** From the stop request until every server was gone */
+  duration_ms: number;
+}
+
+/** Payload of the server-stopping event */
+export interface ServerStoppingEvent {
+  model_name: string | null;
+  force: boolean;
+  timeout_ms: number | null;
+}
+
+/** A llama-server from the app's bin directory that the app does not track */
+export interface OrphanedServer {
+  pid: number;
+  path: string;
+  /** Unix timestamp in seconds */
+  started_at: number;
 }
 
 export interface DownloadProgress {
+  artifact: string;
   downloaded: number;
   total: number | null;
   percentage: number | null;
   message: string;
 }
 
+/** Payload of the extract-progress event, emitted while a downloaded archive is unpacked */
+export interface ExtractProgress {
+  artifact: string;
+  extracted: number;
+  total: number | null;
+  percentage: number | null;
+  current_file: string;
+}
+
+/** One file checked by verify_installation */
+export interface VerificationItem {
+  item: string;
+  path: string;
+  expected: string | null;
+  actual: string | null;
+  ok: boolean;
+  skipped: boolean;
+  error: string | null;
+}
+
+export interface VerifyProgress {
+  item: string;
+  path: string;
+  hashed: number;
+  total: number;
+  percentage: number;
+}
+
+export interface DownloadLifecycleEvent {
+  artifact: string;
+  artifact_type: "model" | "llama";
+  message?: string;
+  error?: string;
+  error_kind?: DownloadErrorKind;
+  reason?: DownloadFailureReason;
+}
+
+/** Finer-grained cause of a download-failed event, where error_kind is not specific enough */
+export type DownloadFailureReason = "checksum_mismatch" | "checksum_mismatch_after_restart";
+
+export type DownloadErrorKind =
+  | "network"
+  | "checksum"
+  | "disk_full"
+  | "cancelled"
+  | "unsupported"
+  | "extraction"
+  | "io"
+  | "other";
+
+export interface DownloadError {
+  kind: DownloadErrorKind;
+  message: string;
+  reason?: DownloadFailureReason;
+  expected?: string;
+  got?: string;
+  etag?: string | null;
+  last_modified?: string | null;
+}
+
+export interface BulkDownloadItem {
+  artifact: string;
+  artifact_type: "model" | "llama";
+  status: "downloaded" | "skipped" | "failed";
+  message: string;
+  error_kind?: DownloadErrorKind;
+}
+
+/** Returned by download_all and sent with the bulk-download-complete event */
+export interface BulkDownloadReport {
+  items: BulkDownloadItem[];
+  downloaded: number;
+  skipped: number;
+  failed: number;
+}
+
+/** Entry of get_download_history, oldest first (the last 100 downloads are kept) */
+export interface DownloadHistoryEntry {
+  item: string;
+  url: string;
+  started_at: string;
+  finished_at: string;
+  bytes: number;
+  result: "completed" | "failed" | "cancelled";
+  error?: string;
+}
+
+export type ServerLogLevel = "info" | "warn" | "error";
+
+/** A line of llama-server output returned by get_server_logs */
+export interface ServerLogLine {
+  timestamp: string;
+  level: ServerLogLevel;
+  message: string;
+}
+
+/** Per-host credential passed to set_download_credential */
+export type DownloadCredential =
+  | { type: "bearer"; token: string }
+  | { type: "basic"; username: string; password: string };
+
+/** Human-readable text for errors returned by Tauri commands (plain strings or DownloadError) */
+export const errorMessage = (error: unknown): string => {
+  if (typeof error === "object" && error !== null && "message" in error) {
+    return String((error as { message: unknown }).message);
+  }
+  return String(error);
+};
+
+/** llama-server --flash-attn mode */
+export type FlashAttn = "auto" | "on" | "off";
+
 export interface AppSettings {
+  /** Layout version of settings.json, upgraded on load */
+  schema_version?: number;
   active_model: string;
   port: number;
   ctx_size: number;
   gpu_layers: number;
+  proxy_url?: string | null;
+  proxy_no_proxy?: string[];
+  download_speed_limit_mbps?: number | null;
+  download_max_retries?: number;
+  download_base_retry_delay_ms?: number;
+  download_max_retry_delay_ms?: number;
+  download_stall_timeout_secs?: number;
+  max_extract_size_gb?: number;
+  models_dir?: string | null;
+  server_start_timeout_secs?: number;
+  auto_restart?: boolean;
+  max_auto_restarts?: number;
+  /** Seconds a stopped server gets to exit on its own before it is killed */
+  stop_grace_period_secs?: number;
+  auto_select_port?: boolean;
+  embeddings?: boolean;
+  extra_server_args?: string[];
+  threads?: number | null;
+  parallel_slots?: number;
+  batch_size?: number;
+  /** Embeddings mode uses batch_size instead */
+  ubatch_size?: number;
+  flash_attn?: FlashAttn;
+  /** Key llama-server requires on API requests (Authorization: Bearer <key>) */
+  api_key?: string | null;
+  /** Address llama-server listens on; anything but loopback exposes the model to the network */
+  bind_host?: string;
+  prefer_ipv4?: boolean;
+  dns_overrides?: Record<string, string>;
+  /** Start llama-server with --slot-save-path so conversations can be saved and restored */
+  persist_kv_cache?: boolean;
+}
+
+export interface ProxyTestResult {
+  proxy: string | null;
+  status: number;
+  latency_ms: number;
+}
+
+export interface AddressProbe {
+  address: string;
+  family: "ipv4" | "ipv6";
+  connect_ms: number | null;
+  error: string | null;
+}
+
+/** Returned by diagnose_connectivity */
+export interface ConnectivityDiagnosis {
+  url: string;
+  host: string;
+  proxy: string | null;
+  prefer_ipv4: boolean;
+  dns_override: string | null;
+  dns_ms: number | null;
+  probes: AddressProbe[];
+  used_address: string | null;
+  used_family: "ipv4" | "ipv6" | null;
+  status: number | null;
+  latency_ms: number | null;
+  error: string | null;
 }
 
 export interface RecommendedSettings {
@@ -22,4 +319,70 @@ export interface RecommendedSettings {
   recommended_model: string;
   recommended_ctx_size: number;
   recommended_gpu_layers: number;
+  cpu_feature_level: "avx512" | "avx2" | "avx" | "none" | null;
+  recommended_threads: number;
+  recommended_parallel_slots: number;
+  recommended_batch_size: number;
+  recommended_ubatch_size: number;
+  /** Model tagged `purpose: "embedding"` in versions.json, preferring a downloaded one */
+  recommended_embeddings_model: string | null;
+  /** GB available to offloaded layers (VRAM, or the usable share of RAM on Apple Silicon); null without a GPU */
+  gpu_memory_gb: number | null;
+  model_size_bytes: number | null;
+  /** e.g. "fits {recommended_gpu_layers}/{model_layer_count} layers in {gpu_memory_gb} GB VRAM" */
+  model_layer_count: number;
+}
+
+/** Returned by check_model_updates for each downloaded model */
+export interface ModelUpdateInfo {
+  name: string;
+  installed_version: string | null;
+  latest_version: string;
+  update_available: boolean;
+}
+
+/** Returned by get_llama_build_info */
+export interface LlamaBuildInfo {
+  /** Version tracked in llama-version.txt */
+  installed_version: string | null;
+  /** Reported by llama-server --version */
+  build_number: number | null;
+  commit: string | null;
+  version_output: string;
+  /** Set when the tracked version and the binary disagree */
+  mismatch_warning: string | null;
+}
+
+/** Returned by check_llama_version */
+export interface LlamaUpdateCheck {
+  needs_update: boolean;
+  /** Only the files that changed will be downloaded */
+  delta: boolean;
+  /** Estimated download size in bytes; null when the full archive is needed */
+  download_bytes: number | null;
+}
+
+export interface StorageInfo {
+  total_bytes: number;
+  free_bytes: number;
+  models_bytes: number;
+}
+
+/** Returned by get_storage_usage; all sizes in bytes */
+export interface StorageUsage {
+  /** [model name, bytes], largest first */
+  models: [string, number][];
+  binaries: number;
+  logs: number;
+  kv_cache: number;
+  other: number;
+  total: number;
+}
+
+/** Payload of the models-migration-progress event, emitted by migrate_models */
+export interface ModelsMigrationProgress {
+  moved_bytes: number;
+  total_bytes: number;
+  percentage: number;
+  current_model: string;
 }